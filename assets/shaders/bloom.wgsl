@@ -0,0 +1,63 @@
+@group(0) @binding(0)
+var source: texture_2d<f32>;
+
+@group(0) @binding(1)
+var source_sampler: sampler;
+
+// One texel along the blur axis
+@group(0) @binding(2)
+var<uniform> direction: vec2<f32>;
+
+struct VertexInput {
+    @location(0) position: vec2<f32>,
+    @location(1) uv: vec2<f32>
+}
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) uv: vec2<f32>
+}
+
+@vertex
+fn vs_main(in: VertexInput) -> VertexOutput {
+    var out: VertexOutput;
+    out.clip_position = vec4<f32>(in.position, 0.0, 1.0);
+    out.uv = in.uv;
+
+    return out;
+}
+
+const THRESHOLD: f32 = 1.0;
+const SOFT_KNEE: f32 = 0.5;
+
+fn luminance(color: vec3<f32>) -> f32 {
+    return dot(color, vec3<f32>(0.2126, 0.7152, 0.0722));
+}
+
+// The bilinear sample at half resolution averages four scene pixels
+@fragment
+fn fs_threshold(in: VertexOutput) -> @location(0) vec4<f32> {
+    let color = textureSample(source, source_sampler, in.uv).rgb;
+
+    let brightness = luminance(color);
+    let knee = clamp(brightness - THRESHOLD + SOFT_KNEE, 0.0, 2.0 * SOFT_KNEE);
+    let contribution = max(knee * knee / (4.0 * SOFT_KNEE), brightness - THRESHOLD);
+
+    return vec4<f32>(color * contribution / max(brightness, 0.0001), 1.0);
+}
+
+// 9 tap gaussian, folded into 5 bilinear samples
+fn blur_tap(uv: vec2<f32>, offset: f32, weight: f32) -> vec3<f32> {
+    let left = textureSample(source, source_sampler, uv - direction * offset).rgb;
+    let right = textureSample(source, source_sampler, uv + direction * offset).rgb;
+    return (left + right) * weight;
+}
+
+@fragment
+fn fs_blur(in: VertexOutput) -> @location(0) vec4<f32> {
+    var color = textureSample(source, source_sampler, in.uv).rgb * 0.2270270270;
+    color += blur_tap(in.uv, 1.3846153846, 0.3162162162);
+    color += blur_tap(in.uv, 3.2307692308, 0.0702702703);
+
+    return vec4<f32>(color, 1.0);
+}
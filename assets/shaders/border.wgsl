@@ -0,0 +1,29 @@
+struct VertexInput {
+    @location(0) position: vec3<f32>
+}
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) frag_pos: vec3<f32>
+}
+
+@vertex
+fn vs_main(in: VertexInput) -> VertexOutput {
+    var out: VertexOutput;
+
+    out.clip_position = camera.projection_matrix * camera.transformation_matrix * vec4<f32>(in.position, 1.0);
+    out.frag_pos = in.position;
+
+    return out;
+}
+
+const BORDER_COLOR: vec3<f32> = vec3<f32>(0.35, 0.6, 1.0);
+const BORDER_ALPHA: f32 = 0.35;
+const FADE_DISTANCE: f32 = 64.0;
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let fade = 1.0 - clamp(distance(camera.position, in.frag_pos) / FADE_DISTANCE, 0.0, 1.0);
+
+    return vec4<f32>(BORDER_COLOR, BORDER_ALPHA * fade);
+}
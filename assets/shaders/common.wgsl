@@ -0,0 +1,24 @@
+// Prepended to every scene shader, matches `Globals` in the renderer
+
+struct CameraUniform {
+    projection_matrix: mat4x4<f32>,
+    transformation_matrix: mat4x4<f32>,
+    position: vec3<f32>
+}
+
+struct FrameUniform {
+    // Seconds since the renderer was created
+    time: f32,
+    delta_time: f32,
+    screen_size: vec2<f32>,
+    sun_direction: vec3<f32>,
+    fog_start: f32,
+    fog_color: vec4<f32>
+}
+
+@group(0) @binding(0)
+var<uniform> camera: CameraUniform;
+
+@group(0) @binding(1)
+var<uniform> frame: FrameUniform;
+
@@ -0,0 +1,72 @@
+struct AtlasUniform {
+    rows: u32,
+    columns: u32
+}
+
+@group(0) @binding(0)
+var texture_atlas: texture_2d<f32>;
+
+@group(0) @binding(1)
+var atlas_sampler: sampler;
+
+@group(0) @binding(2)
+var<uniform> atlas: AtlasUniform;
+
+struct InstanceInput {
+    @location(0) min: vec2<f32>,
+    @location(1) max: vec2<f32>,
+    @location(2) color: vec4<f32>,
+    @location(3) texture_id: u32,
+    @location(4) flags: u32,
+    @builtin(vertex_index) vertex_index: u32
+}
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+    @location(1) color: vec4<f32>,
+    @location(2) @interpolate(flat) textured: u32
+}
+
+// Two triangles of a unit quad, wound counter-clockwise
+var<private> corners: array<vec2<f32>, 6> = array<vec2<f32>, 6>(
+    vec2<f32>(0.0, 0.0),
+    vec2<f32>(1.0, 0.0),
+    vec2<f32>(1.0, 1.0),
+    vec2<f32>(1.0, 1.0),
+    vec2<f32>(0.0, 1.0),
+    vec2<f32>(0.0, 0.0)
+);
+
+const TEXTURED: u32 = 1u;
+const FOLIAGE: u32 = 2u;
+// Same as the particles, the hotbar has no temperature to tint with
+const FOLIAGE_TINT: vec3<f32> = vec3<f32>(0.13, 0.31, 0.04);
+
+@vertex
+fn vs_main(in: InstanceInput) -> VertexOutput {
+    var out: VertexOutput;
+    let corner = corners[in.vertex_index];
+
+    out.clip_position = vec4<f32>(mix(in.min, in.max, corner), 0.0, 1.0);
+
+    let tile = vec2<f32>(f32(in.texture_id % atlas.columns), f32(in.texture_id / atlas.columns));
+    out.uv = (tile + vec2<f32>(corner.x, 1.0 - corner.y)) / vec2<f32>(f32(atlas.columns), f32(atlas.rows));
+
+    let tint = select(vec3<f32>(1.0), FOLIAGE_TINT, (in.flags & FOLIAGE) != 0u);
+    out.color = vec4<f32>(in.color.rgb * tint, in.color.a);
+    out.textured = in.flags & TEXTURED;
+
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    // Sampled outside the branch, derivatives need uniform control flow
+    let texture_color = textureSample(texture_atlas, atlas_sampler, in.uv);
+    if in.textured == 0u {
+        return in.color;
+    }
+
+    return texture_color * in.color;
+}
@@ -0,0 +1,72 @@
+struct AtlasUniform {
+    rows: u32,
+    columns: u32
+}
+
+@group(1) @binding(0)
+var texture_atlas: texture_2d<f32>;
+
+@group(1) @binding(1)
+var atlas_sampler: sampler;
+
+@group(1) @binding(2)
+var<uniform> atlas: AtlasUniform;
+
+struct InstanceInput {
+    @location(0) position: vec3<f32>,
+    @location(1) size: f32,
+    @location(2) uv_offset: vec2<f32>,
+    @location(3) texture_id: u32,
+    @location(4) tint: u32,
+    @location(5) alpha: f32,
+    @builtin(vertex_index) vertex_index: u32
+}
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+    @location(1) tint: vec3<f32>,
+    @location(2) alpha: f32
+}
+
+// Two triangles of a unit quad, wound counter-clockwise
+var<private> corners: array<vec2<f32>, 6> = array<vec2<f32>, 6>(
+    vec2<f32>(0.0, 0.0),
+    vec2<f32>(1.0, 0.0),
+    vec2<f32>(1.0, 1.0),
+    vec2<f32>(1.0, 1.0),
+    vec2<f32>(0.0, 1.0),
+    vec2<f32>(0.0, 0.0)
+);
+
+// Particles carry no temperature, tinted blocks break into the plains color
+const FOLIAGE_TINT: vec3<f32> = vec3<f32>(0.13, 0.31, 0.04);
+// Share of the tile every particle shows
+const PIECE_SIZE: f32 = 0.25;
+
+@vertex
+fn vs_main(in: InstanceInput) -> VertexOutput {
+    var out: VertexOutput;
+    let corner = corners[in.vertex_index];
+
+    // Offset in view space, so the quad always faces the camera
+    let view_position = camera.transformation_matrix * vec4<f32>(in.position, 1.0);
+    let offset = (corner - 0.5) * in.size;
+    out.clip_position = camera.projection_matrix * (view_position + vec4<f32>(offset, 0.0, 0.0));
+
+    let tile = vec2<f32>(f32(in.texture_id % atlas.columns), f32(in.texture_id / atlas.columns));
+    let piece = in.uv_offset + vec2<f32>(corner.x, 1.0 - corner.y) * PIECE_SIZE;
+    out.uv = (tile + piece) / vec2<f32>(f32(atlas.columns), f32(atlas.rows));
+
+    out.tint = select(vec3<f32>(1.0), FOLIAGE_TINT, in.tint != 0u);
+    out.alpha = in.alpha;
+
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let texture_color = textureSample(texture_atlas, atlas_sampler, in.uv);
+
+    return vec4<f32>(texture_color.rgb * in.tint, texture_color.a * in.alpha);
+}
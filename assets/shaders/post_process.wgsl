@@ -0,0 +1,98 @@
+@group(0) @binding(0)
+var scene: texture_2d<f32>;
+
+@group(0) @binding(1)
+var scene_sampler: sampler;
+
+struct PostProcessUniform {
+    underwater: u32,
+    bloom_intensity: f32,
+    paused: u32,
+    exposure: f32,
+    // Applied to the tonemapped color, 1.0 leaves it as is
+    gamma: f32
+}
+
+@group(0) @binding(2)
+var<uniform> settings: PostProcessUniform;
+
+@group(0) @binding(3)
+var bloom: texture_2d<f32>;
+
+struct VertexInput {
+    @location(0) position: vec2<f32>,
+    @location(1) uv: vec2<f32>
+}
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) uv: vec2<f32>
+}
+
+@vertex
+fn vs_main(in: VertexInput) -> VertexOutput {
+    var out: VertexOutput;
+    out.clip_position = vec4<f32>(in.position, 0.0, 1.0);
+    out.uv = in.uv;
+
+    return out;
+}
+
+override ACES: bool = false;
+override SRGB_SURFACE: bool = true;
+
+fn reinhard(color: vec3<f32>) -> vec3<f32> {
+    return color / (1.0 + color);
+}
+
+// Narkowicz's fit of the ACES filmic curve
+fn aces(color: vec3<f32>) -> vec3<f32> {
+    let numerator = color * (2.51 * color + 0.03);
+    let denominator = color * (2.43 * color + 0.59) + 0.14;
+    return clamp(numerator / denominator, vec3<f32>(0.0), vec3<f32>(1.0));
+}
+
+fn linear_to_srgb(color: vec3<f32>) -> vec3<f32> {
+    let low = color * 12.92;
+    let high = 1.055 * pow(color, vec3<f32>(1.0 / 2.4)) - 0.055;
+    return select(high, low, color <= vec3<f32>(0.0031308));
+}
+
+fn tonemap(color: vec3<f32>) -> vec3<f32> {
+    var mapped: vec3<f32>;
+    if ACES {
+        mapped = aces(color * settings.exposure);
+    } else {
+        mapped = reinhard(color * settings.exposure);
+    }
+    mapped = pow(mapped, vec3<f32>(1.0 / settings.gamma));
+
+    if SRGB_SURFACE {
+        return mapped;
+    }
+    return linear_to_srgb(mapped);
+}
+
+// Brightness of the scene left while paused
+const PAUSED_DIM: f32 = 0.4;
+
+const WATER_TINT: vec3<f32> = vec3<f32>(0.1, 0.3, 0.6);
+const WATER_TINT_STRENGTH: f32 = 0.55;
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    var color = textureSample(scene, scene_sampler, in.uv);
+    let highlights = textureSample(bloom, scene_sampler, in.uv).rgb;
+    color = vec4<f32>(color.rgb + highlights * settings.bloom_intensity, color.a);
+
+    let dim = select(1.0, PAUSED_DIM, settings.paused != 0u);
+    if settings.underwater == 0u {
+        return vec4<f32>(tonemap(color.rgb) * dim, color.a);
+    }
+
+    // Darker towards the edges, so only the middle of the screen stays readable
+    let vignette = 1.0 - smoothstep(0.2, 0.8, distance(in.uv, vec2<f32>(0.5)));
+    let tinted = mix(color.rgb, WATER_TINT, WATER_TINT_STRENGTH) * mix(0.45, 1.0, vignette);
+
+    return vec4<f32>(tonemap(tinted) * dim, color.a);
+}
@@ -0,0 +1,30 @@
+@group(0) @binding(0)
+var<uniform> light_matrix: mat4x4<f32>;
+
+@group(1) @binding(0)
+var<uniform> transformation: vec3<i32>;
+
+// Blocks along every side of a chunk, the transformation is in chunks
+override CHUNK_SIZE: i32;
+
+const CROSS_DIRECTION: u32 = 6u;
+
+@vertex
+fn vs_main(@location(0) packed: u32) -> @builtin(position) vec4<f32> {
+    let transformation = vec3<f32>(transformation * CHUNK_SIZE);
+
+    // Plants would cast the shadow of their whole quads, holes included
+    if ((packed >> 6) & 0x7) == CROSS_DIRECTION {
+        return vec4<f32>(0.0);
+    }
+
+    // Half blocks
+    let x = f32((packed >> 25) & 0x7f) * 0.5;
+    let y = f32((packed >> 18) & 0x7f) * 0.5;
+    let z = f32((packed >> 11) & 0x7f) * 0.5;
+
+    return light_matrix * vec4<f32>(transformation + vec3<f32>(x, y, z), 1.0);
+}
+
+@fragment
+fn fs_main() {}
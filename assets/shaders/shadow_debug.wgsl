@@ -0,0 +1,30 @@
+@group(1) @binding(0)
+var shadow_map: texture_depth_2d_array;
+
+struct VertexInput {
+    @location(0) position: vec2<f32>,
+    @location(1) uv: vec2<f32>
+}
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) uv: vec2<f32>
+}
+
+@vertex
+fn vs_main(in: VertexInput) -> VertexOutput {
+    var out: VertexOutput;
+    out.clip_position = vec4<f32>(in.position, 0.0, 1.0);
+    out.uv = in.uv;
+
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let size = vec2<f32>(textureDimensions(shadow_map));
+    let texel = vec2<i32>(min(in.uv * size, size - 1.0));
+    let depth = textureLoad(shadow_map, texel, 0, 0);
+
+    return vec4<f32>(vec3<f32>(depth), 1.0);
+}
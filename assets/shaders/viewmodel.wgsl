@@ -0,0 +1,102 @@
+struct AtlasUniform {
+    rows: u32,
+    columns: u32
+}
+
+@group(1) @binding(0)
+var texture_atlas: texture_2d<f32>;
+
+@group(1) @binding(1)
+var atlas_sampler: sampler;
+
+@group(1) @binding(2)
+var<uniform> atlas: AtlasUniform;
+
+struct ViewmodelUniform {
+    // From the block to view space
+    model: mat4x4<f32>
+}
+
+@group(2) @binding(0)
+var<uniform> viewmodel: ViewmodelUniform;
+
+struct VertexInput {
+    @location(0) packed: u32,
+    @location(1) texture_light: u32,
+    @builtin(vertex_index) vertex_index: u32
+}
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+    @location(1) color: vec3<f32>
+}
+
+// Squeezes the block into the front of the depth range, so it's never inside the world
+const DEPTH_RANGE: f32 = 0.01;
+// The front of the depth range is at 1 instead of 0
+override REVERSED_Z: bool = false;
+// Texels below this alpha are holes
+const ALPHA_CUTOFF: f32 = 0.5;
+// Same as the hotbar, the held block has no temperature to tint with
+const FOLIAGE_TINT: vec3<f32> = vec3<f32>(0.13, 0.31, 0.04);
+const MAX_LIGHT: f32 = 15.0;
+
+// Indexed by the face direction like the world normals, the block is lit from above
+var<private> shades: array<f32, 7> = array<f32, 7>(1.0, 0.5, 0.8, 0.8, 0.65, 0.65, 0.9);
+
+fn calculate_uv(texture_id: u32, vertex_index: u32) -> vec2<f32> {
+    let tile = vec2<f32>(f32(texture_id % atlas.columns), f32(texture_id / atlas.columns));
+    var corner: vec2<f32>;
+    switch (vertex_index % 4u) {
+        case 0u: {
+            corner = vec2<f32>(0.0, 0.0);
+        }
+        case 1u: {
+            corner = vec2<f32>(1.0, 0.0);
+        }
+        case 2u: {
+            corner = vec2<f32>(1.0, 1.0);
+        }
+        case 3u, default: {
+            corner = vec2<f32>(0.0, 1.0);
+        }
+    }
+
+    return (tile + corner) / vec2<f32>(f32(atlas.columns), f32(atlas.rows));
+}
+
+@vertex
+fn vs_main(in: VertexInput) -> VertexOutput {
+    var out: VertexOutput;
+
+    // Half blocks
+    let x = f32((in.packed >> 25) & 0x7f) * 0.5;
+    let y = f32((in.packed >> 18) & 0x7f) * 0.5;
+    let z = f32((in.packed >> 11) & 0x7f) * 0.5;
+    let direction = (in.packed >> 6) & 0x7;
+    let tint = in.packed & 0x3f;
+    let texture_id = in.texture_light >> 4;
+
+    var clip_position = camera.projection_matrix * viewmodel.model * vec4<f32>(x, y, z, 1.0);
+    let near = select(0.0, clip_position.w, REVERSED_Z);
+    clip_position.z = near + (clip_position.z - near) * DEPTH_RANGE;
+    out.clip_position = clip_position;
+    out.uv = calculate_uv(texture_id, in.vertex_index);
+
+    let block_light = f32(in.texture_light & 0xf) / MAX_LIGHT;
+    let tint_color = select(vec3<f32>(1.0), FOLIAGE_TINT, tint != 0u);
+    out.color = tint_color * max(shades[direction], block_light);
+
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let texture_color = textureSample(texture_atlas, atlas_sampler, in.uv);
+    if texture_color.a < ALPHA_CUTOFF {
+        discard;
+    }
+
+    return vec4<f32>(texture_color.rgb * in.color, 1.0);
+}
@@ -1,17 +1,8 @@
-struct CameraUniform {
-    projection_matrix: mat4x4<f32>,
-    transformation_matrix: mat4x4<f32>,
-    position: vec3<f32>    
-}
-
 struct AtlasUniform {
     rows: u32,
     columns: u32
 }
 
-@group(0) @binding(0)
-var<uniform> camera: CameraUniform;
-
 @group(1) @binding(0)
 var texture_atlas: texture_2d<f32>;
 
@@ -21,11 +12,41 @@ var atlas_sampler: sampler;
 @group(1) @binding(2)
 var<uniform> atlas: AtlasUniform;
 
+struct BlocksUniform {
+    break_position: vec3<i32>,
+    break_stage: u32,
+    crack_texture_id: u32,
+    water_texture_id: u32
+}
+
+@group(1) @binding(3)
+var<uniform> blocks: BlocksUniform;
+
 @group(2) @binding(0)
 var<uniform> transformation: vec3<i32>;
 
+// Blocks along every side of a chunk, the transformation is in chunks
+override CHUNK_SIZE: i32;
+
+struct LightingUniform {
+    light_matrices: array<mat4x4<f32>, 2>,
+    sun_direction: vec3<f32>,
+    shadows: u32,
+    cascade_splits: vec4<f32>
+}
+
+@group(3) @binding(0)
+var<uniform> lighting: LightingUniform;
+
+@group(3) @binding(1)
+var shadow_map: texture_depth_2d_array;
+
+@group(3) @binding(2)
+var shadow_sampler: sampler_comparison;
+
 struct VertexInput {
     @location(0) packed: u32,
+    @location(1) texture_light: u32,
     @builtin(vertex_index) vertex_index: u32
 }
 
@@ -34,7 +55,14 @@ struct VertexOutput {
     
     @location(0) uv: vec2<f32>,
     @location(1) ao: f32,
-    @location(2) frag_pos: vec3<f32>
+    @location(2) frag_pos: vec3<f32>,
+    @location(3) @interpolate(flat) direction: u32,
+    @location(4) view_depth: f32,
+    @location(5) tint: vec3<f32>,
+    @location(6) @interpolate(flat) texture_id: u32,
+    @location(7) block_light: f32,
+    // Decoded from the direction, faces are flat so it's the same across the whole face
+    @location(8) @interpolate(flat) normal: vec3<f32>
 }
 
 fn calculate_uv(
@@ -65,40 +93,157 @@ fn calculate_uv(
 
 var<private> ao_lerps: vec4<f32> = vec4<f32>(0.1, 0.25, 0.5, 1.0);
 
+// Indexed by the face direction: top, bottom, left, right, front, back and cross
+var<private> normals: array<vec3<f32>, 7> = array<vec3<f32>, 7>(
+    vec3<f32>(0.0, 1.0, 0.0),
+    vec3<f32>(0.0, -1.0, 0.0),
+    vec3<f32>(-1.0, 0.0, 0.0),
+    vec3<f32>(1.0, 0.0, 0.0),
+    vec3<f32>(0.0, 0.0, 1.0),
+    vec3<f32>(0.0, 0.0, -1.0),
+    // Plants are seen from both sides, they're lit like the ground they stand on
+    vec3<f32>(0.0, 1.0, 0.0)
+);
+
+// Faces of cross shaped blocks, drawn on diagonal planes through the middle of the block
+const CROSS_DIRECTION: u32 = 6u;
+// Texels of cross faces below this alpha are holes
+const ALPHA_CUTOFF: f32 = 0.5;
+
+// Multiplied over the grayscale foliage textures, from the coldest to the hottest columns
+const WINTER_FOLIAGE: vec3<f32> = vec3<f32>(0.08, 0.27, 0.18);
+const PLAINS_FOLIAGE: vec3<f32> = vec3<f32>(0.13, 0.31, 0.04);
+const DESERT_FOLIAGE: vec3<f32> = vec3<f32>(0.38, 0.35, 0.08);
+
+// Zero leaves the face untinted, the rest maps linearly onto the temperature
+fn foliage_tint(tint: u32) -> vec3<f32> {
+    if tint == 0u {
+        return vec3<f32>(1.0);
+    }
+
+    let temperature = f32(tint - 1u) / 62.0;
+    let cold = mix(WINTER_FOLIAGE, PLAINS_FOLIAGE, smoothstep(0.3, 0.45, temperature));
+    return mix(cold, DESERT_FOLIAGE, smoothstep(0.45, 0.6, temperature));
+}
+
+const MAX_LIGHT: f32 = 15.0;
+// Warm color of block light at its brightest
+const BLOCK_LIGHT: vec3<f32> = vec3<f32>(1.0, 0.82, 0.6);
+
 @vertex
 fn vs_main(in: VertexInput) -> VertexOutput {
     var out: VertexOutput;
-    let transformation = vec3<f32>(transformation * 16);
+    let transformation = vec3<f32>(transformation * CHUNK_SIZE);
+
+    // Half blocks
+    let x = f32((in.packed >> 25) & 0x7f) * 0.5;
+    let y = f32((in.packed >> 18) & 0x7f) * 0.5;
+    let z = f32((in.packed >> 11) & 0x7f) * 0.5;
 
-    let x = f32((in.packed >> 27) & 0x1f);
-    let y = f32((in.packed >> 22) & 0x1f);
-    let z = f32((in.packed >> 17) & 0x1f);
+    let ao_value = (in.packed >> 9) & 0x3;
+    let direction = (in.packed >> 6) & 0x7;
+    let tint = in.packed & 0x3f;
+    let texture_id = in.texture_light >> 4;
 
-    let ao_value = (in.packed >> 15) & 0x3;
-    let texture_id = (in.packed >> 9) & 0x3f;
+    let position = vec4<f32>(transformation + vec3<f32>(x, y, z), 1.0);
+    let view_position = camera.transformation_matrix * position;
 
     out.uv = calculate_uv(texture_id, in.vertex_index);
-    out.clip_position = camera.projection_matrix * camera.transformation_matrix * vec4<f32>(transformation + vec3<f32>(x, y, z), 1.0);
+    out.clip_position = camera.projection_matrix * view_position;
     out.ao = ao_lerps[ao_value];
-    out.frag_pos = transformation + vec3<f32>(x, y, z);
+    out.frag_pos = position.xyz;
+    out.direction = direction;
+    out.normal = normals[direction];
+    out.view_depth = -view_position.z;
+    out.tint = foliage_tint(tint);
+    out.texture_id = texture_id;
+    out.block_light = f32(in.texture_light & 0xf) / MAX_LIGHT;
 
     return out;
 }
 
-const FOG_START: f32 = 260.0;
-const FOG_COLOR: vec4<f32> = vec4<f32>(1.0, 1.0, 1.0, 1.0);
-
 fn ease_in_quint(x: f32) -> f32 {
     return x * x * x * x * x;
 }
 
+const AMBIENT_LIGHT: f32 = 0.55;
+// Pushes the lookup off the surface along the normal to avoid shadow acne
+const NORMAL_OFFSET: f32 = 0.05;
+
+fn shadow(frag_pos: vec3<f32>, normal: vec3<f32>, view_depth: f32) -> f32 {
+    if lighting.shadows == 0u || view_depth >= lighting.cascade_splits.z {
+        return 1.0;
+    }
+
+    var cascade = 0u;
+    if view_depth >= lighting.cascade_splits.y {
+        cascade = 1u;
+    }
+
+    let offset = normal * NORMAL_OFFSET * f32(cascade + 1u);
+    let light_position = lighting.light_matrices[cascade] * vec4<f32>(frag_pos + offset, 1.0);
+    let ndc = light_position.xyz / light_position.w;
+    let uv = ndc.xy * vec2<f32>(0.5, -0.5) + 0.5;
+
+    // 3x3 percentage closer filtering
+    let texel = 1.0 / vec2<f32>(textureDimensions(shadow_map));
+    var lit = 0.0;
+    for (var x = -1; x <= 1; x++) {
+        for (var y = -1; y <= 1; y++) {
+            let sample_uv = uv + vec2<f32>(f32(x), f32(y)) * texel;
+            lit += textureSampleCompareLevel(shadow_map, shadow_sampler, sample_uv, cascade, ndc.z);
+        }
+    }
+
+    return lit / 9.0;
+}
+
+// Crack drawn over the faces of the block being broken, transparent everywhere else
+fn crack(frag_pos: vec3<f32>, inward: vec3<f32>, uv: vec2<f32>) -> vec4<f32> {
+    let block = vec3<i32>(floor(frag_pos + inward * 0.5));
+    if blocks.break_stage == 0u || any(block != blocks.break_position) {
+        return vec4<f32>(0.0);
+    }
+
+    let tiles = vec2<f32>(f32(atlas.columns), f32(atlas.rows));
+    let texture_id = blocks.crack_texture_id;
+    let tile = vec2<f32>(f32(texture_id % atlas.columns), f32(texture_id / atlas.columns));
+
+    return textureSampleLevel(texture_atlas, atlas_sampler, (tile + fract(uv * tiles)) / tiles, 0.0);
+}
+
+// Tiles per second the water texture slides by
+const WATER_SCROLL: vec2<f32> = vec2<f32>(0.05, 0.03);
+
+// Slides the water texture within its tile, other textures stay put
+fn scroll(uv: vec2<f32>, texture_id: u32) -> vec2<f32> {
+    let tiles = vec2<f32>(f32(atlas.columns), f32(atlas.rows));
+    let tile = vec2<f32>(f32(texture_id % atlas.columns), f32(texture_id / atlas.columns));
+    let scrolled = (tile + fract(uv * tiles + WATER_SCROLL * frame.time)) / tiles;
+
+    return select(uv, scrolled, texture_id == blocks.water_texture_id);
+}
+
 @fragment
 fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
-    let texture_color = textureSample(texture_atlas, atlas_sampler, in.uv);
-    let color = vec4<f32>(texture_color.rgb * in.ao, texture_color.a);
+    let normal = in.normal;
+    let is_cross = in.direction == CROSS_DIRECTION;
+    // Cross faces already lie inside their block
+    let crack_color = crack(in.frag_pos, select(-normal, vec3<f32>(0.0), is_cross), in.uv);
+    let texture_color = textureSample(texture_atlas, atlas_sampler, scroll(in.uv, in.texture_id));
+    if is_cross && texture_color.a < ALPHA_CUTOFF {
+        discard;
+    }
+    let block_color = mix(texture_color.rgb * in.tint, crack_color.rgb, crack_color.a);
+
+    let diffuse = max(dot(normal, -frame.sun_direction), 0.0);
+    let sunlight = AMBIENT_LIGHT + (1.0 - AMBIENT_LIGHT) * diffuse * shadow(in.frag_pos, normal, in.view_depth);
+    // Squared, so the light falls off faster than linearly with every block
+    let light = max(vec3<f32>(sunlight), BLOCK_LIGHT * in.block_light * in.block_light);
+    let color = vec4<f32>(block_color * in.ao * light, texture_color.a);
 
-    let fog_distance = distance(camera.position.xz, in.frag_pos.xz) / FOG_START;
+    let fog_distance = distance(camera.position.xz, in.frag_pos.xz) / frame.fog_start;
     let fog = ease_in_quint(fog_distance);
     
-    return mix(color, FOG_COLOR, fog);
+    return mix(color, frame.fog_color, fog);
 }
\ No newline at end of file
@@ -0,0 +1,343 @@
+use std::{
+    collections::HashMap,
+    fs, io,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use image::{imageops, ImageError, RgbaImage};
+use thiserror::Error;
+use wgpu::TextureUsages;
+
+use crate::{AsyncTexture, Context, Spritesheet, Texture};
+
+const USAGE: TextureUsages = TextureUsages::TEXTURE_BINDING.union(TextureUsages::COPY_DST);
+
+#[derive(Debug, Error)]
+pub enum AtlasError {
+    #[error("failed to read textures from `{0}`: {1}")]
+    Io(PathBuf, io::Error),
+    #[error("failed to load texture `{0}`: {1}")]
+    Image(PathBuf, ImageError),
+    #[error("texture `{0}` is {1}x{2}, textures have to be square")]
+    NotSquare(String, u32, u32),
+    #[error("texture `{name}` is {found}px wide, the others are {expected}px")]
+    SizeMismatch {
+        name: String,
+        expected: u32,
+        found: u32,
+    },
+    #[error("no textures to build the atlas from")]
+    Empty,
+}
+
+/// Collects same sized square textures and packs them row by row into a single atlas
+#[derive(Debug, Default)]
+pub struct AtlasBuilder {
+    textures: Vec<(String, RgbaImage)>,
+}
+
+impl AtlasBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds every PNG in the directory, named after its file without the extension
+    pub fn from_dir(path: impl AsRef<Path>) -> Result<Self, AtlasError> {
+        png_files(path.as_ref())?
+            .into_iter()
+            .try_fold(Self::new(), |builder, (name, path)| {
+                Ok(builder.with_texture(name, open(&path)?))
+            })
+    }
+
+    pub fn with_texture(mut self, name: impl Into<String>, image: RgbaImage) -> Self {
+        self.textures.push((name.into(), image));
+        self
+    }
+
+    /// Packs the textures in the order of their names, the atlas sides are powers of two
+    pub fn build(mut self) -> Result<Atlas, AtlasError> {
+        self.textures.sort_by(|(a, _), (b, _)| a.cmp(b));
+        let layout = AtlasLayout::new(
+            self.textures
+                .iter()
+                .map(|(name, image)| (name.as_str(), image.dimensions())),
+        )?;
+
+        let (width, height) = layout.size();
+        let mut image = RgbaImage::new(width, height);
+        for (index, (_, texture)) in self.textures.into_iter().enumerate() {
+            let (x, y) = layout.origin(index as u32);
+            imageops::replace(&mut image, &texture, x as i64, y as i64);
+        }
+
+        Ok(Atlas { image, layout })
+    }
+}
+
+/// Where the tiles of an atlas go, which only needs the names and sizes of its textures
+#[derive(Debug, Clone)]
+pub struct AtlasLayout {
+    tile_size: u32,
+    columns: u32,
+    rows: u32,
+    tiles: HashMap<String, u32>,
+}
+
+impl AtlasLayout {
+    // The textures have to be sorted by their names already
+    fn new<'n>(
+        textures: impl ExactSizeIterator<Item = (&'n str, (u32, u32))> + Clone,
+    ) -> Result<Self, AtlasError> {
+        let (_, (tile_size, _)) = textures.clone().next().ok_or(AtlasError::Empty)?;
+        for (name, (width, height)) in textures.clone() {
+            if width != height {
+                return Err(AtlasError::NotSquare(name.to_string(), width, height));
+            }
+            if width != tile_size {
+                return Err(AtlasError::SizeMismatch {
+                    name: name.to_string(),
+                    expected: tile_size,
+                    found: width,
+                });
+            }
+        }
+
+        let count = textures.len() as u32;
+        let columns = (count as f32).sqrt().ceil() as u32;
+        let columns = columns.next_power_of_two();
+        let rows = count.div_ceil(columns).next_power_of_two();
+        let tiles = textures
+            .enumerate()
+            .map(|(index, (name, _))| (name.to_string(), index as u32))
+            .collect();
+
+        Ok(Self {
+            tile_size,
+            columns,
+            rows,
+            tiles,
+        })
+    }
+
+    pub fn tile_size(&self) -> u32 {
+        self.tile_size
+    }
+
+    /// Width and height of the whole atlas in pixels
+    pub fn size(&self) -> (u32, u32) {
+        (self.columns * self.tile_size, self.rows * self.tile_size)
+    }
+
+    pub fn tiles(&self) -> &HashMap<String, u32> {
+        &self.tiles
+    }
+
+    pub fn tile(&self, name: &str) -> Option<u32> {
+        self.tiles.get(name).copied()
+    }
+
+    // Top left pixel of the tile
+    fn origin(&self, index: u32) -> (u32, u32) {
+        (
+            index % self.columns * self.tile_size,
+            index / self.columns * self.tile_size,
+        )
+    }
+}
+
+/// Packed textures, indexed row by row from the top left tile
+#[derive(Debug)]
+pub struct Atlas {
+    image: RgbaImage,
+    layout: AtlasLayout,
+}
+
+impl Atlas {
+    /// Lays out the PNGs in the directory like [`AtlasBuilder::from_dir`] does, but only reads
+    /// their sizes right away. Decoding and packing them happens in the returned texture
+    pub fn load_async(
+        path: impl AsRef<Path>,
+        context: &Arc<Context>,
+    ) -> Result<(AtlasLayout, AsyncTexture), AtlasError> {
+        let mut files = png_files(path.as_ref())?;
+        files.sort_by(|(a, _), (b, _)| a.cmp(b));
+        let sizes = files
+            .iter()
+            .map(|(_, path)| {
+                image::image_dimensions(path).map_err(|err| AtlasError::Image(path.clone(), err))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let layout = AtlasLayout::new(
+            files
+                .iter()
+                .zip(sizes)
+                .map(|((name, _), size)| (name.as_str(), size)),
+        )?;
+
+        let texture = Texture::load_with(layout.size(), USAGE, context, move || {
+            let builder =
+                files
+                    .into_iter()
+                    .try_fold(AtlasBuilder::new(), |builder, (name, path)| {
+                        Ok::<_, AtlasError>(builder.with_texture(name, open(&path)?))
+                    })?;
+            Ok(builder.build()?.image)
+        });
+
+        Ok((layout, texture))
+    }
+
+    pub fn image(&self) -> &RgbaImage {
+        &self.image
+    }
+
+    pub fn layout(&self) -> &AtlasLayout {
+        &self.layout
+    }
+
+    pub fn tile_size(&self) -> u32 {
+        self.layout.tile_size
+    }
+
+    pub fn tiles(&self) -> &HashMap<String, u32> {
+        &self.layout.tiles
+    }
+
+    pub fn tile(&self, name: &str) -> Option<u32> {
+        self.layout.tile(name)
+    }
+
+    pub fn create_spritesheet(&self, context: &Context) -> Spritesheet {
+        let texture = Texture::from_data(&self.image, USAGE, context);
+
+        Spritesheet::new(texture, self.layout.tile_size, context)
+    }
+}
+
+// Every PNG in the directory along with its name, in no particular order
+fn png_files(path: &Path) -> Result<Vec<(String, PathBuf)>, AtlasError> {
+    let entries = fs::read_dir(path).map_err(|err| AtlasError::Io(path.to_owned(), err))?;
+
+    let mut files = Vec::new();
+    for entry in entries {
+        let path = entry
+            .map_err(|err| AtlasError::Io(path.to_owned(), err))?
+            .path();
+        if path.extension().is_none_or(|extension| extension != "png") {
+            continue;
+        }
+
+        let name = path
+            .file_stem()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .into_owned();
+        files.push((name, path));
+    }
+
+    Ok(files)
+}
+
+fn open(path: &Path) -> Result<RgbaImage, AtlasError> {
+    Ok(image::open(path)
+        .map_err(|err| AtlasError::Image(path.to_owned(), err))?
+        .to_rgba8())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, process};
+
+    use image::Rgba;
+
+    use super::*;
+
+    fn tile(size: u32, value: u8) -> RgbaImage {
+        RgbaImage::from_pixel(size, size, Rgba([value, 0, 0, 255]))
+    }
+
+    #[test]
+    fn tiles_are_packed_by_name() {
+        let atlas = ["c", "a", "e", "b", "d"]
+            .into_iter()
+            .enumerate()
+            .fold(AtlasBuilder::new(), |builder, (index, name)| {
+                builder.with_texture(name, tile(8, index as u8))
+            })
+            .build()
+            .unwrap();
+
+        // Five tiles fit into four columns and two rows
+        assert_eq!(atlas.layout().size(), (32, 16));
+        assert_eq!(atlas.tile_size(), 8);
+        assert_eq!(atlas.tile("a"), Some(0));
+        assert_eq!(atlas.tile("e"), Some(4));
+        assert_eq!(atlas.tile("f"), None);
+        assert_eq!(atlas.tiles().len(), 5);
+
+        // Sorted, "d" ends the first row and "e" starts the second
+        assert_eq!(atlas.image().get_pixel(27, 3), &Rgba([4, 0, 0, 255]));
+        assert_eq!(atlas.image().get_pixel(3, 12), &Rgba([2, 0, 0, 255]));
+        // Past the last tile
+        assert_eq!(atlas.image().get_pixel(12, 12), &Rgba([0, 0, 0, 0]));
+    }
+
+    #[test]
+    fn sides_are_powers_of_two() {
+        for count in 1..40 {
+            let layout = AtlasLayout::new(
+                (0..count)
+                    .map(|index| format!("{index:02}"))
+                    .collect::<Vec<_>>()
+                    .iter()
+                    .map(|name| (name.as_str(), (16, 16))),
+            )
+            .unwrap();
+            let (width, height) = layout.size();
+            assert!(width.is_power_of_two() && height.is_power_of_two());
+            assert!((width / 16) * (height / 16) >= count);
+            assert!(layout.origin(count - 1).1 + 16 <= height);
+        }
+    }
+
+    #[test]
+    fn mismatched_textures_are_rejected() {
+        assert!(matches!(
+            AtlasBuilder::new().build(),
+            Err(AtlasError::Empty)
+        ));
+        assert!(matches!(
+            AtlasBuilder::new()
+                .with_texture("wide", RgbaImage::new(16, 8))
+                .build(),
+            Err(AtlasError::NotSquare(name, 16, 8)) if name == "wide"
+        ));
+        assert!(matches!(
+            AtlasBuilder::new()
+                .with_texture("a", tile(16, 0))
+                .with_texture("b", tile(32, 0))
+                .build(),
+            Err(AtlasError::SizeMismatch { name, expected: 16, found: 32 }) if name == "b"
+        ));
+    }
+
+    #[test]
+    fn directories_load_their_pngs() {
+        let dir = env::temp_dir().join(format!("voxel-atlas-{}", process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        tile(4, 1).save(dir.join("stone.png")).unwrap();
+        tile(4, 2).save(dir.join("dirt.png")).unwrap();
+        fs::write(dir.join("notes.txt"), "not a texture").unwrap();
+
+        let atlas = AtlasBuilder::from_dir(&dir).map(AtlasBuilder::build);
+        fs::remove_dir_all(&dir).unwrap();
+
+        let atlas = atlas.unwrap().unwrap();
+        assert_eq!(atlas.tile("dirt"), Some(0));
+        assert_eq!(atlas.tile("stone"), Some(1));
+        assert_eq!(atlas.tiles().len(), 2);
+        assert_eq!(atlas.image().get_pixel(5, 0), &Rgba([1, 0, 0, 255]));
+    }
+}
@@ -1,10 +1,10 @@
-use std::{marker::PhantomData, num::NonZeroU32, ops::Deref, sync::OnceLock};
+use std::{marker::PhantomData, num::NonZeroU32, ops::Deref};
 use wgpu::{
-    BindGroup, BindGroupEntry, BindGroupLayout, BindGroupLayoutEntry, BindingResource, BindingType,
-    ShaderStages,
+    BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayout, BindGroupLayoutEntry,
+    BindingResource, BindingType, ShaderStages,
 };
 
-use crate::{context::Context, count, tuple_impl};
+use crate::{context::Context, tuple_impl};
 type SmallVec<T> = smallvec::SmallVec<[T; 8]>;
 
 pub trait AsShaderStages {
@@ -41,7 +41,7 @@ impl AsShaderStages for VertexFragment {
 pub trait Binding {
     fn ty() -> BindingType;
     fn count() -> Option<NonZeroU32>;
-    fn resource(&self) -> BindingResource;
+    fn resource(&self) -> BindingResource<'_>;
 }
 
 #[derive(Debug)]
@@ -65,11 +65,11 @@ pub trait BindingEntries {
     type Bindings<'b>: BindingResources
     where
         Self: 'b;
-    fn binding_entries() -> &'static [BindGroupLayoutEntry];
+    fn binding_entries() -> SmallVec<BindGroupLayoutEntry>;
 }
 
 pub trait BindingResources {
-    fn binding_resources(&self) -> SmallVec<BindGroupEntry>;
+    fn binding_resources(&self) -> SmallVec<BindGroupEntry<'_>>;
 }
 
 #[derive(Debug)]
@@ -90,6 +90,16 @@ impl ShaderResource {
     pub fn layout(&self) -> &BindGroupLayout {
         &self.layout
     }
+
+    /// Binds other resources to the same layout, pipelines created with it keep working
+    pub fn rebind<L: BindingEntries>(&mut self, bindings: L::Bindings<'_>, context: &Context) {
+        let resources = bindings.binding_resources();
+        self.bind_group = context.device().create_bind_group(&BindGroupDescriptor {
+            label: None,
+            layout: &self.layout,
+            entries: &resources,
+        });
+    }
 }
 
 pub trait AsBindGroup {
@@ -121,12 +131,12 @@ macro_rules! impl_into_binding_entries {
         impl<$([<$generic S>]: AsShaderStages, [<$generic B>]: Binding),*> BindingEntries for ($(([<$generic S>], [<$generic B>])),*, ) {
             type Bindings<'b> = ($(&'b [<$generic B>]),*,) where Self: 'b;
 
-            fn binding_entries() -> &'static [BindGroupLayoutEntry] {
-                static LOCK: OnceLock<[BindGroupLayoutEntry; { count!($($generic)*) }]> = OnceLock::new();
-
+            // A `static` here would be shared by every tuple of the same arity
+            fn binding_entries() -> SmallVec<BindGroupLayoutEntry> {
                 let mut index = 0;
-                LOCK.get_or_init(|| {
-                    [$(BindGroupLayoutEntry {
+
+                #[allow(unused_assignments)]
+                SmallVec::from_iter([$(BindGroupLayoutEntry {
                     binding: {
                         let binding = index;
                         index += 1;
@@ -135,13 +145,12 @@ macro_rules! impl_into_binding_entries {
                     ty: [<$generic B>]::ty(),
                     count: [<$generic B>]::count(),
                     visibility: [<$generic S>]::as_shader_stages()
-                    }),*]
-                })
+                }),*])
             }
         }
 
         impl<'b, $($generic: Binding),*> BindingResources for ($(&'b $generic),*, ) {
-            fn binding_resources(&self) -> SmallVec<BindGroupEntry> {
+            fn binding_resources(&self) -> SmallVec<BindGroupEntry<'_>> {
                 let ($([<$generic:lower>]),*,) = self;
                 let mut index = 0;
 
@@ -167,22 +176,18 @@ impl<AS: AsShaderStages, AB: Binding> BindingEntries for (AS, AB) {
     where
         Self: 'b;
 
-    fn binding_entries() -> &'static [BindGroupLayoutEntry] {
-        static LOCK: OnceLock<[BindGroupLayoutEntry; 1]> = OnceLock::new();
-
-        LOCK.get_or_init(|| {
-            [BindGroupLayoutEntry {
-                binding: 0,
-                visibility: AS::as_shader_stages(),
-                ty: AB::ty(),
-                count: AB::count(),
-            }]
-        })
+    fn binding_entries() -> SmallVec<BindGroupLayoutEntry> {
+        SmallVec::from_iter([BindGroupLayoutEntry {
+            binding: 0,
+            visibility: AS::as_shader_stages(),
+            ty: AB::ty(),
+            count: AB::count(),
+        }])
     }
 }
 
-impl<'b, A: Binding> BindingResources for &'b A {
-    fn binding_resources(&self) -> SmallVec<BindGroupEntry> {
+impl<A: Binding> BindingResources for &A {
+    fn binding_resources(&self) -> SmallVec<BindGroupEntry<'_>> {
         SmallVec::from_iter([BindGroupEntry {
             binding: 0,
             resource: self.resource(),
@@ -0,0 +1,143 @@
+use std::{marker::PhantomData, mem::size_of};
+
+use bytemuck::Pod;
+use wgpu::{Buffer, BufferAddress, BufferDescriptor, BufferSlice, BufferUsages};
+
+use crate::Context;
+
+const GROWTH_FACTOR: usize = 2;
+
+/// Buffer rewritten every frame with however many elements there are, recreated larger
+/// when they no longer fit
+#[derive(Debug)]
+pub struct GrowableBuffer<T> {
+    buffer: Buffer,
+    label: &'static str,
+    usage: BufferUsages,
+    capacity: usize,
+    len: usize,
+    _marker: PhantomData<T>,
+}
+
+impl<T: Pod> GrowableBuffer<T> {
+    /// `usage` is what the buffer is bound as, uploads add `COPY_DST` on their own
+    pub fn new(
+        label: &'static str,
+        usage: BufferUsages,
+        capacity: usize,
+        context: &Context,
+    ) -> Self {
+        let usage = usage | BufferUsages::COPY_DST;
+        let capacity = capacity.max(1);
+
+        Self {
+            buffer: Self::create_buffer(label, usage, capacity, context),
+            label,
+            usage,
+            capacity,
+            len: 0,
+            _marker: PhantomData,
+        }
+    }
+
+    fn create_buffer(
+        label: &'static str,
+        usage: BufferUsages,
+        capacity: usize,
+        context: &Context,
+    ) -> Buffer {
+        context.device().create_buffer(&BufferDescriptor {
+            label: Some(label),
+            size: (capacity * size_of::<T>()) as BufferAddress,
+            usage,
+            mapped_at_creation: false,
+        })
+    }
+
+    /// Replaces the contents with `data` through the staging belt, returns whether the
+    /// buffer had to be recreated
+    pub fn write(&mut self, data: &[T], context: &Context) -> bool {
+        let capacity = grown_capacity(self.capacity, data.len());
+        let recreated = capacity != self.capacity;
+        if recreated {
+            self.buffer = Self::create_buffer(self.label, self.usage, capacity, context);
+            self.capacity = capacity;
+        }
+
+        self.len = data.len();
+        context.staging_belt().write_buffer(
+            &self.buffer,
+            0,
+            bytemuck::cast_slice(data),
+            context.device(),
+        );
+
+        recreated
+    }
+
+    /// Part of the buffer holding the last write, it can't be empty
+    pub fn slice(&self) -> BufferSlice<'_> {
+        self.buffer
+            .slice(..(self.len * size_of::<T>()) as BufferAddress)
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn buffer(&self) -> &Buffer {
+        &self.buffer
+    }
+}
+
+/// Capacity for `len` elements, kept as is while they fit and multiplied otherwise
+fn grown_capacity(capacity: usize, len: usize) -> usize {
+    let mut capacity = capacity;
+    while capacity < len {
+        capacity *= GROWTH_FACTOR;
+    }
+    capacity
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn capacity_doubles_until_it_fits() {
+        let mut capacity = 4;
+        let mut grown = Vec::new();
+        for len in [3, 5, 9, 9, 17, 40, 100] {
+            capacity = grown_capacity(capacity, len);
+            assert!(capacity >= len);
+            grown.push(capacity);
+        }
+        assert_eq!(grown, [4, 8, 16, 16, 32, 64, 128]);
+    }
+
+    #[test]
+    fn exact_fits_are_kept() {
+        assert_eq!(grown_capacity(16, 16), 16);
+        assert_eq!(grown_capacity(16, 0), 16);
+        assert_eq!(grown_capacity(16, 17), 32);
+        assert_eq!(grown_capacity(1, 1024), 1024);
+    }
+
+    #[test]
+    fn shrinking_keeps_the_capacity() {
+        let capacity = grown_capacity(8, 60);
+        assert_eq!(capacity, 64);
+        // Fewer elements never give memory back
+        assert_eq!(grown_capacity(capacity, 2), 64);
+        // And growing again starts from what is there
+        assert_eq!(grown_capacity(grown_capacity(capacity, 2), 65), 128);
+    }
+}
@@ -3,18 +3,20 @@ use std::{
     sync::{Arc, Mutex, MutexGuard},
 };
 
+use arc_swap::ArcSwap;
+use log::info;
 use thiserror::Error;
 use wgpu::{
-    Backends, BindGroup, BindGroupDescriptor, BindGroupLayout, BindGroupLayoutDescriptor,
-    CreateSurfaceError, Device, DeviceDescriptor, Instance, InstanceDescriptor, PipelineLayout,
-    PipelineLayoutDescriptor, PowerPreference, PresentMode, Queue, RequestAdapterOptions,
-    RequestDeviceError, Surface, SurfaceConfiguration,
+    AdapterInfo, Backends, BindGroup, BindGroupDescriptor, BindGroupLayout,
+    BindGroupLayoutDescriptor, CreateSurfaceError, Device, DeviceDescriptor, Features, Instance,
+    InstanceDescriptor, PipelineLayout, PipelineLayoutDescriptor, PowerPreference, PresentMode,
+    Queue, RequestAdapterOptions, RequestDeviceError, Surface, SurfaceConfiguration,
 };
 use winit::{dpi::PhysicalSize, window::Window};
 
 use crate::{
     bind_group::{BindingEntries, BindingResources, Layout, ShaderResource},
-    BasePipeline, RenderPipelineBuilder, VertexLayout,
+    BasePipeline, RenderPipelineBuilder, Staging, VertexLayout,
 };
 
 #[derive(Debug, Error, Clone)]
@@ -29,12 +31,81 @@ pub enum ContextError {
     Adapter,
 }
 
+/// Surface changes requested since the configuration was last applied
+#[derive(Debug, Default, Clone, Copy)]
+struct PendingConfig {
+    size: Option<PhysicalSize<u32>>,
+    present_mode: Option<PresentMode>,
+}
+
+/// Surface configuration readers take snapshots of, and the changes waiting to replace it
+#[derive(Debug)]
+struct SurfaceConfigs {
+    // Readers take snapshots, so a resize never waits on whoever holds the configuration
+    current: ArcSwap<SurfaceConfiguration>,
+    pending: Mutex<PendingConfig>,
+}
+
+impl SurfaceConfigs {
+    fn new(config: SurfaceConfiguration) -> Self {
+        Self {
+            current: ArcSwap::from_pointee(config),
+            pending: Mutex::default(),
+        }
+    }
+
+    fn current(&self) -> Arc<SurfaceConfiguration> {
+        self.current.load_full()
+    }
+
+    fn request_size(&self, size: PhysicalSize<u32>) {
+        self.pending.lock().expect("lock failed").size = Some(size);
+    }
+
+    fn request_present_mode(&self, present_mode: PresentMode) {
+        self.pending.lock().expect("lock failed").present_mode = Some(present_mode);
+    }
+
+    /// Configuration the pending requests lead to, `None` when it's the current one or the
+    /// surface would have no area
+    fn pending(&self) -> Option<SurfaceConfiguration> {
+        let pending = *self.pending.lock().expect("lock failed");
+        let current = self.current();
+        let size = pending
+            .size
+            .unwrap_or(PhysicalSize::new(current.width, current.height));
+        if size.width == 0 || size.height == 0 {
+            return None;
+        }
+
+        let config = SurfaceConfiguration {
+            width: size.width,
+            height: size.height,
+            present_mode: pending.present_mode.unwrap_or(current.present_mode),
+            ..SurfaceConfiguration::clone(&current)
+        };
+        let changed = config.width != current.width
+            || config.height != current.height
+            || config.present_mode != current.present_mode;
+        changed.then_some(config)
+    }
+
+    /// Replaces the current configuration with the pending one and returns it, if it changed
+    fn publish(&self) -> Option<Arc<SurfaceConfiguration>> {
+        let config = Arc::new(self.pending()?);
+        self.current.store(Arc::clone(&config));
+        Some(config)
+    }
+}
+
 #[derive(Debug)]
 pub struct Context {
     device: Device,
     queue: Queue,
-    config: Mutex<SurfaceConfiguration>,
+    configs: SurfaceConfigs,
     surface: Surface<'static>,
+    staging: Mutex<Staging>,
+    adapter_info: AdapterInfo,
 }
 
 impl Context {
@@ -59,10 +130,29 @@ impl Context {
             .ok_or(ContextError::Adapter)?;
 
         let (device, queue) = adapter
-            .request_device(&DeviceDescriptor::default(), None)
+            .request_device(
+                &DeviceDescriptor {
+                    // Optional, GPU timers turn themselves off without it
+                    required_features: adapter.features() & Features::TIMESTAMP_QUERY,
+                    ..Default::default()
+                },
+                None,
+            )
             .await
             .map_err(ContextError::Device)?;
 
+        let adapter_info = adapter.get_info();
+        info!(
+            "using {} ({:?}, {:?}), driver {} {}",
+            adapter_info.name,
+            adapter_info.backend,
+            adapter_info.device_type,
+            adapter_info.driver,
+            adapter_info.driver_info
+        );
+        info!("granted features: {:?}", device.features());
+        info!("granted limits: {:?}", device.limits());
+
         let mut config = surface
             .get_default_config(&adapter, size.width, size.height)
             .ok_or(ContextError::Config)?;
@@ -75,7 +165,9 @@ impl Context {
             surface,
             device,
             queue,
-            config: Mutex::new(config),
+            configs: SurfaceConfigs::new(config),
+            staging: Mutex::new(Staging::new()),
+            adapter_info,
         })
     }
 
@@ -86,7 +178,7 @@ impl Context {
             self.device()
                 .create_bind_group_layout(&BindGroupLayoutDescriptor {
                     label: None,
-                    entries,
+                    entries: &entries,
                 }),
             PhantomData,
         )
@@ -135,18 +227,41 @@ impl Context {
         RenderPipelineBuilder::new::<V>(self, base_pipeline)
     }
 
-    pub fn resize(&self, new_size: PhysicalSize<u32>) {
-        let mut config = self.config();
-        config.width = new_size.width;
-        config.height = new_size.height;
+    /// Remembers the size for the next [`Context::apply_pending_config`], so a burst of
+    /// resizes reconfigures the surface only once
+    pub fn request_resize(&self, new_size: PhysicalSize<u32>) {
+        self.configs.request_size(new_size);
+    }
+
+    /// Applied together with the pending size, see [`Context::request_resize`]
+    pub fn request_present_mode(&self, present_mode: PresentMode) {
+        self.configs.request_present_mode(present_mode);
+    }
+
+    pub fn needs_reconfigure(&self) -> bool {
+        self.configs.pending().is_some()
+    }
 
-        self.surface().configure(&self.device, &config)
+    /// Publishes the pending configuration before reconfiguring the surface with it, returns
+    /// whether anything changed. Meant to be called once per frame before the surface texture
+    /// is acquired, from a single thread
+    pub fn apply_pending_config(&self) -> bool {
+        let Some(config) = self.configs.publish() else {
+            return false;
+        };
+        self.surface().configure(&self.device, &config);
+        true
     }
 
     pub fn surface(&self) -> &Surface<'static> {
         &self.surface
     }
 
+    /// Adapter the device was created on
+    pub fn adapter_info(&self) -> &AdapterInfo {
+        &self.adapter_info
+    }
+
     pub fn device(&self) -> &Device {
         &self.device
     }
@@ -155,7 +270,98 @@ impl Context {
         &self.queue
     }
 
-    pub fn config(&self) -> MutexGuard<'_, SurfaceConfiguration> {
-        self.config.lock().expect("lock failed")
+    pub fn staging_belt(&self) -> MutexGuard<'_, Staging> {
+        self.staging.lock().expect("lock failed")
+    }
+
+    /// Snapshot of the current surface configuration, later resizes don't change it
+    pub fn config(&self) -> Arc<SurfaceConfiguration> {
+        self.configs.current()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+
+    use wgpu::{CompositeAlphaMode, TextureFormat, TextureUsages};
+
+    use super::*;
+
+    const READERS: usize = 4;
+    const RESIZES: u32 = 2000;
+
+    fn config(width: u32, height: u32) -> SurfaceConfiguration {
+        SurfaceConfiguration {
+            usage: TextureUsages::RENDER_ATTACHMENT,
+            format: TextureFormat::Bgra8UnormSrgb,
+            width,
+            height,
+            present_mode: PresentMode::AutoNoVsync,
+            desired_maximum_frame_latency: 2,
+            alpha_mode: CompositeAlphaMode::Auto,
+            view_formats: Vec::new(),
+        }
+    }
+
+    // Each width is published with exactly one height
+    fn published_size(step: u32) -> PhysicalSize<u32> {
+        PhysicalSize::new(step * 16, step * 9)
+    }
+
+    #[test]
+    fn only_requests_that_change_something_are_published() {
+        let configs = SurfaceConfigs::new(config(160, 90));
+        assert!(configs.publish().is_none());
+
+        configs.request_size(PhysicalSize::new(160, 90));
+        assert!(configs.publish().is_none());
+        configs.request_size(PhysicalSize::new(0, 90));
+        assert!(configs.publish().is_none());
+
+        configs.request_size(PhysicalSize::new(320, 180));
+        configs.request_present_mode(PresentMode::Fifo);
+        let published = configs.publish().expect("nothing was published");
+        assert_eq!((published.width, published.height), (320, 180));
+        assert_eq!(published.present_mode, PresentMode::Fifo);
+        assert!(Arc::ptr_eq(&published, &configs.current()));
+        assert!(configs.publish().is_none());
+    }
+
+    #[test]
+    fn readers_only_see_published_sizes() {
+        let configs = SurfaceConfigs::new(config(16, 9));
+        thread::scope(|scope| {
+            let readers = (0..READERS)
+                .map(|_| {
+                    scope.spawn(|| {
+                        let mut last_width = 0;
+                        let mut reads = 0;
+                        while last_width < published_size(RESIZES).width {
+                            let config = configs.current();
+                            let step = config.width / 16;
+                            assert_eq!(
+                                PhysicalSize::new(config.width, config.height),
+                                published_size(step),
+                                "torn read"
+                            );
+                            // Sizes only grow, so snapshots never go back in time
+                            assert!(config.width >= last_width);
+                            last_width = config.width;
+                            reads += 1;
+                        }
+                        reads
+                    })
+                })
+                .collect::<Vec<_>>();
+
+            for step in 2..=RESIZES {
+                configs.request_size(published_size(step));
+                assert!(configs.publish().is_some());
+            }
+            for reader in readers {
+                assert!(reader.join().unwrap() > 0);
+            }
+        });
     }
 }
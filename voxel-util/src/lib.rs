@@ -1,17 +1,25 @@
+pub mod atlas;
 pub mod bind_group;
+pub mod buffer;
 pub mod context;
 pub mod render_pipeline;
 pub mod sampler;
 pub mod spritesheet;
+pub mod staging;
 pub mod texture;
+pub mod timer;
 pub mod uniform;
 
+pub use atlas::{Atlas, AtlasBuilder, AtlasError, AtlasLayout};
 pub use bind_group::{AsBindGroup, Binding, BindingEntries, Fragment, ShaderResource, Vertex};
+pub use buffer::GrowableBuffer;
 pub use context::Context;
 pub use render_pipeline::{BasePipeline, ColorTargetStateExt, RenderPipelineBuilder, VertexLayout};
-pub use sampler::Sampler;
+pub use sampler::{ComparisonSampler, Sampler};
 pub use spritesheet::Spritesheet;
-pub use texture::Texture;
+pub use staging::Staging;
+pub use texture::{AsyncTexture, DepthTextureArray, Texture, TextureError};
+pub use timer::GpuTimer;
 pub use uniform::Uniform;
 
 #[macro_export]
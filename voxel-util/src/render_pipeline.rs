@@ -84,6 +84,7 @@ pub struct RenderPipelineBuilder<'c> {
     layout: Option<&'c PipelineLayout>,
     depth: Option<(TextureFormat, CompareFunction)>,
     depth_write: bool,
+    depth_bias: DepthBiasState,
 
     overrides: HashMap<String, f64>,
 
@@ -100,6 +101,7 @@ impl<'c> RenderPipelineBuilder<'c> {
             targets: SmallVec::new(),
             layout: None,
             depth_write: true,
+            depth_bias: DepthBiasState::default(),
             label: None,
             depth: None,
             cull_mode: None,
@@ -128,6 +130,15 @@ impl<'c> RenderPipelineBuilder<'c> {
         self
     }
 
+    pub fn depth_bias(mut self, constant: i32, slope_scale: f32) -> Self {
+        self.depth_bias = DepthBiasState {
+            constant,
+            slope_scale,
+            clamp: 0.0,
+        };
+        self
+    }
+
     pub fn cull_mode(mut self, cull_mode: Face) -> Self {
         self.cull_mode = Some(cull_mode);
         self
@@ -182,7 +193,7 @@ impl<'c> RenderPipelineBuilder<'c> {
             depth_write_enabled: self.depth_write,
             depth_compare,
             stencil: StencilState::default(),
-            bias: DepthBiasState::default(),
+            bias: self.depth_bias,
         });
 
         self.context
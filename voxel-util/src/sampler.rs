@@ -1,5 +1,8 @@
 use std::num::NonZero;
-use wgpu::{BindingResource, BindingType, FilterMode, SamplerBindingType, SamplerDescriptor};
+use wgpu::{
+    AddressMode, BindingResource, BindingType, CompareFunction, FilterMode, SamplerBindingType,
+    SamplerDescriptor,
+};
 
 use crate::{Binding, Context};
 
@@ -27,7 +30,40 @@ impl Binding for Sampler {
         None
     }
 
-    fn resource(&self) -> BindingResource {
+    fn resource(&self) -> BindingResource<'_> {
+        BindingResource::Sampler(&self.0)
+    }
+}
+
+/// Sampler returning the result of comparing the sampled depth against a reference
+#[derive(Debug)]
+pub struct ComparisonSampler(wgpu::Sampler);
+
+impl ComparisonSampler {
+    pub fn new(compare: CompareFunction, context: &Context) -> Self {
+        let sampler = context.device().create_sampler(&SamplerDescriptor {
+            address_mode_u: AddressMode::ClampToEdge,
+            address_mode_v: AddressMode::ClampToEdge,
+            mag_filter: FilterMode::Linear,
+            min_filter: FilterMode::Linear,
+            compare: Some(compare),
+            ..Default::default()
+        });
+
+        Self(sampler)
+    }
+}
+
+impl Binding for ComparisonSampler {
+    fn ty() -> BindingType {
+        BindingType::Sampler(SamplerBindingType::Comparison)
+    }
+
+    fn count() -> Option<NonZero<u32>> {
+        None
+    }
+
+    fn resource(&self) -> BindingResource<'_> {
         BindingResource::Sampler(&self.0)
     }
 }
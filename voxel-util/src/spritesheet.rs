@@ -1,7 +1,9 @@
 use bytemuck::{Pod, Zeroable};
 use wgpu::FilterMode;
 
-use crate::{AsBindGroup, BindingEntries, Context, Fragment, Sampler, Texture, Uniform, Vertex};
+use crate::{
+    AsBindGroup, AtlasLayout, BindingEntries, Context, Fragment, Sampler, Texture, Uniform, Vertex,
+};
 
 #[repr(C)]
 #[derive(Debug, Clone, Copy, Pod, Zeroable)]
@@ -10,6 +12,16 @@ pub struct TextureAtlasUniform {
     columns: u32,
 }
 
+impl From<&AtlasLayout> for TextureAtlasUniform {
+    fn from(layout: &AtlasLayout) -> Self {
+        let (width, height) = layout.size();
+        Self {
+            rows: height / layout.tile_size(),
+            columns: width / layout.tile_size(),
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct Spritesheet {
     texture: Texture,
@@ -0,0 +1,65 @@
+use std::num::NonZero;
+
+use wgpu::{
+    util::StagingBelt, Buffer, BufferAddress, CommandBuffer, CommandEncoder,
+    CommandEncoderDescriptor, Device,
+};
+
+const CHUNK_SIZE: BufferAddress = 4096;
+
+/// Batches buffer uploads for the frame into a single command buffer
+#[derive(Debug)]
+pub struct Staging {
+    belt: StagingBelt,
+    encoder: Option<CommandEncoder>,
+}
+
+impl Staging {
+    pub fn new() -> Self {
+        Self {
+            belt: StagingBelt::new(CHUNK_SIZE),
+            encoder: None,
+        }
+    }
+
+    pub fn write_buffer(
+        &mut self,
+        target: &Buffer,
+        offset: BufferAddress,
+        data: &[u8],
+        device: &Device,
+    ) {
+        let Some(size) = NonZero::new(data.len() as BufferAddress) else {
+            return;
+        };
+
+        let encoder = self.encoder.get_or_insert_with(|| {
+            device.create_command_encoder(&CommandEncoderDescriptor {
+                label: Some("Staging Command Encoder"),
+            })
+        });
+
+        self.belt
+            .write_buffer(encoder, target, offset, size, device)
+            .copy_from_slice(data);
+    }
+
+    /// Closes the uploads staged so far, they have to be submitted before the frame using them
+    pub fn finish(&mut self) -> Option<CommandBuffer> {
+        let encoder = self.encoder.take()?;
+        self.belt.finish();
+
+        Some(encoder.finish())
+    }
+
+    /// Reclaims the staging buffers once the finished uploads were submitted
+    pub fn recall(&mut self) {
+        self.belt.recall();
+    }
+}
+
+impl Default for Staging {
+    fn default() -> Self {
+        Self::new()
+    }
+}
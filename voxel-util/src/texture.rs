@@ -1,13 +1,34 @@
-use std::num::NonZero;
+use std::{
+    num::NonZero,
+    path::{Path, PathBuf},
+    sync::{
+        mpsc::{self, Receiver, TryRecvError},
+        Arc,
+    },
+};
 
-use image::RgbaImage;
+use image::{ImageError, Rgba, RgbaImage};
+use thiserror::Error;
 use wgpu::{
     BindingResource, BindingType, Extent3d, ImageCopyTexture, ImageDataLayout, Origin3d,
     TextureAspect, TextureDescriptor, TextureDimension, TextureFormat, TextureSampleType,
     TextureUsages, TextureView, TextureViewDescriptor, TextureViewDimension,
 };
 
-use crate::{Binding, Context};
+use crate::{AtlasError, Binding, Context};
+
+#[derive(Debug, Error)]
+pub enum TextureError {
+    #[error("failed to load texture `{0}`: {1}")]
+    Load(PathBuf, ImageError),
+    #[error(transparent)]
+    Atlas(#[from] AtlasError),
+    #[error("texture loader stopped without an answer")]
+    Disconnected,
+}
+
+// Shown while a texture loads, plain gray doesn't stand out against most of them
+const PLACEHOLDER_COLOR: Rgba<u8> = Rgba([128, 128, 128, 255]);
 
 #[derive(Debug, Clone, Copy)]
 pub struct TextureData<'d> {
@@ -90,6 +111,58 @@ impl Texture {
         texture
     }
 
+    /// Decodes the image file at `path` and uploads it as an sRGB texture
+    pub fn from_path(
+        path: impl AsRef<Path>,
+        usage: TextureUsages,
+        context: &Context,
+    ) -> Result<Self, TextureError> {
+        let path = path.as_ref();
+        let image = image::open(path)
+            .map_err(|err| TextureError::Load(path.to_owned(), err))?
+            .to_rgba8();
+
+        Ok(Self::from_data(&image, usage, context))
+    }
+
+    /// Like [`Texture::from_path`], but decodes and uploads the image on the rayon thread pool.
+    /// A placeholder stands in for the texture until [`AsyncTexture::poll`] swaps it in
+    pub fn load_async(
+        path: impl Into<PathBuf>,
+        usage: TextureUsages,
+        context: &Arc<Context>,
+    ) -> AsyncTexture {
+        let path = path.into();
+        Self::load_with((1, 1), usage, context, move || {
+            Ok(image::open(&path)
+                .map_err(|err| TextureError::Load(path, err))?
+                .to_rgba8())
+        })
+    }
+
+    /// Runs `load` on the rayon thread pool and uploads the image it returns. A placeholder of
+    /// `size` stands in for the texture until [`AsyncTexture::poll`] swaps it in
+    pub fn load_with(
+        (width, height): (u32, u32),
+        usage: TextureUsages,
+        context: &Arc<Context>,
+        load: impl FnOnce() -> Result<RgbaImage, TextureError> + Send + 'static,
+    ) -> AsyncTexture {
+        let (sender, receiver) = mpsc::channel();
+        let worker_context = Arc::clone(context);
+        rayon::spawn(move || {
+            let texture = load().map(|image| Self::from_data(&image, usage, &worker_context));
+            // Nobody is waiting anymore if the handle was dropped
+            let _ = sender.send(texture);
+        });
+
+        let placeholder = RgbaImage::from_pixel(width, height, PLACEHOLDER_COLOR);
+        AsyncTexture {
+            texture: Self::from_data(&placeholder, usage, context),
+            receiver: Some(receiver),
+        }
+    }
+
     pub fn upload_data<'d, D>(&self, texture_data: D, context: &Context)
     where
         TextureData<'d>: From<D>,
@@ -174,7 +247,132 @@ impl Binding for Texture {
         None
     }
 
-    fn resource(&self) -> BindingResource {
+    fn resource(&self) -> BindingResource<'_> {
+        BindingResource::TextureView(&self.view)
+    }
+}
+
+/// Texture that is loaded in the background, see [`Texture::load_async`]
+#[derive(Debug)]
+pub struct AsyncTexture {
+    // The placeholder until the load finished
+    texture: Texture,
+    // Gone once the load finished or failed
+    receiver: Option<Receiver<Result<Texture, TextureError>>>,
+}
+
+impl AsyncTexture {
+    /// Swaps in the loaded texture once it's ready and returns whether it did, bind groups
+    /// created with the placeholder have to be created again then. A failed load keeps the
+    /// placeholder and reports the error once, so does a loader that panicked
+    pub fn poll(&mut self) -> Result<bool, TextureError> {
+        let Some(receiver) = &self.receiver else {
+            return Ok(false);
+        };
+
+        let result = match receiver.try_recv() {
+            Ok(result) => result,
+            Err(TryRecvError::Empty) => return Ok(false),
+            Err(TryRecvError::Disconnected) => Err(TextureError::Disconnected),
+        };
+        self.receiver = None;
+        self.texture = result?;
+        Ok(true)
+    }
+
+    pub fn is_loading(&self) -> bool {
+        self.receiver.is_some()
+    }
+
+    /// The loaded texture, or the placeholder while it's still loading
+    pub fn texture(&self) -> &Texture {
+        &self.texture
+    }
+}
+
+/// Depth texture with several layers, each of them can be rendered into separately
+#[derive(Debug)]
+pub struct DepthTextureArray {
+    texture: wgpu::Texture,
+    view: TextureView,
+    layer_views: Vec<TextureView>,
+    size: (u32, u32),
+}
+
+impl DepthTextureArray {
+    pub fn new(
+        (width, height): (u32, u32),
+        layers: u32,
+        format: TextureFormat,
+        context: &Context,
+    ) -> Self {
+        assert!(format.is_depth_stencil_format());
+
+        let texture = context.device().create_texture(&TextureDescriptor {
+            label: None,
+            size: Extent3d {
+                width,
+                height,
+                depth_or_array_layers: layers,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format,
+            usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        });
+
+        let view = texture.create_view(&TextureViewDescriptor {
+            dimension: Some(TextureViewDimension::D2Array),
+            ..Default::default()
+        });
+        let layer_views = (0..layers)
+            .map(|layer| {
+                texture.create_view(&TextureViewDescriptor {
+                    dimension: Some(TextureViewDimension::D2),
+                    base_array_layer: layer,
+                    array_layer_count: Some(1),
+                    ..Default::default()
+                })
+            })
+            .collect();
+
+        Self {
+            texture,
+            view,
+            layer_views,
+            size: (width, height),
+        }
+    }
+
+    pub fn layer_view(&self, layer: u32) -> &TextureView {
+        &self.layer_views[layer as usize]
+    }
+
+    pub fn layers(&self) -> u32 {
+        self.texture.depth_or_array_layers()
+    }
+
+    pub fn size(&self) -> (u32, u32) {
+        self.size
+    }
+}
+
+impl Binding for DepthTextureArray {
+    fn ty() -> BindingType {
+        BindingType::Texture {
+            sample_type: TextureSampleType::Depth,
+            view_dimension: TextureViewDimension::D2Array,
+            multisampled: false,
+        }
+    }
+
+    fn count() -> Option<NonZero<u32>> {
+        None
+    }
+
+    fn resource(&self) -> BindingResource<'_> {
         BindingResource::TextureView(&self.view)
     }
 }
@@ -0,0 +1,329 @@
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+
+use wgpu::{
+    Buffer, BufferAddress, BufferDescriptor, BufferUsages, CommandEncoder, Device, Features,
+    Maintain, MapMode, QuerySet, QuerySetDescriptor, QueryType, Queue, RenderPassTimestampWrites,
+    QUERY_SIZE,
+};
+
+use crate::Context;
+
+// Results are read this many frames after they were written, so mapping never stalls
+const READBACK_FRAMES: usize = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ReadbackState {
+    Free,
+    // Copied into by a frame that hasn't been submitted yet
+    Resolved,
+    Mapping,
+}
+
+#[derive(Debug)]
+struct Readback {
+    buffer: Buffer,
+    state: ReadbackState,
+    // Set by the map callback, the buffer is only touched again after that
+    mapped: Arc<AtomicBool>,
+}
+
+#[derive(Debug)]
+struct Queries {
+    query_set: QuerySet,
+    resolve_buffer: Buffer,
+    readbacks: [Readback; READBACK_FRAMES],
+    current: usize,
+    // Nanoseconds per timestamp tick
+    period: f32,
+}
+
+/// Measures how long spans of GPU work take using timestamp queries
+///
+/// Every span gets a begin and an end timestamp written by the render passes it covers.
+/// Without [`Features::TIMESTAMP_QUERY`] the timer does nothing and never reports timings
+#[derive(Debug)]
+pub struct GpuTimer {
+    queries: Option<Queries>,
+    spans: u32,
+    timings: Vec<f32>,
+}
+
+impl GpuTimer {
+    pub fn new(spans: u32, context: &Context) -> Self {
+        Self::with_device(spans, context.device(), context.queue())
+    }
+
+    fn with_device(spans: u32, device: &Device, queue: &Queue) -> Self {
+        let queries = device
+            .features()
+            .contains(Features::TIMESTAMP_QUERY)
+            .then(|| Self::create_queries(spans, device, queue));
+
+        Self {
+            queries,
+            spans,
+            timings: Vec::new(),
+        }
+    }
+
+    fn create_queries(spans: u32, device: &Device, queue: &Queue) -> Queries {
+        let size = (spans * 2) as BufferAddress * QUERY_SIZE as BufferAddress;
+
+        let query_set = device.create_query_set(&QuerySetDescriptor {
+            label: Some("GPU Timer Query Set"),
+            ty: QueryType::Timestamp,
+            count: spans * 2,
+        });
+        let resolve_buffer = device.create_buffer(&BufferDescriptor {
+            label: Some("GPU Timer Resolve Buffer"),
+            size,
+            usage: BufferUsages::QUERY_RESOLVE | BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+        let readbacks = [(); READBACK_FRAMES].map(|_| Readback {
+            buffer: device.create_buffer(&BufferDescriptor {
+                label: Some("GPU Timer Readback Buffer"),
+                size,
+                usage: BufferUsages::MAP_READ | BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            }),
+            state: ReadbackState::Free,
+            mapped: Default::default(),
+        });
+
+        Queries {
+            query_set,
+            resolve_buffer,
+            readbacks,
+            current: 0,
+            period: queue.get_timestamp_period(),
+        }
+    }
+
+    pub fn is_supported(&self) -> bool {
+        self.queries.is_some()
+    }
+
+    /// Timestamp writes for a render pass inside `span`, `begin` and `end` pick which
+    /// side of the span the pass marks, so one span can cover several passes
+    pub fn timestamp_writes(
+        &self,
+        span: u32,
+        begin: bool,
+        end: bool,
+    ) -> Option<RenderPassTimestampWrites<'_>> {
+        assert!(span < self.spans);
+        let queries = self.queries.as_ref()?;
+
+        Some(RenderPassTimestampWrites {
+            query_set: &queries.query_set,
+            beginning_of_pass_write_index: begin.then_some(span * 2),
+            end_of_pass_write_index: end.then_some(span * 2 + 1),
+        })
+    }
+
+    /// Writes for the `pass`th of `passes` render passes sharing one span,
+    /// the first of them begins it and the last one ends it
+    pub fn split_writes<'q>(
+        timestamp_writes: Option<&RenderPassTimestampWrites<'q>>,
+        pass: usize,
+        passes: usize,
+    ) -> Option<RenderPassTimestampWrites<'q>> {
+        timestamp_writes.map(|writes| RenderPassTimestampWrites {
+            query_set: writes.query_set,
+            beginning_of_pass_write_index: writes
+                .beginning_of_pass_write_index
+                .filter(|_| pass == 0),
+            end_of_pass_write_index: writes
+                .end_of_pass_write_index
+                .filter(|_| pass + 1 == passes),
+        })
+    }
+
+    /// Copies this frame's timestamps out, skipped while the next readback buffer is still busy
+    pub fn resolve(&mut self, encoder: &mut CommandEncoder) {
+        let Some(queries) = &mut self.queries else {
+            return;
+        };
+
+        let readback = &mut queries.readbacks[queries.current];
+        if readback.state != ReadbackState::Free {
+            return;
+        }
+        readback.state = ReadbackState::Resolved;
+
+        encoder.resolve_query_set(
+            &queries.query_set,
+            0..self.spans * 2,
+            &queries.resolve_buffer,
+            0,
+        );
+        encoder.copy_buffer_to_buffer(
+            &queries.resolve_buffer,
+            0,
+            &readback.buffer,
+            0,
+            queries.resolve_buffer.size(),
+        );
+    }
+
+    /// Starts mapping the buffer resolved into this frame, has to be called after submitting it
+    pub fn submitted(&mut self) {
+        let Some(queries) = &mut self.queries else {
+            return;
+        };
+
+        let readback = &mut queries.readbacks[queries.current];
+        if readback.state == ReadbackState::Resolved {
+            readback.state = ReadbackState::Mapping;
+
+            let mapped = Arc::clone(&readback.mapped);
+            readback
+                .buffer
+                .slice(..)
+                .map_async(MapMode::Read, move |result| {
+                    if result.is_ok() {
+                        mapped.store(true, Ordering::Release);
+                    }
+                });
+        }
+        queries.current = (queries.current + 1) % READBACK_FRAMES;
+    }
+
+    /// Reads back the frames whose timestamps arrived, keeping the last one read
+    pub fn collect(&mut self, context: &Context) {
+        self.collect_from(context.device());
+    }
+
+    fn collect_from(&mut self, device: &Device) {
+        let Some(queries) = &mut self.queries else {
+            return;
+        };
+        device.poll(Maintain::Poll);
+
+        for readback in &mut queries.readbacks {
+            if !readback.mapped.swap(false, Ordering::AcqRel) {
+                continue;
+            }
+
+            {
+                let data = readback.buffer.slice(..).get_mapped_range();
+                let timestamps: &[u64] = bytemuck::cast_slice(&data);
+
+                self.timings = timestamps
+                    .chunks_exact(2)
+                    .map(|span| span[1].saturating_sub(span[0]) as f32 * queries.period / 1e6)
+                    .collect();
+            }
+
+            readback.buffer.unmap();
+            readback.state = ReadbackState::Free;
+        }
+    }
+
+    /// Milliseconds every span took, empty until the first results come back
+    pub fn timings(&self) -> &[f32] {
+        &self.timings
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use wgpu::{
+        Color, CommandEncoderDescriptor, DeviceDescriptor, Extent3d, Instance, LoadOp, Operations,
+        RenderPassColorAttachment, RenderPassDescriptor, RequestAdapterOptions, StoreOp,
+        TextureDescriptor, TextureDimension, TextureFormat, TextureUsages, TextureViewDescriptor,
+    };
+
+    use super::*;
+
+    // Frames to wait for the first timestamps, well past the readback delay
+    const MAX_FRAMES: usize = 32;
+
+    /// Device with timestamp queries, `None` without an adapter that has them
+    fn headless_device() -> Option<(Device, Queue)> {
+        let instance = Instance::default();
+        let adapter =
+            pollster::block_on(instance.request_adapter(&RequestAdapterOptions::default()))?;
+        if !adapter.features().contains(Features::TIMESTAMP_QUERY) {
+            return None;
+        }
+
+        let descriptor = DeviceDescriptor {
+            required_features: Features::TIMESTAMP_QUERY,
+            ..Default::default()
+        };
+        pollster::block_on(adapter.request_device(&descriptor, None)).ok()
+    }
+
+    #[test]
+    fn timings_are_read_back_in_order() {
+        // Skipped on machines without a GPU that supports timestamps
+        let Some((device, queue)) = headless_device() else {
+            return;
+        };
+        let target = device
+            .create_texture(&TextureDescriptor {
+                label: None,
+                size: Extent3d {
+                    width: 64,
+                    height: 64,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: TextureDimension::D2,
+                format: TextureFormat::Rgba8Unorm,
+                usage: TextureUsages::RENDER_ATTACHMENT,
+                view_formats: &[],
+            })
+            .create_view(&TextureViewDescriptor::default());
+
+        // The first span covers all three passes, the second only the middle one
+        let mut timer = GpuTimer::with_device(2, &device, &queue);
+        assert!(timer.is_supported());
+        for _ in 0..MAX_FRAMES {
+            let mut encoder = device.create_command_encoder(&CommandEncoderDescriptor::default());
+            let outer = timer.timestamp_writes(0, true, true);
+            for pass in 0..3 {
+                let timestamp_writes = match pass {
+                    1 => timer.timestamp_writes(1, true, true),
+                    _ => GpuTimer::split_writes(outer.as_ref(), pass, 3),
+                };
+                encoder.begin_render_pass(&RenderPassDescriptor {
+                    label: None,
+                    color_attachments: &[Some(RenderPassColorAttachment {
+                        view: &target,
+                        resolve_target: None,
+                        ops: Operations {
+                            load: LoadOp::Clear(Color::BLACK),
+                            store: StoreOp::Store,
+                        },
+                    })],
+                    depth_stencil_attachment: None,
+                    timestamp_writes,
+                    occlusion_query_set: None,
+                });
+            }
+            timer.resolve(&mut encoder);
+            queue.submit([encoder.finish()]);
+            timer.submitted();
+
+            device.poll(Maintain::Wait);
+            timer.collect_from(&device);
+            if !timer.timings().is_empty() {
+                break;
+            }
+        }
+
+        let timings = timer.timings();
+        assert_eq!(timings.len(), 2, "no timestamps came back");
+        assert!(timings
+            .iter()
+            .all(|timing| timing.is_finite() && *timing >= 0.0));
+        assert!(timings[0] >= timings[1]);
+    }
+}
@@ -39,13 +39,24 @@ impl<T: Pod> Uniform<T> {
             .write_buffer(&self.buffer, 0, bytemuck::cast_slice(&[data]));
     }
 
+    /// Same as [`Uniform::update`], but the upload goes through the context's staging belt
+    pub fn stage(&mut self, data: T, context: &Context) {
+        self.data = data;
+        context.staging_belt().write_buffer(
+            &self.buffer,
+            0,
+            bytemuck::cast_slice(&[data]),
+            context.device(),
+        );
+    }
+
     pub fn data(&self) -> &T {
         &self.data
     }
 }
 
 impl<T> Binding for Uniform<T> {
-    fn resource(&self) -> BindingResource {
+    fn resource(&self) -> BindingResource<'_> {
         self.buffer.as_entire_binding()
     }
 
@@ -1,65 +1,463 @@
 use std::{
     collections::HashMap,
+    hash::{BuildHasher, RandomState},
+    iter,
     sync::{
-        mpsc::{channel, Receiver, Sender},
+        atomic::{AtomicBool, AtomicU32, Ordering},
+        mpsc::{channel, Sender},
         Arc,
     },
-    thread,
-    time::Instant,
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
 };
 
+use arc_swap::{ArcSwap, Guard};
 use glam::{IVec3, Vec3};
-use parking_lot::{RwLock, RwLockReadGuard};
-use rayon::iter::{ParallelDrainRange, ParallelIterator};
-use voxel_util::{AsBindGroup, Context};
+#[cfg(feature = "serde")]
+use log::warn;
+use log::{error, info};
+use parking_lot::RwLock;
+use rayon::{ThreadPool, ThreadPoolBuilder};
+use voxel_util::{Atlas, Context};
+use wgpu::PresentMode;
 use winit::{
     application::ApplicationHandler,
     dpi::{PhysicalPosition, PhysicalSize},
-    event::{DeviceEvent, DeviceId, ElementState, KeyEvent, WindowEvent},
+    event::{
+        DeviceEvent, DeviceId, ElementState, KeyEvent, Modifiers, MouseButton, MouseScrollDelta,
+        WindowEvent,
+    },
     event_loop::ActiveEventLoop,
-    keyboard::{KeyCode, PhysicalKey},
-    window::{CursorGrabMode, Window, WindowId},
+    keyboard::{KeyCode, ModifiersState, PhysicalKey},
+    window::{CursorGrabMode, Fullscreen, Window, WindowId},
 };
 
+#[cfg(feature = "gamepad")]
+use crate::gamepad::Gamepads;
 use crate::{
+    asset,
+    bench::Benchmark,
     camera::{Camera, Projection, Transformation},
+    console::Console,
+    crash,
     error::Error,
-    render::{frustum_culling::Frustum, world_pass::ChunkBuffer, Renderer},
-    world::{chunk::ChunkNeighborhood, meshes::create_mesh, Chunks, World},
+    frame_limiter::FrameLimiter,
+    interaction::{InteractionListener, SilentListener},
+    mesh_queue::{MeshJob, MeshQueue},
+    metrics::{Metrics, Progress},
+    render::{
+        hotbar_pass::SLOTS, post_process_pass::EXPOSURE_STEP, world_pass::ChunkBuffer,
+        DebugOverlay, Renderer,
+    },
+    session::Session,
+    settings::{Settings, DEFAULT_BLOOM_INTENSITY},
+    window::TITLE,
+    world::{
+        chunk::ChunkNeighborhood,
+        coords,
+        lod::Lod,
+        meshes::{create_mesh, remesh},
+        source::LocalSource,
+        Block, BlockEvent, Chunks, DirtyRegion, World,
+    },
+};
+#[cfg(feature = "serde")]
+use crate::{
+    bench::REPORT_PATH,
+    session::SESSION_PATH,
+    world::{dump::ChunkDump, structure::Structure},
 };
 
-enum MeshGeneratorMessage {
-    SetVisible { positions: Box<[IVec3]> },
+pub(crate) enum MeshGeneratorMessage {
+    SetVisible {
+        origin: IVec3,
+        positions: Box<[(IVec3, Lod)]>,
+    },
+    Remesh(Box<[(IVec3, DirtyRegion)]>),
+    Clear,
+    Shutdown,
+}
+
+enum MeshWorkerEvent {
+    Queue {
+        origin: IVec3,
+        to_generate: Vec<(IVec3, Lod)>,
+    },
+    // Meshed before anything else that is queued
+    Remesh(Vec<(IVec3, Lod, DirtyRegion)>),
+    Meshed {
+        generation: u32,
+        position: IVec3,
+        mesh: Option<Arc<ChunkBuffer>>,
+    },
+    Shutdown,
 }
 
-pub struct MeshGenerator(Sender<MeshGeneratorMessage>);
+/// Blocks of an edited chunk that changed, and its last mesh to patch them into
+type Edit<'m> = (DirtyRegion, Option<&'m ChunkBuffer>);
+
+/// Builds the mesh of a chunk on a mesh worker, with ambient occlusion if the flag is set
+type BuildMesh =
+    dyn Fn(ChunkNeighborhood, Lod, bool, Option<Edit<'_>>) -> ChunkBuffer + Send + Sync;
+
+pub struct MeshGenerator {
+    sender: Sender<MeshGeneratorMessage>,
+    // Bumped on every clear, meshes built for an older generation are discarded
+    generation: Arc<AtomicU32>,
+    ambient_occlusion: Arc<AtomicBool>,
+    // Set once the generator is dropped, meshes that haven't started yet are skipped
+    shutdown: Arc<AtomicBool>,
+    threads: Vec<JoinHandle<()>>,
+}
 
 impl MeshGenerator {
-    fn new(sender: Sender<MeshGeneratorMessage>) -> Self {
-        Self(sender)
+    pub(crate) fn new(sender: Sender<MeshGeneratorMessage>, ambient_occlusion: bool) -> Self {
+        Self {
+            sender,
+            generation: Default::default(),
+            ambient_occlusion: Arc::new(AtomicBool::new(ambient_occlusion)),
+            shutdown: Default::default(),
+            threads: Vec::new(),
+        }
     }
 
-    pub fn set_visible(&self, positions: Box<[IVec3]>) {
-        self.0
-            .send(MeshGeneratorMessage::SetVisible { positions })
+    /// Starts the threads handing visible and edited chunks to `mesh_pool`, their meshes are
+    /// built by `build` and published into `meshes`
+    fn spawn(
+        chunks: Chunks,
+        meshes: Arc<Meshes>,
+        mesh_pool: ThreadPool,
+        metrics: Arc<Metrics>,
+        settings: Settings,
+        build: Arc<BuildMesh>,
+    ) -> Self {
+        let (mesh_generator_sender, mesh_generator_receiver) = channel();
+        let (worker_sender, worker_receiver) = channel();
+
+        let mut mesh_generator =
+            MeshGenerator::new(mesh_generator_sender, settings.ambient_occlusion);
+        // Chunks that are still visible and the resolution they're wanted at,
+        // work for anything else is dropped
+        let wanted = Arc::new(RwLock::new(HashMap::new()));
+        {
+            let meshes = Arc::clone(&meshes);
+            let wanted = Arc::clone(&wanted);
+            let worker_sender = worker_sender.clone();
+
+            mesh_generator.threads.push(thread::spawn(move || {
+                for message in mesh_generator_receiver.iter() {
+                    match message {
+                        MeshGeneratorMessage::SetVisible { origin, positions } => {
+                            let visible = positions.iter().copied().collect::<HashMap<_, _>>();
+                            // Meshes at the wrong resolution stay until their replacement is ready
+                            meshes.update(|generated| {
+                                generated.retain(|position, _| visible.contains_key(position))
+                            });
+
+                            let to_generate = {
+                                let generated = meshes.read();
+                                positions
+                                    .iter()
+                                    .copied()
+                                    .filter(|(position, lod)| {
+                                        generated.get(position).map(|mesh| mesh.lod()) != Some(*lod)
+                                    })
+                                    .collect()
+                            };
+                            *wanted.write() = visible;
+
+                            worker_sender
+                                .send(MeshWorkerEvent::Queue {
+                                    origin,
+                                    to_generate,
+                                })
+                                .unwrap();
+                        }
+                        MeshGeneratorMessage::Remesh(chunks) => {
+                            // Chunks that aren't visible get meshed once they are
+                            let to_remesh = {
+                                let wanted = wanted.read();
+                                chunks
+                                    .iter()
+                                    .filter_map(|&(position, region)| {
+                                        wanted.get(&position).map(|&lod| (position, lod, region))
+                                    })
+                                    .collect()
+                            };
+
+                            worker_sender
+                                .send(MeshWorkerEvent::Remesh(to_remesh))
+                                .unwrap();
+                        }
+                        MeshGeneratorMessage::Clear => {
+                            wanted.write().clear();
+                            meshes.update(HashMap::clear);
+                            worker_sender
+                                .send(MeshWorkerEvent::Queue {
+                                    origin: IVec3::ZERO,
+                                    to_generate: Vec::new(),
+                                })
+                                .unwrap();
+                        }
+                        MeshGeneratorMessage::Shutdown => {
+                            worker_sender.send(MeshWorkerEvent::Shutdown).unwrap();
+                            break;
+                        }
+                    }
+                }
+            }));
+        }
+        {
+            let generation = Arc::clone(&mesh_generator.generation);
+            let ambient_occlusion = Arc::clone(&mesh_generator.ambient_occlusion);
+            let shutdown = Arc::clone(&mesh_generator.shutdown);
+
+            mesh_generator.threads.push(thread::spawn(move || {
+                let mut queue = MeshQueue::new();
+
+                'events: while let Ok(event) = worker_receiver.recv() {
+                    let mut meshed = Vec::new();
+                    for event in iter::once(event).chain(worker_receiver.try_iter()) {
+                        match event {
+                            MeshWorkerEvent::Queue {
+                                origin,
+                                to_generate,
+                            } => queue.set_queue(origin, to_generate),
+                            MeshWorkerEvent::Remesh(chunks) => queue.remesh(chunks),
+                            MeshWorkerEvent::Meshed {
+                                generation: mesh_generation,
+                                position,
+                                mesh,
+                            } => {
+                                queue.finish(position);
+                                // Meshes built before the last clear belong to the old world
+                                if let Some(mesh) = mesh.filter(|_| {
+                                    mesh_generation == generation.load(Ordering::Acquire)
+                                }) {
+                                    meshed.push((position, mesh));
+                                }
+                            }
+                            // Meshes still in flight have nowhere to go after this
+                            MeshWorkerEvent::Shutdown => break 'events,
+                        }
+                    }
+
+                    // Published as one snapshot, so the renderer only ever loads a pointer
+                    if !meshed.is_empty() {
+                        meshes.update(|generated| generated.extend(meshed.iter().cloned()));
+                        let built = metrics
+                            .meshes_built
+                            .fetch_add(meshed.len() as u64, Ordering::Relaxed)
+                            + meshed.len() as u64;
+                        metrics.report(Progress::Meshes {
+                            built,
+                            queued: queue.queued() as u64,
+                        });
+                    }
+
+                    while queue.in_flight() < settings.max_meshes_in_flight {
+                        let Some(MeshJob {
+                            position,
+                            lod,
+                            region,
+                        }) = queue.pop()
+                        else {
+                            break;
+                        };
+
+                        let chunks = chunks.clone();
+                        let meshes = Arc::clone(&meshes);
+                        let wanted = Arc::clone(&wanted);
+                        let build = Arc::clone(&build);
+                        let generation = generation.load(Ordering::Acquire);
+                        let ambient_occlusion = ambient_occlusion.load(Ordering::Acquire);
+                        let shutdown = Arc::clone(&shutdown);
+                        let worker_sender = worker_sender.clone();
+
+                        mesh_pool.spawn(move || {
+                            let is_wanted = || {
+                                !shutdown.load(Ordering::Acquire)
+                                    && wanted.read().get(&position) == Some(&lod)
+                            };
+
+                            let mesh = is_wanted()
+                                .then(|| {
+                                    // The chunk may be gone if the world was reset meanwhile
+                                    chunks.neighborhood(position, settings.world_bounds)
+                                })
+                                .flatten()
+                                .map(|neighborhood| {
+                                    // Patched into the mesh of the previous job, which is
+                                    // published before this one starts
+                                    let previous =
+                                        region.and_then(|_| meshes.read().get(&position).cloned());
+                                    let edit = region.map(|region| (region, previous.as_deref()));
+                                    Arc::new(build(neighborhood, lod, ambient_occlusion, edit))
+                                })
+                                // The chunk could have left the view while it was meshed
+                                .filter(|_| is_wanted());
+
+                            // The worker is gone once it shut down
+                            let _ = worker_sender.send(MeshWorkerEvent::Meshed {
+                                generation,
+                                position,
+                                mesh,
+                            });
+                        });
+                    }
+                }
+            }));
+        }
+
+        mesh_generator
+    }
+
+    pub fn set_visible(&self, origin: IVec3, positions: Box<[(IVec3, Lod)]>) {
+        self.sender
+            .send(MeshGeneratorMessage::SetVisible { origin, positions })
             .unwrap();
     }
+
+    /// Builds the meshes of changed chunks again, old meshes are kept until they're replaced.
+    /// Small regions are patched into the old mesh instead
+    pub fn remesh(&self, chunks: Box<[(IVec3, DirtyRegion)]>) {
+        self.sender
+            .send(MeshGeneratorMessage::Remesh(chunks))
+            .unwrap();
+    }
+
+    pub fn clear(&self) {
+        self.generation.fetch_add(1, Ordering::AcqRel);
+        self.sender.send(MeshGeneratorMessage::Clear).unwrap();
+    }
+
+    pub fn generation(&self) -> u32 {
+        self.generation.load(Ordering::Acquire)
+    }
+
+    pub fn ambient_occlusion(&self) -> bool {
+        self.ambient_occlusion.load(Ordering::Acquire)
+    }
+
+    // Takes effect for meshes built after the next clear
+    pub fn set_ambient_occlusion(&self, ambient_occlusion: bool) {
+        self.ambient_occlusion
+            .store(ambient_occlusion, Ordering::Release);
+    }
 }
 
-#[derive(Default)]
+impl Drop for MeshGenerator {
+    /// Stops the threads passing work to the mesh workers and waits for them to exit, meshes
+    /// still being built are thrown away
+    fn drop(&mut self) {
+        self.shutdown.store(true, Ordering::Release);
+        // Fails if the threads already died, joining reports why
+        let _ = self.sender.send(MeshGeneratorMessage::Shutdown);
+        for thread in self.threads.drain(..) {
+            if thread.join().is_err() {
+                error!("mesh thread panicked");
+            }
+        }
+    }
+}
+
+pub type MeshMap = HashMap<IVec3, Arc<ChunkBuffer>>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum State {
+    Running,
+    // The world and the camera stand still, the last frame keeps being drawn dimmed
+    Paused,
+}
+
+// Readers get an immutable snapshot, writers publish a modified copy
 pub struct Meshes {
-    generated: RwLock<HashMap<IVec3, ChunkBuffer>>,
+    generated: ArcSwap<MeshMap>,
+    metrics: Arc<Metrics>,
 }
 
 impl Meshes {
-    pub fn read(&self) -> RwLockReadGuard<'_, HashMap<IVec3, ChunkBuffer>> {
-        self.generated.read()
+    fn new(metrics: Arc<Metrics>) -> Self {
+        Self {
+            generated: Default::default(),
+            metrics,
+        }
     }
+
+    pub fn read(&self) -> Guard<Arc<MeshMap>> {
+        self.generated.load()
+    }
+
+    fn update(&self, mut update: impl FnMut(&mut MeshMap)) {
+        self.generated.rcu(|generated| {
+            let mut generated = MeshMap::clone(generated);
+            update(&mut generated);
+            generated
+        });
+
+        let generated = self.read();
+        self.metrics.meshes.set(generated.len() as u64);
+        self.metrics.mesh_bytes.set(
+            generated
+                .values()
+                .map(|mesh| mesh.buffers_size())
+                .sum::<u64>(),
+        );
+    }
+}
+
+const IDLE_FPS: u32 = 10;
+const TITLE_UPDATE_INTERVAL: Duration = Duration::from_secs(1);
+// Longer frames are simulated as this long, so stalls don't teleport the camera
+const MAX_DELTA_TIME: Duration = Duration::from_millis(100);
+// Movement and digging advance in steps of this length, whatever the frame rate
+const TICK: Duration = Duration::from_nanos(1_000_000_000 / 60);
+pub const DETERMINISTIC_SEED: u32 = 1;
+// Blocks further away than this can't be broken or placed against
+const REACH: f32 = 6.0;
+// Blocks the hotbar starts with, picked with the number keys or the mouse wheel
+const INVENTORY: [Block; SLOTS] = [
+    Block::Grass,
+    Block::Dirt,
+    Block::Stone,
+    Block::Sand,
+    Block::Gravel,
+    Block::Snow,
+    Block::Ice,
+    Block::Water,
+    Block::Glowstone,
+];
+const SLOT_KEYS: [KeyCode; SLOTS] = [
+    KeyCode::Digit1,
+    KeyCode::Digit2,
+    KeyCode::Digit3,
+    KeyCode::Digit4,
+    KeyCode::Digit5,
+    KeyCode::Digit6,
+    KeyCode::Digit7,
+    KeyCode::Digit8,
+    KeyCode::Digit9,
+];
+// Touchpads scroll in pixels, this many make up one notch of a wheel
+const PIXELS_PER_NOTCH: f64 = 40.0;
+
+/// Time simulated for a frame that took `elapsed`, stalls count as `MAX_DELTA_TIME`
+fn frame_delta(elapsed: Duration) -> Duration {
+    elapsed.min(MAX_DELTA_TIME)
+}
+
+/// Adds `delta_time` to the time not yet simulated and takes the whole ticks out of it
+fn take_ticks(accumulator: &mut Duration, delta_time: Duration) -> u32 {
+    *accumulator += delta_time;
+    let ticks = (accumulator.as_nanos() / TICK.as_nanos()) as u32;
+    *accumulator -= TICK * ticks;
+    ticks
 }
 
 pub struct Application {
     context: Arc<Context>,
     window: Arc<Window>,
+    settings: Settings,
 
     renderer: Renderer,
     world: World,
@@ -67,85 +465,155 @@ pub struct Application {
 
     meshes: Arc<Meshes>,
     mesh_generator: MeshGenerator,
-    mesh_receiver: Receiver<(IVec3, ChunkBuffer)>,
+    metrics: Arc<Metrics>,
+    // Drives the camera instead of the input while a benchmark runs
+    benchmark: Option<Benchmark>,
+    // Every frame simulates exactly one tick, and none until the world around the camera loaded
+    deterministic: bool,
+    console: Console,
+    interaction_listener: Box<dyn InteractionListener>,
+    #[cfg(feature = "gamepad")]
+    gamepads: Option<Gamepads>,
 
+    modifiers: ModifiersState,
+    focused: bool,
+    // Toggled with escape, the cursor is only grabbed while running
+    state: State,
+    // Held down to break the targeted block
+    digging: bool,
+    // Block being broken and how far along it is, in `0.0..1.0`
+    break_progress: Option<(IVec3, f32)>,
+    inventory: [Block; SLOTS],
+    selected_slot: usize,
+    // Scrolled notches not yet turned into a slot change
+    scroll: f64,
+    frame_limiter: FrameLimiter,
     last_frame_time: Instant,
+    // Time not yet simulated, always less than a tick after an update
+    tick_accumulator: Duration,
+    last_title_update: Instant,
 }
 
 impl Application {
-    pub async fn new(window: Window) -> Result<Self, Error> {
+    pub async fn new(
+        window: Window,
+        mut settings: Settings,
+        session: Option<Session>,
+    ) -> Result<Self, Error> {
+        if let Some(session) = &session {
+            settings.ambient_occlusion = session.ambient_occlusion;
+            settings.lod_distance = session.lod_distance;
+            settings.fly_speed = session.fly_speed;
+            settings.debug_overlay = session.debug_overlay;
+            settings.exposure = session.exposure;
+            settings.gamma = session.gamma;
+        }
+
         let window = Arc::new(window);
+        crash::set_window(&window);
         let _ = window.set_cursor_grab(CursorGrabMode::Locked);
+        if settings.start_fullscreen {
+            window.set_fullscreen(Some(fullscreen(&window, &settings)));
+        }
 
         let context = Arc::new(Context::new(Arc::clone(&window)).await?);
         let camera = Camera::new(
-            Transformation::new(Vec3::new(-2.0, 90.0, -2.0), -90.0_f32.to_radians(), 0.0),
-            Projection::new(window.inner_size(), 70.0_f32.to_radians(), 0.1, 1000.0),
+            // Restored before the first world update, so generation starts around it
+            session.as_ref().map_or(
+                Transformation::new(Vec3::new(-2.0, 90.0, -2.0), -90.0_f32.to_radians(), 0.0),
+                |session| session.transformation,
+            ),
+            Projection::new(window.inner_size(), 70.0_f32.to_radians(), 0.1, 1000.0)
+                .with_reversed_z(settings.reversed_z),
             &context,
-        );
+        )
+        .with_bounds(settings.world_bounds)
+        .with_speed(settings.fly_speed);
 
         let chunks = Chunks::default();
-        let renderer = Renderer::new(camera.as_shader_resource(&context), Arc::clone(&context));
-        let world = World::new(chunks.clone());
-
-        let (mesh_generator_sender, mesh_generator_receiver) = channel();
-        let (to_generate_sender, to_generate_receiver) = channel();
-        let (mesh_sender, mesh_receiver) = channel();
+        let (atlas_layout, atlas) = Atlas::load_async(asset!("blocks"), &context)?;
+        Block::resolve_textures(&atlas_layout)?;
 
-        let mesh_generator = MeshGenerator::new(mesh_generator_sender);
-        let meshes = Arc::new(Meshes::default());
-        {
-            let meshes = Arc::clone(&meshes);
+        let metrics = Arc::new(Metrics::default());
+        let mut renderer = Renderer::new(
+            &camera,
+            &atlas_layout,
+            atlas,
+            settings.world_bounds,
+            settings.shadow_map_size,
+            Arc::clone(&metrics),
+            Arc::clone(&context),
+        )?;
+        let generation_pool = (settings.generation_threads > 0)
+            .then(|| {
+                ThreadPoolBuilder::new()
+                    .num_threads(settings.generation_threads)
+                    .thread_name(|index| format!("generator-{index}"))
+                    .build()
+            })
+            .transpose()?;
+        let mesh_pool = ThreadPoolBuilder::new()
+            .num_threads(settings.mesh_threads)
+            .thread_name(|index| format!("mesher-{index}"))
+            .build()?;
 
-            thread::spawn(move || {
-                for message in mesh_generator_receiver.iter() {
-                    match message {
-                        MeshGeneratorMessage::SetVisible { positions } => {
-                            let mut positions = positions.to_vec();
-                            meshes.generated.write().retain(|mesh_position, _| {
-                                positions
-                                    .iter()
-                                    .position(|position| position == mesh_position)
-                                    .map(|index| positions.remove(index))
-                                    .is_some()
-                            });
-
-                            positions.reverse();
-                            to_generate_sender.send(positions).unwrap();
-                        }
-                    }
-                }
-            });
+        #[cfg_attr(not(feature = "serde"), allow(unused_mut))]
+        let mut world = World::new(chunks.clone(), settings.world_bounds)
+            .with_seed(session.as_ref().map_or(0, |session| session.seed))
+            .with_source(LocalSource::new(0).with_pool(generation_pool))
+            .with_lod_distance(settings.lod_distance)
+            .with_render_distance(settings.render_distance, settings.vertical_render_distance)
+            .with_spherical_render_distance(settings.spherical_render_distance)
+            .with_generation_distance(settings.generation_distance)
+            .with_random_tick_speed(settings.random_tick_speed)
+            .with_metrics(Arc::clone(&metrics));
+        // The platform is stored as JSON
+        #[cfg(feature = "serde")]
+        if session.is_none() {
+            // Under the camera, written over the terrain once it's generated
+            let origin =
+                coords::position_to_block(camera.transformation().position()) - IVec3::Y * 2;
+            world.place_structure(origin, &Structure::spawn_platform());
         }
-        {
-            let context = Arc::clone(&context);
-
-            rayon::spawn(move || {
-                let mut to_generate = to_generate_receiver.recv().unwrap();
-                loop {
-                    to_generate = to_generate_receiver
-                        .try_iter()
-                        .last()
-                        .unwrap_or(to_generate);
-
-                    to_generate
-                        .par_drain(to_generate.len().saturating_sub(8)..)
-                        .for_each(|position| {
-                            let mesh = {
-                                let chunks = chunks.read();
-                                let neighborhood = ChunkNeighborhood::new(&chunks, position);
-                                create_mesh(neighborhood, &context)
-                            };
-
-                            mesh_sender.send((position, mesh)).unwrap();
-                        });
-                }
-            });
+        renderer.set_seed(world.seed());
+        renderer.set_hotbar(INVENTORY, 0);
+        renderer.set_bloom(settings.bloom);
+        renderer.set_debug_overlay(settings.debug_overlay);
+        renderer.set_exposure(settings.exposure);
+        renderer.set_gamma(settings.gamma);
+        if let Some(session) = &session {
+            renderer.set_shadows(session.shadows);
         }
 
+        let meshes = Arc::new(Meshes::new(Arc::clone(&metrics)));
+        let mesh_generator = {
+            let context = Arc::clone(&context);
+            MeshGenerator::spawn(
+                chunks,
+                Arc::clone(&meshes),
+                mesh_pool,
+                Arc::clone(&metrics),
+                settings,
+                Arc::new(
+                    move |neighborhood, lod, ambient_occlusion, edit| match edit {
+                        Some((region, previous)) => remesh(
+                            neighborhood,
+                            lod,
+                            ambient_occlusion,
+                            previous,
+                            region,
+                            &context,
+                        ),
+                        None => create_mesh(neighborhood, lod, ambient_occlusion, &context),
+                    },
+                ),
+            )
+        };
+
         Ok(Self {
             context,
             window,
+            settings,
 
             renderer,
             world,
@@ -153,53 +621,593 @@ impl Application {
 
             mesh_generator,
             meshes,
+            metrics,
+            benchmark: None,
+            deterministic: false,
+            console: Console::new(),
+            interaction_listener: Box::new(SilentListener),
 
+            modifiers: ModifiersState::empty(),
+            focused: true,
+            state: State::Running,
+            digging: false,
+            break_progress: None,
+            inventory: INVENTORY,
+            selected_slot: 0,
+            scroll: 0.0,
+            frame_limiter: FrameLimiter::new(),
             last_frame_time: Instant::now(),
-            mesh_receiver,
+            tick_accumulator: Duration::ZERO,
+            last_title_update: Instant::now(),
+            #[cfg(feature = "gamepad")]
+            gamepads: Gamepads::new(),
         })
     }
 
     pub fn draw(&mut self) {
-        let frustum = Frustum::from_projection(self.camera.calculate_matrix());
+        self.renderer.draw(&self.camera, &self.meshes);
 
-        self.renderer.draw(&frustum, &self.meshes);
-        self.update()
+        let delta_time = if !self.deterministic {
+            frame_delta(self.last_frame_time.elapsed())
+        } else if self.is_world_loaded() {
+            TICK
+        } else {
+            Duration::ZERO
+        };
+        self.update(delta_time)
     }
 
-    pub fn update(&mut self) {
-        let delta_time = self.last_frame_time.elapsed();
+    /// Advances everything by `delta_time`, the wall clock only shows up in the stats
+    pub fn update(&mut self, delta_time: Duration) {
+        let frame_time = self.last_frame_time.elapsed();
 
         self.renderer.update(delta_time);
-        self.camera.update(delta_time, &self.context);
-        self.world.update(&self.camera, &self.mesh_generator);
-        self.receive_meshes();
+        self.update_title(frame_time);
+        if self.state == State::Running {
+            for _ in 0..take_ticks(&mut self.tick_accumulator, delta_time) {
+                self.tick();
+            }
+            self.update_world();
+        }
+        // Also while paused, so edits from the console show up right away
+        self.world.update_dynamic(&self.mesh_generator);
+        let loaded = self.is_world_loaded();
+        if let Some(benchmark) = &mut self.benchmark {
+            benchmark.frame(frame_time, loaded, &self.meshes.read());
+        }
+        self.camera.interpolate(
+            self.tick_accumulator.as_secs_f32() / TICK.as_secs_f32(),
+            &self.context,
+        );
 
         self.last_frame_time = Instant::now();
+
+        // The frame is already submitted at this point, so waiting here doesn't delay it
+        let frame_cap = self.frame_cap();
+        self.renderer.set_frame_cap(frame_cap);
+        self.frame_limiter.wait(frame_cap);
+
         self.window.request_redraw();
     }
 
-    fn receive_meshes(&self) {
-        let mut meshes = self.mesh_receiver.try_iter().peekable();
-        if meshes.peek().is_some() {
-            self.meshes.generated.write().extend(meshes);
+    /// Advances movement and digging by a single fixed step
+    fn tick(&mut self) {
+        #[cfg(feature = "gamepad")]
+        if let Some(gamepads) = &mut self.gamepads {
+            gamepads.update(&mut self.camera, &self.settings.gamepad);
+        }
+        match &mut self.benchmark {
+            Some(benchmark) => {
+                self.camera.step_to(benchmark.tick(TICK));
+                if let Some(edit) = benchmark.edit(&self.world, &self.meshes.read()) {
+                    self.world.set_blocks([edit]);
+                }
+            }
+            None => self.camera.update(TICK),
+        }
+        self.update_digging(TICK);
+        self.world.tick();
+    }
+
+    /// Catches the world up with the camera once per frame, stands still while paused
+    fn update_world(&mut self) {
+        let origin = coords::block_to_chunk(coords::position_to_block(
+            self.camera.transformation().position(),
+        ));
+        self.world.update_streaming(
+            origin,
+            self.camera.transformation().direction(),
+            &self.mesh_generator,
+        );
+        for event in self.world.take_block_events() {
+            match event {
+                BlockEvent::Broken { position, block } => {
+                    self.renderer.spawn_break_particles(position, block);
+                    self.interaction_listener.on_break(block, position);
+                }
+                BlockEvent::Placed { position, block } => {
+                    self.interaction_listener.on_place(block, position)
+                }
+            }
         }
+
+        let eye = coords::position_to_block(self.camera.transformation().position());
+        self.renderer
+            .set_underwater(self.world.block(eye) == Block::Water);
+    }
+
+    /// Advances breaking the targeted block, starting over on a new target or a released button
+    fn update_digging(&mut self, delta_time: Duration) {
+        let transformation = self.camera.transformation();
+        let hit = self
+            .digging
+            .then(|| {
+                self.world
+                    .raycast(transformation.position(), transformation.direction(), REACH)
+            })
+            .flatten();
+
+        self.break_progress = hit.and_then(|hit| {
+            let hardness = hit.block.hardness()?;
+            let progress = self
+                .break_progress
+                .filter(|&(target, _)| target == hit.position)
+                .map_or(0.0, |(_, progress)| progress)
+                + delta_time.as_secs_f32() * self.settings.break_speed / hardness;
+
+            if progress >= 1.0 {
+                self.world.set_blocks([(hit.position, Block::Air)]);
+                return None;
+            }
+            Some((hit.position, progress))
+        });
+        self.renderer.set_break_progress(self.break_progress);
     }
 
+    fn frame_cap(&self) -> Option<u32> {
+        if !self.focused && self.benchmark.is_none() {
+            return Some(IDLE_FPS);
+        }
+
+        match self.context.config().present_mode {
+            PresentMode::AutoVsync | PresentMode::Fifo | PresentMode::FifoRelaxed => None,
+            _ => self.settings.max_fps,
+        }
+    }
+
+    /// Mirrors the overlay stats into the window title unless the full overlay shows them
+    pub fn update_title(&mut self, delta_time: Duration) {
+        if self.last_title_update.elapsed() < TITLE_UPDATE_INTERVAL {
+            return;
+        }
+        self.last_title_update = Instant::now();
+
+        let title =
+            if !self.settings.title_stats || self.renderer.debug_overlay() == DebugOverlay::Full {
+                TITLE.to_string()
+            } else {
+                let fps = 1.0 / delta_time.as_secs_f32();
+                let position = self.camera.transformation().position().floor();
+                format!(
+                    "{TITLE} | seed {} | {} FPS | ({}, {}, {})",
+                    self.world.seed(),
+                    fps.round(),
+                    position.x,
+                    position.y,
+                    position.z
+                )
+            };
+        self.window.set_title(&title);
+    }
+
+    /// Flies the camera along the benchmark path from now on, see [`Benchmark`]
+    pub fn start_benchmark(&mut self) {
+        self.camera.step_to(Benchmark::start_transformation());
+        self.benchmark = Some(Benchmark::new());
+    }
+
+    /// Whether nothing around the camera is left to generate or mesh
+    fn is_world_loaded(&self) -> bool {
+        let visible = self.metrics.visible_chunks.get();
+        self.world.pending_sections() == 0 && visible > 0 && self.metrics.meshes.get() >= visible
+    }
+
+    pub fn deterministic_transformation() -> Transformation {
+        Transformation::new(
+            Vec3::new(0.5, 90.0, 0.5),
+            45.0_f32.to_radians(),
+            -20.0_f32.to_radians(),
+        )
+    }
+
+    /// Renders the same frames on every run from [`Application::deterministic_transformation`]
+    /// with [`DETERMINISTIC_SEED`], the frames count from the first one with the world loaded
+    pub fn start_deterministic(&mut self) {
+        self.camera.step_to(Self::deterministic_transformation());
+        self.deterministic = true;
+        self.settings.title_stats = false;
+        self.renderer.set_debug_overlay(DebugOverlay::Off);
+    }
+
+    pub fn is_benchmark_finished(&self) -> bool {
+        self.benchmark.as_ref().is_some_and(Benchmark::is_finished)
+    }
+
+    /// Writes the report of the benchmark into [`REPORT_PATH`], or only logs it without the
+    /// `serde` feature
+    pub fn finish_benchmark(&mut self) {
+        let Some(benchmark) = self.benchmark.take() else {
+            return;
+        };
+
+        let present_mode = format!("{:?}", self.context.config().present_mode);
+        let report = benchmark.report(&self.metrics, present_mode);
+        #[cfg(feature = "serde")]
+        match report.save(REPORT_PATH) {
+            Ok(()) => info!("wrote benchmark report into {REPORT_PATH}"),
+            Err(err) => error!("failed to write benchmark report: {err}"),
+        }
+        #[cfg(not(feature = "serde"))]
+        info!("benchmark report: {report:#?}");
+    }
+
+    pub fn metrics(&self) -> &Arc<Metrics> {
+        &self.metrics
+    }
+
+    /// See [`Metrics::on_progress`]
+    pub fn on_progress(&self, callback: impl Fn(Progress) + Send + Sync + 'static) {
+        self.metrics.on_progress(callback);
+    }
+
+    pub fn world(&self) -> &World {
+        &self.world
+    }
+
+    pub fn world_mut(&mut self) -> &mut World {
+        &mut self.world
+    }
+
+    pub fn console(&self) -> &Console {
+        &self.console
+    }
+
+    /// Registers commands with the console, next to the built in ones
+    pub fn console_mut(&mut self) -> &mut Console {
+        &mut self.console
+    }
+
+    /// Gets told about blocks being broken and placed, e.g. to play sounds for them
+    pub fn set_interaction_listener(&mut self, listener: impl InteractionListener + 'static) {
+        self.interaction_listener = Box::new(listener);
+    }
+
+    pub fn exposure(&self) -> f32 {
+        self.renderer.exposure()
+    }
+
+    pub fn set_exposure(&mut self, exposure: f32) {
+        self.renderer.set_exposure(exposure);
+        self.renderer.show_color_grading();
+    }
+
+    /// Multiplies the exposure by [`EXPOSURE_STEP`] once per step, dividing for negative ones
+    fn step_exposure(&mut self, steps: i32) {
+        self.set_exposure(self.exposure() * EXPOSURE_STEP.powi(steps));
+    }
+
+    pub fn gamma(&self) -> f32 {
+        self.renderer.gamma()
+    }
+
+    pub fn set_gamma(&mut self, gamma: f32) {
+        self.renderer.set_gamma(gamma);
+        self.renderer.show_color_grading();
+    }
+
+    pub fn teleport(&mut self, position: Vec3) {
+        self.camera.set_position(position);
+    }
+
+    pub fn reset_world(&mut self, seed: u32) {
+        self.world.reset(seed, &self.mesh_generator);
+        self.renderer.set_seed(seed);
+    }
+
+    /// Only records the size, the renderer reconfigures the surface before the next frame
     pub fn resize(&mut self, new_size: PhysicalSize<u32>) {
-        self.context.resize(new_size);
-        self.renderer.resize(new_size);
-        self.camera.resize(new_size);
+        self.context.request_resize(new_size);
+        // A minimized window has no aspect ratio, the old one is kept until it's restored
+        if new_size.width > 0 && new_size.height > 0 {
+            self.camera.resize(new_size);
+        }
+    }
+
+    /// Sends the keys to the console while it's open, to the camera and shortcuts otherwise
+    pub fn key_event(&mut self, event: KeyEvent) {
+        if self.console.is_open() {
+            if let Some(line) = self.console.key_input(&event) {
+                self.run_command(&line);
+            }
+            self.update_console();
+            return;
+        }
+
+        if let PhysicalKey::Code(key_code) = event.physical_key {
+            self.keyboard_input(key_code, event.state);
+        }
+    }
+
+    pub fn state(&self) -> State {
+        self.state
+    }
+
+    /// Freezes the world and frees the cursor, resuming grabs the cursor again
+    pub fn set_paused(&mut self, paused: bool) {
+        let (state, mode) = if paused {
+            (State::Paused, CursorGrabMode::None)
+        } else {
+            (State::Running, CursorGrabMode::Locked)
+        };
+        self.state = state;
+        let _ = self.window.set_cursor_grab(mode);
+        self.window.set_cursor_visible(paused);
+        self.renderer.set_paused(paused);
+
+        if paused {
+            self.camera.release_keys();
+            self.digging = false;
+        } else {
+            // The paused time doesn't count as one long frame
+            self.last_frame_time = Instant::now();
+        }
+    }
+
+    fn set_console_open(&mut self, open: bool) {
+        self.console.set_open(open);
+        if open {
+            self.camera.release_keys();
+            self.digging = false;
+        }
+        self.update_console();
+    }
+
+    fn update_console(&mut self) {
+        let input = self.console.is_open().then(|| self.console.input());
+        self.renderer.set_console(input, self.console.log());
+    }
+
+    pub fn run_command(&mut self, line: &str) {
+        self.console.print(format!("> {line}"));
+
+        let result = self
+            .console
+            .parse(line)
+            .and_then(|(handler, args)| handler(self, &args));
+        match result {
+            Ok(output) => self.console.print(output),
+            Err(err) => self.console.print(format!("error: {err}")),
+        }
     }
 
     pub fn keyboard_input(&mut self, key_code: KeyCode, state: ElementState) {
         self.camera.process_key(key_code, state);
+        if !state.is_pressed() {
+            return;
+        }
+
+        if let Some(slot) = SLOT_KEYS.iter().position(|&key| key == key_code) {
+            self.select_slot(slot);
+            return;
+        }
+
+        match key_code {
+            KeyCode::KeyR if self.modifiers.control_key() => {
+                let seed = if self.modifiers.shift_key() {
+                    self.world.seed()
+                } else {
+                    RandomState::new().hash_one(Instant::now()) as u32
+                };
+
+                self.reset_world(seed);
+            }
+            KeyCode::F3 => {
+                let overlay = self.renderer.debug_overlay().next();
+                self.renderer.set_debug_overlay(overlay);
+            }
+            KeyCode::F4 => {
+                let shadows = !self.renderer.shadows();
+                self.renderer.set_shadows(shadows);
+            }
+            KeyCode::F5 => {
+                let debug = !self.renderer.shadow_debug();
+                self.renderer.set_shadow_debug(debug);
+            }
+            KeyCode::F6 => {
+                let bloom = match self.renderer.bloom() {
+                    Some(..) => None,
+                    // Falls back to a default when bloom was turned off in the settings
+                    None => Some(self.settings.bloom.unwrap_or(DEFAULT_BLOOM_INTENSITY)),
+                };
+                self.renderer.set_bloom(bloom);
+            }
+            KeyCode::KeyT | KeyCode::Slash => self.set_console_open(true),
+            KeyCode::F7 => self.toggle_ambient_occlusion(),
+            KeyCode::BracketLeft => self.step_exposure(-1),
+            KeyCode::BracketRight => self.step_exposure(1),
+            #[cfg(feature = "serde")]
+            KeyCode::F9 => self.dump_chunk(),
+            KeyCode::F11 => self.toggle_fullscreen(),
+            KeyCode::Escape => self.set_paused(self.state == State::Running),
+            _ => {}
+        }
+    }
+
+    /// The resize that follows goes through [`Application::resize`] like any other
+    pub fn toggle_fullscreen(&mut self) {
+        let fullscreen = match self.window.fullscreen() {
+            Some(..) => None,
+            None => Some(fullscreen(&self.window, &self.settings)),
+        };
+        self.window.set_fullscreen(fullscreen);
+
+        // Some compositors drop the grab when the window changes modes
+        if self.state == State::Running {
+            let _ = self.window.set_cursor_grab(CursorGrabMode::Locked);
+            self.window.set_cursor_visible(false);
+        }
+    }
+
+    pub fn toggle_ambient_occlusion(&mut self) {
+        let ambient_occlusion = !self.mesh_generator.ambient_occlusion();
+        self.mesh_generator.set_ambient_occlusion(ambient_occlusion);
+        self.world.remesh(&self.mesh_generator);
+
+        info!(
+            "ambient occlusion {}",
+            if ambient_occlusion {
+                "enabled"
+            } else {
+                "disabled"
+            }
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    pub fn dump_chunk(&self) {
+        let position = coords::block_to_chunk(coords::position_to_block(
+            self.camera.transformation().position(),
+        ));
+
+        let Some(dump) = ChunkDump::capture(&self.world.chunks().read(), position) else {
+            warn!("there is no chunk at {position} to dump");
+            return;
+        };
+
+        let stem = format!("chunk_{}_{}_{}", position.x, position.y, position.z);
+        match dump.save_with_mesh(&stem) {
+            Ok(()) => info!("dumped chunk {position} into {stem}.json and {stem}.obj"),
+            Err(err) => error!("failed to dump chunk {position}: {err}"),
+        }
+    }
+
+    pub fn session(&self) -> Session {
+        Session::new(
+            self.world.seed(),
+            self.camera.transformation(),
+            self.mesh_generator.ambient_occlusion(),
+            self.settings.lod_distance,
+            self.renderer.shadows(),
+            self.camera.speed(),
+            self.renderer.debug_overlay(),
+        )
+        .with_color_grading(self.renderer.exposure(), self.renderer.gamma())
+    }
+
+    #[cfg(feature = "serde")]
+    pub fn save_session(&self) {
+        match self.session().save(SESSION_PATH) {
+            Ok(()) => info!("saved session into {SESSION_PATH}"),
+            Err(err) => error!("failed to save session: {err}"),
+        }
+    }
+
+    pub fn modifiers_changed(&mut self, modifiers: Modifiers) {
+        self.modifiers = modifiers.state();
     }
 
     pub fn mouse_motion(&mut self, dx: f64, dy: f64) {
+        if self.console.is_open() || self.state == State::Paused {
+            return;
+        }
         self.camera.process_mouse(dx, dy);
     }
 
+    pub fn mouse_input(&mut self, state: ElementState) {
+        // The click that resumes doesn't start digging
+        if self.state == State::Paused {
+            if state.is_pressed() {
+                self.set_paused(false);
+            }
+            return;
+        }
+        self.digging = state.is_pressed() && !self.console.is_open();
+        if self.digging {
+            self.renderer.swing();
+        }
+    }
+
+    /// Places the selected block against the face of the targeted block
+    pub fn place_block(&mut self) {
+        if self.state == State::Paused || self.console.is_open() {
+            return;
+        }
+        self.renderer.swing();
+
+        let transformation = self.camera.transformation();
+        let Some(hit) =
+            self.world
+                .raycast(transformation.position(), transformation.direction(), REACH)
+        else {
+            return;
+        };
+
+        let position = hit.position + hit.normal;
+        let eye = coords::position_to_block(transformation.position());
+        let block = self.inventory[self.selected_slot];
+        // A zero normal means the camera is inside the hit block
+        if hit.normal == IVec3::ZERO
+            || position == eye
+            || block == Block::Air
+            || !matches!(self.world.block(position), Block::Air | Block::Water)
+        {
+            return;
+        }
+        self.world.set_blocks([(position, block)]);
+    }
+
+    pub fn select_slot(&mut self, slot: usize) {
+        self.selected_slot = slot % SLOTS;
+        self.renderer.set_hotbar(self.inventory, self.selected_slot);
+    }
+
+    /// Moves through the hotbar a slot per notch, scrolling down moves right. With Alt held it
+    /// changes the fly speed instead
+    pub fn mouse_wheel(&mut self, delta: MouseScrollDelta) {
+        if self.state == State::Paused || self.console.is_open() {
+            return;
+        }
+
+        self.scroll += match delta {
+            MouseScrollDelta::LineDelta(_, y) => y as f64,
+            MouseScrollDelta::PixelDelta(position) => position.y / PIXELS_PER_NOTCH,
+        };
+        let notches = self.scroll.trunc();
+        self.scroll -= notches;
+        if notches == 0.0 {
+            return;
+        }
+
+        if self.modifiers.alt_key() {
+            self.camera.adjust_speed(notches as f32);
+            self.renderer.show_speed(self.camera.speed());
+            return;
+        }
+
+        let notches = if self.settings.natural_scrolling {
+            notches
+        } else {
+            -notches
+        };
+        let slot = (self.selected_slot as i64 + notches as i64).rem_euclid(SLOTS as i64);
+        self.select_slot(slot as usize);
+    }
+
     pub fn mouse_moved(&self) {
+        if self.state == State::Paused {
+            return;
+        }
+
         let size = self.window.inner_size();
         let _ = self
             .window
@@ -207,24 +1215,67 @@ impl Application {
     }
 }
 
+/// Fullscreen on the monitor picked in the settings, exclusive fullscreen takes its biggest
+/// and fastest video mode
+fn fullscreen(window: &Window, settings: &Settings) -> Fullscreen {
+    let monitor = settings
+        .monitor
+        .and_then(|index| window.available_monitors().nth(index))
+        .or_else(|| window.current_monitor());
+
+    let video_mode = settings
+        .exclusive_fullscreen
+        .then(|| {
+            monitor.as_ref()?.video_modes().max_by_key(|mode| {
+                let size = mode.size();
+                (size.width * size.height, mode.refresh_rate_millihertz())
+            })
+        })
+        .flatten();
+    match video_mode {
+        Some(video_mode) => Fullscreen::Exclusive(video_mode),
+        None => Fullscreen::Borderless(monitor),
+    }
+}
+
 impl ApplicationHandler for Application {
     fn resumed(&mut self, _: &ActiveEventLoop) {}
 
     fn window_event(&mut self, event_loop: &ActiveEventLoop, _: WindowId, event: WindowEvent) {
         match event {
-            WindowEvent::RedrawRequested => self.draw(),
+            WindowEvent::RedrawRequested => {
+                self.draw();
+                if self.is_benchmark_finished() {
+                    self.finish_benchmark();
+                    event_loop.exit();
+                }
+            }
             WindowEvent::Resized(new_size) => self.resize(new_size),
-            WindowEvent::CloseRequested => event_loop.exit(),
-            WindowEvent::KeyboardInput {
-                event:
-                    KeyEvent {
-                        physical_key: PhysicalKey::Code(key_code),
-                        state,
-                        ..
-                    },
-                ..
-            } => self.keyboard_input(key_code, state),
+            WindowEvent::CloseRequested => {
+                // Benchmarks and deterministic runs start from their own session, not the player's
+                #[cfg(feature = "serde")]
+                if self.benchmark.is_none() && !self.deterministic {
+                    self.save_session();
+                }
+                event_loop.exit();
+            }
+            WindowEvent::KeyboardInput { event, .. } => self.key_event(event),
+            WindowEvent::ModifiersChanged(modifiers) => self.modifiers_changed(modifiers),
+            WindowEvent::Focused(focused) => self.focused = focused,
+            // Nothing is drawn while the window is hidden, the time until then isn't a frame
+            WindowEvent::Occluded(false) => self.last_frame_time = Instant::now(),
             WindowEvent::CursorMoved { .. } => self.mouse_moved(),
+            WindowEvent::MouseInput {
+                state,
+                button: MouseButton::Left,
+                ..
+            } => self.mouse_input(state),
+            WindowEvent::MouseInput {
+                state: ElementState::Pressed,
+                button: MouseButton::Right,
+                ..
+            } => self.place_block(),
+            WindowEvent::MouseWheel { delta, .. } => self.mouse_wheel(delta),
             _ => {}
         }
     }
@@ -235,3 +1286,194 @@ impl ApplicationHandler for Application {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::mpsc;
+
+    use super::*;
+    use crate::camera::CameraController;
+
+    const JOIN_TIMEOUT: Duration = Duration::from_secs(10);
+
+    // Without a window there's nothing to build buffers on, so no chunk is ever loaded
+    fn spawn_generator() -> MeshGenerator {
+        let metrics = Arc::new(Metrics::default());
+        MeshGenerator::spawn(
+            Chunks::default(),
+            Arc::new(Meshes::new(Arc::clone(&metrics))),
+            ThreadPoolBuilder::new().num_threads(2).build().unwrap(),
+            metrics,
+            Settings::default(),
+            Arc::new(|_, _, _, _| unreachable!("no chunk is loaded")),
+        )
+    }
+
+    // Drops the generator on another thread, so a hanging join fails the test
+    fn drop_in_time(mesh_generator: MeshGenerator) {
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || {
+            drop(mesh_generator);
+            sender.send(()).unwrap();
+        });
+        receiver
+            .recv_timeout(JOIN_TIMEOUT)
+            .expect("the mesh threads weren't joined");
+    }
+
+    #[test]
+    fn stalls_move_the_camera_one_clamped_frame() {
+        let mut controller = CameraController::new();
+        controller.process_key(KeyCode::KeyW, ElementState::Pressed);
+        let start = Transformation::new(Vec3::ZERO, 0.0, 0.0);
+        let mut transformation = start;
+
+        let mut accumulator = Duration::ZERO;
+        let ticks = take_ticks(&mut accumulator, frame_delta(Duration::from_secs(2)));
+        for _ in 0..ticks {
+            controller.update_camera(&mut transformation, TICK);
+        }
+
+        let moved = transformation.position().distance(start.position());
+        let allowed = controller.speed() * MAX_DELTA_TIME.as_secs_f32();
+        assert!(moved > 0.0);
+        assert!(moved <= allowed * 1.001, "moved {moved}, allowed {allowed}");
+        assert!(accumulator < TICK);
+    }
+
+    #[test]
+    fn ticks_carry_the_rest_over() {
+        let mut accumulator = Duration::ZERO;
+        let half = TICK / 2;
+        let ticks =
+            [half, half, half, TICK * 3].map(|delta_time| take_ticks(&mut accumulator, delta_time));
+        assert_eq!(ticks, [0, 1, 0, 3]);
+        assert_eq!(accumulator, half);
+    }
+
+    #[test]
+    fn idle_generators_shut_down() {
+        drop_in_time(spawn_generator());
+    }
+
+    #[test]
+    fn busy_generators_shut_down() {
+        let mesh_generator = spawn_generator();
+        let positions = (-8..8)
+            .flat_map(|x| (-8..8).map(move |z| (IVec3::new(x, 0, z), Lod::Full)))
+            .collect::<Box<_>>();
+        let edited = positions
+            .iter()
+            .map(|&(position, _)| (position, DirtyRegion::Whole))
+            .collect();
+
+        mesh_generator.set_visible(IVec3::ZERO, positions.clone());
+        mesh_generator.remesh(edited);
+        mesh_generator.clear();
+        mesh_generator.set_visible(IVec3::ZERO, positions);
+        drop_in_time(mesh_generator);
+    }
+    // Stand-ins for the mesh map, buffers can't be built without a window
+    type StandInMap = HashMap<IVec3, Arc<u64>>;
+
+    // About the chunks in view at the default render distance
+    const BENCH_MESHES: i32 = 4096;
+    const BENCH_BATCH: i32 = 16;
+    const BENCH_FRAMES: u32 = 100_000;
+
+    fn stand_in_map() -> StandInMap {
+        (0..BENCH_MESHES)
+            .map(|index| {
+                (
+                    IVec3::new(index % 64, 0, index / 64),
+                    Arc::new(index as u64),
+                )
+            })
+            .collect()
+    }
+
+    /// Swaps a batch of meshes for new ones, the way a finished batch replaces stale meshes
+    fn publish_batch(map: &mut StandInMap, round: i32) {
+        for index in 0..BENCH_BATCH {
+            let position = IVec3::new(index, 0, round % 64);
+            map.insert(position, Arc::new(round as u64));
+        }
+    }
+
+    /// Mean and longest frame of a renderer walking the map through `walk` while another
+    /// thread keeps publishing batches, and how many batches got in
+    fn frames_under_publishing(
+        walk: impl Fn() -> u64 + Sync,
+        publish: impl Fn(i32) + Sync,
+    ) -> (Duration, Duration, i32) {
+        let done = AtomicBool::new(false);
+        thread::scope(|scope| {
+            let publisher = scope.spawn(|| {
+                let mut rounds = 0;
+                while !done.load(Ordering::Acquire) {
+                    publish(rounds);
+                    rounds += 1;
+                }
+                rounds
+            });
+
+            let mut total = Duration::ZERO;
+            let mut longest = Duration::ZERO;
+            for _ in 0..BENCH_FRAMES {
+                let start = Instant::now();
+                std::hint::black_box(walk());
+                let frame = start.elapsed();
+                total += frame;
+                longest = longest.max(frame);
+            }
+            done.store(true, Ordering::Release);
+            (total / BENCH_FRAMES, longest, publisher.join().unwrap())
+        })
+    }
+
+    #[test]
+    #[ignore = "benchmark, run with --release -- --ignored --nocapture"]
+    fn mesh_map_reads_while_publishing() {
+        let walk = |map: &StandInMap| map.values().map(|mesh| **mesh).sum::<u64>();
+
+        let locked = RwLock::new(stand_in_map());
+        let (mean, longest, batches) = frames_under_publishing(
+            || walk(&locked.read()),
+            |round| publish_batch(&mut locked.write(), round),
+        );
+        println!("locked map: {mean:?} per frame, {longest:?} at most, {batches} batches");
+
+        let swapped = ArcSwap::from_pointee(stand_in_map());
+        let (mean, longest, batches) = frames_under_publishing(
+            || walk(&swapped.load()),
+            |round| {
+                swapped.rcu(|map| {
+                    let mut map = StandInMap::clone(map);
+                    publish_batch(&mut map, round);
+                    map
+                });
+            },
+        );
+        println!("swapped map: {mean:?} per frame, {longest:?} at most, {batches} batches");
+    }
+    #[test]
+    #[ignore = "benchmark, run with --release -- --ignored --nocapture"]
+    fn publishing_a_batch_of_meshes() {
+        const ROUNDS: u32 = 2000;
+
+        // What a frame paid for every batch it received before the dispatcher published them
+        let meshes = ArcSwap::from_pointee(stand_in_map());
+        let start = Instant::now();
+        for round in 0..ROUNDS {
+            meshes.rcu(|map| {
+                let mut map = StandInMap::clone(map);
+                publish_batch(&mut map, round as i32);
+                map
+            });
+        }
+        println!(
+            "{:?} to publish {BENCH_BATCH} meshes into {BENCH_MESHES}",
+            start.elapsed() / ROUNDS
+        );
+    }
+}
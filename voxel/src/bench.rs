@@ -0,0 +1,244 @@
+use std::{
+    f32::consts::PI,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+#[cfg(feature = "serde")]
+use std::{
+    fs::File,
+    io::{self, BufWriter},
+    path::Path,
+};
+
+use glam::{vec3, IVec3, Vec3};
+#[cfg(feature = "serde")]
+use serde::Serialize;
+#[cfg(feature = "serde")]
+use thiserror::Error;
+
+use crate::{
+    application::MeshMap,
+    camera::Transformation,
+    metrics::Metrics,
+    render::world_pass::ChunkBuffer,
+    world::{coords, Block, World},
+};
+
+pub const BENCH_SEED: u32 = 1;
+#[cfg(feature = "serde")]
+pub const REPORT_PATH: &str = "bench.json";
+
+const START: Vec3 = vec3(0.5, 100.0, 0.5);
+// Blocks per second, fast enough to keep generation and meshing busy
+const FLIGHT_SPEED: f32 = 40.0;
+const FLIGHT_TIME: f32 = 15.0;
+const TURN_TIME: f32 = 3.0;
+const FLIGHT_END: f32 = FLIGHT_TIME * 2.0 + TURN_TIME;
+// Spent breaking the block under the start and putting it back, after the flight
+const EDIT_TIME: f32 = 10.0;
+// Between the mesh of an edit showing up and the next edit
+const EDIT_INTERVAL: f32 = 0.1;
+// Deep enough to reach the terrain from the start
+const EDIT_REACH: f32 = 200.0;
+
+#[cfg(feature = "serde")]
+#[derive(Debug, Error)]
+pub enum BenchError {
+    #[error("failed to write report: {0}")]
+    Io(#[from] io::Error),
+    #[error("failed to encode report: {0}")]
+    Format(#[from] serde_json::Error),
+}
+
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct BenchReport {
+    pub seed: u32,
+    pub present_mode: String,
+    /// Until every chunk around the start was generated and meshed
+    pub initial_generation_secs: f32,
+    pub meshes_per_second: f32,
+    pub frames: usize,
+    pub average_frame_ms: f32,
+    pub p99_frame_ms: f32,
+    /// Edits whose mesh showed up before the benchmark ended
+    pub edits: usize,
+    /// From an edit until the mesh of its chunk is rebuilt
+    pub average_edit_ms: f32,
+    pub p99_edit_ms: f32,
+    pub peak_chunks: u64,
+    pub peak_chunk_bytes: u64,
+    pub peak_meshes: u64,
+    pub peak_mesh_bytes: u64,
+}
+
+#[cfg(feature = "serde")]
+impl BenchReport {
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), BenchError> {
+        let writer = BufWriter::new(File::create(path)?);
+        serde_json::to_writer_pretty(writer, self)?;
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+struct PendingEdit {
+    chunk: IVec3,
+    // Mesh of the chunk when the edit was made, replaced once the edit is meshed
+    mesh: Option<Arc<ChunkBuffer>>,
+    made: Instant,
+}
+
+/// Waits for the world around the start to load, then flies forward, turns around and flies
+/// back, timing every frame of the flight. Back at the start it keeps breaking the block
+/// below and putting it back, timing how long the meshes take to catch up
+#[derive(Debug)]
+pub struct Benchmark {
+    started: Instant,
+    initial_generation: Option<Duration>,
+    // Simulated time since the flight started, goes on through the edits
+    flight_time: f32,
+    frame_times: Vec<Duration>,
+    // Block broken by the last edit, put back by the next one
+    broken: Option<(IVec3, Block)>,
+    pending_edit: Option<PendingEdit>,
+    next_edit: f32,
+    edit_times: Vec<Duration>,
+}
+
+impl Benchmark {
+    pub fn new() -> Self {
+        Self {
+            started: Instant::now(),
+            initial_generation: None,
+            flight_time: 0.0,
+            frame_times: Vec::new(),
+            broken: None,
+            pending_edit: None,
+            next_edit: FLIGHT_END,
+            edit_times: Vec::new(),
+        }
+    }
+
+    pub fn start_transformation() -> Transformation {
+        Transformation::new(START, 0.0, 0.0)
+    }
+
+    /// Where the camera is after the tick, it stays at the start until the world is loaded
+    pub fn tick(&mut self, delta_time: Duration) -> Transformation {
+        if self.initial_generation.is_none() {
+            return Self::start_transformation();
+        }
+        self.flight_time += delta_time.as_secs_f32();
+
+        let time = self.flight_time;
+        let turn_point = START + Vec3::X * FLIGHT_SPEED * FLIGHT_TIME;
+        if time < FLIGHT_TIME {
+            Transformation::new(START + Vec3::X * FLIGHT_SPEED * time, 0.0, 0.0)
+        } else if time < FLIGHT_TIME + TURN_TIME {
+            Transformation::new(turn_point, (time - FLIGHT_TIME) / TURN_TIME * PI, 0.0)
+        } else {
+            let back = (time - FLIGHT_TIME - TURN_TIME).min(FLIGHT_TIME);
+            Transformation::new(turn_point - Vec3::X * FLIGHT_SPEED * back, PI, 0.0)
+        }
+    }
+
+    /// Block to set this tick, once the flight is over and the last edit was meshed
+    pub fn edit(&mut self, world: &World, meshes: &MeshMap) -> Option<(IVec3, Block)> {
+        if self.flight_time < self.next_edit || self.pending_edit.is_some() {
+            return None;
+        }
+
+        let edit = match self.broken.take() {
+            Some(broken) => broken,
+            None => {
+                let hit = world.raycast(START, Vec3::NEG_Y, EDIT_REACH)?;
+                self.broken = Some((hit.position, hit.block));
+                (hit.position, Block::Air)
+            }
+        };
+        let chunk = coords::block_to_chunk(edit.0);
+        self.pending_edit = Some(PendingEdit {
+            chunk,
+            mesh: meshes.get(&chunk).cloned(),
+            made: Instant::now(),
+        });
+        Some(edit)
+    }
+
+    /// Records a frame, `loaded` tells whether nothing around the camera is left to generate
+    /// or mesh
+    pub fn frame(&mut self, frame_time: Duration, loaded: bool, meshes: &MeshMap) {
+        match self.initial_generation {
+            Some(..) if self.flight_time < FLIGHT_END => self.frame_times.push(frame_time),
+            Some(..) => {}
+            None if loaded => self.initial_generation = Some(self.started.elapsed()),
+            None => {}
+        }
+
+        let Some(edit) = &self.pending_edit else {
+            return;
+        };
+        if let Some(mesh) = meshes.get(&edit.chunk).filter(|mesh| {
+            !edit
+                .mesh
+                .as_ref()
+                .is_some_and(|previous| Arc::ptr_eq(previous, mesh))
+        }) {
+            // Built before this frame, the frame rate doesn't count
+            self.edit_times
+                .push(mesh.built().saturating_duration_since(edit.made));
+            self.pending_edit = None;
+            self.next_edit = self.flight_time + EDIT_INTERVAL;
+        }
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.flight_time >= FLIGHT_END + EDIT_TIME
+    }
+
+    pub fn report(&self, metrics: &Metrics, present_mode: String) -> BenchReport {
+        let (average, p99) = average_and_p99(&self.frame_times);
+        let (average_edit, p99_edit) = average_and_p99(&self.edit_times);
+
+        BenchReport {
+            seed: BENCH_SEED,
+            present_mode,
+            initial_generation_secs: self.initial_generation.unwrap_or_default().as_secs_f32(),
+            meshes_per_second: metrics.meshes_built() as f32 / self.started.elapsed().as_secs_f32(),
+            frames: self.frame_times.len(),
+            average_frame_ms: average * 1000.0,
+            p99_frame_ms: p99 * 1000.0,
+            edits: self.edit_times.len(),
+            average_edit_ms: average_edit * 1000.0,
+            p99_edit_ms: p99_edit * 1000.0,
+            peak_chunks: metrics.chunks.peak(),
+            peak_chunk_bytes: metrics.chunk_bytes.peak(),
+            peak_meshes: metrics.meshes.peak(),
+            peak_mesh_bytes: metrics.mesh_bytes.peak(),
+        }
+    }
+}
+
+impl Default for Benchmark {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Average and 99th percentile of the durations in seconds
+fn average_and_p99(durations: &[Duration]) -> (f32, f32) {
+    let mut seconds = durations
+        .iter()
+        .map(Duration::as_secs_f32)
+        .collect::<Vec<_>>();
+    seconds.sort_by(f32::total_cmp);
+
+    let average = seconds.iter().sum::<f32>() / seconds.len().max(1) as f32;
+    let p99 = seconds
+        .get((seconds.len() as f32 * 0.99) as usize)
+        .or(seconds.last())
+        .copied()
+        .unwrap_or_default();
+    (average, p99)
+}
@@ -2,9 +2,14 @@ use std::time::Duration;
 
 use bytemuck::{Pod, Zeroable};
 use glam::{Mat4, Vec3};
-use voxel_util::{bind_group::VertexFragment, AsBindGroup, BindingEntries, Context, Uniform};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use voxel_util::{Context, Uniform};
+use wgpu::CompareFunction;
 use winit::{dpi::PhysicalSize, event::ElementState, keyboard::KeyCode};
 
+use crate::world::WorldBounds;
+
 #[repr(C)]
 #[derive(Debug, Default, Clone, Copy, Pod, Zeroable)]
 pub struct CameraUniform {
@@ -40,6 +45,10 @@ pub struct Camera {
 
     projection: Projection,
     transformation: Transformation,
+    // Transformation before the last step, rendering blends between the two
+    previous_transformation: Transformation,
+    rendered_transformation: Transformation,
+    bounds: Option<WorldBounds>,
 }
 
 impl Camera {
@@ -50,21 +59,74 @@ impl Camera {
 
             projection,
             transformation,
+            previous_transformation: transformation,
+            rendered_transformation: transformation,
+            bounds: None,
         }
     }
 
-    pub fn update(&mut self, dt: Duration, context: &Context) {
+    pub fn with_bounds(mut self, bounds: Option<WorldBounds>) -> Self {
+        self.bounds = bounds;
+        self
+    }
+
+    pub fn with_speed(mut self, speed: f32) -> Self {
+        self.controller.set_speed(speed);
+        self
+    }
+
+    /// Moves the camera by one simulation step of `dt`
+    pub fn update(&mut self, dt: Duration) {
+        self.previous_transformation = self.transformation;
         self.controller.update_camera(&mut self.transformation, dt);
-        self.uniform.map(
-            |uniform| uniform.update_view_projection(&self.projection, &self.transformation),
-            context,
-        );
+        if let Some(bounds) = self.bounds {
+            self.transformation.position = bounds.clamp(self.transformation.position);
+        }
+    }
+
+    /// Renders the camera `alpha` of the way from the previous step to the current one
+    pub fn interpolate(&mut self, alpha: f32, context: &Context) {
+        self.rendered_transformation = self
+            .previous_transformation
+            .lerp(self.transformation, alpha);
+
+        let uniform = self
+            .uniform
+            .data()
+            .update_view_projection(&self.projection, &self.rendered_transformation);
+        self.uniform.stage(uniform, context);
     }
 
     pub fn resize(&mut self, new_size: PhysicalSize<u32>) {
         self.projection.aspect = new_size.width as f32 / new_size.height as f32;
     }
 
+    pub fn set_position(&mut self, position: Vec3) {
+        self.transformation.position = position;
+        self.previous_transformation = self.transformation;
+    }
+
+    /// Moves the camera to `transformation` as one simulation step, bypassing the controller
+    pub fn step_to(&mut self, transformation: Transformation) {
+        self.previous_transformation = self.transformation;
+        self.transformation = transformation;
+    }
+
+    /// Stops any movement from keys that are still held down
+    pub fn release_keys(&mut self) {
+        self.controller = CameraController::new().with_speed(self.controller.speed());
+    }
+
+    /// Scales the fly speed by `notches` of the mouse wheel, scrolling up speeds up
+    pub fn adjust_speed(&mut self, notches: f32) {
+        self.controller.adjust_speed(notches);
+    }
+
+    /// Fly speed without sprinting, in blocks per second
+    pub fn speed(&self) -> f32 {
+        self.controller.speed()
+    }
+
     pub fn process_mouse(&mut self, mouse_dx: f64, mouse_dy: f64) {
         self.controller.process_mouse(mouse_dx, mouse_dy)
     }
@@ -73,8 +135,13 @@ impl Camera {
         self.controller.process_key(key_code, state)
     }
 
+    #[cfg(feature = "gamepad")]
+    pub fn process_analog(&mut self, input: AnalogInput) {
+        self.controller.process_analog(input)
+    }
+
     pub fn calculate_matrix(&self) -> Mat4 {
-        self.projection.calculate_matrix() * self.transformation.calculate_matrix()
+        self.projection.calculate_matrix() * self.rendered_transformation.calculate_matrix()
     }
 
     pub fn projection(&self) -> Projection {
@@ -84,17 +151,19 @@ impl Camera {
     pub fn transformation(&self) -> Transformation {
         self.transformation
     }
-}
 
-impl AsBindGroup for Camera {
-    type BindingEntries = ((VertexFragment, Uniform<CameraUniform>),);
+    /// Transformation the last frame was rendered with
+    pub fn rendered_transformation(&self) -> Transformation {
+        self.rendered_transformation
+    }
 
-    fn resources(&self) -> <Self::BindingEntries as BindingEntries>::Bindings<'_> {
-        (&self.uniform,)
+    pub fn uniform(&self) -> &Uniform<CameraUniform> {
+        &self.uniform
     }
 }
 
 #[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Transformation {
     position: Vec3,
     yaw: f32,
@@ -111,20 +180,36 @@ impl Transformation {
     }
 
     pub fn calculate_matrix(&self) -> Mat4 {
-        let (sin_pitch, cos_pitch) = self.pitch.sin_cos();
-        let (sin_yaw, cos_yaw) = self.yaw.sin_cos();
-
-        Mat4::look_to_rh(
-            self.position,
-            Vec3::new(cos_pitch * cos_yaw, sin_pitch, cos_pitch * sin_yaw).normalize(),
-            Vec3::Y,
-        )
+        Mat4::look_to_rh(self.position, self.direction(), Vec3::Y)
     }
 
     pub fn position(&self) -> Vec3 {
         self.position
     }
 
+    pub fn yaw(&self) -> f32 {
+        self.yaw
+    }
+
+    pub fn pitch(&self) -> f32 {
+        self.pitch
+    }
+
+    pub fn lerp(self, other: Self, alpha: f32) -> Self {
+        Self {
+            position: self.position.lerp(other.position, alpha),
+            yaw: self.yaw + (other.yaw - self.yaw) * alpha,
+            pitch: self.pitch + (other.pitch - self.pitch) * alpha,
+        }
+    }
+
+    pub fn direction(&self) -> Vec3 {
+        let (sin_pitch, cos_pitch) = self.pitch.sin_cos();
+        let (sin_yaw, cos_yaw) = self.yaw.sin_cos();
+
+        Vec3::new(cos_pitch * cos_yaw, sin_pitch, cos_pitch * sin_yaw).normalize()
+    }
+
     pub fn forward_horizontal(&self) -> (Vec3, Vec3) {
         let (yaw_sin, yaw_cos) = self.yaw.sin_cos();
         let pitch_cos = self.pitch.cos();
@@ -142,6 +227,9 @@ pub struct Projection {
     fovy: f32,
     znear: f32,
     zfar: f32,
+    // Maps the near plane to a depth of 1 and the far one to 0, floats are most precise
+    // around 0, which evens out the precision lost to the perspective divide
+    reversed_z: bool,
 }
 
 impl Projection {
@@ -151,53 +239,152 @@ impl Projection {
             fovy,
             znear,
             zfar,
+            reversed_z: true,
         }
     }
 
+    pub fn with_reversed_z(mut self, reversed_z: bool) -> Self {
+        self.reversed_z = reversed_z;
+        self
+    }
+
     pub fn calculate_matrix(&self) -> Mat4 {
-        Mat4::perspective_rh(self.fovy, self.aspect, self.znear, self.zfar)
+        if self.reversed_z {
+            // Swapping the planes flips the depth range without changing anything else
+            Mat4::perspective_rh(self.fovy, self.aspect, self.zfar, self.znear)
+        } else {
+            Mat4::perspective_rh(self.fovy, self.aspect, self.znear, self.zfar)
+        }
+    }
+
+    pub fn reversed_z(&self) -> bool {
+        self.reversed_z
+    }
+
+    /// Passes fragments closer to the camera than the depth already written
+    pub fn depth_compare(&self) -> CompareFunction {
+        if self.reversed_z {
+            CompareFunction::Greater
+        } else {
+            CompareFunction::Less
+        }
+    }
+
+    /// Depth of the far plane, which the depth buffer is cleared to
+    pub fn far_depth(&self) -> f32 {
+        if self.reversed_z {
+            0.0
+        } else {
+            1.0
+        }
+    }
+
+    pub fn fovy(&self) -> f32 {
+        self.fovy
+    }
+
+    pub fn aspect(&self) -> f32 {
+        self.aspect
     }
 }
 
 #[derive(Debug, Default, Clone, Copy)]
 struct Direction {
-    pos: bool,
-    neg: bool,
+    pos: f32,
+    neg: f32,
+    analog: f32,
 }
 
 impl Direction {
     fn value(self) -> f32 {
-        f32::from(self.pos) - f32::from(self.neg)
+        (self.pos - self.neg + self.analog).clamp(-1.0, 1.0)
     }
 
     fn set_pos(&mut self, pos: bool) {
-        self.pos = pos;
+        self.pos = f32::from(pos);
     }
 
     fn set_neg(&mut self, neg: bool) {
-        self.neg = neg;
+        self.neg = f32::from(neg);
+    }
+
+    #[cfg(feature = "gamepad")]
+    fn set_analog(&mut self, analog: f32) {
+        self.analog = analog;
     }
 }
 
+/// Controller input, applied on top of the keyboard and mouse
+#[cfg(feature = "gamepad")]
+#[derive(Debug, Default, Clone, Copy)]
+pub struct AnalogInput {
+    pub forward: f32,
+    pub horizontal: f32,
+    pub vertical: f32,
+    // Degrees per second
+    pub look_horizontal: f32,
+    pub look_vertical: f32,
+    pub sprint: bool,
+}
+
 const SENSITIVITY: f32 = 90.0;
-const SPEED: f32 = 100.0;
-const VERTICAL_SPEED: f32 = 150.0;
+// In world units per second, a block is one unit wide
+pub const DEFAULT_SPEED: f32 = 100.0;
+const MIN_SPEED: f32 = 1.0;
+const MAX_SPEED: f32 = 10_000.0;
+// Each notch of the mouse wheel multiplies the speed by this
+const SPEED_STEP: f32 = 1.2;
+// Relative to the horizontal speed
+const VERTICAL_SPEED: f32 = 1.5;
 const SPRINT_MULTIPLIER: f32 = 3.0;
 
-#[derive(Debug, Default, Clone, Copy)]
+#[derive(Debug, Clone, Copy)]
 pub struct CameraController {
     rotate_horizontal: f32,
     rotate_vertical: f32,
+    look_horizontal: f32,
+    look_vertical: f32,
 
     forward: Direction,
     horizontal: Direction,
     vertical: Direction,
     sprint: bool,
+    analog_sprint: bool,
+    speed: f32,
 }
 
 impl CameraController {
     pub fn new() -> Self {
-        Self::default()
+        Self {
+            rotate_horizontal: 0.0,
+            rotate_vertical: 0.0,
+            look_horizontal: 0.0,
+            look_vertical: 0.0,
+
+            forward: Direction::default(),
+            horizontal: Direction::default(),
+            vertical: Direction::default(),
+            sprint: false,
+            analog_sprint: false,
+            speed: DEFAULT_SPEED,
+        }
+    }
+
+    pub fn with_speed(mut self, speed: f32) -> Self {
+        self.set_speed(speed);
+        self
+    }
+
+    pub fn speed(&self) -> f32 {
+        self.speed
+    }
+
+    pub fn set_speed(&mut self, speed: f32) {
+        self.speed = speed.clamp(MIN_SPEED, MAX_SPEED);
+    }
+
+    pub fn adjust_speed(&mut self, notches: f32) {
+        self.set_speed(self.speed * SPEED_STEP.powf(notches));
     }
 
     pub fn process_key(&mut self, key_code: KeyCode, state: ElementState) {
@@ -220,8 +407,19 @@ impl CameraController {
     }
 
     pub fn process_mouse(&mut self, mouse_dx: f64, mouse_dy: f64) {
-        self.rotate_horizontal = mouse_dx as f32;
-        self.rotate_vertical = mouse_dy as f32;
+        // Several motions may arrive between two steps
+        self.rotate_horizontal += mouse_dx as f32;
+        self.rotate_vertical += mouse_dy as f32;
+    }
+
+    #[cfg(feature = "gamepad")]
+    pub fn process_analog(&mut self, input: AnalogInput) {
+        self.forward.set_analog(input.forward);
+        self.horizontal.set_analog(input.horizontal);
+        self.vertical.set_analog(input.vertical);
+        self.look_horizontal = input.look_horizontal;
+        self.look_vertical = input.look_vertical;
+        self.analog_sprint = input.sprint;
     }
 
     pub fn update_camera(&mut self, transformation: &mut Transformation, dt: Duration) {
@@ -232,20 +430,78 @@ impl CameraController {
 
     fn update_position(&mut self, transformation: &mut Transformation, dt: f32) {
         let (forward, horizontal) = transformation.forward_horizontal();
-        let sprint = if self.sprint { SPRINT_MULTIPLIER } else { 1.0 };
-
-        transformation.position += forward * (self.forward.value() * SPEED * sprint * dt);
-        transformation.position += horizontal * (self.horizontal.value() * SPEED * sprint * dt);
-        transformation.position += Vec3::Y * (self.vertical.value() * VERTICAL_SPEED * dt);
+        let sprint = if self.sprint || self.analog_sprint {
+            SPRINT_MULTIPLIER
+        } else {
+            1.0
+        };
+
+        let speed = self.speed * sprint * dt;
+        transformation.position += forward * (self.forward.value() * speed);
+        transformation.position += horizontal * (self.horizontal.value() * speed);
+        transformation.position +=
+            Vec3::Y * (self.vertical.value() * self.speed * VERTICAL_SPEED * dt);
     }
 
     fn update_rotations(&mut self, transformation: &mut Transformation, dt: f32) {
-        transformation.yaw += self.rotate_horizontal.to_radians() * SENSITIVITY * dt;
+        transformation.yaw +=
+            (self.rotate_horizontal * SENSITIVITY + self.look_horizontal).to_radians() * dt;
         transformation.pitch = (transformation.pitch
-            - self.rotate_vertical.to_radians() * SENSITIVITY * dt)
+            - (self.rotate_vertical * SENSITIVITY - self.look_vertical).to_radians() * dt)
             .clamp(-89.9_f32.to_radians(), 89.9_f32.to_radians());
 
         self.rotate_horizontal = 0.0;
         self.rotate_vertical = 0.0;
     }
 }
+
+impl Default for CameraController {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::Vec4Swizzles;
+
+    use super::*;
+
+    // Depth of a point on the view axis at `distance` in front of the camera
+    fn depth(projection: &Projection, distance: f32) -> f32 {
+        let clip = projection.calculate_matrix() * (Vec3::NEG_Z * distance).extend(1.0);
+        clip.z / clip.w
+    }
+
+    #[test]
+    fn reversed_depth_puts_near_at_one() {
+        let projection = Projection::new(PhysicalSize::new(800, 600), 1.2, 0.1, 1000.0);
+        assert!((depth(&projection, 0.1) - 1.0).abs() < 1e-5);
+        assert!(depth(&projection, 1000.0).abs() < 1e-5);
+        assert!(depth(&projection, 10.0) > depth(&projection, 20.0));
+        assert_eq!(projection.depth_compare(), CompareFunction::Greater);
+        assert_eq!(projection.far_depth(), 0.0);
+    }
+
+    #[test]
+    fn standard_depth_puts_near_at_zero() {
+        let projection =
+            Projection::new(PhysicalSize::new(800, 600), 1.2, 0.1, 1000.0).with_reversed_z(false);
+        assert!(depth(&projection, 0.1).abs() < 1e-5);
+        assert!((depth(&projection, 1000.0) - 1.0).abs() < 1e-5);
+        assert!(depth(&projection, 10.0) < depth(&projection, 20.0));
+        assert_eq!(projection.depth_compare(), CompareFunction::Less);
+        assert_eq!(projection.far_depth(), 1.0);
+    }
+
+    #[test]
+    fn both_depths_keep_the_same_view() {
+        let size = PhysicalSize::new(800, 600);
+        let standard = Projection::new(size, 1.2, 0.1, 1000.0).with_reversed_z(false);
+        let reversed = Projection::new(size, 1.2, 0.1, 1000.0);
+        let point = Vec3::new(3.0, -2.0, -15.0).extend(1.0);
+        let standard = standard.calculate_matrix() * point;
+        let reversed = reversed.calculate_matrix() * point;
+        assert!((standard.xy() / standard.w - reversed.xy() / reversed.w).length() < 1e-6);
+    }
+}
@@ -0,0 +1,341 @@
+use std::{collections::VecDeque, str::FromStr};
+
+use glam::{ivec3, vec3, IVec3};
+use thiserror::Error;
+use winit::{
+    event::KeyEvent,
+    keyboard::{Key, NamedKey},
+};
+
+use crate::{
+    application::Application,
+    world::{block::UnknownBlock, Block},
+};
+
+// Lines of output kept around, older ones scroll out
+const LOG_LINES: usize = 12;
+const HISTORY_LINES: usize = 32;
+// Biggest volume a single `fill` may change
+const MAX_FILL: i64 = 64 * 64 * 64;
+// In chunks, the offsets of every visible chunk are kept around
+const MAX_RENDER_DISTANCE: u32 = 64;
+
+#[derive(Debug, Error)]
+pub enum CommandError {
+    #[error("unknown command `{0}`, try `help`")]
+    Unknown(String),
+    #[error("usage: {0}")]
+    Usage(String),
+    #[error("invalid argument `{0}`")]
+    InvalidArgument(String),
+    #[error("{0}")]
+    Failed(String),
+}
+
+pub type Handler = fn(&mut Application, &[&str]) -> Result<String, CommandError>;
+
+/// A console command, arguments named in brackets are optional
+#[derive(Debug, Clone, Copy)]
+pub struct Command {
+    pub name: &'static str,
+    pub args: &'static [&'static str],
+    pub handler: Handler,
+}
+
+impl Command {
+    pub fn usage(&self) -> String {
+        let mut usage = self.name.to_string();
+        for arg in self.args {
+            usage.push(' ');
+            usage.push_str(arg);
+        }
+        usage
+    }
+
+    fn accepts(&self, args: usize) -> bool {
+        let required = self.args.iter().filter(|arg| !arg.starts_with('[')).count();
+        (required..=self.args.len()).contains(&args)
+    }
+}
+
+/// Parses a single command argument
+pub fn parse<T: FromStr>(arg: &str) -> Result<T, CommandError> {
+    arg.parse()
+        .map_err(|_| CommandError::InvalidArgument(arg.to_string()))
+}
+
+fn parse_block(arg: &str) -> Result<Block, CommandError> {
+    arg.parse()
+        .map_err(|err: UnknownBlock| CommandError::Failed(err.to_string()))
+}
+
+fn parse_distance(arg: &str) -> Result<u32, CommandError> {
+    let distance = parse(arg)?;
+    if distance > MAX_RENDER_DISTANCE {
+        return Err(CommandError::Failed(format!(
+            "render distance can't be more than {MAX_RENDER_DISTANCE} chunks"
+        )));
+    }
+    Ok(distance)
+}
+
+fn parse_ivec3(args: &[&str]) -> Result<IVec3, CommandError> {
+    Ok(ivec3(parse(args[0])?, parse(args[1])?, parse(args[2])?))
+}
+
+/// Line of text input for commands, with the output of the previous ones above it
+#[derive(Debug)]
+pub struct Console {
+    open: bool,
+    input: String,
+    log: VecDeque<String>,
+    history: VecDeque<String>,
+    // Entry of the history being browsed, `None` while editing a new line
+    history_cursor: Option<usize>,
+    commands: Vec<Command>,
+}
+
+impl Console {
+    pub fn new() -> Self {
+        let mut console = Self {
+            open: false,
+            input: String::new(),
+            log: VecDeque::with_capacity(LOG_LINES),
+            history: VecDeque::with_capacity(HISTORY_LINES),
+            history_cursor: None,
+            commands: Vec::new(),
+        };
+
+        console.register(Command {
+            name: "help",
+            args: &[],
+            handler: |application, _| Ok(application.console().help()),
+        });
+        console.register(Command {
+            name: "tp",
+            args: &["x", "y", "z"],
+            handler: |application, args| {
+                let position = vec3(parse(args[0])?, parse(args[1])?, parse(args[2])?);
+                application.teleport(position);
+                Ok(format!("teleported to {position}"))
+            },
+        });
+        console.register(Command {
+            name: "seed",
+            args: &["[seed]"],
+            handler: |application, args| match args.first() {
+                Some(seed) => {
+                    let seed = parse(seed)?;
+                    application.reset_world(seed);
+                    Ok(format!("generating world {seed}"))
+                }
+                None => Ok(format!("seed: {}", application.world().seed())),
+            },
+        });
+        console.register(Command {
+            name: "distance",
+            args: &["[horizontal]", "[vertical]"],
+            handler: |application, args| {
+                let world = application.world_mut();
+                if let Some(horizontal) = args.first() {
+                    world.set_render_distance(parse_distance(horizontal)?);
+                }
+                if let Some(vertical) = args.get(1) {
+                    world.set_vertical_render_distance(parse_distance(vertical)?);
+                }
+                Ok(format!(
+                    "render distance: {} chunks, {} vertically, generating {}",
+                    world.render_distance(),
+                    world.vertical_render_distance(),
+                    world.generation_distance()
+                ))
+            },
+        });
+        console.register(Command {
+            name: "randomtickspeed",
+            args: &["[speed]"],
+            handler: |application, args| {
+                let world = application.world_mut();
+                if let Some(speed) = args.first() {
+                    world.set_random_tick_speed(parse(speed)?);
+                }
+                Ok(format!(
+                    "random tick speed: {} blocks per chunk",
+                    world.random_tick_speed()
+                ))
+            },
+        });
+        console.register(Command {
+            name: "exposure",
+            args: &["[exposure]"],
+            handler: |application, args| {
+                if let Some(exposure) = args.first() {
+                    application.set_exposure(parse(exposure)?);
+                }
+                Ok(format!("exposure: {:.2}", application.exposure()))
+            },
+        });
+        console.register(Command {
+            name: "gamma",
+            args: &["[gamma]"],
+            handler: |application, args| {
+                if let Some(gamma) = args.first() {
+                    application.set_gamma(parse(gamma)?);
+                }
+                Ok(format!("gamma: {:.2}", application.gamma()))
+            },
+        });
+        console.register(Command {
+            name: "fill",
+            args: &["x1", "y1", "z1", "x2", "y2", "z2", "block"],
+            handler: |application, args| {
+                let (first, second) = (parse_ivec3(&args[0..3])?, parse_ivec3(&args[3..6])?);
+                let block = parse_block(args[6])?;
+
+                let (min, max) = (first.min(second), first.max(second));
+                let volume = (max - min + 1).as_i64vec3().element_product();
+                if volume > MAX_FILL {
+                    return Err(CommandError::Failed(format!(
+                        "can't fill {volume} blocks, the limit is {MAX_FILL}"
+                    )));
+                }
+
+                let blocks = (min.z..=max.z).flat_map(move |z| {
+                    (min.y..=max.y)
+                        .flat_map(move |y| (min.x..=max.x).map(move |x| (ivec3(x, y, z), block)))
+                });
+                application.world_mut().set_blocks(blocks);
+                Ok(format!("filled {volume} blocks with {}", block.name()))
+            },
+        });
+        console.register(Command {
+            name: "setblock",
+            args: &["x", "y", "z", "block"],
+            handler: |application, args| {
+                let position = parse_ivec3(&args[0..3])?;
+                let block = parse_block(args[3])?;
+
+                application.world_mut().set_blocks([(position, block)]);
+                Ok(format!("set {position} to {}", block.name()))
+            },
+        });
+
+        console
+    }
+
+    /// Adds a command, replacing any registered under the same name
+    pub fn register(&mut self, command: Command) {
+        self.commands.retain(|other| other.name != command.name);
+        self.commands.push(command);
+    }
+
+    fn help(&self) -> String {
+        let usages = self.commands.iter().map(Command::usage).collect::<Vec<_>>();
+        format!("commands: {}", usages.join(", "))
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.open
+    }
+
+    pub fn set_open(&mut self, open: bool) {
+        self.open = open;
+        self.input.clear();
+        self.history_cursor = None;
+    }
+
+    pub fn input(&self) -> &str {
+        &self.input
+    }
+
+    /// Output of the previous commands, oldest first
+    pub fn log(&self) -> impl Iterator<Item = &str> {
+        self.log.iter().map(String::as_str)
+    }
+
+    pub fn print(&mut self, line: impl Into<String>) {
+        if self.log.len() == LOG_LINES {
+            self.log.pop_front();
+        }
+        self.log.push_back(line.into());
+    }
+
+    /// Edits the input line, returns it once it's submitted
+    pub fn key_input(&mut self, event: &KeyEvent) -> Option<String> {
+        if !event.state.is_pressed() {
+            return None;
+        }
+
+        match &event.logical_key {
+            Key::Named(NamedKey::Enter) => {
+                let line = self.input.trim().to_string();
+                self.input.clear();
+                self.history_cursor = None;
+                if line.is_empty() {
+                    return None;
+                }
+
+                if self.history.front() != Some(&line) {
+                    if self.history.len() == HISTORY_LINES {
+                        self.history.pop_back();
+                    }
+                    self.history.push_front(line.clone());
+                }
+                return Some(line);
+            }
+            Key::Named(NamedKey::Escape) => self.set_open(false),
+            Key::Named(NamedKey::Backspace) => {
+                self.input.pop();
+            }
+            Key::Named(NamedKey::ArrowUp) => {
+                let cursor = self.history_cursor.map_or(0, |cursor| cursor + 1);
+                if let Some(line) = self.history.get(cursor) {
+                    self.input.clone_from(line);
+                    self.history_cursor = Some(cursor);
+                }
+            }
+            Key::Named(NamedKey::ArrowDown) => match self.history_cursor {
+                Some(0) | None => {
+                    self.input.clear();
+                    self.history_cursor = None;
+                }
+                Some(cursor) => {
+                    self.input.clone_from(&self.history[cursor - 1]);
+                    self.history_cursor = Some(cursor - 1);
+                }
+            },
+            _ => {
+                if let Some(text) = &event.text {
+                    self.input
+                        .extend(text.chars().filter(|char| !char.is_control()));
+                }
+            }
+        }
+
+        None
+    }
+
+    /// Looks up the command of the line and checks its arguments
+    pub fn parse<'l>(&self, line: &'l str) -> Result<(Handler, Vec<&'l str>), CommandError> {
+        let mut words = line.split_whitespace();
+        let name = words.next().unwrap_or_default();
+        let args = words.collect::<Vec<_>>();
+
+        let command = self
+            .commands
+            .iter()
+            .find(|command| command.name == name)
+            .ok_or_else(|| CommandError::Unknown(name.to_string()))?;
+        if !command.accepts(args.len()) {
+            return Err(CommandError::Usage(command.usage()));
+        }
+
+        Ok((command.handler, args))
+    }
+}
+
+impl Default for Console {
+    fn default() -> Self {
+        Self::new()
+    }
+}
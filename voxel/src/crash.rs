@@ -0,0 +1,103 @@
+use std::{
+    backtrace::Backtrace,
+    fs,
+    panic::{self, PanicHookInfo},
+    sync::{Arc, Weak},
+    thread,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use parking_lot::Mutex;
+use winit::window::{CursorGrabMode, Window};
+
+// Empty until the application created its window
+static WINDOW: Mutex<Weak<Window>> = Mutex::new(Weak::new());
+
+/// Lets the panic hook give the cursor back if the application crashes
+pub fn set_window(window: &Arc<Window>) {
+    *WINDOW.lock() = Arc::downgrade(window);
+}
+
+/// Writes panics to `crash-{timestamp}.log` in the working directory,
+/// panics on the main thread also release the cursor before the process goes down
+pub fn install_hook() {
+    let default_hook = panic::take_hook();
+
+    panic::set_hook(Box::new(move |info| {
+        // Workers dying don't end the application, only the main thread owns the window
+        let main_thread = thread::current().name() == Some("main");
+        if main_thread {
+            release_window();
+        }
+
+        let report = report(info);
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |duration| duration.as_secs());
+        let path = format!("crash-{timestamp}.log");
+        let written = fs::write(&path, &report).is_ok();
+
+        default_hook(info);
+        if written {
+            eprintln!("crash report written to {path}");
+        }
+
+        #[cfg(feature = "message-box")]
+        if main_thread {
+            show_message_box(info, written.then_some(path.as_str()));
+        }
+    }));
+}
+
+fn release_window() {
+    // The panic might have happened while the window was being stored
+    let Some(window) = WINDOW.try_lock().and_then(|window| window.upgrade()) else {
+        return;
+    };
+
+    window.set_fullscreen(None);
+    let _ = window.set_cursor_grab(CursorGrabMode::None);
+    window.set_cursor_visible(true);
+}
+
+fn message<'i>(info: &'i PanicHookInfo<'_>) -> &'i str {
+    let payload = info.payload();
+    payload
+        .downcast_ref::<&str>()
+        .copied()
+        .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+        .unwrap_or("unknown panic payload")
+}
+
+fn report(info: &PanicHookInfo<'_>) -> String {
+    let thread = thread::current();
+    let location = info
+        .location()
+        .map_or("unknown location".to_string(), ToString::to_string);
+
+    format!(
+        "thread '{}' panicked at {location}:\n{}\n\nbacktrace:\n{}\n",
+        thread.name().unwrap_or("<unnamed>"),
+        message(info),
+        Backtrace::force_capture()
+    )
+}
+
+#[cfg(feature = "message-box")]
+fn show_message_box(info: &PanicHookInfo<'_>, path: Option<&str>) {
+    use rfd::{MessageButtons, MessageDialog, MessageLevel};
+
+    use crate::window::TITLE;
+
+    let mut description = message(info).to_string();
+    if let Some(path) = path {
+        description.push_str(&format!("\n\nThe crash report was written to {path}"));
+    }
+
+    MessageDialog::new()
+        .set_level(MessageLevel::Error)
+        .set_title(format!("{TITLE} crashed"))
+        .set_description(description)
+        .set_buttons(MessageButtons::Ok)
+        .show();
+}
@@ -1,8 +1,23 @@
+use rayon::ThreadPoolBuildError;
 use thiserror::Error;
-use voxel_util::context::ContextError;
+use voxel_util::{context::ContextError, AtlasError};
 
 #[derive(Debug, Error)]
 pub enum Error {
     #[error("failed to create context")]
     Context(#[from] ContextError),
+    #[error("failed to build the texture atlas: {0}")]
+    Atlas(#[from] AtlasError),
+    #[error(transparent)]
+    MissingTexture(#[from] MissingTexture),
+    #[error("failed to start worker threads: {0}")]
+    ThreadPool(#[from] ThreadPoolBuildError),
+}
+
+/// A texture looked up by name that isn't in the atlas
+#[derive(Debug, Error)]
+#[error("missing texture `{name}.png` of {user}")]
+pub struct MissingTexture {
+    pub name: String,
+    pub user: String,
 }
@@ -0,0 +1,47 @@
+use std::{
+    hint, thread,
+    time::{Duration, Instant},
+};
+
+// Sleeping is imprecise, so the last part of the wait is spent spinning
+const SPIN_DURATION: Duration = Duration::from_micros(500);
+
+#[derive(Debug, Clone, Copy)]
+pub struct FrameLimiter {
+    last_frame: Instant,
+}
+
+impl FrameLimiter {
+    pub fn new() -> Self {
+        Self {
+            last_frame: Instant::now(),
+        }
+    }
+
+    pub fn wait(&mut self, max_fps: Option<u32>) {
+        if let Some(max_fps) = max_fps.filter(|&max_fps| max_fps > 0) {
+            self.wait_until(self.last_frame + Duration::from_secs_f64(1.0 / max_fps as f64));
+        }
+
+        self.last_frame = Instant::now();
+    }
+
+    fn wait_until(&self, deadline: Instant) {
+        if let Some(remaining) = deadline
+            .checked_duration_since(Instant::now())
+            .and_then(|remaining| remaining.checked_sub(SPIN_DURATION))
+        {
+            thread::sleep(remaining);
+        }
+
+        while Instant::now() < deadline {
+            hint::spin_loop();
+        }
+    }
+}
+
+impl Default for FrameLimiter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
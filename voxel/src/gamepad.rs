@@ -0,0 +1,82 @@
+use gilrs::{Axis, Button, EventType, GamepadId, Gilrs};
+use glam::{vec2, Vec2};
+
+use crate::{
+    camera::{AnalogInput, Camera},
+    settings::GamepadSettings,
+};
+
+pub struct Gamepads {
+    gilrs: Gilrs,
+    // The controller that sent the latest event
+    active: Option<GamepadId>,
+}
+
+impl Gamepads {
+    pub fn new() -> Option<Self> {
+        match Gilrs::new() {
+            Ok(gilrs) => Some(Self {
+                gilrs,
+                active: None,
+            }),
+            Err(err) => {
+                log::warn!("gamepad input is unavailable: {err}");
+                None
+            }
+        }
+    }
+
+    pub fn update(&mut self, camera: &mut Camera, settings: &GamepadSettings) {
+        while let Some(event) = self.gilrs.next_event() {
+            match event.event {
+                EventType::Disconnected if self.active == Some(event.id) => self.active = None,
+                EventType::Disconnected => {}
+                _ => self.active = Some(event.id),
+            }
+        }
+
+        let input = self
+            .active
+            .map(|id| {
+                let gamepad = self.gilrs.gamepad(id);
+                let trigger = |button| gamepad.button_data(button).map_or(0.0, |data| data.value());
+
+                let movement = apply_deadzone(
+                    vec2(
+                        gamepad.value(Axis::LeftStickX),
+                        gamepad.value(Axis::LeftStickY),
+                    ),
+                    settings.deadzone,
+                );
+                let look = apply_deadzone(
+                    vec2(
+                        gamepad.value(Axis::RightStickX),
+                        gamepad.value(Axis::RightStickY),
+                    ),
+                    settings.deadzone,
+                ) * settings.look_sensitivity;
+
+                AnalogInput {
+                    forward: movement.y,
+                    horizontal: movement.x,
+                    vertical: trigger(Button::RightTrigger2) - trigger(Button::LeftTrigger2),
+                    look_horizontal: look.x,
+                    look_vertical: look.y,
+                    sprint: gamepad.is_pressed(Button::LeftThumb),
+                }
+            })
+            .unwrap_or_default();
+
+        camera.process_analog(input);
+    }
+}
+
+/// Radial deadzone, rescaled so the output still starts from zero at its edge
+fn apply_deadzone(stick: Vec2, deadzone: f32) -> Vec2 {
+    let length = stick.length();
+    if length <= deadzone {
+        return Vec2::ZERO;
+    }
+
+    stick / length * ((length - deadzone) / (1.0 - deadzone)).min(1.0)
+}
@@ -0,0 +1,71 @@
+use std::io::{self, ErrorKind, Write};
+
+use log::error;
+use wgpu::{Adapter, Backends, Instance, InstanceDescriptor};
+
+// Kept in step with the workspace manifest, wgpu doesn't expose its own version
+const WGPU_VERSION: &str = "22.1.0";
+
+/// Prints every adapter of every backend with what it supports, no window or surface needed
+pub fn print_adapters() {
+    match write_adapters(&mut io::stdout().lock()) {
+        // The reader went away early, like `head` does
+        Err(err) if err.kind() == ErrorKind::BrokenPipe => {}
+        Err(err) => error!("failed to print adapters: {err}"),
+        Ok(()) => {}
+    }
+}
+
+fn write_adapters(out: &mut impl Write) -> io::Result<()> {
+    writeln!(
+        out,
+        "voxel {} (wgpu {WGPU_VERSION})",
+        env!("CARGO_PKG_VERSION")
+    )?;
+
+    let instance = Instance::new(InstanceDescriptor {
+        backends: Backends::all(),
+        ..Default::default()
+    });
+    let adapters = instance.enumerate_adapters(Backends::all());
+    if adapters.is_empty() {
+        writeln!(out, "\nno adapters found")?;
+    }
+
+    for (index, adapter) in adapters.iter().enumerate() {
+        write_adapter(out, index, adapter)?;
+    }
+    out.flush()
+}
+
+fn write_adapter(out: &mut impl Write, index: usize, adapter: &Adapter) -> io::Result<()> {
+    let info = adapter.get_info();
+    let features = adapter.features();
+    let rows = [
+        ("name", info.name),
+        ("backend", format!("{:?}", info.backend)),
+        ("device type", format!("{:?}", info.device_type)),
+        ("vendor", format!("{:#06x}", info.vendor)),
+        ("device", format!("{:#06x}", info.device)),
+        ("driver", info.driver),
+        ("driver info", info.driver_info),
+    ];
+
+    writeln!(out, "\nadapter {index}")?;
+    for (name, value) in rows {
+        writeln!(out, "  {name:<14}{value}")?;
+    }
+
+    writeln!(out, "  features")?;
+    for (name, _) in features.iter_names() {
+        writeln!(out, "    {name}")?;
+    }
+
+    // Limits print as `name: value` pairs, one per line
+    writeln!(out, "  limits")?;
+    let limits = format!("{:#?}", adapter.limits());
+    for line in limits.lines().skip(1).filter(|line| line.trim() != "}") {
+        writeln!(out, "    {}", line.trim().trim_end_matches(','))?;
+    }
+    Ok(())
+}
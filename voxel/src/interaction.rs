@@ -0,0 +1,18 @@
+use glam::IVec3;
+
+use crate::world::Block;
+
+/// Hooks for reacting to the player changing the world, like playing sounds,
+/// so the game itself doesn't depend on any audio backend
+pub trait InteractionListener {
+    fn on_break(&mut self, _block: Block, _position: IVec3) {}
+
+    /// Also called for every block placed by commands like `fill`
+    fn on_place(&mut self, _block: Block, _position: IVec3) {}
+}
+
+/// Listener that ignores everything, used until another one is set
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SilentListener;
+
+impl InteractionListener for SilentListener {}
@@ -1,16 +1,38 @@
-use std::process::{self};
+use std::{
+    env,
+    process::{self},
+};
 
-use application::Application;
-use window::Window;
+use application::{Application, DETERMINISTIC_SEED};
+use bench::{Benchmark, BENCH_SEED};
+use log::warn;
+use session::Session;
+#[cfg(feature = "serde")]
+use session::SESSION_PATH;
+use settings::Settings;
+use window::{Window, TITLE};
 use winit::{
     event_loop::{ActiveEventLoop, EventLoop},
-    window::WindowAttributes,
+    window::{Icon, WindowAttributes},
 };
 
 pub mod application;
+pub mod bench;
 pub mod camera;
+pub mod console;
+pub mod crash;
 pub mod error;
+pub mod frame_limiter;
+#[cfg(feature = "gamepad")]
+pub mod gamepad;
+pub mod gpu_info;
+pub mod interaction;
+pub mod mesh_queue;
+pub mod metrics;
+pub mod protocol;
 pub mod render;
+pub mod session;
+pub mod settings;
 pub mod window;
 pub mod world;
 
@@ -21,17 +43,90 @@ macro_rules! asset {
     };
 }
 
+/// Shader module of a scene shader, prefixed with the declarations they all share
+#[macro_export]
+macro_rules! scene_shader {
+    ($path:literal) => {
+        wgpu::ShaderModuleDescriptor {
+            label: Some($path),
+            source: wgpu::ShaderSource::Wgsl(std::borrow::Cow::Borrowed(concat!(
+                include_str!($crate::asset!("shaders/common.wgsl")),
+                include_str!($crate::asset!($path))
+            ))),
+        }
+    };
+}
+
+fn load_icon() -> Option<Icon> {
+    let icon = image::load_from_memory(include_bytes!(asset!("icon.png")))
+        .expect("failed to load icon")
+        .to_rgba8();
+    let (width, height) = icon.dimensions();
+
+    // Platforms without window icons just keep their default one
+    Icon::from_rgba(icon.into_raw(), width, height)
+        .inspect_err(|err| warn!("failed to create window icon: {err}"))
+        .ok()
+}
+
+fn window_attributes() -> WindowAttributes {
+    WindowAttributes::default()
+        .with_title(TITLE)
+        .with_window_icon(load_icon())
+}
+
 fn main() {
     env_logger::init();
+    crash::install_hook();
+    if env::args().any(|arg| arg == "--gpu-info") {
+        gpu_info::print_adapters();
+        return;
+    }
+
     let event_loop = EventLoop::new().expect("failed to create event loop");
+    #[cfg(feature = "serde")]
+    let new_session = env::args().any(|arg| arg == "--new");
+    let bench = env::args().any(|arg| arg == "--bench");
+    let deterministic = env::args().any(|arg| arg == "--deterministic");
 
     let mut window = Window::new(|event_loop: &ActiveEventLoop| {
         let window = event_loop
-            .create_window(WindowAttributes::default())
+            .create_window(window_attributes())
             .expect("failed to create window");
 
-        match pollster::block_on(Application::new(window)) {
-            Ok(application) => application,
+        let settings = Settings::default();
+        let session = if bench {
+            Some(Session::from_settings(
+                BENCH_SEED,
+                Benchmark::start_transformation(),
+                &settings,
+            ))
+        } else if deterministic {
+            Some(Session::from_settings(
+                DETERMINISTIC_SEED,
+                Application::deterministic_transformation(),
+                &settings,
+            ))
+        } else {
+            #[cfg(feature = "serde")]
+            let session = (!new_session)
+                .then(|| Session::restore(SESSION_PATH))
+                .flatten();
+            // Nothing was saved that could be restored
+            #[cfg(not(feature = "serde"))]
+            let session = None;
+            session
+        };
+
+        match pollster::block_on(Application::new(window, settings, session)) {
+            Ok(mut application) => {
+                if bench {
+                    application.start_benchmark();
+                } else if deterministic {
+                    application.start_deterministic();
+                }
+                application
+            }
             Err(err) => {
                 eprintln!("{err}");
                 process::exit(1)
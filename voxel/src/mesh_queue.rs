@@ -0,0 +1,163 @@
+use std::{cmp::Reverse, collections::HashSet};
+
+use glam::IVec3;
+
+use crate::world::{lod::Lod, DirtyRegion};
+
+/// Chunk handed to a mesh worker
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MeshJob {
+    pub position: IVec3,
+    pub lod: Lod,
+    /// Blocks that changed since the last mesh, `None` for chunks meshed from scratch
+    pub region: Option<DirtyRegion>,
+}
+
+/// Chunks waiting for the mesh workers. Edited chunks go first, and a chunk is never meshed
+/// twice at once, so every edit starts from the mesh the edits before it produced
+#[derive(Debug, Default)]
+pub struct MeshQueue {
+    to_generate: Vec<(IVec3, Lod)>,
+    // Edited chunks, kept apart so a new queue doesn't drop them
+    to_remesh: Vec<(IVec3, Lod, DirtyRegion)>,
+    in_flight: HashSet<IVec3>,
+}
+
+impl MeshQueue {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Replaces the chunks to mesh from scratch, the ones nearest to `origin` go first
+    pub fn set_queue(&mut self, origin: IVec3, mut to_generate: Vec<(IVec3, Lod)>) {
+        // Nearest chunks are popped from the back first
+        to_generate
+            .sort_unstable_by_key(|(position, _)| Reverse((*position - origin).length_squared()));
+        self.to_generate = to_generate;
+    }
+
+    /// Queues edited chunks before everything else
+    pub fn remesh(&mut self, chunks: impl IntoIterator<Item = (IVec3, Lod, DirtyRegion)>) {
+        for (position, lod, region) in chunks {
+            // Edits of a chunk that is still queued coalesce into a single remesh, moved to
+            // the back to be popped first
+            let region = match self
+                .to_remesh
+                .iter()
+                .position(|&(queued, ..)| queued == position)
+            {
+                Some(index) => self.to_remesh.remove(index).2.merge(region),
+                None => region,
+            };
+            self.to_remesh.push((position, lod, region));
+        }
+    }
+
+    /// Takes the next chunk that isn't being meshed already
+    pub fn pop(&mut self) -> Option<MeshJob> {
+        let in_flight = &self.in_flight;
+        let job = if let Some(index) = self
+            .to_remesh
+            .iter()
+            .rposition(|(position, ..)| !in_flight.contains(position))
+        {
+            let (position, lod, region) = self.to_remesh.remove(index);
+            MeshJob {
+                position,
+                lod,
+                region: Some(region),
+            }
+        } else {
+            let index = self
+                .to_generate
+                .iter()
+                .rposition(|(position, _)| !in_flight.contains(position))?;
+            let (position, lod) = self.to_generate.remove(index);
+            MeshJob {
+                position,
+                lod,
+                region: None,
+            }
+        };
+
+        self.in_flight.insert(job.position);
+        Some(job)
+    }
+
+    /// Lets the chunk be meshed again once its mesh is done
+    pub fn finish(&mut self, position: IVec3) {
+        self.in_flight.remove(&position);
+    }
+
+    pub fn in_flight(&self) -> usize {
+        self.in_flight.len()
+    }
+
+    /// Chunks waiting for a worker
+    pub fn queued(&self) -> usize {
+        self.to_generate.len() + self.to_remesh.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::UVec3;
+
+    use super::*;
+
+    fn job(position: IVec3, region: Option<DirtyRegion>) -> Option<MeshJob> {
+        Some(MeshJob {
+            position,
+            lod: Lod::Full,
+            region,
+        })
+    }
+
+    #[test]
+    fn edits_coalesce_and_go_first() {
+        let mut queue = MeshQueue::new();
+        queue.set_queue(
+            IVec3::ZERO,
+            vec![(IVec3::X * 3, Lod::Full), (IVec3::X, Lod::Full)],
+        );
+        queue.remesh([
+            (IVec3::Y, Lod::Full, DirtyRegion::block(UVec3::splat(2))),
+            (IVec3::Z, Lod::Full, DirtyRegion::Whole),
+        ]);
+        queue.remesh([(IVec3::Y, Lod::Full, DirtyRegion::block(UVec3::splat(4)))]);
+        assert_eq!(queue.queued(), 4);
+
+        let merged = DirtyRegion::Blocks {
+            min: UVec3::splat(2),
+            max: UVec3::splat(4),
+        };
+        assert_eq!(queue.pop(), job(IVec3::Y, Some(merged)));
+        assert_eq!(queue.pop(), job(IVec3::Z, Some(DirtyRegion::Whole)));
+        assert_eq!(queue.pop(), job(IVec3::X, None));
+        assert_eq!(queue.pop(), job(IVec3::X * 3, None));
+        assert_eq!(queue.pop(), None);
+        assert_eq!(queue.in_flight(), 4);
+    }
+
+    #[test]
+    fn chunks_in_flight_wait_for_their_mesh() {
+        let mut queue = MeshQueue::new();
+        let first = DirtyRegion::block(UVec3::ONE);
+        let second = DirtyRegion::block(UVec3::splat(5));
+        queue.remesh([(IVec3::ZERO, Lod::Full, first)]);
+        assert_eq!(queue.pop(), job(IVec3::ZERO, Some(first)));
+
+        // Patching now would start from the mesh without the first edit
+        queue.remesh([(IVec3::ZERO, Lod::Full, second)]);
+        queue.set_queue(
+            IVec3::ZERO,
+            vec![(IVec3::ZERO, Lod::Full), (IVec3::X, Lod::Full)],
+        );
+        assert_eq!(queue.pop(), job(IVec3::X, None));
+        assert_eq!(queue.pop(), None);
+
+        queue.finish(IVec3::ZERO);
+        assert_eq!(queue.pop(), job(IVec3::ZERO, Some(second)));
+        assert_eq!(queue.queued(), 1);
+    }
+}
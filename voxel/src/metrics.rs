@@ -0,0 +1,100 @@
+use std::{
+    fmt::{self, Debug},
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use parking_lot::RwLock;
+
+/// Current value of a counter along with the highest it has been
+#[derive(Debug, Default)]
+pub struct Gauge {
+    current: AtomicU64,
+    peak: AtomicU64,
+}
+
+impl Gauge {
+    pub fn set(&self, value: u64) {
+        self.current.store(value, Ordering::Relaxed);
+        self.peak.fetch_max(value, Ordering::Relaxed);
+    }
+
+    pub fn get(&self) -> u64 {
+        self.current.load(Ordering::Relaxed)
+    }
+
+    pub fn peak(&self) -> u64 {
+        self.peak.load(Ordering::Relaxed)
+    }
+}
+
+/// Generation and meshing progress, reported once per batch of finished work
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Progress {
+    /// Sections generated since the start and the ones still requested
+    Sections { generated: u64, pending: u64 },
+    /// Meshes built since the start and the ones still waiting for a worker
+    Meshes { built: u64, queued: u64 },
+}
+
+type ProgressCallback = Box<dyn Fn(Progress) + Send + Sync>;
+
+/// Counters shared between the world, the mesh workers and the renderer
+#[derive(Default)]
+pub struct Metrics {
+    /// Loaded chunks and the bytes their blocks and light take
+    pub chunks: Gauge,
+    pub chunk_bytes: Gauge,
+    /// Chunks in the render distance that have been generated
+    pub visible_chunks: Gauge,
+    /// Meshes ready to draw and the bytes of their GPU buffers
+    pub meshes: Gauge,
+    pub mesh_bytes: Gauge,
+    /// Chunks that passed frustum culling in the last frame
+    pub drawn_chunks: Gauge,
+    pub sections_generated: AtomicU64,
+    pub meshes_built: AtomicU64,
+    pub frames: AtomicU64,
+    progress: RwLock<Option<ProgressCallback>>,
+}
+
+impl Metrics {
+    /// Calls `callback` from the threads doing the work whenever some of it finishes,
+    /// replacing the previous callback
+    pub fn on_progress(&self, callback: impl Fn(Progress) + Send + Sync + 'static) {
+        *self.progress.write() = Some(Box::new(callback));
+    }
+
+    pub fn report(&self, progress: Progress) {
+        if let Some(callback) = &*self.progress.read() {
+            callback(progress);
+        }
+    }
+
+    pub fn sections_generated(&self) -> u64 {
+        self.sections_generated.load(Ordering::Relaxed)
+    }
+
+    pub fn meshes_built(&self) -> u64 {
+        self.meshes_built.load(Ordering::Relaxed)
+    }
+
+    pub fn frames(&self) -> u64 {
+        self.frames.load(Ordering::Relaxed)
+    }
+}
+
+impl Debug for Metrics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Metrics")
+            .field("chunks", &self.chunks)
+            .field("chunk_bytes", &self.chunk_bytes)
+            .field("visible_chunks", &self.visible_chunks)
+            .field("meshes", &self.meshes)
+            .field("mesh_bytes", &self.mesh_bytes)
+            .field("drawn_chunks", &self.drawn_chunks)
+            .field("sections_generated", &self.sections_generated)
+            .field("meshes_built", &self.meshes_built)
+            .field("frames", &self.frames)
+            .finish_non_exhaustive()
+    }
+}
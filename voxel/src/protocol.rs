@@ -0,0 +1,543 @@
+use glam::{IVec3, Vec3};
+use thiserror::Error;
+
+use crate::{
+    camera::Transformation,
+    world::{
+        chunk::{ChunkSectionPosition, RawChunk, Temperatures, CHUNK_SIZE},
+        Block,
+    },
+};
+
+/// Bumped whenever the layout of a message changes
+pub const PROTOCOL_VERSION: u8 = 1;
+
+const CHUNK: u8 = 0;
+const SECTION_REQUEST: u8 = 1;
+const BLOCK_EDITS: u8 = 2;
+const PLAYER_TRANSFORM: u8 = 3;
+
+// Version, kind and payload length
+const HEADER_SIZE: usize = 6;
+const CHUNK_VOLUME: usize = CHUNK_SIZE.pow(3);
+
+// Runs of a chunk are counted in `u16`, so are their lengths
+const _: () = assert!(CHUNK_VOLUME <= u16::MAX as usize);
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum ProtocolError {
+    #[error("message ended early")]
+    UnexpectedEnd,
+    #[error("message has {0} bytes after its payload")]
+    TrailingBytes(usize),
+    #[error("unsupported protocol version {0}, expected {PROTOCOL_VERSION}")]
+    Version(u8),
+    #[error("unknown message kind {0}")]
+    UnknownMessage(u8),
+    #[error("unknown block id {0}")]
+    InvalidBlock(u8),
+    #[error("palette index {0} is out of the palette")]
+    InvalidPaletteIndex(u8),
+    #[error("chunk runs cover {0} blocks instead of {CHUNK_VOLUME}")]
+    InvalidRuns(usize),
+    #[error("transformation isn't finite")]
+    NonFinite,
+}
+
+/// Everything a server and its clients exchange, each encoded into a frame of
+/// `[version][kind][payload length: u32][payload]`, numbers in little endian
+#[derive(Clone)]
+pub enum Message {
+    /// Blocks and temperatures of a chunk, light is recomputed by the receiver
+    Chunk {
+        position: IVec3,
+        chunk: Box<RawChunk>,
+    },
+    /// Asks for every chunk of the section
+    SectionRequest(ChunkSectionPosition),
+    BlockEdits(Vec<(IVec3, Block)>),
+    PlayerTransform(Transformation),
+}
+
+impl Message {
+    pub fn encode(&self) -> Vec<u8> {
+        let mut payload = Writer::default();
+        let kind = match self {
+            Self::Chunk { position, chunk } => {
+                payload.ivec3(*position);
+                payload.chunk(chunk);
+                CHUNK
+            }
+            Self::SectionRequest(position) => {
+                payload.i32(position.x);
+                payload.i32(position.z);
+                SECTION_REQUEST
+            }
+            Self::BlockEdits(edits) => {
+                payload.u32(edits.len() as u32);
+                for &(position, block) in edits {
+                    payload.ivec3(position);
+                    payload.block(block);
+                }
+                BLOCK_EDITS
+            }
+            Self::PlayerTransform(transformation) => {
+                let position = transformation.position();
+                payload.f32(position.x);
+                payload.f32(position.y);
+                payload.f32(position.z);
+                payload.f32(transformation.yaw());
+                payload.f32(transformation.pitch());
+                PLAYER_TRANSFORM
+            }
+        };
+
+        let mut frame = Writer(Vec::with_capacity(HEADER_SIZE + payload.0.len()));
+        frame.u8(PROTOCOL_VERSION);
+        frame.u8(kind);
+        frame.u32(payload.0.len() as u32);
+        frame.0.extend(payload.0);
+        frame.0
+    }
+
+    /// Decodes a single frame, malformed input is an error and never a panic
+    pub fn decode(bytes: &[u8]) -> Result<Self, ProtocolError> {
+        let mut frame = Reader(bytes);
+        let version = frame.u8()?;
+        if version != PROTOCOL_VERSION {
+            return Err(ProtocolError::Version(version));
+        }
+        let kind = frame.u8()?;
+        let length = frame.u32()? as usize;
+        let mut payload = Reader(frame.take(length)?);
+        frame.finish()?;
+
+        let message = match kind {
+            CHUNK => Self::Chunk {
+                position: payload.ivec3()?,
+                chunk: Box::new(payload.chunk()?),
+            },
+            SECTION_REQUEST => {
+                Self::SectionRequest(ChunkSectionPosition::new(payload.i32()?, payload.i32()?))
+            }
+            BLOCK_EDITS => {
+                let count = payload.u32()?;
+                // Not preallocated, the count could claim more than the payload holds
+                let mut edits = Vec::new();
+                for _ in 0..count {
+                    edits.push((payload.ivec3()?, payload.block()?));
+                }
+                Self::BlockEdits(edits)
+            }
+            PLAYER_TRANSFORM => {
+                let position = Vec3::new(payload.f32()?, payload.f32()?, payload.f32()?);
+                let (yaw, pitch) = (payload.f32()?, payload.f32()?);
+                if !position.is_finite() || !yaw.is_finite() || !pitch.is_finite() {
+                    return Err(ProtocolError::NonFinite);
+                }
+                Self::PlayerTransform(Transformation::new(position, yaw, pitch))
+            }
+            kind => return Err(ProtocolError::UnknownMessage(kind)),
+        };
+        payload.finish()?;
+
+        Ok(message)
+    }
+}
+
+#[derive(Default)]
+struct Writer(Vec<u8>);
+
+impl Writer {
+    fn u8(&mut self, value: u8) {
+        self.0.push(value);
+    }
+
+    fn u16(&mut self, value: u16) {
+        self.0.extend(value.to_le_bytes());
+    }
+
+    fn u32(&mut self, value: u32) {
+        self.0.extend(value.to_le_bytes());
+    }
+
+    fn i32(&mut self, value: i32) {
+        self.0.extend(value.to_le_bytes());
+    }
+
+    fn f32(&mut self, value: f32) {
+        self.0.extend(value.to_le_bytes());
+    }
+
+    fn ivec3(&mut self, value: IVec3) {
+        self.i32(value.x);
+        self.i32(value.y);
+        self.i32(value.z);
+    }
+
+    fn block(&mut self, block: Block) {
+        self.u8(block.id());
+    }
+
+    /// Palette of the blocks in the chunk, then runs of palette indices in the order of
+    /// [`RawChunk::iter`], then the temperature of every column
+    fn chunk(&mut self, chunk: &RawChunk) {
+        let mut palette = Vec::new();
+        let mut runs: Vec<(u8, u16)> = Vec::new();
+        for block in chunk.iter() {
+            let index = match palette.iter().position(|&entry| entry == block) {
+                Some(index) => index,
+                None => {
+                    palette.push(block);
+                    palette.len() - 1
+                }
+            } as u8;
+
+            match runs.last_mut() {
+                Some((last, length)) if *last == index => *length += 1,
+                _ => runs.push((index, 1)),
+            }
+        }
+
+        self.u8(palette.len() as u8);
+        for &block in &palette {
+            self.block(block);
+        }
+        self.u16(runs.len() as u16);
+        for (index, length) in runs {
+            self.u8(index);
+            self.u16(length);
+        }
+        self.0.extend(chunk.temperatures().iter().flatten());
+    }
+}
+
+struct Reader<'b>(&'b [u8]);
+
+impl<'b> Reader<'b> {
+    fn take(&mut self, length: usize) -> Result<&'b [u8], ProtocolError> {
+        if self.0.len() < length {
+            return Err(ProtocolError::UnexpectedEnd);
+        }
+        let (taken, rest) = self.0.split_at(length);
+        self.0 = rest;
+        Ok(taken)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], ProtocolError> {
+        let mut array = [0; N];
+        array.copy_from_slice(self.take(N)?);
+        Ok(array)
+    }
+
+    fn finish(self) -> Result<(), ProtocolError> {
+        match self.0.len() {
+            0 => Ok(()),
+            trailing => Err(ProtocolError::TrailingBytes(trailing)),
+        }
+    }
+
+    fn u8(&mut self) -> Result<u8, ProtocolError> {
+        Ok(self.array::<1>()?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, ProtocolError> {
+        self.array().map(u16::from_le_bytes)
+    }
+
+    fn u32(&mut self) -> Result<u32, ProtocolError> {
+        self.array().map(u32::from_le_bytes)
+    }
+
+    fn i32(&mut self) -> Result<i32, ProtocolError> {
+        self.array().map(i32::from_le_bytes)
+    }
+
+    fn f32(&mut self) -> Result<f32, ProtocolError> {
+        self.array().map(f32::from_le_bytes)
+    }
+
+    fn ivec3(&mut self) -> Result<IVec3, ProtocolError> {
+        Ok(IVec3::new(self.i32()?, self.i32()?, self.i32()?))
+    }
+
+    fn block(&mut self) -> Result<Block, ProtocolError> {
+        let id = self.u8()?;
+        Block::from_id(id).ok_or(ProtocolError::InvalidBlock(id))
+    }
+
+    fn chunk(&mut self) -> Result<RawChunk, ProtocolError> {
+        let palette_len = self.u8()?;
+        let palette = (0..palette_len)
+            .map(|_| self.block())
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let runs = self.u16()?;
+        let mut blocks = Vec::with_capacity(CHUNK_VOLUME);
+        for _ in 0..runs {
+            let index = self.u8()?;
+            let length = self.u16()? as usize;
+            let block = *palette
+                .get(index as usize)
+                .ok_or(ProtocolError::InvalidPaletteIndex(index))?;
+            if blocks.len() + length > CHUNK_VOLUME {
+                return Err(ProtocolError::InvalidRuns(blocks.len() + length));
+            }
+            blocks.extend(std::iter::repeat_n(block, length));
+        }
+        if blocks.len() != CHUNK_VOLUME {
+            return Err(ProtocolError::InvalidRuns(blocks.len()));
+        }
+
+        let mut temperatures = Temperatures::default();
+        for row in &mut temperatures {
+            *row = self.array()?;
+        }
+
+        let mut chunk =
+            RawChunk::try_from(blocks).map_err(|_| ProtocolError::InvalidRuns(CHUNK_VOLUME))?;
+        chunk.set_temperatures(temperatures);
+        Ok(chunk)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::UVec3;
+
+    use super::*;
+
+    fn chunk() -> Box<RawChunk> {
+        let mut chunk = RawChunk::filled(Block::Stone);
+        for x in 0..CHUNK_SIZE as u32 {
+            chunk.set_block(UVec3::new(x, CHUNK_SIZE as u32 - 1, 3), Block::Air);
+        }
+        chunk.set_block(UVec3::new(4, 5, 6), Block::Glowstone);
+        chunk.set_block(UVec3::new(0, 0, 0), Block::Water);
+        let mut temperatures = Temperatures::default();
+        for (z, row) in temperatures.iter_mut().enumerate() {
+            for (x, temperature) in row.iter_mut().enumerate() {
+                *temperature = (x * 16 + z) as u8;
+            }
+        }
+        chunk.set_temperatures(temperatures);
+        Box::new(chunk)
+    }
+
+    fn messages() -> Vec<Message> {
+        vec![
+            Message::Chunk {
+                position: IVec3::new(-3, 7, 12),
+                chunk: chunk(),
+            },
+            Message::SectionRequest(ChunkSectionPosition::new(-5, i32::MAX)),
+            Message::BlockEdits(vec![
+                (IVec3::new(1, -2, 3), Block::Sand),
+                (IVec3::new(i32::MIN, 0, 9), Block::Air),
+            ]),
+            Message::PlayerTransform(Transformation::new(Vec3::new(1.5, 64.0, -8.25), 0.5, -1.0)),
+        ]
+    }
+
+    fn assert_same(left: &Message, right: &Message) {
+        match (left, right) {
+            (
+                Message::Chunk { position, chunk },
+                Message::Chunk {
+                    position: other_position,
+                    chunk: other_chunk,
+                },
+            ) => {
+                assert_eq!(position, other_position);
+                assert!(chunk.iter().eq(other_chunk.iter()));
+                assert_eq!(chunk.temperatures(), other_chunk.temperatures());
+            }
+            (Message::SectionRequest(position), Message::SectionRequest(other)) => {
+                assert_eq!(position, other);
+            }
+            (Message::BlockEdits(edits), Message::BlockEdits(other)) => assert_eq!(edits, other),
+            (Message::PlayerTransform(transformation), Message::PlayerTransform(other)) => {
+                assert_eq!(transformation.position(), other.position());
+                assert_eq!(transformation.yaw(), other.yaw());
+                assert_eq!(transformation.pitch(), other.pitch());
+            }
+            _ => panic!("decoded into a different kind of message"),
+        }
+    }
+
+    #[test]
+    fn messages_round_trip() {
+        for message in messages() {
+            let decoded = Message::decode(&message.encode()).unwrap();
+            assert_same(&message, &decoded);
+        }
+    }
+
+    #[test]
+    fn uniform_chunks_round_trip() {
+        for &block in Block::ALL {
+            let message = Message::Chunk {
+                position: IVec3::ZERO,
+                chunk: Box::new(RawChunk::filled(block)),
+            };
+            assert_same(&message, &Message::decode(&message.encode()).unwrap());
+        }
+    }
+
+    #[test]
+    fn truncated_frames_are_errors() {
+        for message in messages() {
+            let bytes = message.encode();
+            for length in 0..bytes.len() {
+                assert_eq!(
+                    Message::decode(&bytes[..length]).err(),
+                    Some(ProtocolError::UnexpectedEnd),
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn truncated_payloads_are_errors() {
+        for message in messages() {
+            let mut bytes = message.encode();
+            bytes.pop();
+            let length = (bytes.len() - HEADER_SIZE) as u32;
+            bytes[2..HEADER_SIZE].copy_from_slice(&length.to_le_bytes());
+            assert!(Message::decode(&bytes).is_err());
+        }
+    }
+
+    #[test]
+    fn trailing_bytes_are_errors() {
+        let mut bytes = messages()[1].encode();
+        bytes.extend([0, 0]);
+        assert_eq!(
+            Message::decode(&bytes).err(),
+            Some(ProtocolError::TrailingBytes(2))
+        );
+    }
+
+    #[test]
+    fn bad_headers_are_errors() {
+        let mut bytes = messages()[1].encode();
+        bytes[0] = PROTOCOL_VERSION + 1;
+        assert_eq!(
+            Message::decode(&bytes).err(),
+            Some(ProtocolError::Version(PROTOCOL_VERSION + 1))
+        );
+
+        let mut bytes = messages()[1].encode();
+        bytes[1] = 200;
+        assert_eq!(
+            Message::decode(&bytes).err(),
+            Some(ProtocolError::UnknownMessage(200))
+        );
+    }
+
+    #[test]
+    fn invalid_blocks_are_errors() {
+        let mut bytes = Message::BlockEdits(vec![(IVec3::ZERO, Block::Dirt)]).encode();
+        *bytes.last_mut().unwrap() = u8::MAX;
+        assert_eq!(
+            Message::decode(&bytes).err(),
+            Some(ProtocolError::InvalidBlock(u8::MAX))
+        );
+    }
+
+    fn chunk_frame(payload: impl FnOnce(&mut Writer)) -> Vec<u8> {
+        let mut writer = Writer::default();
+        writer.ivec3(IVec3::ZERO);
+        payload(&mut writer);
+        let mut frame = vec![PROTOCOL_VERSION, CHUNK];
+        frame.extend((writer.0.len() as u32).to_le_bytes());
+        frame.extend(writer.0);
+        frame
+    }
+
+    #[test]
+    fn invalid_chunks_are_errors() {
+        let temperatures = [0; CHUNK_SIZE * CHUNK_SIZE];
+
+        let bytes = chunk_frame(|writer| {
+            writer.u8(1);
+            writer.block(Block::Stone);
+            writer.u16(1);
+            writer.u8(1);
+            writer.u16(CHUNK_VOLUME as u16);
+            writer.0.extend(temperatures);
+        });
+        assert_eq!(
+            Message::decode(&bytes).err(),
+            Some(ProtocolError::InvalidPaletteIndex(1))
+        );
+
+        let bytes = chunk_frame(|writer| {
+            writer.u8(1);
+            writer.block(Block::Stone);
+            writer.u16(1);
+            writer.u8(0);
+            writer.u16(CHUNK_VOLUME as u16 - 1);
+            writer.0.extend(temperatures);
+        });
+        assert_eq!(
+            Message::decode(&bytes).err(),
+            Some(ProtocolError::InvalidRuns(CHUNK_VOLUME - 1))
+        );
+
+        let bytes = chunk_frame(|writer| {
+            writer.u8(1);
+            writer.block(Block::Stone);
+            writer.u16(2);
+            writer.u8(0);
+            writer.u16(CHUNK_VOLUME as u16);
+            writer.u8(0);
+            writer.u16(1);
+            writer.0.extend(temperatures);
+        });
+        assert_eq!(
+            Message::decode(&bytes).err(),
+            Some(ProtocolError::InvalidRuns(CHUNK_VOLUME + 1))
+        );
+    }
+
+    #[test]
+    fn non_finite_transformations_are_errors() {
+        for value in [f32::NAN, f32::INFINITY, f32::NEG_INFINITY] {
+            let message = Message::PlayerTransform(Transformation::new(Vec3::ZERO, value, 0.0));
+            assert_eq!(
+                Message::decode(&message.encode()).err(),
+                Some(ProtocolError::NonFinite)
+            );
+        }
+    }
+
+    #[test]
+    fn garbage_never_panics() {
+        // Small xorshift, the test has to be reproducible
+        let mut state = 0x2545_f491_u32;
+        let mut next = move || {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            state
+        };
+
+        for message in messages() {
+            let bytes = message.encode();
+            for _ in 0..256 {
+                let mut corrupted = bytes.clone();
+                for _ in 0..1 + next() % 4 {
+                    let index = next() as usize % corrupted.len();
+                    corrupted[index] = next() as u8;
+                }
+                let _ = Message::decode(&corrupted);
+            }
+        }
+
+        for length in 0..64 {
+            let garbage: Vec<u8> = (0..length).map(|_| next() as u8).collect();
+            let _ = Message::decode(&garbage);
+        }
+    }
+}
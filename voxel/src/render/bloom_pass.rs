@@ -0,0 +1,166 @@
+use glam::{vec2, Vec2};
+use voxel_util::{
+    BasePipeline, Context, Fragment, GpuTimer, Sampler, ShaderResource, Texture, Uniform,
+};
+use wgpu::{
+    include_wgsl, BindGroupLayout, Buffer, Color, CommandEncoder, LoadOp, Operations,
+    RenderPassColorAttachment, RenderPassDescriptor, RenderPassTimestampWrites, RenderPipeline,
+    StoreOp, TextureUsages,
+};
+
+use crate::asset;
+
+use super::{post_process_pass::SCENE_FORMAT, vertex::QuadVertex};
+
+// Each iteration is one horizontal and one vertical blur
+const BLUR_ITERATIONS: usize = 2;
+
+type Source = (
+    (Fragment, Texture),
+    (Fragment, Sampler),
+    (Fragment, Uniform<Vec2>),
+);
+
+/// Blurs the parts of the scene brighter than white at half resolution,
+/// the result is added back onto the scene when post processing
+#[derive(Debug)]
+pub struct BloomPass {
+    threshold_pipeline: RenderPipeline,
+    blur_pipeline: RenderPipeline,
+    // The blur ping-pongs between the two, the result always ends up in the first one
+    textures: [Texture; 2],
+    threshold_resource: ShaderResource,
+    blur_resources: [ShaderResource; 2],
+}
+
+impl BloomPass {
+    pub fn new(scene: &Texture, sampler: &Sampler, context: &Context) -> Self {
+        let (textures, threshold_resource, blur_resources) =
+            Self::create_resources(scene, sampler, context);
+
+        let threshold_pipeline =
+            Self::create_pipeline("fs_threshold", threshold_resource.layout(), context);
+        let blur_pipeline = Self::create_pipeline("fs_blur", threshold_resource.layout(), context);
+
+        Self {
+            threshold_pipeline,
+            blur_pipeline,
+            textures,
+            threshold_resource,
+            blur_resources,
+        }
+    }
+
+    fn create_resources(
+        scene: &Texture,
+        sampler: &Sampler,
+        context: &Context,
+    ) -> ([Texture; 2], ShaderResource, [ShaderResource; 2]) {
+        let (width, height) = scene.size();
+        let size = ((width / 2).max(1), (height / 2).max(1));
+        let texel = vec2(1.0 / size.0 as f32, 1.0 / size.1 as f32);
+
+        let textures = [(); 2].map(|_| {
+            Texture::new(
+                size,
+                TextureUsages::RENDER_ATTACHMENT | TextureUsages::TEXTURE_BINDING,
+                SCENE_FORMAT,
+                context,
+            )
+        });
+
+        let threshold_resource = context.create_shader_resource::<Source>((
+            scene,
+            sampler,
+            &Uniform::new(Vec2::ZERO, context),
+        ));
+        let blur_resources = [
+            (&textures[0], vec2(texel.x, 0.0)),
+            (&textures[1], vec2(0.0, texel.y)),
+        ]
+        .map(|(source, direction)| {
+            context.create_shader_resource::<Source>((
+                source,
+                sampler,
+                &Uniform::new(direction, context),
+            ))
+        });
+
+        (textures, threshold_resource, blur_resources)
+    }
+
+    fn create_pipeline(
+        fragment_entry: &'static str,
+        source_layout: &BindGroupLayout,
+        context: &Context,
+    ) -> RenderPipeline {
+        let shader = context
+            .device()
+            .create_shader_module(include_wgsl!(asset!("shaders/bloom.wgsl")));
+
+        let pipeline_layout = context.create_pipeline_layout(&[source_layout]);
+
+        context
+            .create_render_pipeline::<QuadVertex>(BasePipeline {
+                vertex: (&shader, "vs_main"),
+                fragment: (&shader, fragment_entry),
+            })
+            .label("Bloom Pipeline")
+            .layout(&pipeline_layout)
+            .target(SCENE_FORMAT)
+            .build()
+    }
+
+    /// Blurred highlights, sampled by the post process pass
+    pub fn output(&self) -> &Texture {
+        &self.textures[0]
+    }
+
+    pub fn resize(&mut self, scene: &Texture, sampler: &Sampler, context: &Context) {
+        (self.textures, self.threshold_resource, self.blur_resources) =
+            Self::create_resources(scene, sampler, context);
+    }
+
+    /// Runs the threshold and blur passes, or only clears the output when `enabled` is off
+    pub fn render(
+        &self,
+        encoder: &mut CommandEncoder,
+        screen_quad: &Buffer,
+        enabled: bool,
+        timestamp_writes: Option<RenderPassTimestampWrites<'_>>,
+    ) {
+        let steps = if enabled { 1 + BLUR_ITERATIONS * 2 } else { 1 };
+
+        for step in 0..steps {
+            // Threshold and vertical blurs write into the first texture, horizontal ones into the second
+            let (pipeline, resource, target) = match step {
+                0 => (&self.threshold_pipeline, &self.threshold_resource, 0),
+                step if step % 2 == 1 => (&self.blur_pipeline, &self.blur_resources[0], 1),
+                _ => (&self.blur_pipeline, &self.blur_resources[1], 0),
+            };
+
+            let timestamp_writes = GpuTimer::split_writes(timestamp_writes.as_ref(), step, steps);
+
+            let mut render_pass = encoder.begin_render_pass(&RenderPassDescriptor {
+                label: Some("Bloom Render Pass"),
+                color_attachments: &[Some(RenderPassColorAttachment {
+                    view: self.textures[target].view(),
+                    resolve_target: None,
+                    ops: Operations {
+                        load: LoadOp::Clear(Color::BLACK),
+                        store: StoreOp::Store,
+                    },
+                })],
+                timestamp_writes,
+                ..Default::default()
+            });
+
+            if enabled {
+                render_pass.set_pipeline(pipeline);
+                render_pass.set_bind_group(0, resource.bind_group(), &[]);
+                render_pass.set_vertex_buffer(0, screen_quad.slice(..));
+                render_pass.draw(0..6, 0..1);
+            }
+        }
+    }
+}
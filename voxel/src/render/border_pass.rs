@@ -0,0 +1,135 @@
+use std::mem::size_of;
+
+use bytemuck::{Pod, Zeroable};
+use glam::{vec3, Vec3};
+use voxel_util::{BasePipeline, ColorTargetStateExt, Context, ShaderResource, VertexLayout};
+use wgpu::{
+    util::{BufferInitDescriptor, DeviceExt},
+    vertex_attr_array, BindGroupLayout, BlendComponent, BlendFactor, BlendOperation, Buffer,
+    BufferAddress, BufferUsages, ColorTargetState, CompareFunction, RenderPass, RenderPipeline,
+    TextureFormat, VertexAttribute, VertexBufferLayout, VertexStepMode,
+};
+
+use crate::{scene_shader, world::WorldBounds};
+
+use super::{post_process_pass::SCENE_FORMAT, Draw, Frame};
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod, Zeroable)]
+pub struct BorderVertex {
+    position: Vec3,
+}
+
+impl BorderVertex {
+    const ATTRIBUTES: [VertexAttribute; 1] = vertex_attr_array![0 => Float32x3];
+}
+
+impl VertexLayout for BorderVertex {
+    fn vertex_layout() -> VertexBufferLayout<'static> {
+        VertexBufferLayout {
+            array_stride: size_of::<BorderVertex>() as BufferAddress,
+            step_mode: VertexStepMode::Vertex,
+            attributes: &BorderVertex::ATTRIBUTES,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct BorderPass {
+    render_pipeline: RenderPipeline,
+    vertices: Buffer,
+    vertices_len: u32,
+}
+
+impl BorderPass {
+    pub fn new(
+        bounds: WorldBounds,
+        camera_resource: &ShaderResource,
+        depth_compare: CompareFunction,
+        context: &Context,
+    ) -> Self {
+        let vertices = Self::create_walls(bounds);
+
+        let render_pipeline =
+            Self::create_pipeline(camera_resource.layout(), depth_compare, context);
+        let vertices_len = vertices.len() as u32;
+        let vertices = context.device().create_buffer_init(&BufferInitDescriptor {
+            label: Some("Border Vertex Buffer"),
+            contents: bytemuck::cast_slice(&vertices),
+            usage: BufferUsages::VERTEX,
+        });
+
+        Self {
+            render_pipeline,
+            vertices,
+            vertices_len,
+        }
+    }
+
+    fn create_walls(bounds: WorldBounds) -> Vec<BorderVertex> {
+        let min = bounds.min_block().as_vec3();
+        let max = bounds.max_block().as_vec3();
+
+        let corners = [
+            vec3(min.x, 0.0, min.z),
+            vec3(max.x, 0.0, min.z),
+            vec3(max.x, 0.0, max.z),
+            vec3(min.x, 0.0, max.z),
+        ];
+
+        (0..corners.len())
+            .flat_map(|index| {
+                let start = corners[index];
+                let end = corners[(index + 1) % corners.len()];
+                let height = Vec3::Y * max.y;
+
+                [
+                    start,
+                    end,
+                    end + height,
+                    end + height,
+                    start + height,
+                    start,
+                ]
+            })
+            .map(|position| BorderVertex { position })
+            .collect()
+    }
+
+    fn create_pipeline(
+        camera_layout: &BindGroupLayout,
+        depth_compare: CompareFunction,
+        context: &Context,
+    ) -> RenderPipeline {
+        let shader = context
+            .device()
+            .create_shader_module(scene_shader!("shaders/border.wgsl"));
+
+        let pipeline_layout = context.create_pipeline_layout(&[camera_layout]);
+        let blend = BlendComponent {
+            src_factor: BlendFactor::SrcAlpha,
+            dst_factor: BlendFactor::OneMinusSrcAlpha,
+            operation: BlendOperation::Add,
+        };
+
+        context
+            .create_render_pipeline::<BorderVertex>(BasePipeline {
+                vertex: (&shader, "vs_main"),
+                fragment: (&shader, "fs_main"),
+            })
+            .label("Border Render Pipeline")
+            .layout(&pipeline_layout)
+            .target(ColorTargetState::builder(SCENE_FORMAT).blend(blend, blend))
+            .depth(TextureFormat::Depth32Float, depth_compare)
+            .depth_write(false)
+            .build()
+    }
+}
+
+impl Draw for BorderPass {
+    fn draw<'r>(&'r self, render_pass: &mut RenderPass<'r>, _frame: &Frame<'_>) {
+        render_pass.set_pipeline(&self.render_pipeline);
+        render_pass.set_vertex_buffer(0, self.vertices.slice(..));
+        render_pass.draw(0..self.vertices_len, 0..1);
+    }
+}
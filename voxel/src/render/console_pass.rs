@@ -0,0 +1,64 @@
+use wgpu_text::glyph_brush::{ab_glyph::PxScale, OwnedSection, OwnedText};
+use winit::dpi::PhysicalSize;
+
+use super::{text_pass::TEXT_SCALE, TextPass};
+
+const LINE_HEIGHT: f32 = TEXT_SCALE;
+const INPUT_COLOR: [f32; 4] = [1.0, 1.0, 1.0, 1.0];
+const LOG_COLOR: [f32; 4] = [0.8, 0.8, 0.8, 1.0];
+
+/// Input line of the console at the bottom of the screen, with its log above, queued into the
+/// [`TextPass`] while it's open
+pub struct ConsolePass {
+    input_section: OwnedSection,
+    log_section: OwnedSection,
+    height: f32,
+    visible: bool,
+}
+
+impl ConsolePass {
+    pub fn new(height: u32) -> Self {
+        Self {
+            input_section: OwnedSection::default(),
+            log_section: OwnedSection::default(),
+            height: height as f32,
+            visible: false,
+        }
+    }
+
+    /// Shows the input line with the log above it, `None` hides the console
+    pub fn set_text<'l>(&mut self, input: Option<&str>, log: impl Iterator<Item = &'l str>) {
+        self.visible = input.is_some();
+        let Some(input) = input else {
+            return;
+        };
+        let log = log.collect::<Vec<_>>();
+
+        let input_y = self.height - LINE_HEIGHT - 5.0;
+        self.input_section = OwnedSection::default()
+            .with_screen_position((5.0, input_y))
+            .add_text(
+                OwnedText::new(format!("> {input}_"))
+                    .with_scale(PxScale::from(LINE_HEIGHT))
+                    .with_color(INPUT_COLOR),
+            );
+        self.log_section = OwnedSection::default()
+            .with_screen_position((5.0, input_y - LINE_HEIGHT * log.len() as f32))
+            .add_text(
+                OwnedText::new(log.join("\n"))
+                    .with_scale(PxScale::from(LINE_HEIGHT))
+                    .with_color(LOG_COLOR),
+            );
+    }
+
+    pub fn update(&self, text: &mut TextPass) {
+        if self.visible {
+            text.queue(self.log_section.clone());
+            text.queue(self.input_section.clone());
+        }
+    }
+
+    pub fn resize(&mut self, new_size: PhysicalSize<u32>) {
+        self.height = new_size.height as f32;
+    }
+}
@@ -1,54 +1,106 @@
-use std::time::{Duration, Instant};
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
 
+use glam::{vec2, vec4, Vec3, Vec4};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
 use voxel_util::Context;
-use wgpu::RenderPass;
-use wgpu_text::{
-    glyph_brush::{
-        ab_glyph::{FontRef, PxScale},
-        OwnedSection, OwnedText,
-    },
-    BrushBuilder, TextBrush,
-};
-use winit::dpi::PhysicalSize;
+use wgpu_text::glyph_brush::OwnedSection;
 
-use crate::asset;
+use super::{
+    text_pass::{text_section, BACKDROP_COLOR, TEXT_COLOR},
+    world_pass::CullingStats,
+    TextPass,
+};
 
-pub trait OwnedSectionExt {
-    fn set_text<T: Into<String>>(&mut self, text: T) -> &mut OwnedText;
-}
+const POSITION_COLOR: [f32; 4] = [1.0, 0.85, 0.3, 1.0];
+// How long a changed value like the speed stays on screen
+const NOTICE_DURATION: Duration = Duration::from_secs(2);
 
-impl OwnedSectionExt for OwnedSection {
-    fn set_text<T: Into<String>>(&mut self, text: T) -> &mut OwnedText {
-        let text = OwnedText::new(text.into());
+// Frames in the graph, each one a bar of `GRAPH_BAR_WIDTH` pixels
+const GRAPH_FRAMES: usize = 120;
+const GRAPH_BAR_WIDTH: f32 = 2.0;
+const GRAPH_ORIGIN: (f32, f32) = (5.0, 225.0);
+const GRAPH_HEIGHT: f32 = 60.0;
+// Frame time at the top of the graph, longer frames are cut off
+const GRAPH_MAX_MILLISECONDS: f32 = 1000.0 / 30.0;
+const TARGET_MILLISECONDS: f32 = 1000.0 / 60.0;
+const FAST_FRAME_COLOR: Vec4 = vec4(0.3, 0.85, 0.3, 0.9);
+const SLOW_FRAME_COLOR: Vec4 = vec4(0.95, 0.75, 0.2, 0.9);
+const DROPPED_FRAME_COLOR: Vec4 = vec4(0.9, 0.25, 0.2, 0.9);
+const TARGET_LINE_COLOR: Vec4 = vec4(1.0, 1.0, 1.0, 0.5);
 
-        match self.text.first() {
-            Some(..) => self.text[0] = text,
-            None => self.text.push(text),
-        };
+/// How much of the debug overlay is shown
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum DebugOverlay {
+    Off,
+    Fps,
+    #[default]
+    Full,
+}
 
-        &mut self.text[0]
+impl DebugOverlay {
+    /// Level that follows this one, wrapping around to [`DebugOverlay::Off`]
+    pub fn next(self) -> Self {
+        match self {
+            Self::Off => Self::Fps,
+            Self::Fps => Self::Full,
+            Self::Full => Self::Off,
+        }
     }
 }
 
+/// Sections of the debug overlay, only the ones of the shown level are queued into the
+/// [`TextPass`]
 pub struct DebugPass {
-    brush: TextBrush<FontRef<'static>>,
-
     fps_section: OwnedSection,
     last_fps_update: Instant,
+    seed_section: OwnedSection,
+    frame_cap_section: OwnedSection,
+    frame_cap: Option<Option<u32>>,
+    culling_section: OwnedSection,
+    gpu_section: OwnedSection,
+    adapter_section: OwnedSection,
+    memory_section: OwnedSection,
+    position_section: OwnedSection,
+    // Milliseconds of the last frames, the newest at the back
+    frame_times: VecDeque<f32>,
+    // Value that just changed, shown for a moment below the rest
+    notice_section: OwnedSection,
+    notice_shown_until: Instant,
+    overlay: DebugOverlay,
 }
 
 impl DebugPass {
     pub fn new(context: &Context) -> Self {
-        let config = context.config();
-
-        let brush = BrushBuilder::using_font_bytes(include_bytes!(asset!("monogram.ttf")))
-            .expect("invalid font")
-            .build(context.device(), config.width, config.height, config.format);
+        let info = context.adapter_info();
+        let adapter_section = text_section(
+            (5.0, 125.0),
+            format!(
+                "Adapter: {} ({:?}, {} {})",
+                info.name, info.backend, info.driver, info.driver_info
+            ),
+            TEXT_COLOR,
+        );
 
         Self {
-            brush,
-            fps_section: OwnedSection::default().with_screen_position((5.0, 5.0)),
+            fps_section: OwnedSection::default(),
             last_fps_update: Instant::now(),
+            seed_section: OwnedSection::default(),
+            frame_cap_section: OwnedSection::default(),
+            frame_cap: None,
+            culling_section: OwnedSection::default(),
+            gpu_section: OwnedSection::default(),
+            adapter_section,
+            memory_section: OwnedSection::default(),
+            position_section: OwnedSection::default(),
+            frame_times: VecDeque::with_capacity(GRAPH_FRAMES),
+            notice_section: OwnedSection::default(),
+            notice_shown_until: Instant::now(),
+            overlay: DebugOverlay::default(),
         }
     }
 
@@ -56,32 +108,174 @@ impl DebugPass {
         if self.last_fps_update.elapsed() > Duration::from_millis(250) {
             let fps = 1.0 / delta_time.as_secs_f32();
 
-            let text = self.fps_section.set_text(format!("FPS: {}", fps.round()));
-            text.scale = PxScale::from(24.0);
+            self.fps_section =
+                text_section((5.0, 5.0), format!("FPS: {}", fps.round()), TEXT_COLOR);
 
             self.last_fps_update = Instant::now();
         }
     }
 
-    pub fn update(&mut self, delta_time: Duration, context: &Context) {
-        self.update_fps(delta_time);
+    pub fn set_seed(&mut self, seed: u32) {
+        self.seed_section = text_section((5.0, 29.0), format!("Seed: {seed}"), TEXT_COLOR);
+    }
+
+    pub fn set_frame_cap(&mut self, frame_cap: Option<u32>) {
+        if self.frame_cap == Some(frame_cap) {
+            return;
+        }
+        self.frame_cap = Some(frame_cap);
 
-        self.brush
-            .queue(context.device(), context.queue(), [&self.fps_section])
-            .expect("cache texture limit exceeded");
+        let text = match frame_cap {
+            Some(frame_cap) => format!("Cap: {frame_cap} FPS"),
+            None => "Cap: none".to_string(),
+        };
+        self.frame_cap_section = text_section((5.0, 53.0), text, TEXT_COLOR);
+    }
+
+    pub fn overlay(&self) -> DebugOverlay {
+        self.overlay
     }
 
-    pub fn resize(&mut self, new_size: PhysicalSize<u32>, context: &Context) {
-        self.brush.resize_view(
-            new_size.width as f32,
-            new_size.height as f32,
-            context.queue(),
+    pub fn set_overlay(&mut self, overlay: DebugOverlay) {
+        self.overlay = overlay;
+    }
+
+    pub fn set_culling_stats(&mut self, stats: CullingStats) {
+        if self.overlay != DebugOverlay::Full {
+            return;
+        }
+        let text = format!(
+            "Chunks: {}/{} ({} culled), vertices: {}, indices: {}",
+            stats.passed,
+            stats.tested,
+            stats.culled(),
+            stats.vertices,
+            stats.indices
         );
+        self.culling_section = text_section((5.0, 77.0), text, TEXT_COLOR);
     }
-}
 
-impl DebugPass {
-    pub fn draw<'r>(&'r self, render_pass: &mut RenderPass<'r>) {
-        self.brush.draw(render_pass);
+    /// Milliseconds each named span took on the GPU, `None` without timestamp support
+    pub fn set_gpu_timings<'n>(&mut self, timings: Option<impl Iterator<Item = (&'n str, f32)>>) {
+        if self.overlay != DebugOverlay::Full {
+            return;
+        }
+        let text = match timings {
+            Some(timings) => {
+                let timings = timings
+                    .map(|(name, milliseconds)| format!("{name} {milliseconds:.2}ms"))
+                    .collect::<Vec<_>>();
+                format!("GPU: {}", timings.join(", "))
+            }
+            None => "GPU: timestamps unsupported".to_string(),
+        };
+        self.gpu_section = text_section((5.0, 101.0), text, TEXT_COLOR);
+    }
+
+    /// Bytes taken by the blocks of the loaded chunks and by the mesh buffers
+    pub fn set_memory(&mut self, chunk_bytes: u64, mesh_bytes: u64) {
+        if self.overlay != DebugOverlay::Full {
+            return;
+        }
+        let mebibytes = |bytes: u64| bytes as f64 / (1024.0 * 1024.0);
+        let text = format!(
+            "Memory: chunks {:.1} MiB, meshes {:.1} MiB",
+            mebibytes(chunk_bytes),
+            mebibytes(mesh_bytes)
+        );
+        self.memory_section = text_section((5.0, 149.0), text, TEXT_COLOR);
+    }
+
+    pub fn set_camera_position(&mut self, position: Vec3) {
+        if self.overlay != DebugOverlay::Full {
+            return;
+        }
+        let text = format!(
+            "Position: {:.1} {:.1} {:.1}",
+            position.x, position.y, position.z
+        );
+        self.position_section = text_section((5.0, 173.0), text, POSITION_COLOR);
+    }
+
+    fn show_notice(&mut self, text: String) {
+        self.notice_section = text_section((5.0, 197.0), text, POSITION_COLOR);
+        self.notice_shown_until = Instant::now() + NOTICE_DURATION;
+    }
+
+    pub fn show_speed(&mut self, speed: f32) {
+        self.show_notice(format!("Speed: {} blocks/s", speed.round()));
+    }
+
+    pub fn show_color_grading(&mut self, exposure: f32, gamma: f32) {
+        self.show_notice(format!("Exposure: {exposure:.2}, gamma: {gamma:.2}"));
+    }
+
+    /// Queues the sections of the shown level into `text`, each over a backdrop
+    pub fn update(&mut self, delta_time: Duration, text: &mut TextPass) {
+        // Recorded while hidden too, so the graph is filled once it's shown
+        if self.frame_times.len() == GRAPH_FRAMES {
+            self.frame_times.pop_front();
+        }
+        self.frame_times
+            .push_back(delta_time.as_secs_f32() * 1000.0);
+
+        if self.overlay == DebugOverlay::Off {
+            return;
+        }
+        self.update_fps(delta_time);
+        text.queue_with_backdrop(self.fps_section.clone());
+
+        if self.overlay == DebugOverlay::Full {
+            for section in [
+                &self.seed_section,
+                &self.frame_cap_section,
+                &self.culling_section,
+                &self.gpu_section,
+                &self.adapter_section,
+                &self.memory_section,
+                &self.position_section,
+            ] {
+                text.queue_with_backdrop(section.clone());
+            }
+            self.queue_frame_graph(text);
+        }
+        if Instant::now() < self.notice_shown_until {
+            text.queue_with_backdrop(self.notice_section.clone());
+        }
+    }
+
+    /// Bars of the last frame times, green until 60 FPS, yellow until 30 FPS and red beyond
+    fn queue_frame_graph(&self, text: &mut TextPass) {
+        let origin = vec2(GRAPH_ORIGIN.0, GRAPH_ORIGIN.1);
+        let size = vec2(GRAPH_FRAMES as f32 * GRAPH_BAR_WIDTH, GRAPH_HEIGHT);
+        text.queue_quad(origin, origin + size, BACKDROP_COLOR);
+
+        let bottom = origin.y + GRAPH_HEIGHT;
+        let height =
+            |milliseconds: f32| (milliseconds / GRAPH_MAX_MILLISECONDS).min(1.0) * GRAPH_HEIGHT;
+        // Right aligned, so the newest frame is always at the same spot
+        let first = GRAPH_FRAMES - self.frame_times.len();
+        for (index, &milliseconds) in self.frame_times.iter().enumerate() {
+            let x = origin.x + (first + index) as f32 * GRAPH_BAR_WIDTH;
+            let color = if milliseconds <= TARGET_MILLISECONDS {
+                FAST_FRAME_COLOR
+            } else if milliseconds <= GRAPH_MAX_MILLISECONDS {
+                SLOW_FRAME_COLOR
+            } else {
+                DROPPED_FRAME_COLOR
+            };
+            text.queue_quad(
+                vec2(x, bottom - height(milliseconds)),
+                vec2(x + GRAPH_BAR_WIDTH, bottom),
+                color,
+            );
+        }
+
+        let target = bottom - height(TARGET_MILLISECONDS);
+        text.queue_quad(
+            vec2(origin.x, target),
+            vec2(origin.x + size.x, target + 1.0),
+            TARGET_LINE_COLOR,
+        );
     }
 }
@@ -0,0 +1,18 @@
+use voxel_util::ShaderResource;
+use wgpu::RenderPass;
+
+use crate::application::Meshes;
+
+use super::Frustum;
+
+/// Per-frame state shared by everything drawn inside the main render pass
+pub struct Frame<'f> {
+    pub frustum: &'f Frustum,
+    pub meshes: &'f Meshes,
+    pub lighting: &'f ShaderResource,
+    pub spritesheet: &'f ShaderResource,
+}
+
+pub trait Draw {
+    fn draw<'r>(&'r self, render_pass: &mut RenderPass<'r>, frame: &Frame<'_>);
+}
@@ -1,4 +1,4 @@
-use glam::{vec3, Mat4, Vec3, Vec4, Vec4Swizzles};
+use glam::{Mat4, Vec3, Vec4, Vec4Swizzles};
 
 #[derive(Debug, Clone, Copy)]
 pub struct Plane {
@@ -44,7 +44,9 @@ impl Frustum {
         let bottom_face = Plane::from_vector(matrix.row(3) + matrix.row(1)).normalize();
         let top_face = Plane::from_vector(matrix.row(3) - matrix.row(1)).normalize();
 
-        let near_face = Plane::from_vector(matrix.row(3) + matrix.row(2)).normalize();
+        // Clip space depth goes from 0 to 1 in wgpu, a reversed depth only swaps
+        // which of the two planes is the near one
+        let near_face = Plane::from_vector(matrix.row(2)).normalize();
         let far_face = Plane::from_vector(matrix.row(3) - matrix.row(2)).normalize();
 
         Self {
@@ -57,6 +59,12 @@ impl Frustum {
         }
     }
 
+    /// Cheaper and looser than the box test, used to reject chunks early
+    pub fn contains_sphere(&self, sphere: &Sphere) -> bool {
+        self.iter()
+            .all(|plane| sphere.center.dot(plane.normal) - plane.distance >= -sphere.radius)
+    }
+
     pub fn iter(&self) -> impl Iterator<Item = &Plane> {
         [
             &self.left_face,
@@ -70,6 +78,12 @@ impl Frustum {
     }
 }
 
+#[derive(Debug, Clone, Copy)]
+pub struct Sphere {
+    center: Vec3,
+    radius: f32,
+}
+
 #[derive(Debug, Clone, Copy)]
 pub struct AABB {
     min: Vec3,
@@ -81,33 +95,194 @@ impl AABB {
         Self { min, max }
     }
 
-    fn is_point_on_plane(plane: &Plane, point: Vec3) -> bool {
-        let distance = point.dot(plane.normal);
-        distance >= plane.distance
+    pub fn bounding_sphere(&self) -> Sphere {
+        Sphere {
+            center: (self.min + self.max) / 2.0,
+            radius: (self.max - self.min).length() / 2.0,
+        }
     }
 
+    /// Whether any part of the box is on the inner side of the plane,
+    /// checked with the corner furthest along the plane normal
     pub fn is_on_plane(self, plane: &Plane) -> bool {
-        let corners = [
-            self.min,
-            vec3(self.max.x, self.min.y, self.min.z),
-            vec3(self.min.x, self.max.y, self.min.z),
-            vec3(self.max.x, self.max.y, self.min.z),
-            vec3(self.min.x, self.min.y, self.max.z),
-            vec3(self.max.x, self.min.y, self.max.z),
-            vec3(self.min.x, self.max.y, self.max.z),
-            self.max,
+        let positive = Vec3::select(plane.normal.cmpge(Vec3::ZERO), self.max, self.min);
+        positive.dot(plane.normal) >= plane.distance
+    }
+
+    pub fn is_on_frustum(&self, frustum: &Frustum) -> bool {
+        frustum.iter().all(|plane| self.is_on_plane(plane))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{hint::black_box, time::Instant};
+
+    use glam::vec3;
+    use winit::dpi::PhysicalSize;
+
+    use crate::camera::{Projection, Transformation};
+
+    use super::*;
+
+    // Clip space containment, `None` for points too close to a plane to tell apart
+    fn clip_contains(matrix: Mat4, point: Vec3) -> Option<bool> {
+        let clip = matrix * point.extend(1.0);
+        let margin = clip.w.abs() * 1e-3;
+        let distances = [
+            clip.w + clip.x,
+            clip.w - clip.x,
+            clip.w + clip.y,
+            clip.w - clip.y,
+            clip.z,
+            clip.w - clip.z,
         ];
+        if distances.iter().any(|distance| distance.abs() < margin) {
+            return None;
+        }
+        Some(distances.iter().all(|&distance| distance > 0.0))
+    }
+
+    fn matches_clip_space(reversed_z: bool) {
+        let projection = Projection::new(PhysicalSize::new(1600, 900), 1.2, 0.1, 1000.0)
+            .with_reversed_z(reversed_z);
+        let transformation = Transformation::new(vec3(3.0, 40.0, -7.0), 0.7, -0.3);
+        let matrix = projection.calculate_matrix() * transformation.calculate_matrix();
+        let frustum = Frustum::from_projection(matrix);
 
-        let first = AABB::is_point_on_plane(plane, corners[0]);
-        for point in corners[1..].iter() {
-            if AABB::is_point_on_plane(plane, *point) != first {
-                return true;
+        let mut inside = 0;
+        for x in -30..=30 {
+            for y in -30..=30 {
+                for z in -30..=30 {
+                    // Denser near the camera, reaching past the far plane
+                    let offset = vec3(x as f32, y as f32, z as f32);
+                    let point = transformation.position() + offset * offset.abs() * 1.3;
+                    let Some(expected) = clip_contains(matrix, point) else {
+                        continue;
+                    };
+                    let culled = !AABB::new(point, point).is_on_frustum(&frustum);
+                    assert_eq!(!culled, expected, "{point} reversed {reversed_z}");
+                    inside += expected as u32;
+                }
             }
         }
-        first
+        assert!(inside > 0);
     }
 
-    pub fn is_on_frustum(&self, frustum: &Frustum) -> bool {
-        frustum.iter().all(|plane| self.is_on_plane(plane))
+    #[test]
+    fn frustum_matches_clip_space() {
+        matches_clip_space(false);
+    }
+
+    #[test]
+    fn reversed_frustum_matches_clip_space() {
+        matches_clip_space(true);
+    }
+
+    #[test]
+    fn spheres_are_looser_than_boxes() {
+        let projection = Projection::new(PhysicalSize::new(800, 600), 1.2, 0.1, 1000.0);
+        let transformation = Transformation::new(Vec3::ZERO, 0.0, 0.0);
+        let matrix = projection.calculate_matrix() * transformation.calculate_matrix();
+        let frustum = Frustum::from_projection(matrix);
+
+        let ahead = transformation.direction() * 50.0;
+        let aabb = AABB::new(ahead - 1.0, ahead + 1.0);
+        assert!(aabb.is_on_frustum(&frustum));
+        assert!(frustum.contains_sphere(&aabb.bounding_sphere()));
+
+        let behind = AABB::new(-ahead - 1.0, -ahead + 1.0);
+        assert!(!behind.is_on_frustum(&frustum));
+        assert!(!frustum.contains_sphere(&behind.bounding_sphere()));
+    }
+
+    #[test]
+    fn boxes_with_a_visible_point_are_kept() {
+        let projection = Projection::new(PhysicalSize::new(1280, 720), 1.2, 0.1, 1000.0);
+        let transformation = Transformation::new(vec3(0.0, 60.0, 0.0), 2.1, 0.4);
+        let matrix = projection.calculate_matrix() * transformation.calculate_matrix();
+        let frustum = Frustum::from_projection(matrix);
+
+        // Small xorshift, the test has to be reproducible
+        let mut state = 0x9e37_79b9_u32;
+        let mut next = move || {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            (state % 2000) as f32 / 1000.0 - 1.0
+        };
+        let mut kept = 0;
+        for _ in 0..2000 {
+            // Many of them reach behind the camera, across the near plane
+            let center = transformation.position() + vec3(next(), next(), next()) * 60.0;
+            let extent = vec3(next(), next(), next()).abs() * 12.0;
+            let aabb = AABB::new(center - extent, center + extent);
+
+            let samples = 4;
+            let is_visible = (0..=samples).any(|x| {
+                (0..=samples).any(|y| {
+                    (0..=samples).any(|z| {
+                        let t = vec3(x as f32, y as f32, z as f32) / samples as f32;
+                        let point = aabb.min + (aabb.max - aabb.min) * t;
+                        clip_contains(matrix, point) == Some(true)
+                    })
+                })
+            });
+            if is_visible {
+                assert!(aabb.is_on_frustum(&frustum), "{aabb:?}");
+                kept += 1;
+            }
+        }
+        assert!(kept > 0);
+    }
+    #[test]
+    #[ignore = "benchmark, run with --release -- --ignored --nocapture"]
+    fn spheres_reject_chunks_before_boxes() {
+        const ROUNDS: u32 = 2000;
+        const DISTANCE: i32 = 16;
+        const HEIGHT: i32 = 16;
+
+        let projection = Projection::new(PhysicalSize::new(1600, 900), 1.2, 0.1, 1000.0);
+        let transformation = Transformation::new(vec3(8.0, 80.0, 8.0), 0.7, -0.2);
+        let matrix = projection.calculate_matrix() * transformation.calculate_matrix();
+        let frustum = Frustum::from_projection(matrix);
+
+        // The chunks in view at a render distance of 16
+        let chunks = (-DISTANCE..=DISTANCE)
+            .flat_map(|x| {
+                (0..HEIGHT).flat_map(move |y| (-DISTANCE..=DISTANCE).map(move |z| (x, y, z)))
+            })
+            .map(|(x, y, z)| {
+                let min = vec3(x as f32, y as f32, z as f32) * 16.0;
+                let aabb = AABB::new(min, min + 16.0);
+                (aabb, aabb.bounding_sphere())
+            })
+            .collect::<Vec<_>>();
+
+        let time = |is_visible: &dyn Fn(&(AABB, Sphere)) -> bool| {
+            let start = Instant::now();
+            let mut visible = 0;
+            for _ in 0..ROUNDS {
+                visible = black_box(&chunks)
+                    .iter()
+                    .filter(|chunk| is_visible(chunk))
+                    .count();
+            }
+            (start.elapsed() / ROUNDS, visible)
+        };
+        let (boxes, visible) = time(&|(aabb, _)| aabb.is_on_frustum(&frustum));
+        let (spheres_first, visible_after_spheres) =
+            time(&|(aabb, sphere)| frustum.contains_sphere(sphere) && aabb.is_on_frustum(&frustum));
+        assert_eq!(visible, visible_after_spheres);
+
+        let rejected = chunks
+            .iter()
+            .filter(|(_, sphere)| !frustum.contains_sphere(sphere))
+            .count();
+        println!(
+            "{} chunks, {visible} visible, {rejected} rejected by spheres: {boxes:?} a frame \
+             with boxes, {spheres_first:?} with spheres first",
+            chunks.len()
+        );
     }
 }
@@ -0,0 +1,201 @@
+use std::mem::size_of;
+
+use bytemuck::{Pod, Zeroable};
+use glam::{vec2, vec4, Vec2, Vec4};
+use voxel_util::{BasePipeline, ColorTargetStateExt, Context, GrowableBuffer, VertexLayout};
+use wgpu::{
+    include_wgsl, vertex_attr_array, BindGroupLayout, BlendComponent, BlendFactor, BlendOperation,
+    BufferAddress, BufferUsages, ColorTargetState, CompareFunction, RenderPass, RenderPipeline,
+    TextureFormat, VertexAttribute, VertexBufferLayout, VertexStepMode,
+};
+use winit::dpi::PhysicalSize;
+
+use crate::{
+    asset,
+    world::{Block, Tint},
+};
+
+use super::{Draw, Frame};
+
+pub const SLOTS: usize = 9;
+// Pixels
+const SLOT_SIZE: f32 = 48.0;
+const SLOT_GAP: f32 = 4.0;
+const ICON_PADDING: f32 = 8.0;
+const SELECTION_BORDER: f32 = 3.0;
+const BOTTOM_MARGIN: f32 = 12.0;
+// Frame of the selected slot, then the background and icon of every slot
+const MAX_INSTANCES: usize = SLOTS * 2 + 1;
+
+const SLOT_COLOR: Vec4 = vec4(0.0, 0.0, 0.0, 0.5);
+const SELECTION_COLOR: Vec4 = vec4(1.0, 1.0, 1.0, 0.9);
+
+const TEXTURED: u32 = 1;
+const FOLIAGE: u32 = 2;
+
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy, Pod, Zeroable)]
+pub struct HotbarInstance {
+    // Corners in clip space
+    min: Vec2,
+    max: Vec2,
+    color: Vec4,
+    texture_id: u32,
+    flags: u32,
+    _padding: [u32; 2],
+}
+
+impl HotbarInstance {
+    const ATTRIBUTES: [VertexAttribute; 5] = vertex_attr_array![
+        0 => Float32x2,
+        1 => Float32x2,
+        2 => Float32x4,
+        3 => Uint32,
+        4 => Uint32
+    ];
+}
+
+impl VertexLayout for HotbarInstance {
+    fn vertex_layout() -> VertexBufferLayout<'static> {
+        VertexBufferLayout {
+            array_stride: size_of::<HotbarInstance>() as BufferAddress,
+            step_mode: VertexStepMode::Instance,
+            attributes: &HotbarInstance::ATTRIBUTES,
+        }
+    }
+}
+
+/// Row of block slots at the bottom of the screen, the selected one framed
+#[derive(Debug)]
+pub struct HotbarPass {
+    render_pipeline: RenderPipeline,
+    instances: GrowableBuffer<HotbarInstance>,
+    blocks: [Block; SLOTS],
+    selected: usize,
+    screen_size: Vec2,
+}
+
+impl HotbarPass {
+    pub fn new(spritesheet_layout: &BindGroupLayout, context: &Context) -> Self {
+        let render_pipeline = Self::create_pipeline(spritesheet_layout, context);
+        let instances = GrowableBuffer::new(
+            "Hotbar Instance Buffer",
+            BufferUsages::VERTEX,
+            MAX_INSTANCES,
+            context,
+        );
+        let config = context.config();
+
+        Self {
+            render_pipeline,
+            instances,
+            blocks: [Block::Air; SLOTS],
+            selected: 0,
+            screen_size: vec2(config.width as f32, config.height as f32),
+        }
+    }
+
+    fn create_pipeline(spritesheet_layout: &BindGroupLayout, context: &Context) -> RenderPipeline {
+        let shader = context
+            .device()
+            .create_shader_module(include_wgsl!(asset!("shaders/hotbar.wgsl")));
+
+        let pipeline_layout = context.create_pipeline_layout(&[spritesheet_layout]);
+        let blend = BlendComponent {
+            src_factor: BlendFactor::SrcAlpha,
+            dst_factor: BlendFactor::OneMinusSrcAlpha,
+            operation: BlendOperation::Add,
+        };
+
+        context
+            .create_render_pipeline::<HotbarInstance>(BasePipeline {
+                vertex: (&shader, "vs_main"),
+                fragment: (&shader, "fs_main"),
+            })
+            .label("Hotbar Render Pipeline")
+            .layout(&pipeline_layout)
+            .target(ColorTargetState::builder(context.config().format).blend(blend, blend))
+            .depth(TextureFormat::Depth32Float, CompareFunction::Always)
+            .depth_write(false)
+            .build()
+    }
+
+    /// Shows the blocks in the slots, air leaves a slot empty
+    pub fn set_slots(&mut self, blocks: [Block; SLOTS], selected: usize, context: &Context) {
+        self.blocks = blocks;
+        self.selected = selected;
+        self.update_instances(context);
+    }
+
+    pub fn resize(&mut self, new_size: PhysicalSize<u32>, context: &Context) {
+        self.screen_size = vec2(new_size.width as f32, new_size.height as f32);
+        self.update_instances(context);
+    }
+
+    /// Quad between the pixel corners, converted to clip space
+    fn quad(&self, min: Vec2, max: Vec2, color: Vec4) -> HotbarInstance {
+        let to_clip = |pixel: Vec2| {
+            vec2(pixel.x, self.screen_size.y - pixel.y) / self.screen_size * 2.0 - 1.0
+        };
+        let (bottom_left, top_right) = (to_clip(vec2(min.x, max.y)), to_clip(vec2(max.x, min.y)));
+
+        HotbarInstance {
+            min: bottom_left,
+            max: top_right,
+            color,
+            ..Default::default()
+        }
+    }
+
+    fn update_instances(&mut self, context: &Context) {
+        let width = SLOTS as f32 * (SLOT_SIZE + SLOT_GAP) - SLOT_GAP;
+        let origin = vec2(
+            (self.screen_size.x - width) / 2.0,
+            self.screen_size.y - BOTTOM_MARGIN - SLOT_SIZE,
+        );
+        let slot_min = |slot: usize| origin + Vec2::X * slot as f32 * (SLOT_SIZE + SLOT_GAP);
+
+        let selected = slot_min(self.selected);
+        let mut instances = vec![self.quad(
+            selected - SELECTION_BORDER,
+            selected + SLOT_SIZE + SELECTION_BORDER,
+            SELECTION_COLOR,
+        )];
+        for (slot, block) in self.blocks.into_iter().enumerate() {
+            let min = slot_min(slot);
+            instances.push(self.quad(min, min + SLOT_SIZE, SLOT_COLOR));
+
+            if block == Block::Air {
+                continue;
+            }
+            let mut flags = TEXTURED;
+            if block.tint() != Tint::None {
+                flags |= FOLIAGE;
+            }
+            instances.push(HotbarInstance {
+                texture_id: block.texture_id(),
+                flags,
+                ..self.quad(
+                    min + ICON_PADDING,
+                    min + SLOT_SIZE - ICON_PADDING,
+                    Vec4::ONE,
+                )
+            });
+        }
+
+        self.instances.write(&instances, context);
+    }
+}
+
+impl Draw for HotbarPass {
+    fn draw<'r>(&'r self, render_pass: &mut RenderPass<'r>, frame: &Frame<'_>) {
+        if self.instances.is_empty() {
+            return;
+        }
+
+        render_pass.set_pipeline(&self.render_pipeline);
+        render_pass.set_bind_group(0, frame.spritesheet.bind_group(), &[]);
+        render_pass.set_vertex_buffer(0, self.instances.slice());
+        render_pass.draw(0..6, 0..self.instances.len() as u32);
+    }
+}
@@ -1,10 +1,74 @@
+pub mod bloom_pass;
+pub mod border_pass;
+pub mod console_pass;
 pub mod debug_pass;
+pub mod draw;
 pub mod frustum_culling;
+pub mod hotbar_pass;
+pub mod particle_pass;
+pub mod post_process_pass;
 pub mod renderer;
+pub mod shadow_pass;
+pub mod text_pass;
 pub mod vertex;
+pub mod viewmodel_pass;
 pub mod world_pass;
 
-pub use debug_pass::DebugPass;
+pub use bloom_pass::BloomPass;
+pub use border_pass::BorderPass;
+pub use console_pass::ConsolePass;
+pub use debug_pass::{DebugOverlay, DebugPass};
+pub use draw::{Draw, Frame};
 pub use frustum_culling::Frustum;
+pub use hotbar_pass::HotbarPass;
+pub use particle_pass::ParticlePass;
+pub use post_process_pass::PostProcessPass;
 pub use renderer::Renderer;
-pub use vertex::Vertex;
+pub use shadow_pass::ShadowPass;
+pub use text_pass::TextPass;
+pub use vertex::{QuadVertex, UnpackedVertex, Vertex};
+pub use viewmodel_pass::ViewmodelPass;
+
+#[cfg(test)]
+mod tests {
+    use wgpu::{
+        include_wgsl,
+        naga::{
+            front::wgsl,
+            valid::{Capabilities, ValidationFlags, Validator},
+        },
+        ShaderModuleDescriptor, ShaderSource,
+    };
+
+    use crate::{asset, scene_shader};
+
+    fn validate(descriptor: ShaderModuleDescriptor) {
+        let label = descriptor.label.unwrap_or_default();
+        let ShaderSource::Wgsl(source) = descriptor.source else {
+            panic!("{label} is not WGSL");
+        };
+        let module = wgsl::parse_str(&source)
+            .unwrap_or_else(|error| panic!("{}", error.emit_to_string_with_path(&source, label)));
+        Validator::new(ValidationFlags::all(), Capabilities::all())
+            .validate(&module)
+            .unwrap_or_else(|error| panic!("{label}: {error:?}"));
+    }
+
+    #[test]
+    fn scene_shaders_are_valid() {
+        validate(scene_shader!("shaders/world.wgsl"));
+        validate(scene_shader!("shaders/border.wgsl"));
+        validate(scene_shader!("shaders/particle.wgsl"));
+        validate(scene_shader!("shaders/viewmodel.wgsl"));
+    }
+
+    #[test]
+    fn shaders_are_valid() {
+        validate(include_wgsl!(asset!("shaders/backdrop.wgsl")));
+        validate(include_wgsl!(asset!("shaders/bloom.wgsl")));
+        validate(include_wgsl!(asset!("shaders/hotbar.wgsl")));
+        validate(include_wgsl!(asset!("shaders/post_process.wgsl")));
+        validate(include_wgsl!(asset!("shaders/shadow.wgsl")));
+        validate(include_wgsl!(asset!("shaders/shadow_debug.wgsl")));
+    }
+}
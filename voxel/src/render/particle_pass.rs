@@ -0,0 +1,209 @@
+use std::mem::size_of;
+
+use bytemuck::{Pod, Zeroable};
+use glam::{vec2, vec3, IVec3, Vec2, Vec3};
+use voxel_util::{BasePipeline, ColorTargetStateExt, Context, GrowableBuffer, VertexLayout};
+use wgpu::{
+    vertex_attr_array, BindGroupLayout, BlendComponent, BlendFactor, BlendOperation, BufferAddress,
+    BufferUsages, ColorTargetState, CompareFunction, RenderPass, RenderPipeline, TextureFormat,
+    VertexAttribute, VertexBufferLayout, VertexStepMode,
+};
+
+use crate::{
+    scene_shader,
+    world::{Block, Tint},
+};
+
+use super::{post_process_pass::SCENE_FORMAT, Draw, Frame};
+
+// Oldest particles are dropped first once the cap is reached
+const MAX_PARTICLES: usize = 1024;
+const PARTICLES_PER_BLOCK: usize = 24;
+// The instance buffer grows from here up to the cap
+const INITIAL_INSTANCES: usize = PARTICLES_PER_BLOCK * 4;
+// Blocks per second squared
+const GRAVITY: f32 = 18.0;
+const PARTICLE_SIZE: f32 = 0.12;
+// Seconds, every particle picks its lifetime between the two
+const MIN_LIFETIME: f32 = 0.5;
+const MAX_LIFETIME: f32 = 1.0;
+
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy, Pod, Zeroable)]
+pub struct ParticleInstance {
+    position: Vec3,
+    size: f32,
+    // Top left corner of the sampled piece, in tiles
+    uv_offset: Vec2,
+    texture_id: u32,
+    // Zero when the tile is used as is, see `Tint`
+    tint: u32,
+    alpha: f32,
+}
+
+impl ParticleInstance {
+    const ATTRIBUTES: [VertexAttribute; 6] = vertex_attr_array![
+        0 => Float32x3,
+        1 => Float32,
+        2 => Float32x2,
+        3 => Uint32,
+        4 => Uint32,
+        5 => Float32
+    ];
+}
+
+impl VertexLayout for ParticleInstance {
+    fn vertex_layout() -> VertexBufferLayout<'static> {
+        VertexBufferLayout {
+            array_stride: size_of::<ParticleInstance>() as BufferAddress,
+            step_mode: VertexStepMode::Instance,
+            attributes: &ParticleInstance::ATTRIBUTES,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Particle {
+    position: Vec3,
+    velocity: Vec3,
+    age: f32,
+    lifetime: f32,
+    uv_offset: Vec2,
+    block: Block,
+}
+
+/// Short lived bits of broken blocks, simulated on the CPU and drawn as camera facing quads
+#[derive(Debug)]
+pub struct ParticlePass {
+    render_pipeline: RenderPipeline,
+    instances: GrowableBuffer<ParticleInstance>,
+    particles: Vec<Particle>,
+    // State of the xorshift generator scattering the particles
+    random: u32,
+}
+
+impl ParticlePass {
+    pub fn new(
+        camera_layout: &BindGroupLayout,
+        spritesheet_layout: &BindGroupLayout,
+        depth_compare: CompareFunction,
+        context: &Context,
+    ) -> Self {
+        let render_pipeline =
+            Self::create_pipeline(camera_layout, spritesheet_layout, depth_compare, context);
+        let instances = GrowableBuffer::new(
+            "Particle Instance Buffer",
+            BufferUsages::VERTEX,
+            INITIAL_INSTANCES,
+            context,
+        );
+
+        Self {
+            render_pipeline,
+            instances,
+            particles: Vec::with_capacity(MAX_PARTICLES),
+            random: 0x9e37_79b9,
+        }
+    }
+
+    fn create_pipeline(
+        camera_layout: &BindGroupLayout,
+        spritesheet_layout: &BindGroupLayout,
+        depth_compare: CompareFunction,
+        context: &Context,
+    ) -> RenderPipeline {
+        let shader = context
+            .device()
+            .create_shader_module(scene_shader!("shaders/particle.wgsl"));
+
+        let pipeline_layout = context.create_pipeline_layout(&[camera_layout, spritesheet_layout]);
+        let blend = BlendComponent {
+            src_factor: BlendFactor::SrcAlpha,
+            dst_factor: BlendFactor::OneMinusSrcAlpha,
+            operation: BlendOperation::Add,
+        };
+
+        context
+            .create_render_pipeline::<ParticleInstance>(BasePipeline {
+                vertex: (&shader, "vs_main"),
+                fragment: (&shader, "fs_main"),
+            })
+            .label("Particle Render Pipeline")
+            .layout(&pipeline_layout)
+            .target(ColorTargetState::builder(SCENE_FORMAT).blend(blend, blend))
+            .depth(TextureFormat::Depth32Float, depth_compare)
+            .depth_write(false)
+            .build()
+    }
+
+    fn next_random(&mut self) -> f32 {
+        self.random ^= self.random << 13;
+        self.random ^= self.random >> 17;
+        self.random ^= self.random << 5;
+
+        self.random as f32 / u32::MAX as f32
+    }
+
+    /// Bursts a broken block at `position` into pieces of its texture
+    pub fn spawn(&mut self, position: IVec3, block: Block) {
+        let center = position.as_vec3() + Vec3::splat(0.5);
+
+        for _ in 0..PARTICLES_PER_BLOCK {
+            let offset = vec3(self.next_random(), self.next_random(), self.next_random()) - 0.5;
+            let velocity = offset * 4.0 + Vec3::Y * (2.0 + self.next_random() * 3.0);
+            let lifetime = MIN_LIFETIME + self.next_random() * (MAX_LIFETIME - MIN_LIFETIME);
+            let uv_offset = vec2(self.next_random(), self.next_random()) * 0.75;
+
+            if self.particles.len() == MAX_PARTICLES {
+                self.particles.remove(0);
+            }
+            self.particles.push(Particle {
+                position: center + offset * 0.8,
+                velocity,
+                age: 0.0,
+                lifetime,
+                uv_offset,
+                block,
+            });
+        }
+    }
+
+    /// Moves the particles forward by `delta_time` seconds and uploads the ones still alive
+    pub fn update(&mut self, delta_time: f32, context: &Context) {
+        for particle in &mut self.particles {
+            particle.velocity.y -= GRAVITY * delta_time;
+            particle.position += particle.velocity * delta_time;
+            particle.age += delta_time;
+        }
+        self.particles
+            .retain(|particle| particle.age < particle.lifetime);
+
+        let instances = self
+            .particles
+            .iter()
+            .map(|particle| ParticleInstance {
+                position: particle.position,
+                size: PARTICLE_SIZE,
+                uv_offset: particle.uv_offset,
+                texture_id: particle.block.texture_id(),
+                tint: (particle.block.tint() != Tint::None) as u32,
+                alpha: 1.0 - particle.age / particle.lifetime,
+            })
+            .collect::<Vec<_>>();
+
+        self.instances.write(&instances, context);
+    }
+}
+
+impl Draw for ParticlePass {
+    fn draw<'r>(&'r self, render_pass: &mut RenderPass<'r>, frame: &Frame<'_>) {
+        if self.instances.is_empty() {
+            return;
+        }
+
+        render_pass.set_pipeline(&self.render_pipeline);
+        render_pass.set_bind_group(1, frame.spritesheet.bind_group(), &[]);
+        render_pass.set_vertex_buffer(0, self.instances.slice());
+        render_pass.draw(0..6, 0..self.instances.len() as u32);
+    }
+}
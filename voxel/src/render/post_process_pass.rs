@@ -0,0 +1,255 @@
+use bytemuck::{Pod, Zeroable};
+use glam::vec2;
+use voxel_util::{BasePipeline, Context, Fragment, Sampler, ShaderResource, Texture, Uniform};
+use wgpu::{
+    include_wgsl,
+    util::{BufferInitDescriptor, DeviceExt},
+    BindGroupLayout, Buffer, BufferUsages, CommandEncoder, CompareFunction, FilterMode, RenderPass,
+    RenderPassTimestampWrites, RenderPipeline, TextureFormat, TextureUsages, TextureView,
+};
+
+use crate::asset;
+
+use super::{bloom_pass::BloomPass, vertex::QuadVertex, Draw, Frame};
+
+/// Linear format the scene is rendered in, tonemapping maps it to the surface at the end
+pub const SCENE_FORMAT: TextureFormat = TextureFormat::Rgba16Float;
+
+type Screen = (
+    (Fragment, Texture),
+    (Fragment, Sampler),
+    (Fragment, Uniform<PostProcessUniform>),
+    (Fragment, Texture),
+);
+
+pub const DEFAULT_EXPOSURE: f32 = 1.6;
+pub const MIN_EXPOSURE: f32 = 0.05;
+pub const MAX_EXPOSURE: f32 = 20.0;
+// Factor every step of the exposure multiplies or divides it by
+pub const EXPOSURE_STEP: f32 = 1.1;
+pub const DEFAULT_GAMMA: f32 = 1.0;
+pub const MIN_GAMMA: f32 = 0.2;
+pub const MAX_GAMMA: f32 = 5.0;
+
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Pod, Zeroable)]
+pub struct PostProcessUniform {
+    underwater: u32,
+    bloom_intensity: f32,
+    paused: u32,
+    exposure: f32,
+    gamma: f32,
+}
+
+/// Draws the rendered scene onto the surface, applying screen space effects and tonemapping
+/// on the way
+#[derive(Debug)]
+pub struct PostProcessPass {
+    render_pipeline: RenderPipeline,
+    color_texture: Texture,
+    sampler: Sampler,
+    uniform: Uniform<PostProcessUniform>,
+    settings: PostProcessUniform,
+    screen_resource: ShaderResource,
+    vertices: Buffer,
+    bloom_pass: BloomPass,
+}
+
+impl PostProcessPass {
+    pub fn new(context: &Context) -> Self {
+        let color_texture = Self::create_color_texture(context);
+        // Linear so the half resolution bloom gets smoothly upscaled
+        let sampler = Sampler::new(FilterMode::Linear, context);
+        let settings = PostProcessUniform {
+            exposure: DEFAULT_EXPOSURE,
+            gamma: DEFAULT_GAMMA,
+            ..Default::default()
+        };
+        let uniform = Uniform::new(settings, context);
+        let bloom_pass = BloomPass::new(&color_texture, &sampler, context);
+        let screen_resource = context.create_shader_resource::<Screen>((
+            &color_texture,
+            &sampler,
+            &uniform,
+            bloom_pass.output(),
+        ));
+
+        let render_pipeline = Self::create_pipeline(screen_resource.layout(), context);
+        let vertices = context.device().create_buffer_init(&BufferInitDescriptor {
+            label: Some("Post Process Vertex Buffer"),
+            contents: bytemuck::cast_slice(&Self::create_screen_quad()),
+            usage: BufferUsages::VERTEX,
+        });
+
+        Self {
+            render_pipeline,
+            color_texture,
+            sampler,
+            uniform,
+            settings,
+            screen_resource,
+            vertices,
+            bloom_pass,
+        }
+    }
+
+    fn create_color_texture(context: &Context) -> Texture {
+        let config = context.config();
+        Texture::new(
+            (config.width, config.height),
+            TextureUsages::RENDER_ATTACHMENT | TextureUsages::TEXTURE_BINDING,
+            SCENE_FORMAT,
+            context,
+        )
+    }
+
+    fn create_screen_quad() -> [QuadVertex; 6] {
+        [
+            (vec2(-1.0, 1.0), vec2(0.0, 0.0)),
+            (vec2(-1.0, -1.0), vec2(0.0, 1.0)),
+            (vec2(1.0, -1.0), vec2(1.0, 1.0)),
+            (vec2(1.0, -1.0), vec2(1.0, 1.0)),
+            (vec2(1.0, 1.0), vec2(1.0, 0.0)),
+            (vec2(-1.0, 1.0), vec2(0.0, 0.0)),
+        ]
+        .map(|(position, uv)| QuadVertex::new(position, uv))
+    }
+
+    fn create_pipeline(screen_layout: &BindGroupLayout, context: &Context) -> RenderPipeline {
+        let shader = context
+            .device()
+            .create_shader_module(include_wgsl!(asset!("shaders/post_process.wgsl")));
+
+        let pipeline_layout = context.create_pipeline_layout(&[screen_layout]);
+        let surface_format = context.config().format;
+
+        context
+            .create_render_pipeline::<QuadVertex>(BasePipeline {
+                vertex: (&shader, "vs_main"),
+                fragment: (&shader, "fs_main"),
+            })
+            .label("Post Process Pipeline")
+            .layout(&pipeline_layout)
+            .target(surface_format)
+            .override_const("ACES", cfg!(feature = "aces") as u32 as f64)
+            // Non-sRGB surfaces don't encode on write, so the shader has to
+            .override_const("SRGB_SURFACE", surface_format.is_srgb() as u32 as f64)
+            .depth(TextureFormat::Depth32Float, CompareFunction::Always)
+            .depth_write(false)
+            .build()
+    }
+
+    /// Target the scene is rendered into before being post processed
+    pub fn color_view(&self) -> &TextureView {
+        self.color_texture.view()
+    }
+
+    fn set_settings(&mut self, settings: PostProcessUniform, context: &Context) {
+        if self.settings != settings {
+            self.settings = settings;
+            self.uniform.stage(settings, context);
+        }
+    }
+
+    pub fn set_underwater(&mut self, underwater: bool, context: &Context) {
+        self.set_settings(
+            PostProcessUniform {
+                underwater: underwater as u32,
+                ..self.settings
+            },
+            context,
+        );
+    }
+
+    /// Dims the whole scene, the overlays drawn after it stay bright
+    pub fn set_paused(&mut self, paused: bool, context: &Context) {
+        self.set_settings(
+            PostProcessUniform {
+                paused: paused as u32,
+                ..self.settings
+            },
+            context,
+        );
+    }
+
+    /// Intensity the blurred highlights are added with, `None` skips the bloom passes
+    pub fn bloom(&self) -> Option<f32> {
+        Some(self.settings.bloom_intensity).filter(|&intensity| intensity > 0.0)
+    }
+
+    pub fn set_bloom(&mut self, intensity: Option<f32>, context: &Context) {
+        self.set_settings(
+            PostProcessUniform {
+                bloom_intensity: intensity.unwrap_or(0.0),
+                ..self.settings
+            },
+            context,
+        );
+    }
+
+    pub fn exposure(&self) -> f32 {
+        self.settings.exposure
+    }
+
+    /// Scales the scene before it's tonemapped, clamped to `MIN_EXPOSURE..=MAX_EXPOSURE`
+    pub fn set_exposure(&mut self, exposure: f32, context: &Context) {
+        self.set_settings(
+            PostProcessUniform {
+                exposure: exposure.clamp(MIN_EXPOSURE, MAX_EXPOSURE),
+                ..self.settings
+            },
+            context,
+        );
+    }
+
+    pub fn gamma(&self) -> f32 {
+        self.settings.gamma
+    }
+
+    /// Brightens the midtones of the tonemapped scene above `1.0` and darkens them below,
+    /// clamped to `MIN_GAMMA..=MAX_GAMMA`
+    pub fn set_gamma(&mut self, gamma: f32, context: &Context) {
+        self.set_settings(
+            PostProcessUniform {
+                gamma: gamma.clamp(MIN_GAMMA, MAX_GAMMA),
+                ..self.settings
+            },
+            context,
+        );
+    }
+
+    /// Has to run between rendering the scene and drawing this pass
+    pub fn render_bloom(
+        &self,
+        encoder: &mut CommandEncoder,
+        timestamp_writes: Option<RenderPassTimestampWrites<'_>>,
+    ) {
+        self.bloom_pass.render(
+            encoder,
+            &self.vertices,
+            self.bloom().is_some(),
+            timestamp_writes,
+        );
+    }
+
+    pub fn resize(&mut self, context: &Context) {
+        self.color_texture = Self::create_color_texture(context);
+        self.bloom_pass
+            .resize(&self.color_texture, &self.sampler, context);
+        self.screen_resource = context.create_shader_resource::<Screen>((
+            &self.color_texture,
+            &self.sampler,
+            &self.uniform,
+            self.bloom_pass.output(),
+        ));
+    }
+}
+
+impl Draw for PostProcessPass {
+    fn draw<'r>(&'r self, render_pass: &mut RenderPass<'r>, _frame: &Frame<'_>) {
+        render_pass.set_pipeline(&self.render_pipeline);
+        render_pass.set_bind_group(0, self.screen_resource.bind_group(), &[]);
+        render_pass.set_vertex_buffer(0, self.vertices.slice(..));
+        render_pass.draw(0..6, 0..1);
+    }
+}
@@ -1,27 +1,106 @@
-use std::{iter, sync::Arc, time::Duration};
-use voxel_util::{Context, ShaderResource, Texture};
+use std::{
+    iter,
+    sync::{atomic::Ordering, Arc},
+    time::Duration,
+};
+
+use bytemuck::{Pod, Zeroable};
+use voxel_util::{
+    bind_group::VertexFragment, AsyncTexture, AtlasLayout, Context, GpuTimer, ShaderResource,
+    Texture, Uniform,
+};
 use wgpu::{
     Color, CommandEncoderDescriptor, LoadOp, Operations, RenderPassColorAttachment,
     RenderPassDepthStencilAttachment, RenderPassDescriptor, StoreOp, TextureFormat, TextureUsages,
     TextureViewDescriptor,
 };
+use wgpu_text::glyph_brush::OwnedSection;
 use winit::dpi::PhysicalSize;
 
-use crate::application::Meshes;
+use glam::{vec2, vec4, IVec3, Vec2, Vec3, Vec4};
+
+use crate::{
+    application::Meshes,
+    camera::{Camera, CameraUniform},
+    error::MissingTexture,
+    metrics::Metrics,
+    world::{Block, WorldBounds},
+};
+
+use super::{
+    frustum_culling::Frustum, hotbar_pass, world_pass::WorldPass, BorderPass, ConsolePass,
+    DebugOverlay, DebugPass, Draw, Frame, HotbarPass, ParticlePass, PostProcessPass, ShadowPass,
+    TextPass, ViewmodelPass,
+};
+
+// Linear #87CEEB, the world shader fades into the same color
+pub const SKY_COLOR: Color = Color {
+    r: 0.242,
+    g: 0.617,
+    b: 0.831,
+    a: 1.0,
+};
+
+// Distance from the camera where the fog starts to thicken into the clear color
+const FOG_START: f32 = 260.0;
 
-use super::{frustum_culling::Frustum, world_pass::WorldPass, DebugPass};
+// Bound at group 0 of every scene pass, see `assets/shaders/common.wgsl`
+type Globals = (
+    (VertexFragment, Uniform<CameraUniform>),
+    (VertexFragment, Uniform<FrameUniform>),
+);
+
+/// Per frame data shared by the scene shaders
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy, Pod, Zeroable)]
+pub struct FrameUniform {
+    time: f32,
+    delta_time: f32,
+    screen_size: Vec2,
+    sun_direction: Vec3,
+    fog_start: f32,
+    fog_color: Vec4,
+}
+
+// Spans of GPU work measured by the timer, in submission order
+const GPU_SPANS: [&str; 4] = ["shadow", "scene", "bloom", "screen"];
+const SHADOW_SPAN: u32 = 0;
+const SCENE_SPAN: u32 = 1;
+const BLOOM_SPAN: u32 = 2;
+const SCREEN_SPAN: u32 = 3;
 
 pub struct Renderer {
     context: Arc<Context>,
-    camera_resource: ShaderResource,
+    frame_uniform: Uniform<FrameUniform>,
+    globals_resource: ShaderResource,
     depth_texture: Texture,
+    color_operations: Operations<Color>,
+    depth_operations: Operations<f32>,
+    gpu_timer: GpuTimer,
 
+    shadow_pass: ShadowPass,
     world_pass: WorldPass,
+    border_pass: Option<BorderPass>,
+    particle_pass: ParticlePass,
+    viewmodel_pass: ViewmodelPass,
+    post_process_pass: PostProcessPass,
+    hotbar_pass: HotbarPass,
     debug_pass: DebugPass,
+    console_pass: ConsolePass,
+    text_pass: TextPass,
+    metrics: Arc<Metrics>,
 }
 
 impl Renderer {
-    pub fn new(camera_resource: ShaderResource, context: Arc<Context>) -> Self {
+    pub fn new(
+        camera: &Camera,
+        atlas_layout: &AtlasLayout,
+        atlas: AsyncTexture,
+        world_bounds: Option<WorldBounds>,
+        shadow_map_size: u32,
+        metrics: Arc<Metrics>,
+        context: Arc<Context>,
+    ) -> Result<Self, MissingTexture> {
         let depth_texture = {
             let config = context.config();
             Texture::new(
@@ -32,33 +111,290 @@ impl Renderer {
             )
         };
 
-        let world_pass = WorldPass::new(&camera_resource, &context);
+        let projection = camera.projection();
+        let frame_uniform = Uniform::new(FrameUniform::default(), &context);
+        let globals_resource =
+            context.create_shader_resource::<Globals>((camera.uniform(), &frame_uniform));
+
+        let shadow_pass = ShadowPass::new(shadow_map_size, &globals_resource, &context);
+        let world_pass = WorldPass::new(
+            atlas_layout,
+            atlas,
+            &globals_resource,
+            shadow_pass.lighting_resource(),
+            projection.depth_compare(),
+            &context,
+        )?;
+        let border_pass = world_bounds.map(|bounds| {
+            BorderPass::new(
+                bounds,
+                &globals_resource,
+                projection.depth_compare(),
+                &context,
+            )
+        });
+        let particle_pass = ParticlePass::new(
+            globals_resource.layout(),
+            world_pass.spritesheet_resource().layout(),
+            projection.depth_compare(),
+            &context,
+        );
+        let viewmodel_pass = ViewmodelPass::new(
+            globals_resource.layout(),
+            world_pass.spritesheet_resource().layout(),
+            &projection,
+            &context,
+        );
+        let post_process_pass = PostProcessPass::new(&context);
+        let hotbar_pass = HotbarPass::new(world_pass.spritesheet_resource().layout(), &context);
+        let gpu_timer = GpuTimer::new(GPU_SPANS.len() as u32, &context);
         let debug_pass = DebugPass::new(&context);
+        let console_pass = ConsolePass::new(context.config().height);
+        let text_pass = TextPass::new(&context);
 
-        Self {
+        let mut renderer = Self {
             context,
-            camera_resource,
+            frame_uniform,
+            globals_resource,
             depth_texture,
+            color_operations: Operations {
+                load: LoadOp::Clear(SKY_COLOR),
+                store: StoreOp::Store,
+            },
+            depth_operations: Operations {
+                load: LoadOp::Clear(projection.far_depth()),
+                store: StoreOp::Store,
+            },
+            gpu_timer,
+            shadow_pass,
             world_pass,
+            border_pass,
+            particle_pass,
+            viewmodel_pass,
+            post_process_pass,
+            hotbar_pass,
             debug_pass,
+            console_pass,
+            text_pass,
+            metrics,
+        };
+        // Staged ahead of the first frame, which is drawn before any update
+        renderer.update_frame_uniform(Duration::ZERO);
+        Ok(renderer)
+    }
+
+    /// Passes drawn in order into the scene, before it gets post processed
+    fn scene_passes(&self) -> impl Iterator<Item = &dyn Draw> {
+        iter::once(&self.world_pass as &dyn Draw)
+            .chain(self.border_pass.as_ref().map(|pass| pass as &dyn Draw))
+            .chain(iter::once(&self.particle_pass as &dyn Draw))
+            .chain(iter::once(&self.viewmodel_pass as &dyn Draw))
+            .chain(iter::once(&self.shadow_pass as &dyn Draw))
+    }
+
+    /// Passes drawn in order onto the surface
+    fn screen_passes(&self) -> impl Iterator<Item = &dyn Draw> {
+        iter::once(&self.post_process_pass as &dyn Draw)
+            .chain(iter::once(&self.hotbar_pass as &dyn Draw))
+            .chain(iter::once(&self.text_pass as &dyn Draw))
+    }
+
+    /// Color behind the world, distant blocks fade into it
+    pub fn set_clear_color(&mut self, color: Color) {
+        self.color_operations.load = LoadOp::Clear(color);
+    }
+
+    fn clear_color(&self) -> Color {
+        match self.color_operations.load {
+            LoadOp::Clear(color) => color,
+            LoadOp::Load => SKY_COLOR,
         }
     }
 
+    pub fn set_seed(&mut self, seed: u32) {
+        self.debug_pass.set_seed(seed);
+    }
+
+    pub fn debug_overlay(&self) -> DebugOverlay {
+        self.debug_pass.overlay()
+    }
+
+    pub fn set_debug_overlay(&mut self, overlay: DebugOverlay) {
+        self.debug_pass.set_overlay(overlay);
+    }
+
+    pub fn shadows(&self) -> bool {
+        self.shadow_pass.is_enabled()
+    }
+
+    pub fn set_shadows(&mut self, enabled: bool) {
+        self.shadow_pass.set_enabled(enabled);
+    }
+
+    pub fn shadow_debug(&self) -> bool {
+        self.shadow_pass.is_debug()
+    }
+
+    pub fn set_shadow_debug(&mut self, debug: bool) {
+        self.shadow_pass.set_debug(debug);
+    }
+
+    pub fn set_underwater(&mut self, underwater: bool) {
+        self.post_process_pass
+            .set_underwater(underwater, &self.context);
+    }
+
+    pub fn set_paused(&mut self, paused: bool) {
+        self.post_process_pass.set_paused(paused, &self.context);
+    }
+
+    pub fn bloom(&self) -> Option<f32> {
+        self.post_process_pass.bloom()
+    }
+
+    pub fn set_bloom(&mut self, intensity: Option<f32>) {
+        self.post_process_pass.set_bloom(intensity, &self.context);
+    }
+
+    pub fn exposure(&self) -> f32 {
+        self.post_process_pass.exposure()
+    }
+
+    pub fn set_exposure(&mut self, exposure: f32) {
+        self.post_process_pass.set_exposure(exposure, &self.context);
+    }
+
+    pub fn gamma(&self) -> f32 {
+        self.post_process_pass.gamma()
+    }
+
+    pub fn set_gamma(&mut self, gamma: f32) {
+        self.post_process_pass.set_gamma(gamma, &self.context);
+    }
+
+    /// Input line and log of the console, `None` while it's closed
+    pub fn set_console<'l>(&mut self, input: Option<&str>, log: impl Iterator<Item = &'l str>) {
+        self.console_pass.set_text(input, log);
+    }
+
+    /// Draws `section` over everything else in the next frame, it has to be queued again for
+    /// the frames after
+    pub fn queue_text(&mut self, section: OwnedSection) {
+        self.text_pass.queue(section);
+    }
+
+    /// Blocks in the hotbar slots and which one is selected
+    pub fn set_hotbar(&mut self, blocks: [Block; hotbar_pass::SLOTS], selected: usize) {
+        self.hotbar_pass.set_slots(blocks, selected, &self.context);
+        self.viewmodel_pass
+            .set_block(blocks[selected], &self.context);
+    }
+
+    /// Swings the block held in the corner of the screen
+    pub fn swing(&mut self) {
+        self.viewmodel_pass.swing();
+    }
+
+    pub fn set_break_progress(&mut self, progress: Option<(IVec3, f32)>) {
+        self.world_pass.set_break_progress(progress, &self.context);
+    }
+
+    pub fn spawn_break_particles(&mut self, position: IVec3, block: Block) {
+        self.particle_pass.spawn(position, block);
+    }
+
+    /// Briefly shows the fly speed in the debug overlay, in blocks per second
+    pub fn show_speed(&mut self, speed: f32) {
+        self.debug_pass.show_speed(speed);
+    }
+
+    /// Briefly shows the exposure and gamma in the debug overlay
+    pub fn show_color_grading(&mut self) {
+        self.debug_pass
+            .show_color_grading(self.exposure(), self.gamma());
+    }
+
+    pub fn set_frame_cap(&mut self, frame_cap: Option<u32>) {
+        self.debug_pass.set_frame_cap(frame_cap);
+    }
+
     pub fn update(&mut self, delta_time: Duration) {
-        self.debug_pass.update(delta_time, &self.context);
+        // The placeholder stays, a gray world is still playable
+        if let Err(err) = self.world_pass.poll_atlas(&self.context) {
+            log::error!("{err}");
+        }
+        let culling_stats = self.world_pass.culling_stats();
+        self.metrics.drawn_chunks.set(culling_stats.passed as u64);
+        self.debug_pass.set_culling_stats(culling_stats);
+        self.debug_pass.set_memory(
+            self.metrics.chunk_bytes.get(),
+            self.metrics.mesh_bytes.get(),
+        );
+        self.gpu_timer.collect(&self.context);
+        let timings = self.gpu_timer.timings();
+        self.debug_pass.set_gpu_timings(
+            self.gpu_timer
+                .is_supported()
+                .then(|| GPU_SPANS.into_iter().zip(timings.iter().copied())),
+        );
+        self.particle_pass
+            .update(delta_time.as_secs_f32(), &self.context);
+        self.update_frame_uniform(delta_time);
+        self.debug_pass.update(delta_time, &mut self.text_pass);
+        self.console_pass.update(&mut self.text_pass);
+        self.text_pass.update(&self.context);
+    }
+
+    fn update_frame_uniform(&mut self, delta_time: Duration) {
+        let config = self.context.config();
+        let fog_color = self.clear_color();
+        let uniform = FrameUniform {
+            time: self.frame_uniform.data().time + delta_time.as_secs_f32(),
+            delta_time: delta_time.as_secs_f32(),
+            screen_size: vec2(config.width as f32, config.height as f32),
+            sun_direction: self.shadow_pass.sun_direction(),
+            fog_start: FOG_START,
+            fog_color: vec4(
+                fog_color.r as f32,
+                fog_color.g as f32,
+                fog_color.b as f32,
+                fog_color.a as f32,
+            ),
+        };
+        self.frame_uniform.stage(uniform, &self.context);
     }
 
-    pub fn resize(&mut self, new_size: PhysicalSize<u32>) {
+    /// Recreates the screen sized targets for the configuration the context just applied
+    fn resize(&mut self, new_size: PhysicalSize<u32>) {
         self.depth_texture = Texture::new(
             (new_size.width, new_size.height),
             TextureUsages::RENDER_ATTACHMENT,
             TextureFormat::Depth32Float,
             &self.context,
         );
-        self.debug_pass.resize(new_size, &self.context);
+        self.post_process_pass.resize(&self.context);
+        self.hotbar_pass.resize(new_size, &self.context);
+        self.console_pass.resize(new_size);
+        self.text_pass.resize(new_size, &self.context);
     }
 
-    pub fn draw(&mut self, frustum: &Frustum, meshes: &Meshes) {
+    pub fn draw(&mut self, camera: &Camera, meshes: &Meshes) {
+        // Resizes are only applied here, at most once a frame and before the surface texture
+        // of the frame is taken
+        if self.context.apply_pending_config() {
+            let config = self.context.config();
+            self.resize(PhysicalSize::new(config.width, config.height));
+        }
+
+        let frustum = Frustum::from_projection(camera.calculate_matrix());
+        self.debug_pass
+            .set_camera_position(camera.rendered_transformation().position());
+        self.viewmodel_pass.update(
+            camera.rendered_transformation(),
+            self.frame_uniform.data().delta_time,
+            &self.context,
+        );
+        self.shadow_pass.update(camera, &self.context);
         let output = self
             .context
             .surface()
@@ -76,35 +412,50 @@ impl Renderer {
                 label: Some("Render Command Encoder"),
             });
 
+        self.shadow_pass.render(
+            &mut encoder,
+            meshes,
+            self.gpu_timer.timestamp_writes(SHADOW_SPAN, true, true),
+        );
+
+        let frame = Frame {
+            frustum: &frustum,
+            meshes,
+            lighting: self.shadow_pass.lighting_resource(),
+            spritesheet: self.world_pass.spritesheet_resource(),
+        };
+
         {
             let mut render_pass = encoder.begin_render_pass(&RenderPassDescriptor {
-                label: Some("Render Pass"),
+                label: Some("Scene Render Pass"),
                 color_attachments: &[Some(RenderPassColorAttachment {
-                    view: &view,
+                    view: self.post_process_pass.color_view(),
                     resolve_target: None,
-                    ops: Operations {
-                        load: LoadOp::Clear(Color::WHITE),
-                        store: StoreOp::Store,
-                    },
+                    ops: self.color_operations,
                 })],
                 depth_stencil_attachment: Some(RenderPassDepthStencilAttachment {
                     view: self.depth_texture.view(),
-                    depth_ops: Some(Operations {
-                        load: LoadOp::Clear(1.0),
-                        store: StoreOp::Store,
-                    }),
+                    depth_ops: Some(self.depth_operations),
                     stencil_ops: None,
                 }),
+                timestamp_writes: self.gpu_timer.timestamp_writes(SCENE_SPAN, true, true),
                 ..Default::default()
             });
 
-            render_pass.set_bind_group(0, self.camera_resource.bind_group(), &[]);
-            self.world_pass.draw(&mut render_pass, frustum, meshes);
+            render_pass.set_bind_group(0, self.globals_resource.bind_group(), &[]);
+            for pass in self.scene_passes() {
+                pass.draw(&mut render_pass, &frame);
+            }
         }
 
+        self.post_process_pass.render_bloom(
+            &mut encoder,
+            self.gpu_timer.timestamp_writes(BLOOM_SPAN, true, true),
+        );
+
         {
-            let mut text_render_pass = encoder.begin_render_pass(&RenderPassDescriptor {
-                label: Some("Text Render Pass"),
+            let mut render_pass = encoder.begin_render_pass(&RenderPassDescriptor {
+                label: Some("Screen Render Pass"),
                 color_attachments: &[Some(RenderPassColorAttachment {
                     view: &view,
                     resolve_target: None,
@@ -113,12 +464,33 @@ impl Renderer {
                         store: StoreOp::Store,
                     },
                 })],
+                // Overlays are drawn on top of everything but still need a depth attachment
+                depth_stencil_attachment: Some(RenderPassDepthStencilAttachment {
+                    view: self.depth_texture.view(),
+                    depth_ops: Some(Operations {
+                        load: LoadOp::Load,
+                        store: StoreOp::Discard,
+                    }),
+                    stencil_ops: None,
+                }),
+                timestamp_writes: self.gpu_timer.timestamp_writes(SCREEN_SPAN, true, true),
                 ..Default::default()
             });
-            self.debug_pass.draw(&mut text_render_pass);
+
+            for pass in self.screen_passes() {
+                pass.draw(&mut render_pass, &frame);
+            }
         }
 
-        self.context.queue().submit(iter::once(encoder.finish()));
+        self.gpu_timer.resolve(&mut encoder);
+
+        let staged = self.context.staging_belt().finish();
+        self.context
+            .queue()
+            .submit(staged.into_iter().chain(iter::once(encoder.finish())));
+        self.context.staging_belt().recall();
+        self.gpu_timer.submitted();
         output.present();
+        self.metrics.frames.fetch_add(1, Ordering::Relaxed);
     }
 }
@@ -0,0 +1,357 @@
+use bytemuck::{Pod, Zeroable};
+use glam::{vec2, Mat4, Vec3, Vec4};
+use voxel_util::{
+    BasePipeline, ComparisonSampler, Context, DepthTextureArray, Fragment, GpuTimer,
+    ShaderResource, Uniform,
+};
+use wgpu::{
+    include_wgsl,
+    util::{BufferInitDescriptor, DeviceExt},
+    BindGroupLayout, Buffer, BufferUsages, CommandEncoder, CompareFunction, LoadOp, Operations,
+    RenderPass, RenderPassDepthStencilAttachment, RenderPassDescriptor, RenderPassTimestampWrites,
+    RenderPipeline, StoreOp, TextureFormat,
+};
+
+use crate::{application::Meshes, asset, camera::Camera, world::chunk::CHUNK_SIZE};
+
+use super::{
+    frustum_culling::Frustum,
+    post_process_pass::SCENE_FORMAT,
+    vertex::{QuadVertex, Vertex},
+    world_pass::Transformation,
+    Draw, Frame,
+};
+
+pub const CASCADES: usize = 2;
+// View distances where the cascades start and end
+const CASCADE_SPLITS: [f32; CASCADES + 1] = [0.1, 32.0, 160.0];
+// How far towards the sun casters are still rendered into a cascade
+const CASTER_DISTANCE: f32 = 256.0;
+
+type Lighting = (
+    (Fragment, Uniform<LightingUniform>),
+    (Fragment, DepthTextureArray),
+    (Fragment, ComparisonSampler),
+);
+type Cascade = (voxel_util::Vertex, Uniform<Mat4>);
+type ShadowMapView = ((Fragment, DepthTextureArray),);
+
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy, Pod, Zeroable)]
+pub struct LightingUniform {
+    light_matrices: [Mat4; CASCADES],
+    sun_direction: Vec3,
+    shadows: u32,
+    cascade_splits: Vec4,
+}
+
+/// Renders the visible chunks from the sun into one depth layer per cascade
+#[derive(Debug)]
+pub struct ShadowPass {
+    render_pipeline: RenderPipeline,
+    shadow_map: DepthTextureArray,
+    lighting: Uniform<LightingUniform>,
+    lighting_resource: ShaderResource,
+    cascades: [(Uniform<Mat4>, ShaderResource); CASCADES],
+    cascade_frustums: [Frustum; CASCADES],
+    // Side of every cascade in texels
+    map_size: u32,
+    sun_direction: Vec3,
+    enabled: bool,
+
+    debug_pipeline: RenderPipeline,
+    debug_resource: ShaderResource,
+    debug_vertices: Buffer,
+    debug: bool,
+}
+
+impl ShadowPass {
+    pub fn new(map_size: u32, camera_resource: &ShaderResource, context: &Context) -> Self {
+        let sun_direction = Vec3::new(-0.4, -1.0, -0.25).normalize();
+        let map_size = map_size.min(context.device().limits().max_texture_dimension_2d);
+
+        let shadow_map = DepthTextureArray::new(
+            (map_size, map_size),
+            CASCADES as u32,
+            TextureFormat::Depth32Float,
+            context,
+        );
+        let sampler = ComparisonSampler::new(CompareFunction::LessEqual, context);
+
+        let lighting = Uniform::new(LightingUniform::default(), context);
+        let lighting_resource =
+            context.create_shader_resource::<Lighting>((&lighting, &shadow_map, &sampler));
+
+        let cascades = [(); CASCADES].map(|_| {
+            let uniform = Uniform::new(Mat4::IDENTITY, context);
+            let resource = context.create_shader_resource::<Cascade>(&uniform);
+            (uniform, resource)
+        });
+        let render_pipeline = Self::create_pipeline(cascades[0].1.layout(), context);
+
+        let debug_resource = context.create_shader_resource::<ShadowMapView>((&shadow_map,));
+        let debug_pipeline =
+            Self::create_debug_pipeline(camera_resource.layout(), debug_resource.layout(), context);
+        let debug_vertices = context.device().create_buffer_init(&BufferInitDescriptor {
+            label: Some("Shadow Map Debug Vertex Buffer"),
+            contents: bytemuck::cast_slice(&Self::create_debug_quad()),
+            usage: BufferUsages::VERTEX,
+        });
+
+        Self {
+            render_pipeline,
+            shadow_map,
+            lighting,
+            lighting_resource,
+            cascades,
+            cascade_frustums: [Frustum::from_projection(Mat4::IDENTITY); CASCADES],
+            map_size,
+            sun_direction,
+            enabled: true,
+            debug_pipeline,
+            debug_resource,
+            debug_vertices,
+            debug: false,
+        }
+    }
+
+    // Bottom right corner of the screen
+    fn create_debug_quad() -> [QuadVertex; 6] {
+        let (min, max) = (vec2(0.45, -0.95), vec2(0.95, -0.45));
+
+        [
+            (vec2(min.x, max.y), vec2(0.0, 0.0)),
+            (vec2(min.x, min.y), vec2(0.0, 1.0)),
+            (vec2(max.x, min.y), vec2(1.0, 1.0)),
+            (vec2(max.x, min.y), vec2(1.0, 1.0)),
+            (vec2(max.x, max.y), vec2(1.0, 0.0)),
+            (vec2(min.x, max.y), vec2(0.0, 0.0)),
+        ]
+        .map(|(position, uv)| QuadVertex::new(position, uv))
+    }
+
+    fn create_pipeline(cascade_layout: &BindGroupLayout, context: &Context) -> RenderPipeline {
+        let shader = context
+            .device()
+            .create_shader_module(include_wgsl!(asset!("shaders/shadow.wgsl")));
+
+        let transformation_layout = context.create_bind_group_layout::<Transformation>().erase();
+        let pipeline_layout =
+            context.create_pipeline_layout(&[cascade_layout, &transformation_layout]);
+
+        context
+            .create_render_pipeline::<Vertex>(BasePipeline {
+                vertex: (&shader, "vs_main"),
+                fragment: (&shader, "fs_main"),
+            })
+            .label("Shadow Render Pipeline")
+            .layout(&pipeline_layout)
+            .override_const("CHUNK_SIZE", CHUNK_SIZE as f64)
+            .depth(TextureFormat::Depth32Float, CompareFunction::LessEqual)
+            .depth_bias(2, 2.0)
+            .build()
+    }
+
+    fn create_debug_pipeline(
+        camera_layout: &BindGroupLayout,
+        shadow_map_layout: &BindGroupLayout,
+        context: &Context,
+    ) -> RenderPipeline {
+        let shader = context
+            .device()
+            .create_shader_module(include_wgsl!(asset!("shaders/shadow_debug.wgsl")));
+
+        let pipeline_layout = context.create_pipeline_layout(&[camera_layout, shadow_map_layout]);
+
+        context
+            .create_render_pipeline::<QuadVertex>(BasePipeline {
+                vertex: (&shader, "vs_main"),
+                fragment: (&shader, "fs_main"),
+            })
+            .label("Shadow Map Debug Pipeline")
+            .layout(&pipeline_layout)
+            .target(SCENE_FORMAT)
+            .depth(TextureFormat::Depth32Float, CompareFunction::Always)
+            .depth_write(false)
+            .build()
+    }
+
+    /// Uniform, shadow map and sampler the world shader reads the lighting from
+    pub fn lighting_resource(&self) -> &ShaderResource {
+        &self.lighting_resource
+    }
+
+    pub fn sun_direction(&self) -> Vec3 {
+        self.sun_direction
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+    }
+
+    pub fn is_debug(&self) -> bool {
+        self.debug
+    }
+
+    pub fn set_debug(&mut self, debug: bool) {
+        self.debug = debug;
+    }
+
+    /// Fits every cascade around its slice of the camera frustum
+    pub fn update(&mut self, camera: &Camera, context: &Context) {
+        let mut light_matrices = [Mat4::IDENTITY; CASCADES];
+        for (index, (uniform, _)) in self.cascades.iter_mut().enumerate() {
+            let matrix = Self::cascade_matrix(
+                camera,
+                CASCADE_SPLITS[index],
+                CASCADE_SPLITS[index + 1],
+                self.sun_direction,
+                self.map_size,
+            );
+
+            uniform.stage(matrix, context);
+            self.cascade_frustums[index] = Frustum::from_projection(matrix);
+            light_matrices[index] = matrix;
+        }
+
+        self.lighting.stage(
+            LightingUniform {
+                light_matrices,
+                sun_direction: self.sun_direction,
+                shadows: self.enabled as u32,
+                cascade_splits: Vec4::new(
+                    CASCADE_SPLITS[0],
+                    CASCADE_SPLITS[1],
+                    CASCADE_SPLITS[2],
+                    0.0,
+                ),
+            },
+            context,
+        );
+    }
+
+    fn cascade_matrix(
+        camera: &Camera,
+        near: f32,
+        far: f32,
+        sun_direction: Vec3,
+        map_size: u32,
+    ) -> Mat4 {
+        let transformation = camera.rendered_transformation();
+        let projection = camera.projection();
+
+        // Bounding sphere of the slice, so the cascade doesn't change size when turning around
+        let tan_y = (projection.fovy() / 2.0).tan();
+        let tan_x = tan_y * projection.aspect();
+        let center_distance = (near + far) / 2.0;
+        let corner_distance = |distance: f32| {
+            Vec3::new(
+                distance * tan_x,
+                distance * tan_y,
+                distance - center_distance,
+            )
+            .length()
+        };
+        let radius = corner_distance(near).max(corner_distance(far));
+        let center = transformation.position() + transformation.direction() * center_distance;
+
+        // Moving in whole texels keeps the shadow edges from shimmering
+        let texel_size = radius * 2.0 / map_size as f32;
+        let light_rotation = Mat4::look_to_rh(Vec3::ZERO, sun_direction, Vec3::Y);
+        let light_center = light_rotation.transform_point3(center);
+        let light_center = (light_center / texel_size).floor() * texel_size;
+        let center = light_rotation.inverse().transform_point3(light_center);
+
+        let view = Mat4::look_to_rh(
+            center - sun_direction * CASTER_DISTANCE,
+            sun_direction,
+            Vec3::Y,
+        );
+        let projection = Mat4::orthographic_rh(
+            -radius,
+            radius,
+            -radius,
+            radius,
+            0.0,
+            CASTER_DISTANCE + radius,
+        );
+
+        projection * view
+    }
+
+    /// `timestamp_writes` begin with the first cascade and end with the last one
+    pub fn render(
+        &self,
+        encoder: &mut CommandEncoder,
+        meshes: &Meshes,
+        timestamp_writes: Option<RenderPassTimestampWrites<'_>>,
+    ) {
+        if !self.enabled {
+            // Keeps the timestamps written while shadows are off
+            if timestamp_writes.is_some() {
+                encoder.begin_render_pass(&RenderPassDescriptor {
+                    label: Some("Shadow Render Pass"),
+                    depth_stencil_attachment: Some(RenderPassDepthStencilAttachment {
+                        view: self.shadow_map.layer_view(0),
+                        depth_ops: Some(Operations {
+                            load: LoadOp::Load,
+                            store: StoreOp::Store,
+                        }),
+                        stencil_ops: None,
+                    }),
+                    timestamp_writes,
+                    ..Default::default()
+                });
+            }
+            return;
+        }
+
+        let meshes = meshes.read();
+        for (index, (_, cascade_resource)) in self.cascades.iter().enumerate() {
+            let timestamp_writes =
+                GpuTimer::split_writes(timestamp_writes.as_ref(), index, CASCADES);
+
+            let mut render_pass = encoder.begin_render_pass(&RenderPassDescriptor {
+                label: Some("Shadow Render Pass"),
+                color_attachments: &[],
+                depth_stencil_attachment: Some(RenderPassDepthStencilAttachment {
+                    view: self.shadow_map.layer_view(index as u32),
+                    depth_ops: Some(Operations {
+                        load: LoadOp::Clear(1.0),
+                        store: StoreOp::Store,
+                    }),
+                    stencil_ops: None,
+                }),
+                timestamp_writes,
+                ..Default::default()
+            });
+
+            render_pass.set_pipeline(&self.render_pipeline);
+            render_pass.set_bind_group(0, cascade_resource.bind_group(), &[]);
+
+            let frustum = &self.cascade_frustums[index];
+            for chunk_buffer in meshes.values() {
+                if chunk_buffer.is_on_frustum(frustum) {
+                    chunk_buffer.draw(&mut render_pass, 1);
+                }
+            }
+        }
+    }
+}
+
+impl Draw for ShadowPass {
+    fn draw<'r>(&'r self, render_pass: &mut RenderPass<'r>, _frame: &Frame<'_>) {
+        if !self.debug {
+            return;
+        }
+
+        render_pass.set_pipeline(&self.debug_pipeline);
+        render_pass.set_bind_group(1, self.debug_resource.bind_group(), &[]);
+        render_pass.set_vertex_buffer(0, self.debug_vertices.slice(..));
+        render_pass.draw(0..6, 0..1);
+    }
+}
@@ -0,0 +1,198 @@
+use std::mem::size_of;
+
+use bytemuck::{Pod, Zeroable};
+use glam::{vec2, vec4, Vec2, Vec4};
+use voxel_util::{BasePipeline, ColorTargetStateExt, Context, GrowableBuffer, VertexLayout};
+use wgpu::{
+    include_wgsl, vertex_attr_array, BlendComponent, BlendFactor, BlendOperation, BufferAddress,
+    BufferUsages, ColorTargetState, CompareFunction, DepthStencilState, RenderPass, RenderPipeline,
+    TextureFormat, VertexAttribute, VertexBufferLayout, VertexStepMode,
+};
+use wgpu_text::{
+    glyph_brush::{
+        ab_glyph::{FontRef, PxScale},
+        OwnedSection, OwnedText,
+    },
+    BrushBuilder, TextBrush,
+};
+use winit::dpi::PhysicalSize;
+
+use crate::asset;
+
+use super::{Draw, Frame};
+
+pub const TEXT_SCALE: f32 = 24.0;
+pub const TEXT_COLOR: [f32; 4] = [1.0, 1.0, 1.0, 1.0];
+// Keeps light text readable over bright terrain
+pub const BACKDROP_COLOR: Vec4 = vec4(0.0, 0.0, 0.0, 0.45);
+// Pixels the backdrop extends past the text on every side
+const BACKDROP_PADDING: f32 = 2.0;
+
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy, Pod, Zeroable)]
+pub struct BackdropInstance {
+    // Corners in clip space
+    min: Vec2,
+    max: Vec2,
+    color: Vec4,
+}
+
+impl BackdropInstance {
+    const ATTRIBUTES: [VertexAttribute; 3] = vertex_attr_array![
+        0 => Float32x2,
+        1 => Float32x2,
+        2 => Float32x4
+    ];
+}
+
+impl VertexLayout for BackdropInstance {
+    fn vertex_layout() -> VertexBufferLayout<'static> {
+        VertexBufferLayout {
+            array_stride: size_of::<BackdropInstance>() as BufferAddress,
+            step_mode: VertexStepMode::Instance,
+            attributes: &BackdropInstance::ATTRIBUTES,
+        }
+    }
+}
+
+/// Section with a single line of text at `position` on the screen, in pixels
+pub fn text_section(
+    position: (f32, f32),
+    text: impl Into<String>,
+    color: [f32; 4],
+) -> OwnedSection {
+    OwnedSection::default()
+        .with_screen_position(position)
+        .add_text(
+            OwnedText::new(text.into())
+                .with_scale(PxScale::from(TEXT_SCALE))
+                .with_color(color),
+        )
+}
+
+/// Every piece of text on the screen, sections queued during a frame are drawn over
+/// everything else in the next one
+pub struct TextPass {
+    brush: TextBrush<FontRef<'static>>,
+    queued: Vec<OwnedSection>,
+    backdrop_pipeline: RenderPipeline,
+    backdrops: GrowableBuffer<BackdropInstance>,
+    queued_backdrops: Vec<BackdropInstance>,
+    screen_size: Vec2,
+}
+
+impl TextPass {
+    pub fn new(context: &Context) -> Self {
+        let config = context.config();
+
+        let brush = BrushBuilder::using_font_bytes(include_bytes!(asset!("monogram.ttf")))
+            .expect("invalid font")
+            // Text is drawn inside the screen pass, so it has to match its depth attachment
+            .with_depth_stencil(Some(DepthStencilState {
+                format: TextureFormat::Depth32Float,
+                depth_write_enabled: false,
+                depth_compare: CompareFunction::Always,
+                stencil: Default::default(),
+                bias: Default::default(),
+            }))
+            .build(context.device(), config.width, config.height, config.format);
+
+        Self {
+            brush,
+            queued: Vec::new(),
+            backdrop_pipeline: Self::create_backdrop_pipeline(context),
+            backdrops: GrowableBuffer::new(
+                "Text Backdrop Instance Buffer",
+                BufferUsages::VERTEX,
+                16,
+                context,
+            ),
+            queued_backdrops: Vec::new(),
+            screen_size: vec2(config.width as f32, config.height as f32),
+        }
+    }
+
+    fn create_backdrop_pipeline(context: &Context) -> RenderPipeline {
+        let shader = context
+            .device()
+            .create_shader_module(include_wgsl!(asset!("shaders/backdrop.wgsl")));
+
+        let pipeline_layout = context.create_pipeline_layout(&[]);
+        let blend = BlendComponent {
+            src_factor: BlendFactor::SrcAlpha,
+            dst_factor: BlendFactor::OneMinusSrcAlpha,
+            operation: BlendOperation::Add,
+        };
+
+        context
+            .create_render_pipeline::<BackdropInstance>(BasePipeline {
+                vertex: (&shader, "vs_main"),
+                fragment: (&shader, "fs_main"),
+            })
+            .label("Text Backdrop Render Pipeline")
+            .layout(&pipeline_layout)
+            .target(ColorTargetState::builder(context.config().format).blend(blend, blend))
+            .depth(TextureFormat::Depth32Float, CompareFunction::Always)
+            .depth_write(false)
+            .build()
+    }
+
+    pub fn queue(&mut self, section: OwnedSection) {
+        self.queued.push(section);
+    }
+
+    /// Like [`TextPass::queue`], but with a dark rectangle behind the text
+    pub fn queue_with_backdrop(&mut self, section: OwnedSection) {
+        // Empty sections have no bounds
+        if let Some(bounds) = self.brush.glyph_bounds(&section) {
+            self.queue_quad(
+                vec2(bounds.min.x, bounds.min.y) - BACKDROP_PADDING,
+                vec2(bounds.max.x, bounds.max.y) + BACKDROP_PADDING,
+                BACKDROP_COLOR,
+            );
+        }
+        self.queue(section);
+    }
+
+    /// Queues a rectangle between the corners in pixels, drawn below the text
+    pub fn queue_quad(&mut self, min: Vec2, max: Vec2, color: Vec4) {
+        let to_clip =
+            |x: f32, y: f32| vec2(x, self.screen_size.y - y) / self.screen_size * 2.0 - 1.0;
+        self.queued_backdrops.push(BackdropInstance {
+            min: to_clip(min.x, max.y),
+            max: to_clip(max.x, min.y),
+            color,
+        });
+    }
+
+    /// Lays out the sections queued since the last call, they replace the ones drawn before
+    pub fn update(&mut self, context: &Context) {
+        self.brush
+            .queue(context.device(), context.queue(), &self.queued)
+            .expect("cache texture limit exceeded");
+        self.queued.clear();
+
+        self.backdrops.write(&self.queued_backdrops, context);
+        self.queued_backdrops.clear();
+    }
+
+    pub fn resize(&mut self, new_size: PhysicalSize<u32>, context: &Context) {
+        self.screen_size = vec2(new_size.width as f32, new_size.height as f32);
+        self.brush.resize_view(
+            new_size.width as f32,
+            new_size.height as f32,
+            context.queue(),
+        );
+    }
+}
+
+impl Draw for TextPass {
+    fn draw<'r>(&'r self, render_pass: &mut RenderPass<'r>, _frame: &Frame<'_>) {
+        if !self.backdrops.is_empty() {
+            render_pass.set_pipeline(&self.backdrop_pipeline);
+            render_pass.set_vertex_buffer(0, self.backdrops.slice());
+            render_pass.draw(0..6, 0..self.backdrops.len() as u32);
+        }
+        self.brush.draw(render_pass);
+    }
+}
@@ -1,14 +1,60 @@
-use glam::UVec3;
+use glam::{UVec3, Vec2, Vec3};
 use std::mem::size_of;
 use voxel_util::VertexLayout;
 use wgpu::{vertex_attr_array, BufferAddress, VertexAttribute, VertexBufferLayout, VertexStepMode};
 
+use crate::world::chunk::CHUNK_SIZE;
+
+const POSITION_BITS: u32 = 7;
+const AO_BITS: u32 = 2;
+const DIRECTION_BITS: u32 = 3;
+const TINT_BITS: u32 = 6;
+const LIGHT_BITS: u32 = 4;
+
+const X_SHIFT: u32 = u32::BITS - POSITION_BITS;
+const Y_SHIFT: u32 = X_SHIFT - POSITION_BITS;
+const Z_SHIFT: u32 = Y_SHIFT - POSITION_BITS;
+const AO_SHIFT: u32 = Z_SHIFT - AO_BITS;
+const DIRECTION_SHIFT: u32 = AO_SHIFT - DIRECTION_BITS;
+const TINT_SHIFT: u32 = DIRECTION_SHIFT - TINT_BITS;
+const _: () = assert!(
+    TINT_SHIFT == 0,
+    "the packed vertex fields have to fill the word exactly"
+);
+const TEXTURE_SHIFT: u32 = LIGHT_BITS;
+
+const fn mask(bits: u32) -> u32 {
+    (1 << bits) - 1
+}
+
+// The mesher works in the neighborhood space, one block off the chunk, so the far corner
+// of the last block sits at `CHUNK_SIZE + 1` blocks
+const _: () = assert!(
+    (CHUNK_SIZE as u32 + 1) * 2 <= mask(POSITION_BITS),
+    "chunk corners don't fit into the packed vertex position"
+);
+
+/// Vertex of a chunk mesh, packed into two words that `world.wgsl` unpacks again
+///
+/// `packed`, from the highest bit down:
+/// - 31..25, 24..18, 17..11: x, y and z in half blocks. The mesher places a chunk at
+///   `2..=(CHUNK_SIZE + 1) * 2` of them, shifted by a block into its neighborhood, which
+///   leaves room for chunks of up to 62
+/// - 10..9: ambient occlusion level
+/// - 8..6: face direction, 6 for the quads of cross shaped blocks
+/// - 5..0: tint, 0 for none and 1..=63 for the foliage color by temperature
+///
+/// `texture_light` holds the texture id in bits 31..4, and the block light in 3..0
 #[repr(C)]
 #[derive(Debug, Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
-pub struct Vertex(u32);
+pub struct Vertex {
+    packed: u32,
+    // Texture id above the block light in front of the face, `packed` has no bits left
+    texture_light: u32,
+}
 
 impl Vertex {
-    const ATTRIBUTES: [VertexAttribute; 1] = vertex_attr_array![0 => Uint32];
+    const ATTRIBUTES: [VertexAttribute; 2] = vertex_attr_array![0 => Uint32, 1 => Uint32];
 
     pub const fn layout() -> VertexBufferLayout<'static> {
         VertexBufferLayout {
@@ -18,18 +64,81 @@ impl Vertex {
         }
     }
 
-    pub fn new(position: UVec3, ao: u8, texture_id: u32, direction: u32) -> Self {
-        let value = (position.x << 27)
-            | (position.y << 22)
-            | (position.z << 17)
-            | ((ao as u32) << 15)
-            | (texture_id << 9)
-            | (direction << 6);
+    /// Positions are in half blocks, so cross shaped blocks can reach the middle of a block
+    pub fn new(
+        position: UVec3,
+        ao: u8,
+        texture_id: u32,
+        direction: u32,
+        tint: u32,
+        light: u8,
+    ) -> Self {
+        debug_assert!(
+            position.max_element() <= mask(POSITION_BITS),
+            "vertex position {position} out of range"
+        );
+        debug_assert!(
+            ao as u32 <= mask(AO_BITS),
+            "ambient occlusion {ao} out of range"
+        );
+        debug_assert!(
+            direction <= mask(DIRECTION_BITS),
+            "direction {direction} out of range"
+        );
+        debug_assert!(tint <= mask(TINT_BITS), "tint {tint} out of range");
+        debug_assert!(
+            light as u32 <= mask(LIGHT_BITS),
+            "light {light} out of range"
+        );
+        debug_assert!(
+            texture_id <= u32::MAX >> TEXTURE_SHIFT,
+            "texture id {texture_id} out of range"
+        );
+
+        let value = (position.x << X_SHIFT)
+            | (position.y << Y_SHIFT)
+            | (position.z << Z_SHIFT)
+            | ((ao as u32) << AO_SHIFT)
+            | (direction << DIRECTION_SHIFT)
+            | (tint << TINT_SHIFT);
 
-        Self(value)
+        Self {
+            packed: value,
+            texture_light: (texture_id << TEXTURE_SHIFT) | light as u32,
+        }
+    }
+
+    pub fn unpack(self) -> UnpackedVertex {
+        let value = self.packed;
+
+        UnpackedVertex {
+            position: UVec3::new(
+                (value >> X_SHIFT) & mask(POSITION_BITS),
+                (value >> Y_SHIFT) & mask(POSITION_BITS),
+                (value >> Z_SHIFT) & mask(POSITION_BITS),
+            )
+            .as_vec3()
+                / 2.0,
+            ao: ((value >> AO_SHIFT) & mask(AO_BITS)) as u8,
+            texture_id: self.texture_light >> TEXTURE_SHIFT,
+            direction: (value >> DIRECTION_SHIFT) & mask(DIRECTION_BITS),
+            tint: (value >> TINT_SHIFT) & mask(TINT_BITS),
+            light: (self.texture_light & mask(LIGHT_BITS)) as u8,
+        }
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct UnpackedVertex {
+    // In blocks
+    pub position: Vec3,
+    pub ao: u8,
+    pub texture_id: u32,
+    pub direction: u32,
+    pub tint: u32,
+    pub light: u8,
+}
+
 impl VertexLayout for Vertex {
     fn vertex_layout() -> VertexBufferLayout<'static> {
         VertexBufferLayout {
@@ -39,3 +148,87 @@ impl VertexLayout for Vertex {
         }
     }
 }
+
+/// Vertex of a quad drawn directly in clip space
+#[repr(C)]
+#[derive(Debug, Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct QuadVertex {
+    position: Vec2,
+    uv: Vec2,
+}
+
+impl QuadVertex {
+    const ATTRIBUTES: [VertexAttribute; 2] = vertex_attr_array![0 => Float32x2, 1 => Float32x2];
+
+    pub const fn new(position: Vec2, uv: Vec2) -> Self {
+        Self { position, uv }
+    }
+}
+
+impl VertexLayout for QuadVertex {
+    fn vertex_layout() -> VertexBufferLayout<'static> {
+        VertexBufferLayout {
+            array_stride: size_of::<QuadVertex>() as BufferAddress,
+            step_mode: VertexStepMode::Vertex,
+            attributes: &QuadVertex::ATTRIBUTES,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::uvec3;
+
+    use super::*;
+
+    fn round_trip(position: UVec3, ao: u8, texture_id: u32, direction: u32, tint: u32, light: u8) {
+        let vertex = Vertex::new(position, ao, texture_id, direction, tint, light);
+        assert_eq!(
+            vertex.unpack(),
+            UnpackedVertex {
+                position: position.as_vec3() / 2.0,
+                ao,
+                texture_id,
+                direction,
+                tint,
+                light,
+            }
+        );
+    }
+
+    #[test]
+    fn corners_round_trip() {
+        let far = (CHUNK_SIZE as u32 + 1) * 2;
+        for x in [0, 2, far] {
+            for y in [0, 2, far] {
+                for z in [0, 2, far] {
+                    round_trip(uvec3(x, y, z), 3, 7, 5, 1, 15);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn field_extremes_round_trip() {
+        let max = mask(POSITION_BITS);
+        let max_texture = u32::MAX >> TEXTURE_SHIFT;
+        round_trip(UVec3::ZERO, 0, 0, 0, 0, 0);
+        round_trip(UVec3::splat(max), 3, max_texture, 6, 63, 15);
+        // Every field on its own, so none of them bleed into their neighbors
+        round_trip(uvec3(max, 0, 0), 0, 0, 0, 0, 0);
+        round_trip(uvec3(0, max, 0), 0, 0, 0, 0, 0);
+        round_trip(uvec3(0, 0, max), 0, 0, 0, 0, 0);
+        round_trip(UVec3::ZERO, 3, 0, 0, 0, 0);
+        round_trip(UVec3::ZERO, 0, max_texture, 0, 0, 0);
+        round_trip(UVec3::ZERO, 0, 0, 7, 0, 0);
+        round_trip(UVec3::ZERO, 0, 0, 0, 63, 0);
+        round_trip(UVec3::ZERO, 0, 0, 0, 0, 15);
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "out of range")]
+    fn overflowing_fields_are_caught() {
+        Vertex::new(UVec3::splat(mask(POSITION_BITS) + 1), 0, 0, 0, 0, 0);
+    }
+}
@@ -0,0 +1,214 @@
+use std::f32::consts::{FRAC_PI_4, PI};
+
+use bytemuck::{Pod, Zeroable};
+use glam::{vec2, vec3, Mat4, UVec3, Vec2, Vec3};
+use voxel_util::{BasePipeline, Context, GrowableBuffer, ShaderResource, Uniform};
+use wgpu::{
+    BindGroupLayout, BufferUsages, Face as CullFace, FrontFace, IndexFormat, RenderPass,
+    RenderPipeline, TextureFormat,
+};
+
+use crate::{
+    camera::{Projection, Transformation},
+    scene_shader,
+    world::{Block, Direction, Face, RenderShape},
+};
+
+use super::{post_process_pass::SCENE_FORMAT, vertex::Vertex, Draw, Frame};
+
+type Model = (voxel_util::Vertex, Uniform<ViewmodelUniform>);
+
+// In view space, the block rests in the lower right corner of the screen
+const ANCHOR: Vec3 = vec3(0.55, -0.5, -0.9);
+const SCALE: f32 = 0.4;
+// View space units of sway per radian per second the camera turns by
+const SWAY: f32 = 0.015;
+const MAX_SWAY: f32 = 0.08;
+// How quickly the sway catches up with the camera, per second
+const SWAY_SPEED: f32 = 10.0;
+// Seconds a swing takes
+const SWING_DURATION: f32 = 0.25;
+const SWING_ANGLE: f32 = 0.6;
+
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy, Pod, Zeroable)]
+pub struct ViewmodelUniform {
+    model: Mat4,
+}
+
+/// Block selected in the hotbar, held in the corner of the screen. It sways behind the
+/// camera as it turns and swings whenever a block is hit or placed
+#[derive(Debug)]
+pub struct ViewmodelPass {
+    render_pipeline: RenderPipeline,
+    uniform: Uniform<ViewmodelUniform>,
+    model_resource: ShaderResource,
+    vertices: GrowableBuffer<Vertex>,
+    indices: GrowableBuffer<u32>,
+    block: Block,
+    // Rendered transformation of the previous frame, the sway follows the change
+    previous: Option<Transformation>,
+    sway: Vec2,
+    // Seconds since the swing started, `None` while not swinging
+    swing: Option<f32>,
+}
+
+impl ViewmodelPass {
+    pub fn new(
+        globals_layout: &BindGroupLayout,
+        spritesheet_layout: &BindGroupLayout,
+        projection: &Projection,
+        context: &Context,
+    ) -> Self {
+        let uniform = Uniform::new(ViewmodelUniform::default(), context);
+        let model_resource = context.create_shader_resource::<Model>(&uniform);
+        let render_pipeline = Self::create_pipeline(
+            globals_layout,
+            spritesheet_layout,
+            model_resource.layout(),
+            projection,
+            context,
+        );
+
+        Self {
+            render_pipeline,
+            uniform,
+            model_resource,
+            // Enough for a cube, crosses have fewer faces
+            vertices: GrowableBuffer::new(
+                "Viewmodel Vertex Buffer",
+                BufferUsages::VERTEX,
+                24,
+                context,
+            ),
+            indices: GrowableBuffer::new(
+                "Viewmodel Index Buffer",
+                BufferUsages::INDEX,
+                36,
+                context,
+            ),
+            block: Block::Air,
+            previous: None,
+            sway: Vec2::ZERO,
+            swing: None,
+        }
+    }
+
+    fn create_pipeline(
+        globals_layout: &BindGroupLayout,
+        spritesheet_layout: &BindGroupLayout,
+        model_layout: &BindGroupLayout,
+        projection: &Projection,
+        context: &Context,
+    ) -> RenderPipeline {
+        let shader = context
+            .device()
+            .create_shader_module(scene_shader!("shaders/viewmodel.wgsl"));
+        let pipeline_layout =
+            context.create_pipeline_layout(&[globals_layout, spritesheet_layout, model_layout]);
+
+        context
+            .create_render_pipeline::<Vertex>(BasePipeline {
+                vertex: (&shader, "vs_main"),
+                fragment: (&shader, "fs_main"),
+            })
+            .label("Viewmodel Render Pipeline")
+            .layout(&pipeline_layout)
+            .target(SCENE_FORMAT)
+            .override_const("REVERSED_Z", projection.reversed_z() as u32 as f64)
+            .depth(TextureFormat::Depth32Float, projection.depth_compare())
+            .front_face(FrontFace::Ccw)
+            .cull_mode(CullFace::Back)
+            .build()
+    }
+
+    /// Holds `block`, air leaves the hands empty
+    pub fn set_block(&mut self, block: Block, context: &Context) {
+        if self.block == block {
+            return;
+        }
+        self.block = block;
+
+        let faces = match block.shape() {
+            _ if block == Block::Air => Vec::new(),
+            RenderShape::Cube => [
+                Direction::Top,
+                Direction::Bottom,
+                Direction::Left,
+                Direction::Right,
+                Direction::Front,
+                Direction::Back,
+            ]
+            .map(|direction| Face::new(block, UVec3::ZERO, [3; 4], direction))
+            .to_vec(),
+            RenderShape::Cross => Face::cross(block, UVec3::ZERO).to_vec(),
+        };
+        // Any temperature, the shader tints foliage like the hotbar does
+        let faces = faces.into_iter().map(|face| face.tinted(0).lit(0));
+
+        let (vertices, indices): (Vec<_>, Vec<_>) = faces
+            .enumerate()
+            .map(|(index, face)| (face.vertices(), face.indices(index as u32)))
+            .unzip();
+        self.vertices.write(vertices.as_flattened(), context);
+        self.indices.write(indices.as_flattened(), context);
+    }
+
+    /// Starts swinging the block, restarting a swing that is still going
+    pub fn swing(&mut self) {
+        self.swing = Some(0.0);
+    }
+
+    /// Moves the block along with the camera at `transformation`, `delta_time` seconds after
+    /// the last update
+    pub fn update(&mut self, transformation: Transformation, delta_time: f32, context: &Context) {
+        let turn = match self.previous.replace(transformation) {
+            Some(previous) if delta_time > 0.0 => {
+                vec2(
+                    transformation.yaw() - previous.yaw(),
+                    transformation.pitch() - previous.pitch(),
+                ) / delta_time
+            }
+            _ => Vec2::ZERO,
+        };
+        let target = (turn * SWAY).clamp(Vec2::splat(-MAX_SWAY), Vec2::splat(MAX_SWAY));
+        self.sway += (target - self.sway) * (1.0 - (-SWAY_SPEED * delta_time).exp());
+
+        let swing = match &mut self.swing {
+            Some(time) => {
+                *time += delta_time;
+                let progress = *time / SWING_DURATION;
+                if progress >= 1.0 {
+                    self.swing = None;
+                    0.0
+                } else {
+                    (progress * PI).sin()
+                }
+            }
+            None => 0.0,
+        };
+
+        // Lags behind the turn, turning right leaves the block to the left for a moment
+        let model = Mat4::from_translation(ANCHOR + vec3(-self.sway.x, -self.sway.y, 0.0))
+            * Mat4::from_rotation_x(-swing * SWING_ANGLE)
+            * Mat4::from_rotation_y(FRAC_PI_4 + self.sway.x)
+            * Mat4::from_scale(Vec3::splat(SCALE))
+            * Mat4::from_translation(Vec3::splat(-0.5));
+        self.uniform.stage(ViewmodelUniform { model }, context);
+    }
+}
+
+impl Draw for ViewmodelPass {
+    fn draw<'r>(&'r self, render_pass: &mut RenderPass<'r>, frame: &Frame<'_>) {
+        if self.indices.is_empty() {
+            return;
+        }
+
+        render_pass.set_pipeline(&self.render_pipeline);
+        render_pass.set_bind_group(1, frame.spritesheet.bind_group(), &[]);
+        render_pass.set_bind_group(2, self.model_resource.bind_group(), &[]);
+        render_pass.set_vertex_buffer(0, self.vertices.slice());
+        render_pass.set_index_buffer(self.indices.slice(), IndexFormat::Uint32);
+        render_pass.draw_indexed(0..self.indices.len() as u32, 0, 0..1);
+    }
+}
@@ -1,43 +1,85 @@
+use std::{cell::Cell, time::Instant};
+
+use bytemuck::{Pod, Zeroable};
 use glam::IVec3;
 use voxel_util::{
-    AsBindGroup, BasePipeline, Context, ShaderResource, Spritesheet, Texture, Uniform,
+    bind_group::VertexFragment, spritesheet::TextureAtlasUniform, AsyncTexture, AtlasLayout,
+    BasePipeline, Context, Fragment, Sampler, ShaderResource, Texture, TextureError, Uniform,
 };
 use wgpu::{
-    include_wgsl,
     util::{BufferInitDescriptor, DeviceExt},
-    BindGroupLayout, Buffer, BufferUsages, CompareFunction, Face, FrontFace, IndexFormat,
-    RenderPass, RenderPipeline, TextureFormat, TextureUsages,
+    BindGroupLayout, Buffer, BufferAddress, BufferUsages, CompareFunction, Face, FilterMode,
+    FrontFace, IndexFormat, RenderPass, RenderPipeline, TextureFormat,
 };
 
 use crate::{
-    application::Meshes,
-    asset,
+    error::MissingTexture,
+    scene_shader,
     world::{
         chunk::{RawChunk, Volume},
-        RawMesh,
+        coords,
+        lod::Lod,
+        meshes::Indices,
+        Block, Face as BlockFace, RawMesh,
     },
 };
 
 use super::{
-    frustum_culling::{Frustum, AABB},
+    frustum_culling::{Frustum, Sphere, AABB},
+    post_process_pass::SCENE_FORMAT,
     vertex::Vertex,
+    Draw, Frame,
 };
 
-type Transformation = (voxel_util::Vertex, Uniform<IVec3>);
+pub type Transformation = (voxel_util::Vertex, Uniform<IVec3>);
+// The spritesheet with the block being broken, its crack is drawn from the same atlas
+type Blocks = (
+    (Fragment, Texture),
+    (Fragment, Sampler),
+    (VertexFragment, Uniform<TextureAtlasUniform>),
+    (Fragment, Uniform<BlocksUniform>),
+);
+
+// Stages of the crack drawn over a block being broken
+const BREAK_STAGES: u32 = 10;
+
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Pod, Zeroable)]
+pub struct BlocksUniform {
+    break_position: IVec3,
+    // Zero while nothing is being broken, otherwise the crack stage plus one
+    break_stage: u32,
+    crack_texture_id: u32,
+    // Scrolled over time, the atlas decides where it ends up
+    water_texture_id: u32,
+    _padding: [u32; 2],
+}
 
 #[derive(Debug)]
 pub struct ChunkBuffer {
     vertices: Buffer,
+    vertices_len: u32,
     indices: Buffer,
     indices_len: u32,
+    index_format: IndexFormat,
 
     transformation_resource: ShaderResource,
     aabb: AABB,
+    sphere: Sphere,
+    lod: Lod,
+    // Faces the mesh was built from, kept for edited chunks so the next edit can patch them
+    faces: Option<Box<[BlockFace]>>,
+    built: Instant,
 }
 
 impl ChunkBuffer {
-    pub fn from_mesh(mesh: &RawMesh, transformation: IVec3, context: &Context) -> Self {
+    pub fn from_mesh(mesh: &RawMesh, transformation: IVec3, lod: Lod, context: &Context) -> Self {
+        let vertices_len = mesh.verticies().len() as u32;
         let indices_len = mesh.indices().len() as u32;
+        let index_format = match mesh.indices() {
+            Indices::U16(_) => IndexFormat::Uint16,
+            Indices::U32(_) => IndexFormat::Uint32,
+        };
 
         let vertices = context.device().create_buffer_init(&BufferInitDescriptor {
             label: None,
@@ -47,11 +89,11 @@ impl ChunkBuffer {
 
         let indices = context.device().create_buffer_init(&BufferInitDescriptor {
             label: None,
-            contents: bytemuck::cast_slice(mesh.indices()),
+            contents: mesh.indices().as_bytes(),
             usage: BufferUsages::INDEX,
         });
 
-        let min = transformation * RawChunk::SIZE as i32;
+        let min = coords::chunk_to_block_min(transformation);
         let aabb = AABB::new(min.as_vec3(), (min + RawChunk::SIZE as i32).as_vec3());
 
         let transformation_resource = context
@@ -59,59 +101,219 @@ impl ChunkBuffer {
 
         Self {
             vertices,
+            vertices_len,
             indices,
             indices_len,
+            index_format,
             transformation_resource,
             aabb,
+            sphere: aabb.bounding_sphere(),
+            lod,
+            faces: None,
+            built: Instant::now(),
         }
     }
+
+    pub fn with_faces(mut self, faces: Box<[BlockFace]>) -> Self {
+        self.faces = Some(faces);
+        self
+    }
+
+    pub fn faces(&self) -> Option<&[BlockFace]> {
+        self.faces.as_deref()
+    }
+
+    pub fn built(&self) -> Instant {
+        self.built
+    }
+
+    pub fn is_on_frustum(&self, frustum: &Frustum) -> bool {
+        frustum.contains_sphere(&self.sphere) && self.aabb.is_on_frustum(frustum)
+    }
+
+    pub fn lod(&self) -> Lod {
+        self.lod
+    }
+
+    pub fn vertices_len(&self) -> u32 {
+        self.vertices_len
+    }
+
+    pub fn indices_len(&self) -> u32 {
+        self.indices_len
+    }
+
+    /// Bytes taken by the vertex and index buffers
+    pub fn buffers_size(&self) -> BufferAddress {
+        self.vertices.size() + self.indices.size()
+    }
+
+    /// Binds the chunk transformation at `transformation_group` and draws the mesh
+    pub fn draw(&self, render_pass: &mut RenderPass<'_>, transformation_group: u32) {
+        render_pass.set_bind_group(
+            transformation_group,
+            self.transformation_resource.bind_group(),
+            &[],
+        );
+        render_pass.set_vertex_buffer(0, self.vertices.slice(..));
+        render_pass.set_index_buffer(self.indices.slice(..), self.index_format);
+        render_pass.draw_indexed(0..self.indices_len, 0, 0..1);
+    }
+}
+
+/// How many chunks the last draw tested against the frustum and how much of them got drawn
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct CullingStats {
+    pub tested: u32,
+    pub passed: u32,
+    pub vertices: u32,
+    pub indices: u32,
+}
+
+impl CullingStats {
+    pub fn culled(&self) -> u32 {
+        self.tested - self.passed
+    }
 }
 
 #[derive(Debug)]
 pub struct WorldPass {
     render_pipeline: RenderPipeline,
     spritesheet_resource: ShaderResource,
+    // Bound again once the atlas finished loading
+    atlas: AsyncTexture,
+    atlas_sampler: Sampler,
+    atlas_uniform: Uniform<TextureAtlasUniform>,
+    blocks_uniform: Uniform<BlocksUniform>,
+    // Tiles of the crack stages, from the first to the last
+    crack_textures: Box<[u32]>,
+    // Written while drawing, which only gets `&self`
+    culling_stats: Cell<CullingStats>,
 }
 
 impl WorldPass {
-    pub fn new(camera_resource: &ShaderResource, context: &Context) -> Self {
-        let spritesheet = image::load_from_memory(include_bytes!(asset!("texture.png")))
-            .expect("failed to load spritesheet");
-        let spritesheet = Texture::from_data(
-            &spritesheet.to_rgba8(),
-            TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_DST,
+    pub fn new(
+        atlas_layout: &AtlasLayout,
+        atlas: AsyncTexture,
+        camera_resource: &ShaderResource,
+        lighting_resource: &ShaderResource,
+        depth_compare: CompareFunction,
+        context: &Context,
+    ) -> Result<Self, MissingTexture> {
+        let crack_textures = (0..BREAK_STAGES)
+            .map(|stage| {
+                let name = format!("crack_{stage}");
+                atlas_layout.tile(&name).ok_or_else(|| MissingTexture {
+                    name,
+                    user: "the crack of blocks being broken".to_string(),
+                })
+            })
+            .collect::<Result<_, _>>()?;
+
+        let atlas_sampler = Sampler::new(FilterMode::Nearest, context);
+        let atlas_uniform = Uniform::new(TextureAtlasUniform::from(atlas_layout), context);
+        let blocks_uniform = Uniform::new(
+            BlocksUniform {
+                water_texture_id: Block::Water.texture_id(),
+                ..Default::default()
+            },
             context,
         );
-
-        let spritesheet = Spritesheet::new(spritesheet, 16, context);
-        let spritesheet_resource = spritesheet.as_shader_resource(context);
+        let spritesheet_resource = context.create_shader_resource::<Blocks>((
+            atlas.texture(),
+            &atlas_sampler,
+            &atlas_uniform,
+            &blocks_uniform,
+        ));
 
         let render_pipeline = Self::create_pipeline(
             camera_resource.layout(),
             spritesheet_resource.layout(),
+            lighting_resource.layout(),
+            depth_compare,
             context,
         );
 
-        Self {
+        Ok(Self {
             render_pipeline,
             spritesheet_resource,
+            atlas,
+            atlas_sampler,
+            atlas_uniform,
+            blocks_uniform,
+            crack_textures,
+            culling_stats: Default::default(),
+        })
+    }
+
+    /// Block texture atlas, also sampled by the particles
+    pub fn spritesheet_resource(&self) -> &ShaderResource {
+        &self.spritesheet_resource
+    }
+
+    /// Binds the atlas once it finished loading, the placeholder is drawn until then
+    pub fn poll_atlas(&mut self, context: &Context) -> Result<(), TextureError> {
+        if self.atlas.poll()? {
+            self.spritesheet_resource.rebind::<Blocks>(
+                (
+                    self.atlas.texture(),
+                    &self.atlas_sampler,
+                    &self.atlas_uniform,
+                    &self.blocks_uniform,
+                ),
+                context,
+            );
         }
+        Ok(())
+    }
+
+    /// Cracks the block at the position by the progress in `0.0..1.0`, `None` hides the crack
+    pub fn set_break_progress(&mut self, progress: Option<(IVec3, f32)>, context: &Context) {
+        let current = *self.blocks_uniform.data();
+        let uniform = match progress {
+            Some((position, progress)) => {
+                let stage =
+                    (progress * BREAK_STAGES as f32).clamp(0.0, (BREAK_STAGES - 1) as f32) as usize;
+                BlocksUniform {
+                    break_position: position,
+                    break_stage: stage as u32 + 1,
+                    crack_texture_id: self.crack_textures[stage],
+                    ..current
+                }
+            }
+            None => BlocksUniform {
+                break_position: IVec3::ZERO,
+                break_stage: 0,
+                ..current
+            },
+        };
+
+        if current != uniform {
+            self.blocks_uniform.stage(uniform, context);
+        }
+    }
+
+    pub fn culling_stats(&self) -> CullingStats {
+        self.culling_stats.get()
     }
 
     fn create_pipeline(
         camera_layout: &BindGroupLayout,
         spritesheet_layout: &BindGroupLayout,
+        lighting_layout: &BindGroupLayout,
+        depth_compare: CompareFunction,
         context: &Context,
     ) -> RenderPipeline {
         let shader = context
             .device()
-            .create_shader_module(include_wgsl!(asset!("shaders/world.wgsl")));
+            .create_shader_module(scene_shader!("shaders/world.wgsl"));
 
         let transformation_layout = context.create_bind_group_layout::<Transformation>().erase();
         let pipeline_layout = context.create_pipeline_layout(&[
             camera_layout,
             spritesheet_layout,
             &transformation_layout,
+            lighting_layout,
         ]);
 
         context
@@ -121,35 +323,32 @@ impl WorldPass {
             })
             .label("World Render Pipeline")
             .layout(&pipeline_layout)
-            .target(context.config().format)
-            .depth(TextureFormat::Depth32Float, CompareFunction::Less)
-            .front_face(FrontFace::Cw)
+            .target(SCENE_FORMAT)
+            .override_const("CHUNK_SIZE", RawChunk::SIZE as f64)
+            .depth(TextureFormat::Depth32Float, depth_compare)
+            .front_face(FrontFace::Ccw)
             .cull_mode(Face::Back)
             .build()
     }
 }
 
-impl WorldPass {
-    pub fn draw<'r>(
-        &'r self,
-        render_pass: &mut RenderPass<'r>,
-        frustum: &Frustum,
-        meshes: &Meshes,
-    ) {
+impl Draw for WorldPass {
+    fn draw<'r>(&'r self, render_pass: &mut RenderPass<'r>, frame: &Frame<'_>) {
         render_pass.set_pipeline(&self.render_pipeline);
         render_pass.set_bind_group(1, self.spritesheet_resource.bind_group(), &[]);
+        render_pass.set_bind_group(3, frame.lighting.bind_group(), &[]);
+
+        let mut stats = CullingStats::default();
+        for chunk_buffer in frame.meshes.read().values() {
+            stats.tested += 1;
+            if chunk_buffer.is_on_frustum(frame.frustum) {
+                stats.passed += 1;
+                stats.vertices += chunk_buffer.vertices_len;
+                stats.indices += chunk_buffer.indices_len;
 
-        for chunk_buffer in meshes.read().values() {
-            if chunk_buffer.aabb.is_on_frustum(frustum) {
-                render_pass.set_bind_group(
-                    2,
-                    chunk_buffer.transformation_resource.bind_group(),
-                    &[],
-                );
-                render_pass.set_vertex_buffer(0, chunk_buffer.vertices.slice(..));
-                render_pass.set_index_buffer(chunk_buffer.indices.slice(..), IndexFormat::Uint16);
-                render_pass.draw_indexed(0..chunk_buffer.indices_len, 0, 0..1);
+                chunk_buffer.draw(render_pass, 2);
             }
         }
+        self.culling_stats.set(stats);
     }
 }
@@ -0,0 +1,232 @@
+#[cfg(feature = "serde")]
+use std::{
+    fs::{self, File},
+    io::{self, BufWriter},
+    path::Path,
+};
+
+#[cfg(feature = "serde")]
+use log::warn;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "serde")]
+use thiserror::Error;
+
+use crate::{
+    camera::Transformation,
+    render::{
+        post_process_pass::{DEFAULT_EXPOSURE, DEFAULT_GAMMA},
+        DebugOverlay,
+    },
+    settings::Settings,
+};
+
+#[cfg(feature = "serde")]
+pub const SESSION_PATH: &str = "session.json";
+// Bumped whenever the fields change, older sessions are ignored rather than migrated
+#[cfg(feature = "serde")]
+const SESSION_VERSION: u32 = 4;
+
+#[cfg(feature = "serde")]
+#[derive(Debug, Error)]
+pub enum SessionError {
+    #[error("failed to access session file: {0}")]
+    Io(#[from] io::Error),
+    #[error("invalid session: {0}")]
+    Format(#[from] serde_json::Error),
+    #[error("session version {0} is not supported, expected {SESSION_VERSION}")]
+    Version(u32),
+}
+
+#[cfg(feature = "serde")]
+#[derive(Deserialize)]
+struct SessionHeader {
+    version: u32,
+}
+
+/// State restored when the game is launched again, saved only with the `serde` feature
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Session {
+    #[cfg(feature = "serde")]
+    version: u32,
+    pub seed: u32,
+    pub transformation: Transformation,
+    pub ambient_occlusion: bool,
+    pub lod_distance: Option<u32>,
+    pub shadows: bool,
+    pub fly_speed: f32,
+    pub debug_overlay: DebugOverlay,
+    pub exposure: f32,
+    pub gamma: f32,
+}
+
+impl Session {
+    pub fn new(
+        seed: u32,
+        transformation: Transformation,
+        ambient_occlusion: bool,
+        lod_distance: Option<u32>,
+        shadows: bool,
+        fly_speed: f32,
+        debug_overlay: DebugOverlay,
+    ) -> Self {
+        Self {
+            #[cfg(feature = "serde")]
+            version: SESSION_VERSION,
+            seed,
+            transformation,
+            ambient_occlusion,
+            lod_distance,
+            shadows,
+            fly_speed,
+            debug_overlay,
+            exposure: DEFAULT_EXPOSURE,
+            gamma: DEFAULT_GAMMA,
+        }
+    }
+
+    /// Fresh session at `transformation` in the world of `seed`, with shadows on and
+    /// everything else from the settings. Scripted runs start from it instead of the
+    /// player's last session
+    pub fn from_settings(seed: u32, transformation: Transformation, settings: &Settings) -> Self {
+        Self::new(
+            seed,
+            transformation,
+            settings.ambient_occlusion,
+            settings.lod_distance,
+            true,
+            settings.fly_speed,
+            settings.debug_overlay,
+        )
+        .with_color_grading(settings.exposure, settings.gamma)
+    }
+
+    pub fn with_color_grading(mut self, exposure: f32, gamma: f32) -> Self {
+        self.exposure = exposure;
+        self.gamma = gamma;
+        self
+    }
+
+    #[cfg(feature = "serde")]
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, SessionError> {
+        let data = fs::read_to_string(path)?;
+
+        // Checked first, so a changed layout reports the version instead of a missing field
+        let header = serde_json::from_str::<SessionHeader>(&data)?;
+        if header.version != SESSION_VERSION {
+            return Err(SessionError::Version(header.version));
+        }
+
+        Ok(serde_json::from_str(&data)?)
+    }
+
+    /// Like [`Session::load`], but a missing session is expected and anything else only logged
+    #[cfg(feature = "serde")]
+    pub fn restore(path: impl AsRef<Path>) -> Option<Self> {
+        match Self::load(path) {
+            Ok(session) => Some(session),
+            Err(SessionError::Io(err)) if err.kind() == io::ErrorKind::NotFound => None,
+            Err(err) => {
+                warn!("ignoring session: {err}");
+                None
+            }
+        }
+    }
+
+    #[cfg(feature = "serde")]
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), SessionError> {
+        let writer = BufWriter::new(File::create(path)?);
+        Ok(serde_json::to_writer_pretty(writer, self)?)
+    }
+}
+
+#[cfg(all(test, feature = "serde"))]
+mod tests {
+    use std::{env, path::PathBuf, process};
+
+    use glam::Vec3;
+
+    use super::*;
+
+    // Removed again when dropped, named after the test so they can run in parallel
+    struct TempFile(PathBuf);
+
+    impl TempFile {
+        fn new(name: &str) -> Self {
+            Self(env::temp_dir().join(format!("voxel-{}-{name}.json", process::id())))
+        }
+    }
+
+    impl Drop for TempFile {
+        fn drop(&mut self) {
+            let _ = fs::remove_file(&self.0);
+        }
+    }
+
+    fn session() -> Session {
+        Session::new(
+            42,
+            Transformation::new(Vec3::new(1.5, 80.0, -3.0), 0.5, -0.25),
+            false,
+            Some(6),
+            true,
+            12.0,
+            DebugOverlay::Full,
+        )
+        .with_color_grading(1.5, 2.0)
+    }
+
+    #[test]
+    fn saved_sessions_load_again() {
+        let file = TempFile::new("round-trip");
+        let session = session();
+        session.save(&file.0).unwrap();
+
+        let loaded = Session::load(&file.0).unwrap();
+        assert_eq!(
+            serde_json::to_value(&loaded).unwrap(),
+            serde_json::to_value(&session).unwrap()
+        );
+        assert_eq!(loaded.seed, 42);
+        assert_eq!(loaded.lod_distance, Some(6));
+        assert_eq!(loaded.exposure, 1.5);
+    }
+
+    #[test]
+    fn other_versions_are_rejected() {
+        let file = TempFile::new("version");
+        let mut session = serde_json::to_value(session()).unwrap();
+        session["version"] = (SESSION_VERSION + 1).into();
+        fs::write(&file.0, session.to_string()).unwrap();
+        assert!(matches!(
+            Session::load(&file.0),
+            Err(SessionError::Version(version)) if version == SESSION_VERSION + 1
+        ));
+
+        // Reported before any missing field
+        fs::write(&file.0, r#"{"version": 1}"#).unwrap();
+        assert!(matches!(
+            Session::load(&file.0),
+            Err(SessionError::Version(1))
+        ));
+        assert!(Session::restore(&file.0).is_none());
+    }
+
+    #[test]
+    fn broken_or_missing_sessions_are_skipped() {
+        let file = TempFile::new("broken");
+        assert!(matches!(
+            Session::load(&file.0),
+            Err(SessionError::Io(err)) if err.kind() == io::ErrorKind::NotFound
+        ));
+        assert!(Session::restore(&file.0).is_none());
+
+        fs::write(&file.0, "{ not json").unwrap();
+        assert!(matches!(
+            Session::load(&file.0),
+            Err(SessionError::Format(..))
+        ));
+        assert!(Session::restore(&file.0).is_none());
+    }
+}
@@ -0,0 +1,119 @@
+use std::{num::NonZero, thread};
+
+use crate::{
+    camera::DEFAULT_SPEED,
+    render::{
+        post_process_pass::{DEFAULT_EXPOSURE, DEFAULT_GAMMA},
+        DebugOverlay,
+    },
+    world::{WorldBounds, DEFAULT_RENDER_DISTANCE, DEFAULT_VERTICAL_RENDER_DISTANCE},
+};
+
+pub const DEFAULT_BLOOM_INTENSITY: f32 = 0.6;
+
+#[derive(Debug, Clone, Copy)]
+pub struct Settings {
+    pub world_bounds: Option<WorldBounds>,
+    pub max_meshes_in_flight: usize,
+    pub mesh_threads: usize,
+    // Generating on the main thread when `0`, new sections then show up in the same frame
+    pub generation_threads: usize,
+    // In chunks around the camera, the vertical one covers both above and below it
+    pub render_distance: u32,
+    pub vertical_render_distance: u32,
+    // Rounds the shown region off into a sphere, the vertical distance still caps it
+    pub spherical_render_distance: bool,
+    // In sections, never less than a chunk past the render distance, `None` keeps it at that
+    pub generation_distance: Option<u32>,
+    // Ignored while vsync is enabled
+    pub max_fps: Option<u32>,
+    pub ambient_occlusion: bool,
+    // In chunks, `None` meshes everything at full resolution
+    pub lod_distance: Option<u32>,
+    // How strongly highlights bleed into their surroundings, `None` turns bloom off
+    pub bloom: Option<f32>,
+    // Depth buffer precision is spent evenly instead of mostly up close, turning it off brings
+    // back the z-fighting of distant faces
+    pub reversed_z: bool,
+    // Scales the scene before tonemapping, `[` and `]` step it down and up
+    pub exposure: f32,
+    // Applied after tonemapping, above `1.0` brightens the midtones
+    pub gamma: f32,
+    // Resolution of every shadow cascade, bigger maps give sharper but costlier shadows
+    pub shadow_map_size: u32,
+    // Multiplies how fast blocks break, `1.0` takes a block's hardness in seconds
+    pub break_speed: f32,
+    // Shows the FPS and position in the window title while the debug overlay is hidden
+    pub title_stats: bool,
+    // F3 cycles through the levels
+    pub debug_overlay: DebugOverlay,
+    pub start_fullscreen: bool,
+    // Switches the video mode of the monitor instead of covering it with a borderless window
+    pub exclusive_fullscreen: bool,
+    // Index into the available monitors, `None` picks the one the window is on
+    pub monitor: Option<usize>,
+    // Flips which way the mouse wheel moves through the hotbar
+    pub natural_scrolling: bool,
+    // Blocks picked at random in every chunk each tick for things like grass spreading, `0`
+    // turns random ticks off
+    pub random_tick_speed: u32,
+    // Blocks per second without sprinting, scrolling with Alt held changes it
+    pub fly_speed: f32,
+    #[cfg(feature = "gamepad")]
+    pub gamepad: GamepadSettings,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        let parallelism = thread::available_parallelism().map_or(8, NonZero::get);
+
+        Self {
+            world_bounds: None,
+            max_meshes_in_flight: parallelism * 2,
+            // Leaves a core to the main thread and the generators
+            mesh_threads: parallelism.saturating_sub(1).max(1),
+            generation_threads: (parallelism / 4).max(1),
+            render_distance: DEFAULT_RENDER_DISTANCE,
+            vertical_render_distance: DEFAULT_VERTICAL_RENDER_DISTANCE,
+            spherical_render_distance: false,
+            generation_distance: None,
+            max_fps: None,
+            ambient_occlusion: true,
+            lod_distance: Some(8),
+            bloom: Some(DEFAULT_BLOOM_INTENSITY),
+            reversed_z: true,
+            exposure: DEFAULT_EXPOSURE,
+            gamma: DEFAULT_GAMMA,
+            shadow_map_size: 2048,
+            break_speed: 1.0,
+            title_stats: true,
+            debug_overlay: DebugOverlay::default(),
+            start_fullscreen: false,
+            exclusive_fullscreen: false,
+            monitor: None,
+            natural_scrolling: false,
+            random_tick_speed: 0,
+            fly_speed: DEFAULT_SPEED,
+            #[cfg(feature = "gamepad")]
+            gamepad: GamepadSettings::default(),
+        }
+    }
+}
+
+#[cfg(feature = "gamepad")]
+#[derive(Debug, Clone, Copy)]
+pub struct GamepadSettings {
+    pub deadzone: f32,
+    // Degrees per second with the right stick fully deflected
+    pub look_sensitivity: f32,
+}
+
+#[cfg(feature = "gamepad")]
+impl Default for GamepadSettings {
+    fn default() -> Self {
+        Self {
+            deadzone: 0.15,
+            look_sensitivity: 180.0,
+        }
+    }
+}
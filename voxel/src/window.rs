@@ -5,6 +5,8 @@ use winit::{
     window::WindowId,
 };
 
+pub const TITLE: &str = "voxel";
+
 #[derive(Debug, Clone)]
 pub struct Window<A, F> {
     application: Option<A>,
@@ -1,42 +1,356 @@
+use std::{str::FromStr, sync::OnceLock};
+
+#[cfg(feature = "serde")]
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use thiserror::Error;
+use voxel_util::AtlasLayout;
+
+use crate::error::MissingTexture;
+
+// Indexed by the block, filled in from the atlas once it's built
+static TEXTURE_IDS: OnceLock<Box<[u32]>> = OnceLock::new();
+
 macro_rules! define_block {
-    ($($(#[$attr:meta])? $block:ident: $visibility:ident),* $(,)?) => {
+    (@tint) => { Tint::None };
+    (@tint $tint:ident) => { Tint::$tint };
+    (@shape) => { RenderShape::Cube };
+    (@shape $shape:ident) => { RenderShape::$shape };
+    (@light) => { 0 };
+    (@light $light:literal) => { $light };
+    (@texture) => { None };
+    (@texture $texture:literal) => { Some($texture) };
+
+    ($(
+        $(#[$attr:meta])? $block:ident($id:literal, $name:literal): $visibility:ident $(+ $tint:ident)? $(as $shape:ident)?
+            $({ light: $light:literal })? $(= $texture:literal)?
+    ),* $(,)?) => {
         #[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
         pub enum Block {
             $($(#[$attr])? $block),*
         }
 
         impl Block {
+            pub const ALL: &'static [Block] = &[$(Self::$block),*];
+            #[cfg(feature = "serde")]
+            const VARIANTS: &'static [&'static str] = &[$(stringify!($block)),*];
+            const IDS: &'static [u8] = &[$($id),*];
+
+            /// Name of the block in snake case, e.g. `tall_grass`
+            pub fn name(self) -> &'static str {
+                match self {
+                    $(Self::$block => $name),*
+                }
+            }
+
+            /// Stable id of the block on the wire, unlike its position in [`Block::ALL`]
+            pub fn id(self) -> u8 {
+                match self {
+                    $(Self::$block => $id),*
+                }
+            }
+
+            pub fn from_id(id: u8) -> Option<Block> {
+                match id {
+                    $($id => Some(Self::$block),)*
+                    _ => None,
+                }
+            }
+
             pub fn visibility(self) -> Visibility {
                 match self {
                     $(Self::$block => Visibility::$visibility),*
                 }
             }
 
-            pub fn texture_id(self) -> u32 {
-                self as u32
+            /// Name of the texture in `assets/blocks`, `None` for blocks that are never drawn
+            pub fn texture_name(self) -> Option<&'static str> {
+                match self {
+                    $(Self::$block => define_block!(@texture $($texture)?)),*
+                }
+            }
+
+            pub fn tint(self) -> Tint {
+                match self {
+                    $(Self::$block => define_block!(@tint $($tint)?)),*
+                }
+            }
+
+            pub fn shape(self) -> RenderShape {
+                match self {
+                    $(Self::$block => define_block!(@shape $($shape)?)),*
+                }
+            }
+
+            /// Level of the light the block gives off, up to `MAX_LIGHT`
+            pub fn light(self) -> u8 {
+                match self {
+                    $(Self::$block => define_block!(@light $($light)?)),*
+                }
             }
         }
+
+        // Two blocks sharing an id would decode into the same one
+        const _: () = {
+            let mut i = 0;
+            while i < Block::IDS.len() {
+                let mut j = i + 1;
+                while j < Block::IDS.len() {
+                    assert!(Block::IDS[i] != Block::IDS[j], "block ids have to be unique");
+                    j += 1;
+                }
+                i += 1;
+            }
+        };
     };
 }
 define_block!(
-    Dirt: Opaque,
-    Grass: Opaque,
-    Sand: Opaque,
-    Gravel: Opaque,
-    Ice: Opaque,
-    Snow: Opaque,
-    Stone: Opaque,
-
-    Water: Transparent,
+    Dirt(0, "dirt"): Opaque = "dirt",
+    Grass(1, "grass"): Opaque + Foliage = "grass",
+    Sand(2, "sand"): Opaque = "sand",
+    Gravel(3, "gravel"): Opaque = "gravel",
+    Ice(4, "ice"): Opaque = "ice",
+    Snow(5, "snow"): Opaque = "snow",
+    Stone(6, "stone"): Opaque = "stone",
+    Coal(7, "coal"): Opaque = "coal",
+    Iron(8, "iron"): Opaque = "iron",
 
+    Water(9, "water"): Transparent = "water",
+    TallGrass(10, "tall_grass"): Transparent + Foliage as Cross = "tall_grass",
+    Flower(11, "flower"): Transparent as Cross = "flower",
 
     #[default]
-    Air: Empty,
+    Air(12, "air"): Empty,
+
+    Glowstone(13, "glowstone"): Opaque { light: 15 } = "glowstone",
 );
 
+#[derive(Debug, Error)]
+#[error("unknown block `{0}`")]
+pub struct UnknownBlock(pub String);
+
+impl Block {
+    /// Looks up the tile of every block in the atlas, has to run before anything is meshed
+    pub fn resolve_textures(atlas: &AtlasLayout) -> Result<(), MissingTexture> {
+        let ids = Block::ALL
+            .iter()
+            .map(|block| {
+                let Some(name) = block.texture_name() else {
+                    return Ok(0);
+                };
+                atlas.tile(name).ok_or_else(|| MissingTexture {
+                    name: name.to_string(),
+                    user: format!("block {block:?}"),
+                })
+            })
+            .collect::<Result<_, _>>()?;
+
+        // There is a single atlas per run, so only the first one counts
+        let _ = TEXTURE_IDS.set(ids);
+        Ok(())
+    }
+
+    pub fn texture_id(self) -> u32 {
+        TEXTURE_IDS.get().map_or(0, |ids| ids[self as usize])
+    }
+
+    /// Whether the block falls down when there is nothing below it
+    pub fn falls(self) -> bool {
+        matches!(self, Block::Sand | Block::Gravel)
+    }
+
+    /// Seconds it takes to break the block, `None` for blocks that can't be broken
+    pub fn hardness(self) -> Option<f32> {
+        match self {
+            Block::Air | Block::Water => None,
+            Block::TallGrass | Block::Flower => Some(0.05),
+            Block::Snow => Some(0.2),
+            Block::Glowstone => Some(0.3),
+            Block::Dirt | Block::Grass | Block::Sand => Some(0.5),
+            Block::Gravel | Block::Ice => Some(0.6),
+            Block::Stone => Some(1.5),
+            Block::Coal => Some(2.0),
+            Block::Iron => Some(2.5),
+        }
+    }
+}
+
+/// Parses the names given by [`Block::name`], ignoring case
+impl FromStr for Block {
+    type Err = UnknownBlock;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        Block::ALL
+            .iter()
+            .copied()
+            .find(|block| block.name().eq_ignore_ascii_case(name))
+            .ok_or_else(|| UnknownBlock(name.to_string()))
+    }
+}
+
+/// Written as [`Block::name`], so reordering the variants keeps saved blocks intact
+#[cfg(feature = "serde")]
+impl Serialize for Block {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.name())
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> Deserialize<'de> for Block {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        // Owned, readers can't lend out what they read
+        let name = String::deserialize(deserializer)?;
+        // Blocks used to be written by their variant names
+        name.parse().or_else(|err| {
+            Block::VARIANTS
+                .iter()
+                .position(|&variant| variant == name)
+                .map(|index| Block::ALL[index])
+                .ok_or_else(|| de::Error::custom(err))
+        })
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum Visibility {
     Opaque,
     Transparent,
     Empty,
 }
+
+/// Geometry the mesher builds for a block
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RenderShape {
+    /// Faces towards every neighbor that doesn't cover them
+    Cube,
+    /// Two diagonal planes crossing in the middle of the block, seen from both sides
+    Cross,
+}
+
+/// Color multiplied over the texture, the textures of tinted blocks are grayscale
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Tint {
+    None,
+    /// Follows the temperature of the column the block is in
+    Foliage,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ids_round_trip() {
+        for &block in Block::ALL {
+            assert_eq!(Block::from_id(block.id()), Some(block));
+        }
+    }
+
+    #[test]
+    fn ids_are_stable() {
+        // Ids are on the wire, changing one breaks every peer that isn't rebuilt
+        assert_eq!(Block::Dirt.id(), 0);
+        assert_eq!(Block::Water.id(), 9);
+        assert_eq!(Block::Air.id(), 12);
+        assert_eq!(Block::Glowstone.id(), 13);
+    }
+
+    #[test]
+    fn names_parse_back() {
+        for &block in Block::ALL {
+            assert_eq!(block.name().parse::<Block>().unwrap(), block);
+            assert_eq!(block.name().to_uppercase().parse::<Block>().unwrap(), block);
+        }
+        assert_eq!(Block::TallGrass.name(), "tall_grass");
+        assert_eq!(Block::Glowstone.name(), "glowstone");
+    }
+
+    #[test]
+    fn names_are_the_variants_in_snake_case() {
+        for &block in Block::ALL {
+            let mut snake_case = String::new();
+            for (index, char) in format!("{block:?}").chars().enumerate() {
+                if char.is_ascii_uppercase() && index > 0 {
+                    snake_case.push('_');
+                }
+                snake_case.push(char.to_ascii_lowercase());
+            }
+            assert_eq!(block.name(), snake_case);
+        }
+    }
+
+    #[test]
+    fn unknown_names_are_reported() {
+        let err = "bedrock".parse::<Block>().unwrap_err();
+        assert_eq!(err.0, "bedrock");
+        assert_eq!(err.to_string(), "unknown block `bedrock`");
+        // Variant names aren't block names
+        assert!("TallGrass".parse::<Block>().is_err());
+        assert!("".parse::<Block>().is_err());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_round_trip() {
+        for &block in Block::ALL {
+            let json = serde_json::to_string(&block).unwrap();
+            assert_eq!(json, format!("\"{}\"", block.name()));
+            assert_eq!(serde_json::from_str::<Block>(&json).unwrap(), block);
+        }
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_reads_variant_names() {
+        assert_eq!(
+            serde_json::from_str::<Block>("\"TallGrass\"").unwrap(),
+            Block::TallGrass
+        );
+        assert!(serde_json::from_str::<Block>("\"Bedrock\"").is_err());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn visibilities_round_trip() {
+        for visibility in [
+            Visibility::Opaque,
+            Visibility::Transparent,
+            Visibility::Empty,
+        ] {
+            let json = serde_json::to_string(&visibility).unwrap();
+            assert_eq!(
+                serde_json::from_str::<Visibility>(&json).unwrap(),
+                visibility
+            );
+        }
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn directions_round_trip() {
+        use crate::world::Direction;
+
+        for direction in [
+            Direction::Top,
+            Direction::Bottom,
+            Direction::Left,
+            Direction::Right,
+            Direction::Front,
+            Direction::Back,
+        ] {
+            let json = serde_json::to_string(&direction).unwrap();
+            assert_eq!(serde_json::from_str::<Direction>(&json).unwrap(), direction);
+        }
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_reads_from_readers() {
+        let json = serde_json::to_vec(&[Block::Stone, Block::TallGrass]).unwrap();
+        assert_eq!(
+            serde_json::from_reader::<_, Vec<Block>>(json.as_slice()).unwrap(),
+            [Block::Stone, Block::TallGrass]
+        );
+    }
+}
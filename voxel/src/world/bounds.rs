@@ -0,0 +1,112 @@
+use glam::{IVec3, Vec3};
+
+use super::{
+    chunk::{ChunkSectionPosition, SECTION_SIZE},
+    coords,
+};
+
+/// Inclusive range of sections that make up a finite world
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct WorldBounds {
+    pub min_section: ChunkSectionPosition,
+    pub max_section: ChunkSectionPosition,
+}
+
+impl WorldBounds {
+    pub const fn new(min_section: ChunkSectionPosition, max_section: ChunkSectionPosition) -> Self {
+        Self {
+            min_section,
+            max_section,
+        }
+    }
+
+    pub fn contains_section(&self, position: ChunkSectionPosition) -> bool {
+        (self.min_section.x..=self.max_section.x).contains(&position.x)
+            && (self.min_section.z..=self.max_section.z).contains(&position.z)
+    }
+
+    pub fn contains_chunk(&self, position: IVec3) -> bool {
+        self.contains_section(position.into())
+    }
+
+    /// Lowest block coordinate inside the bounds
+    pub fn min_block(&self) -> IVec3 {
+        coords::chunk_to_block_min(self.min_section.with_y(0))
+    }
+
+    /// Block coordinate just past the highest block inside the bounds
+    pub fn max_block(&self) -> IVec3 {
+        coords::chunk_to_block_min(
+            self.max_section.with_y(SECTION_SIZE as i32) + IVec3::new(1, 0, 1),
+        )
+    }
+
+    pub fn clamp(&self, position: Vec3) -> Vec3 {
+        let min = self.min_block().as_vec3();
+        let max = self.max_block().as_vec3();
+
+        Vec3::new(
+            position.x.clamp(min.x, max.x),
+            position.y,
+            position.z.clamp(min.z, max.z),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::UVec3;
+
+    use super::*;
+    use crate::world::{
+        chunk::{Chunk, ChunkNeighborhood, CHUNK_SIZE},
+        Block, ChunkMap,
+    };
+
+    const BOUNDS: WorldBounds = WorldBounds::new(
+        ChunkSectionPosition::new(-1, 0),
+        ChunkSectionPosition::new(1, 2),
+    );
+
+    #[test]
+    fn contains_both_corners() {
+        assert!(BOUNDS.contains_section(ChunkSectionPosition::new(-1, 0)));
+        assert!(BOUNDS.contains_section(ChunkSectionPosition::new(1, 2)));
+        assert!(!BOUNDS.contains_section(ChunkSectionPosition::new(2, 0)));
+        assert!(!BOUNDS.contains_section(ChunkSectionPosition::new(0, -1)));
+        // Any height
+        assert!(BOUNDS.contains_chunk(IVec3::new(0, 100, 0)));
+        assert!(!BOUNDS.contains_chunk(IVec3::new(-2, 0, 0)));
+    }
+
+    #[test]
+    fn block_range_covers_the_sections() {
+        let size = CHUNK_SIZE as i32;
+        assert_eq!(BOUNDS.min_block(), IVec3::new(-size, 0, 0));
+        assert_eq!(
+            BOUNDS.max_block(),
+            IVec3::new(2 * size, SECTION_SIZE as i32 * size, 3 * size)
+        );
+    }
+
+    #[test]
+    fn clamp_keeps_the_height() {
+        let clamped = BOUNDS.clamp(Vec3::new(-100.0, 500.0, 20.0));
+        assert_eq!(clamped, Vec3::new(-16.0, 500.0, 20.0));
+        let inside = Vec3::new(3.5, 10.0, 40.25);
+        assert_eq!(BOUNDS.clamp(inside), inside);
+    }
+
+    #[test]
+    fn faces_toward_the_border_are_hidden() {
+        let chunks = [(IVec3::new(1, 0, 0), Chunk::Uniform(Block::Dirt))]
+            .into_iter()
+            .collect::<ChunkMap>();
+        let neighborhood = ChunkNeighborhood::new(&chunks, IVec3::new(1, 0, 0), Some(BOUNDS))
+            .expect("center chunk is loaded");
+        // Past the border on +X, air that wasn't generated yet on -X
+        let size = CHUNK_SIZE as u32;
+        assert_eq!(neighborhood.get(UVec3::new(size + 1, 1, 1)), Block::Stone);
+        assert_eq!(neighborhood.get(UVec3::new(0, 1, 1)), Block::Air);
+    }
+}
@@ -1,21 +1,36 @@
 use std::{
-    collections::HashMap,
-    ops::{Add, Index, IndexMut},
+    ops::{Add, Index},
+    sync::Arc,
 };
 
 use glam::{uvec3, IVec3, UVec3};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
 
-use super::Block;
+use super::{Block, ChunkMap, Visibility, WorldBounds};
 
+/// Cube of `SIZE` blocks along every side. Indices run along x first, then y, then z, so
+/// x is the contiguous axis. That is not the order [`RawChunk`] stores its blocks in, which
+/// is `[y][x][z]` with z contiguous, so an index doesn't address a block in memory order
 pub trait Volume {
     const SIZE: u32;
 
     fn linearize(position: impl Into<(u32, u32, u32)>) -> u32 {
         let (x, y, z) = position.into();
+        debug_assert!(
+            x < Self::SIZE && y < Self::SIZE && z < Self::SIZE,
+            "position ({x}, {y}, {z}) is outside a volume of size {}",
+            Self::SIZE
+        );
         x + (y * Self::SIZE) + (z * Self::SIZE * Self::SIZE)
     }
 
     fn delinearize(mut index: u32) -> UVec3 {
+        debug_assert!(
+            index < Self::SIZE.pow(3),
+            "index {index} is outside a volume of size {}",
+            Self::SIZE
+        );
         let z = index / (Self::SIZE * Self::SIZE);
         index -= z * (Self::SIZE * Self::SIZE);
 
@@ -28,16 +43,116 @@ pub trait Volume {
     }
 }
 
+/// Blocks along every side of a chunk. Everything else that depends on it is derived from
+/// here, the vertex packing and LOD cells check at compile time that they still fit
 pub const CHUNK_SIZE: usize = 16;
 
-pub type ChunkSlice = [[Block; CHUNK_SIZE]; CHUNK_SIZE];
+pub type ChunkSlice<T = Block> = [[T; CHUNK_SIZE]; CHUNK_SIZE];
+
+/// One bit per block along the z axis, set when the block is not air
+pub type OccupancyRow = u32;
+
+const _: () = assert!(
+    CHUNK_SIZE <= OccupancyRow::BITS as usize,
+    "a chunk row doesn't fit into its occupancy bits"
+);
+
+/// Temperature of every `(x, z)` column, `0` is the coldest and `u8::MAX` the hottest
+pub type Temperatures = [[u8; CHUNK_SIZE]; CHUNK_SIZE];
+
+/// Block light of every block, laid out like the blocks themselves
+pub type LightLevels = [ChunkSlice<u8>; CHUNK_SIZE];
 
 #[derive(Default, Clone)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(try_from = "Vec<Block>", into = "Vec<Block>")
+)]
 pub struct RawChunk {
-    pub stack: [ChunkSlice; CHUNK_SIZE],
+    stack: [ChunkSlice; CHUNK_SIZE],
+    occupancy: [[OccupancyRow; CHUNK_SIZE]; CHUNK_SIZE],
+    // Not part of the serialized blocks, dumped chunks come back at the coldest temperature
+    temperatures: Temperatures,
+    // Only allocated once some light reaches the chunk, also not serialized
+    light: Option<Box<LightLevels>>,
 }
 
 impl RawChunk {
+    pub fn filled(block: Block) -> Self {
+        let row = if block == Block::Air {
+            0
+        } else {
+            OccupancyRow::MAX
+        };
+
+        Self {
+            stack: [[[block; CHUNK_SIZE]; CHUNK_SIZE]; CHUNK_SIZE],
+            occupancy: [[row; CHUNK_SIZE]; CHUNK_SIZE],
+            temperatures: Default::default(),
+            light: None,
+        }
+    }
+
+    pub fn set_block(&mut self, position: UVec3, block: Block) {
+        let (x, y, z) = (
+            position.x as usize,
+            position.y as usize,
+            position.z as usize,
+        );
+
+        self.stack[y][x][z] = block;
+        if block == Block::Air {
+            self.occupancy[y][x] &= !(1 << z);
+        } else {
+            self.occupancy[y][x] |= 1 << z;
+        }
+    }
+
+    pub fn temperature(&self, x: u32, z: u32) -> u8 {
+        self.temperatures[x as usize][z as usize]
+    }
+
+    pub fn temperatures(&self) -> &Temperatures {
+        &self.temperatures
+    }
+
+    pub fn set_temperatures(&mut self, temperatures: Temperatures) {
+        self.temperatures = temperatures;
+    }
+
+    pub fn light(&self, position: UVec3) -> u8 {
+        self.light.as_ref().map_or(0, |light| {
+            light[position.y as usize][position.x as usize][position.z as usize]
+        })
+    }
+
+    pub fn set_light(&mut self, position: UVec3, level: u8) {
+        if self.light.is_none() && level == 0 {
+            return;
+        }
+
+        let light = self.light.get_or_insert_with(Default::default);
+        light[position.y as usize][position.x as usize][position.z as usize] = level;
+    }
+
+    pub fn has_light(&self) -> bool {
+        self.light.is_some()
+    }
+
+    /// Occupancy of the blocks at `(x, y, 0..CHUNK_SIZE)`
+    pub fn row_occupancy(&self, x: u32, y: u32) -> OccupancyRow {
+        self.occupancy[y as usize][x as usize]
+    }
+
+    pub fn is_slice_empty(&self, y: u32) -> bool {
+        self.occupancy[y as usize].iter().all(|&row| row == 0)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.occupancy.iter().flatten().all(|&row| row == 0)
+    }
+
     pub fn iter(&self) -> impl Iterator<Item = Block> + '_ {
         self.stack.iter().copied().flatten().flatten()
     }
@@ -54,6 +169,46 @@ impl RawChunk {
             })
         })
     }
+
+    pub fn is_uniform(&self) -> Option<Block> {
+        if self.is_empty() {
+            return Some(Block::Air);
+        }
+
+        let mut blocks = self.iter();
+        let first = blocks.next()?;
+
+        blocks.all(|block| block == first).then_some(first)
+    }
+}
+
+impl TryFrom<Vec<Block>> for RawChunk {
+    type Error = String;
+
+    fn try_from(blocks: Vec<Block>) -> Result<Self, Self::Error> {
+        let expected = CHUNK_SIZE.pow(3);
+        if blocks.len() != expected {
+            return Err(format!("expected {expected} blocks, got {}", blocks.len()));
+        }
+
+        let mut chunk = Self::default();
+        for (index, block) in blocks.into_iter().enumerate() {
+            let (y, x, z) = (
+                index / (CHUNK_SIZE * CHUNK_SIZE),
+                index / CHUNK_SIZE % CHUNK_SIZE,
+                index % CHUNK_SIZE,
+            );
+            chunk.set_block(uvec3(x as u32, y as u32, z as u32), block);
+        }
+
+        Ok(chunk)
+    }
+}
+
+impl From<RawChunk> for Vec<Block> {
+    fn from(chunk: RawChunk) -> Self {
+        chunk.iter().collect()
+    }
 }
 
 impl Index<UVec3> for RawChunk {
@@ -64,17 +219,132 @@ impl Index<UVec3> for RawChunk {
     }
 }
 
-impl IndexMut<UVec3> for RawChunk {
-    fn index_mut(&mut self, position: UVec3) -> &mut Self::Output {
-        &mut self.stack[position.y as usize][position.x as usize][position.z as usize]
+impl Volume for RawChunk {
+    const SIZE: u32 = CHUNK_SIZE as u32;
+}
+
+/// A chunk as stored in the world, homogeneous chunks keep a single block instead of the whole stack
+#[derive(Clone)]
+pub enum Chunk {
+    // Uniform chunks don't keep temperatures, none of the blocks filling whole chunks are tinted
+    Uniform(Block),
+    Dense(Arc<RawChunk>),
+}
+
+impl Chunk {
+    pub fn get(&self, position: UVec3) -> Block {
+        self[position]
+    }
+
+    /// Sets the block, a uniform chunk is only expanded when the block differs
+    pub fn set(&mut self, position: UVec3, block: Block) {
+        match self {
+            Chunk::Uniform(current) if *current == block => {}
+            Chunk::Uniform(current) => {
+                let mut chunk = RawChunk::filled(*current);
+                chunk.set_block(position, block);
+                *self = Chunk::Dense(Arc::new(chunk));
+            }
+            Chunk::Dense(chunk) => Arc::make_mut(chunk).set_block(position, block),
+        }
+    }
+
+    pub fn temperature(&self, x: u32, z: u32) -> u8 {
+        match self {
+            Chunk::Uniform(..) => 0,
+            Chunk::Dense(chunk) => chunk.temperature(x, z),
+        }
+    }
+
+    /// Block light at the position, uniform chunks are always dark
+    pub fn light(&self, position: UVec3) -> u8 {
+        match self {
+            Chunk::Uniform(..) => 0,
+            Chunk::Dense(chunk) => chunk.light(position),
+        }
+    }
+
+    /// Sets the block light, a uniform chunk is expanded once it gets any
+    pub fn set_light(&mut self, position: UVec3, level: u8) {
+        match self {
+            Chunk::Uniform(..) if level == 0 => {}
+            Chunk::Uniform(block) => {
+                let mut chunk = RawChunk::filled(*block);
+                chunk.set_light(position, level);
+                *self = Chunk::Dense(Arc::new(chunk));
+            }
+            Chunk::Dense(chunk) => Arc::make_mut(chunk).set_light(position, level),
+        }
+    }
+
+    pub fn has_light(&self) -> bool {
+        matches!(self, Chunk::Dense(chunk) if chunk.has_light())
+    }
+
+    /// Bytes the chunk keeps on the heap, uniform chunks have nothing there
+    pub fn heap_size(&self) -> usize {
+        match self {
+            Chunk::Uniform(_) => 0,
+            Chunk::Dense(chunk) if chunk.has_light() => {
+                size_of::<RawChunk>() + size_of::<LightLevels>()
+            }
+            Chunk::Dense(_) => size_of::<RawChunk>(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        match self {
+            Chunk::Uniform(block) => *block == Block::Air,
+            Chunk::Dense(chunk) => chunk.is_empty(),
+        }
+    }
+
+    /// Block filling the whole chunk, edited dense chunks are checked block by block
+    pub fn is_uniform(&self) -> Option<Block> {
+        match self {
+            Chunk::Uniform(block) => Some(*block),
+            Chunk::Dense(chunk) => chunk.is_uniform(),
+        }
+    }
+
+    /// Whether the chunk is known to be filled with an opaque block without looking at its blocks
+    fn is_solid(&self) -> bool {
+        matches!(self, Chunk::Uniform(block) if block.visibility() == Visibility::Opaque)
+    }
+
+    pub fn to_raw(&self) -> RawChunk {
+        match self {
+            Chunk::Uniform(block) => RawChunk::filled(*block),
+            Chunk::Dense(chunk) => RawChunk::clone(chunk),
+        }
     }
 }
 
-impl Volume for RawChunk {
-    const SIZE: u32 = CHUNK_SIZE as u32;
+impl Default for Chunk {
+    fn default() -> Self {
+        Chunk::Uniform(Block::Air)
+    }
+}
+
+impl From<RawChunk> for Chunk {
+    fn from(chunk: RawChunk) -> Self {
+        match chunk.is_uniform() {
+            Some(block) if !chunk.has_light() => Chunk::Uniform(block),
+            _ => Chunk::Dense(Arc::new(chunk)),
+        }
+    }
 }
 
-pub type Chunk = Box<RawChunk>;
+impl Index<UVec3> for Chunk {
+    type Output = Block;
+
+    fn index(&self, position: UVec3) -> &Self::Output {
+        match self {
+            Chunk::Uniform(block) => block,
+            Chunk::Dense(chunk) => &chunk[position],
+        }
+    }
+}
 
 #[derive(Default, Clone, Copy)]
 pub struct ChunkOrAir<'s>(pub Option<&'s Chunk>);
@@ -97,7 +367,7 @@ impl Index<UVec3> for ChunkOrAir<'_> {
     }
 }
 
-const OFFSETS: [IVec3; 6] = [
+pub(super) const OFFSETS: [IVec3; 6] = [
     IVec3::X,
     IVec3::NEG_X,
     IVec3::Y,
@@ -106,57 +376,102 @@ const OFFSETS: [IVec3; 6] = [
     IVec3::NEG_Z,
 ];
 
-#[derive(Clone, Copy)]
-pub struct ChunkNeighborhood<'s> {
-    chunks: &'s HashMap<IVec3, Chunk>,
+// Faces looking outside of the world bounds are hidden behind this chunk
+const BOUNDARY_CHUNK: Chunk = Chunk::Uniform(Block::Stone);
+
+/// Snapshot of a chunk and its direct neighbors, taken without holding on to the chunks lock
+#[derive(Clone)]
+pub struct ChunkNeighborhood {
     center: IVec3,
+    center_chunk: Chunk,
+    neighbors: [Option<Chunk>; 6],
 }
 
-impl<'s> ChunkNeighborhood<'s> {
-    pub fn new(chunks: &'s HashMap<IVec3, Chunk>, center: IVec3) -> Self {
-        Self { chunks, center }
+impl ChunkNeighborhood {
+    pub fn new(chunks: &ChunkMap, center: IVec3, bounds: Option<WorldBounds>) -> Option<Self> {
+        let (center_chunk, neighbors) = chunks.neighbors(center)?;
+        let mut neighbors = neighbors.map(|chunk| chunk.cloned());
+        if let Some(bounds) = bounds {
+            for (neighbor, offset) in neighbors.iter_mut().zip(OFFSETS) {
+                if !bounds.contains_chunk(center + offset) {
+                    *neighbor = Some(BOUNDARY_CHUNK);
+                }
+            }
+        }
+
+        Some(Self {
+            center,
+            center_chunk: center_chunk.clone(),
+            neighbors,
+        })
     }
 
-    pub fn get(&self, position: UVec3) -> Block {
+    /// Chunk holding the position in the neighborhood space and the position within it,
+    /// `None` for corners and edges, which no neighbor covers
+    fn locate(&self, position: UVec3) -> Option<(Option<&Chunk>, UVec3)> {
         const MAX: u32 = RawChunk::SIZE + 1;
 
-        let center = self.chunks.get(&self.center).unwrap();
-        let neighbors = OFFSETS.map(|offset| self.center + offset).map(|position| {
-            self.chunks
-                .get(&position)
-                .map(ChunkOrAir::new)
-                .unwrap_or_default()
-        });
-
-        match (position.x, position.y, position.z) {
-            (1..=RawChunk::SIZE, 1..=RawChunk::SIZE, 1..=RawChunk::SIZE) => {
-                center[(position.x - 1, position.y - 1, position.z - 1).into()]
-            }
+        let neighbor = |index: usize| self.neighbors[index].as_ref();
+        let located = match (position.x, position.y, position.z) {
+            (1..=RawChunk::SIZE, 1..=RawChunk::SIZE, 1..=RawChunk::SIZE) => (
+                Some(&self.center_chunk),
+                (position.x - 1, position.y - 1, position.z - 1),
+            ),
             (MAX, 1..=RawChunk::SIZE, 1..=RawChunk::SIZE) => {
-                neighbors[0][(0, position.y - 1, position.z - 1).into()]
-            }
-            (0, 1..=RawChunk::SIZE, 1..=RawChunk::SIZE) => {
-                neighbors[1][(RawChunk::SIZE - 1, position.y - 1, position.z - 1).into()]
+                (neighbor(0), (0, position.y - 1, position.z - 1))
             }
+            (0, 1..=RawChunk::SIZE, 1..=RawChunk::SIZE) => (
+                neighbor(1),
+                (RawChunk::SIZE - 1, position.y - 1, position.z - 1),
+            ),
             (1..=RawChunk::SIZE, MAX, 1..=RawChunk::SIZE) => {
-                neighbors[2][(position.x - 1, 0, position.z - 1).into()]
-            }
-            (1..=RawChunk::SIZE, 0, 1..=RawChunk::SIZE) => {
-                neighbors[3][(position.x - 1, RawChunk::SIZE - 1, position.z - 1).into()]
+                (neighbor(2), (position.x - 1, 0, position.z - 1))
             }
+            (1..=RawChunk::SIZE, 0, 1..=RawChunk::SIZE) => (
+                neighbor(3),
+                (position.x - 1, RawChunk::SIZE - 1, position.z - 1),
+            ),
             (1..=RawChunk::SIZE, 1..=RawChunk::SIZE, MAX) => {
-                neighbors[4][(position.x - 1, position.y - 1, 0).into()]
-            }
-            (1..=RawChunk::SIZE, 1..=RawChunk::SIZE, 0) => {
-                neighbors[5][(position.x - 1, position.y - 1, RawChunk::SIZE - 1).into()]
+                (neighbor(4), (position.x - 1, position.y - 1, 0))
             }
-            (_, _, _) => Block::Air,
-        }
+            (1..=RawChunk::SIZE, 1..=RawChunk::SIZE, 0) => (
+                neighbor(5),
+                (position.x - 1, position.y - 1, RawChunk::SIZE - 1),
+            ),
+            (_, _, _) => return None,
+        };
+
+        Some((located.0, located.1.into()))
+    }
+
+    pub fn get(&self, position: UVec3) -> Block {
+        self.locate(position)
+            .map_or(Block::Air, |(chunk, local)| ChunkOrAir(chunk)[local])
+    }
+
+    /// Block light at the position, see [`ChunkNeighborhood::get`]
+    pub fn light(&self, position: UVec3) -> u8 {
+        self.locate(position)
+            .and_then(|(chunk, local)| Some(chunk?.light(local)))
+            .unwrap_or(0)
     }
 
     pub fn center(&self) -> IVec3 {
         self.center
     }
+
+    pub fn center_chunk(&self) -> &Chunk {
+        &self.center_chunk
+    }
+
+    /// Whether the center and all of its neighbors are uniformly opaque, so no face can be visible
+    pub fn is_buried(&self) -> bool {
+        self.center_chunk.is_solid()
+            && self
+                .neighbors
+                .iter()
+                .all(|chunk| chunk.as_ref().is_some_and(Chunk::is_solid))
+    }
 }
 
 pub const SECTION_SIZE: usize = 16;
@@ -214,8 +529,14 @@ impl Add for ChunkSectionPosition {
 }
 
 #[derive(Default, Clone)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(try_from = "SerializedSection", into = "SerializedSection")
+)]
 pub struct ChunkSection {
-    chunks: [Option<Chunk>; SECTION_SIZE],
+    chunks: [Option<Box<RawChunk>>; SECTION_SIZE],
+    temperatures: Temperatures,
 }
 
 impl ChunkSection {
@@ -223,24 +544,100 @@ impl ChunkSection {
         self.chunks
             .into_iter()
             .enumerate()
-            .filter_map(|(position, chunk)| {
-                let chunk = chunk?;
-                if chunk.iter().any(|block| block != Block::Air) {
-                    Some((position, chunk))
+            .filter_map(move |(position, chunk)| {
+                let mut chunk = chunk?;
+                chunk.temperatures = self.temperatures;
+                if !chunk.is_empty() {
+                    Some((position, Chunk::from(*chunk)))
                 } else {
                     None
                 }
             })
     }
 
-    pub fn set(&mut self, position: UVec3, block: Block) {
-        assert!(block != Block::Air);
+    pub fn get(&self, position: UVec3) -> Block {
+        self[position]
+    }
 
+    /// Sets the block, chunks emptied by air are only dropped once the section is split
+    pub fn set(&mut self, position: UVec3, block: Block) {
         let index = (position.y / RawChunk::SIZE) as usize;
         let position = position.with_y(position.y % RawChunk::SIZE);
 
-        let chunk = self.chunks[index].get_or_insert_with(Default::default);
-        chunk[position] = block;
+        let chunk = match &mut self.chunks[index] {
+            Some(chunk) => chunk,
+            None if block == Block::Air => return,
+            None => self.chunks[index].insert(Default::default()),
+        };
+        chunk.set_block(position, block);
+    }
+
+    /// Sets every block between `min` and `max`, both inclusive
+    pub fn fill_region(&mut self, min: UVec3, max: UVec3, block: Block) {
+        for y in min.y..=max.y {
+            for z in min.z..=max.z {
+                for x in min.x..=max.x {
+                    self.set(uvec3(x, y, z), block);
+                }
+            }
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.chunks.iter().flatten().all(|chunk| chunk.is_empty())
+    }
+
+    pub fn set_temperature(&mut self, x: u32, z: u32, temperature: u8) {
+        self.temperatures[x as usize][z as usize] = temperature;
+    }
+}
+
+/// Only the chunks with blocks in them are written, along with their place in the section
+#[cfg(feature = "serde")]
+#[derive(Serialize, Deserialize)]
+struct SerializedSection {
+    chunks: Vec<(usize, RawChunk)>,
+    temperatures: Temperatures,
+}
+
+#[cfg(feature = "serde")]
+impl TryFrom<SerializedSection> for ChunkSection {
+    type Error = String;
+
+    fn try_from(serialized: SerializedSection) -> Result<Self, Self::Error> {
+        let mut section = Self {
+            temperatures: serialized.temperatures,
+            ..Default::default()
+        };
+        for (index, chunk) in serialized.chunks {
+            let slot = section
+                .chunks
+                .get_mut(index)
+                .ok_or_else(|| format!("chunk index {index} is outside of the section"))?;
+            if slot.replace(Box::new(chunk)).is_some() {
+                return Err(format!("chunk index {index} appears twice"));
+            }
+        }
+
+        Ok(section)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl From<ChunkSection> for SerializedSection {
+    fn from(section: ChunkSection) -> Self {
+        let chunks = section
+            .chunks
+            .into_iter()
+            .enumerate()
+            .filter_map(|(index, chunk)| Some((index, *chunk?)))
+            .filter(|(_, chunk)| !chunk.is_empty())
+            .collect();
+
+        Self {
+            chunks,
+            temperatures: section.temperatures,
+        }
     }
 }
 
@@ -257,3 +654,176 @@ impl Index<UVec3> for ChunkSection {
         &chunk[position]
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Odd;
+
+    impl Volume for Odd {
+        const SIZE: u32 = 7;
+    }
+
+    fn round_trip<V: Volume>() {
+        for index in 0..V::SIZE.pow(3) {
+            let position = V::delinearize(index);
+            assert!(position.cmplt(UVec3::splat(V::SIZE)).all());
+            assert_eq!(V::linearize(position), index);
+        }
+    }
+
+    #[test]
+    fn linearize_round_trips() {
+        round_trip::<RawChunk>();
+        round_trip::<Odd>();
+    }
+
+    #[test]
+    fn x_is_contiguous() {
+        assert_eq!(RawChunk::linearize((1, 0, 0)), 1);
+        assert_eq!(RawChunk::linearize((0, 1, 0)), RawChunk::SIZE);
+        assert_eq!(RawChunk::linearize((0, 0, 1)), RawChunk::SIZE.pow(2));
+    }
+
+    #[test]
+    fn uniform_chunks_are_recognized() {
+        assert_eq!(RawChunk::default().is_uniform(), Some(Block::Air));
+        let mut chunk = RawChunk::filled(Block::Stone);
+        assert_eq!(chunk.is_uniform(), Some(Block::Stone));
+
+        chunk.set_block(UVec3::splat(15), Block::Dirt);
+        assert_eq!(chunk.is_uniform(), None);
+        chunk.set_block(UVec3::splat(15), Block::Stone);
+        assert_eq!(chunk.is_uniform(), Some(Block::Stone));
+
+        let mut chunk = RawChunk::default();
+        chunk.set_block(UVec3::ZERO, Block::Stone);
+        assert_eq!(chunk.is_uniform(), None);
+        chunk.set_block(UVec3::ZERO, Block::Air);
+        assert_eq!(chunk.is_uniform(), Some(Block::Air));
+    }
+
+    #[test]
+    fn writing_another_block_expands_uniform_chunks() {
+        let mut chunk = Chunk::Uniform(Block::Stone);
+        chunk.set(uvec3(3, 4, 5), Block::Dirt);
+        let Chunk::Dense(raw) = &chunk else {
+            panic!("the chunk stayed uniform");
+        };
+        assert_eq!(raw.is_uniform(), None);
+        assert_eq!(chunk.get(uvec3(3, 4, 5)), Block::Dirt);
+        assert_eq!(chunk.get(uvec3(5, 4, 3)), Block::Stone);
+    }
+
+    #[test]
+    fn writing_the_same_block_keeps_uniform_chunks() {
+        let mut chunk = Chunk::Uniform(Block::Stone);
+        chunk.set(uvec3(3, 4, 5), Block::Stone);
+        assert!(matches!(chunk, Chunk::Uniform(Block::Stone)));
+        assert_eq!(chunk.heap_size(), 0);
+
+        let mut chunk = Chunk::default();
+        chunk.set(UVec3::ZERO, Block::Air);
+        chunk.set_light(UVec3::ZERO, 0);
+        assert!(matches!(chunk, Chunk::Uniform(Block::Air)));
+    }
+
+    #[test]
+    fn sections_split_at_chunk_borders() {
+        let mut section = ChunkSection::default();
+        section.set(uvec3(2, 15, 3), Block::Stone);
+        section.set(uvec3(2, 16, 3), Block::Dirt);
+        assert_eq!(section.get(uvec3(2, 15, 3)), Block::Stone);
+        assert_eq!(section.get(uvec3(2, 16, 3)), Block::Dirt);
+        assert_eq!(
+            section.chunks[0].as_ref().unwrap()[uvec3(2, 15, 3)],
+            Block::Stone
+        );
+        assert_eq!(
+            section.chunks[1].as_ref().unwrap()[uvec3(2, 0, 3)],
+            Block::Dirt
+        );
+
+        // Clearing one side leaves the other alone
+        section.set(uvec3(2, 15, 3), Block::Air);
+        assert_eq!(section.get(uvec3(2, 15, 3)), Block::Air);
+        assert_eq!(section.get(uvec3(2, 16, 3)), Block::Dirt);
+
+        section.fill_region(uvec3(0, 14, 0), uvec3(1, 17, 1), Block::Gravel);
+        for y in 14..=17 {
+            assert_eq!(section.get(uvec3(1, y, 1)), Block::Gravel);
+        }
+        assert_eq!(section.get(uvec3(1, 18, 1)), Block::Air);
+        assert_eq!(section.get(uvec3(1, 13, 1)), Block::Air);
+    }
+
+    #[test]
+    fn air_on_missing_chunks_is_ignored() {
+        let mut section = ChunkSection::default();
+        section.set(uvec3(4, 40, 4), Block::Air);
+        section.fill_region(UVec3::ZERO, uvec3(15, 20, 15), Block::Air);
+        assert!(section.chunks.iter().all(Option::is_none));
+        assert!(section.is_empty());
+    }
+
+    #[test]
+    fn sections_are_empty_again_once_cleared() {
+        let mut section = ChunkSection::default();
+        section.fill_region(uvec3(3, 10, 3), uvec3(5, 20, 5), Block::Stone);
+        assert!(!section.is_empty());
+
+        section.fill_region(uvec3(3, 10, 3), uvec3(5, 20, 5), Block::Air);
+        assert!(section.is_empty());
+        assert_eq!(section.into_chunks().count(), 0);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn sections_round_trip_without_empty_chunks() {
+        let mut section = ChunkSection::default();
+        section.fill_region(uvec3(0, 0, 0), uvec3(15, 3, 15), Block::Stone);
+        section.fill_region(uvec3(4, 14, 2), uvec3(6, 18, 9), Block::Sand);
+        section.set(uvec3(7, 200, 8), Block::Glowstone);
+        section.set_temperature(3, 12, 170);
+        // Left allocated but empty
+        section.set(uvec3(1, 100, 1), Block::Dirt);
+        section.set(uvec3(1, 100, 1), Block::Air);
+
+        let json = serde_json::to_value(&section).unwrap();
+        let indices = json["chunks"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|chunk| chunk[0].as_u64().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(indices, [0, 1, 12]);
+
+        let loaded = serde_json::from_value::<ChunkSection>(json).unwrap();
+        let height = RawChunk::SIZE * SECTION_SIZE as u32;
+        for y in 0..height {
+            for z in 0..RawChunk::SIZE {
+                for x in 0..RawChunk::SIZE {
+                    let position = uvec3(x, y, z);
+                    assert_eq!(loaded.get(position), section.get(position), "{position}");
+                }
+            }
+        }
+        assert_eq!(loaded.temperatures, section.temperatures);
+        assert!(loaded.chunks[6].is_none());
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "outside a volume")]
+    fn linearize_rejects_positions_outside() {
+        RawChunk::linearize((0, RawChunk::SIZE, 0));
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "outside a volume")]
+    fn delinearize_rejects_indices_outside() {
+        RawChunk::delinearize(RawChunk::SIZE.pow(3));
+    }
+}
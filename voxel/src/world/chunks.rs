@@ -1,19 +1,259 @@
 use std::{
     collections::HashMap,
-    ops::{Deref, DerefMut},
+    mem,
+    ops::{Deref, DerefMut, Index},
     sync::Arc,
 };
 
-use glam::IVec3;
-use parking_lot::{RwLock, RwLockReadGuard, RwLockWriteGuard};
+use glam::{IVec3, UVec3};
+use parking_lot::{Mutex, RwLock, RwLockReadGuard, RwLockWriteGuard};
+use rustc_hash::FxHashMap;
 
-use super::chunk::Chunk;
+use super::{
+    chunk::{Chunk, ChunkNeighborhood, RawChunk, Volume, OFFSETS},
+    coords, Block, WorldBounds,
+};
+
+/// Loaded chunks by their position. Every chunk also keeps the slots of its six neighbors,
+/// so [`ChunkMap::neighbors`] takes a single lookup instead of seven
+#[derive(Default, Clone)]
+pub struct ChunkMap {
+    // Hashed with a hasher cheap enough for the many lookups of lighting and meshing
+    slots: FxHashMap<IVec3, u32>,
+    entries: Vec<Option<(IVec3, Chunk)>>,
+    // Slot of the neighbor at each of the `OFFSETS` of every entry, kept up to date on insert
+    // and remove. Apart from the entries, so walking them stays dense
+    neighbors: Vec<[u32; 6]>,
+    // Slots of removed chunks, reused by the next insertions
+    free: Vec<u32>,
+}
+
+// Neighbor slot of a chunk without that neighbor
+const EMPTY: u32 = u32::MAX;
+
+// Index of the offset pointing back, `OFFSETS` alternates between both directions of an axis
+const fn opposite(index: usize) -> usize {
+    index ^ 1
+}
+
+impl ChunkMap {
+    pub fn len(&self) -> usize {
+        self.slots.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.slots.is_empty()
+    }
+
+    pub fn contains_key(&self, position: &IVec3) -> bool {
+        self.slots.contains_key(position)
+    }
+
+    pub fn get(&self, position: &IVec3) -> Option<&Chunk> {
+        let slot = *self.slots.get(position)?;
+        Some(self.at(slot))
+    }
+
+    pub fn get_mut(&mut self, position: &IVec3) -> Option<&mut Chunk> {
+        let slot = *self.slots.get(position)?;
+        Some(self.at_mut(slot))
+    }
+
+    /// The chunk at the position along with the neighbor at each of the `OFFSETS`
+    pub fn neighbors(&self, position: IVec3) -> Option<(&Chunk, [Option<&Chunk>; 6])> {
+        let slot = *self.slots.get(&position)?;
+        let neighbors = self.neighbors[slot as usize]
+            .map(|neighbor| (neighbor != EMPTY).then(|| self.at(neighbor)));
+        Some((self.at(slot), neighbors))
+    }
+
+    /// Inserts the chunk and returns the one it replaced, replacing keeps the neighbors as
+    /// they are
+    pub fn insert(&mut self, position: IVec3, chunk: Chunk) -> Option<Chunk> {
+        if let Some(&slot) = self.slots.get(&position) {
+            return Some(mem::replace(self.at_mut(slot), chunk));
+        }
+
+        let neighbors = OFFSETS.map(|offset| {
+            self.slots
+                .get(&(position + offset))
+                .copied()
+                .unwrap_or(EMPTY)
+        });
+        let slot = match self.free.pop() {
+            Some(slot) => {
+                self.entries[slot as usize] = Some((position, chunk));
+                self.neighbors[slot as usize] = neighbors;
+                slot
+            }
+            None => {
+                self.entries.push(Some((position, chunk)));
+                self.neighbors.push(neighbors);
+                (self.entries.len() - 1) as u32
+            }
+        };
+        self.slots.insert(position, slot);
+        self.link(slot, slot);
 
-type RawChunks = HashMap<IVec3, Chunk>;
+        None
+    }
+
+    pub fn remove(&mut self, position: &IVec3) -> Option<Chunk> {
+        let slot = self.slots.remove(position)?;
+        let (_, chunk) = self.entries[slot as usize]
+            .take()
+            .expect("indexed slots are filled");
+        self.link(slot, EMPTY);
+        self.free.push(slot);
+
+        Some(chunk)
+    }
+
+    /// The chunk at the position, an empty one is inserted if there is none yet
+    pub fn get_or_insert_default(&mut self, position: IVec3) -> &mut Chunk {
+        let slot = match self.slots.get(&position) {
+            Some(&slot) => slot,
+            None => {
+                self.insert(position, Chunk::default());
+                self.slots[&position]
+            }
+        };
+        self.at_mut(slot)
+    }
+
+    pub fn retain(&mut self, mut keep: impl FnMut(&IVec3, &mut Chunk) -> bool) {
+        let removed = self
+            .entries
+            .iter_mut()
+            .flatten()
+            .filter_map(|(position, chunk)| (!keep(position, chunk)).then_some(*position))
+            .collect::<Vec<_>>();
+        for position in removed {
+            self.remove(&position);
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.slots.clear();
+        self.entries.clear();
+        self.neighbors.clear();
+        self.free.clear();
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&IVec3, &Chunk)> {
+        self.entries
+            .iter()
+            .flatten()
+            .map(|(position, chunk)| (position, chunk))
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &IVec3> {
+        self.iter().map(|(position, _)| position)
+    }
+
+    pub fn values(&self) -> impl Iterator<Item = &Chunk> {
+        self.iter().map(|(_, chunk)| chunk)
+    }
+
+    // Points the neighbors of the slot back at `target`
+    fn link(&mut self, slot: u32, target: u32) {
+        for (index, neighbor) in self.neighbors[slot as usize].into_iter().enumerate() {
+            if neighbor != EMPTY {
+                self.neighbors[neighbor as usize][opposite(index)] = target;
+            }
+        }
+    }
+
+    fn at(&self, slot: u32) -> &Chunk {
+        let (_, chunk) = self.entries[slot as usize]
+            .as_ref()
+            .expect("indexed slots are filled");
+        chunk
+    }
+
+    fn at_mut(&mut self, slot: u32) -> &mut Chunk {
+        let (_, chunk) = self.entries[slot as usize]
+            .as_mut()
+            .expect("indexed slots are filled");
+        chunk
+    }
+}
+
+impl Index<&IVec3> for ChunkMap {
+    type Output = Chunk;
+
+    fn index(&self, position: &IVec3) -> &Chunk {
+        self.get(position).expect("no chunk at the position")
+    }
+}
+
+impl Extend<(IVec3, Chunk)> for ChunkMap {
+    fn extend<I: IntoIterator<Item = (IVec3, Chunk)>>(&mut self, chunks: I) {
+        for (position, chunk) in chunks {
+            self.insert(position, chunk);
+        }
+    }
+}
+
+impl FromIterator<(IVec3, Chunk)> for ChunkMap {
+    fn from_iter<I: IntoIterator<Item = (IVec3, Chunk)>>(chunks: I) -> Self {
+        let mut map = Self::default();
+        map.extend(chunks);
+        map
+    }
+}
+
+/// Blocks of a chunk whose faces are out of date
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DirtyRegion {
+    /// Local positions between `min` and `max`, both included
+    Blocks {
+        min: UVec3,
+        max: UVec3,
+    },
+    Whole,
+}
+
+impl DirtyRegion {
+    pub fn block(local: UVec3) -> Self {
+        Self::Blocks {
+            min: local,
+            max: local,
+        }
+    }
+
+    /// Smallest region covering both
+    pub fn merge(self, other: Self) -> Self {
+        match (self, other) {
+            (
+                Self::Blocks { min, max },
+                Self::Blocks {
+                    min: other_min,
+                    max: other_max,
+                },
+            ) => Self::Blocks {
+                min: min.min(other_min),
+                max: max.max(other_max),
+            },
+            _ => Self::Whole,
+        }
+    }
+}
+
+type DirtyMap = HashMap<IVec3, DirtyRegion>;
+
+fn mark(dirty: &mut DirtyMap, position: IVec3, region: DirtyRegion) {
+    dirty
+        .entry(position)
+        .and_modify(|dirty| *dirty = dirty.merge(region))
+        .or_insert(region);
+}
 
 #[derive(Default, Clone)]
 pub struct Chunks {
-    chunks: Arc<RwLock<RawChunks>>,
+    chunks: Arc<RwLock<ChunkMap>>,
+    // Chunks whose meshes are out of date since the last `take_dirty`
+    dirty: Arc<Mutex<DirtyMap>>,
 }
 
 impl Chunks {
@@ -22,32 +262,335 @@ impl Chunks {
     }
 
     pub fn write(&self) -> ChunksWriteGuard<'_> {
-        ChunksWriteGuard(self.chunks.write())
+        ChunksWriteGuard {
+            chunks: self.chunks.write(),
+            dirty: &self.dirty,
+        }
+    }
+
+    /// Snapshot of the chunk and its neighbors, the lock is only held while it's taken
+    pub fn neighborhood(
+        &self,
+        position: IVec3,
+        bounds: Option<WorldBounds>,
+    ) -> Option<ChunkNeighborhood> {
+        ChunkNeighborhood::new(&self.read(), position, bounds)
+    }
+
+    /// Chunks changed since the last call that have to be meshed again, with the blocks
+    /// that changed in them
+    pub fn take_dirty(&self) -> HashMap<IVec3, DirtyRegion> {
+        mem::take(&mut self.dirty.lock())
     }
 }
 
-pub struct ChunksReadGuard<'s>(RwLockReadGuard<'s, RawChunks>);
+pub struct ChunksReadGuard<'s>(RwLockReadGuard<'s, ChunkMap>);
 
 impl Deref for ChunksReadGuard<'_> {
-    type Target = RawChunks;
+    type Target = ChunkMap;
 
     fn deref(&self) -> &Self::Target {
         &self.0
     }
 }
 
-pub struct ChunksWriteGuard<'s>(RwLockWriteGuard<'s, RawChunks>);
+pub struct ChunksWriteGuard<'s> {
+    chunks: RwLockWriteGuard<'s, ChunkMap>,
+    dirty: &'s Mutex<DirtyMap>,
+}
+
+impl ChunksWriteGuard<'_> {
+    /// Sets the block at the world position and returns the one it replaced, the chunk and
+    /// the neighbors sharing the face of the block are marked dirty
+    pub fn set_block(&mut self, position: IVec3, block: Block) -> Block {
+        let chunk_position = coords::block_to_chunk(position);
+        let local = coords::block_to_local(position);
+
+        let chunk = self.chunks.get_or_insert_default(chunk_position);
+        let previous = chunk[local];
+        if previous == block {
+            return previous;
+        }
+        chunk.set(local, block);
+
+        let mut dirty = self.dirty.lock();
+        mark(&mut dirty, chunk_position, DirtyRegion::block(local));
+        // Neighbors only mesh against the block when it's on their border
+        let size = IVec3::splat(RawChunk::SIZE as i32);
+        for offset in OFFSETS {
+            let neighbor = local.as_ivec3() + offset;
+            if neighbor.cmplt(IVec3::ZERO).any() || neighbor.cmpge(size).any() {
+                mark(&mut dirty, chunk_position + offset, DirtyRegion::Whole);
+            }
+        }
+
+        previous
+    }
+
+    /// Marks the chunks as dirty as a whole
+    pub fn mark_dirty(&mut self, positions: impl IntoIterator<Item = IVec3>) {
+        let mut dirty = self.dirty.lock();
+        for position in positions {
+            mark(&mut dirty, position, DirtyRegion::Whole);
+        }
+    }
+}
 
 impl Deref for ChunksWriteGuard<'_> {
-    type Target = RawChunks;
+    type Target = ChunkMap;
 
     fn deref(&self) -> &Self::Target {
-        &self.0
+        &self.chunks
     }
 }
 
 impl DerefMut for ChunksWriteGuard<'_> {
     fn deref_mut(&mut self) -> &mut Self::Target {
-        &mut self.0
+        &mut self.chunks
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        ptr,
+        sync::atomic::{AtomicBool, Ordering},
+        thread,
+        time::Instant,
+    };
+
+    use super::*;
+
+    // Small xorshift, the tests have to be reproducible
+    struct Rng(u32);
+
+    impl Rng {
+        fn next(&mut self) -> u32 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 17;
+            self.0 ^= self.0 << 5;
+            self.0
+        }
+
+        fn position(&mut self) -> IVec3 {
+            IVec3::new(
+                (self.next() % 6) as i32 - 3,
+                (self.next() % 6) as i32 - 3,
+                (self.next() % 6) as i32 - 3,
+            )
+        }
+    }
+
+    /// Every cached neighbor has to be the chunk a lookup finds
+    fn assert_adjacency(chunks: &ChunkMap) {
+        assert_eq!(chunks.iter().count(), chunks.len());
+        for &position in chunks.keys() {
+            let (center, neighbors) = chunks.neighbors(position).unwrap();
+            assert!(ptr::eq(center, &chunks[&position]));
+            for (neighbor, offset) in neighbors.into_iter().zip(OFFSETS) {
+                let expected = chunks.get(&(position + offset));
+                assert_eq!(neighbor.map(ptr::from_ref), expected.map(ptr::from_ref));
+            }
+        }
+    }
+
+    #[test]
+    fn neighbors_follow_inserts_and_removes() {
+        let mut chunks = ChunkMap::default();
+        let mut rng = Rng(0x9e37_79b9);
+        for _ in 0..4000 {
+            let position = rng.position();
+            match rng.next() % 8 {
+                0..=3 => {
+                    chunks.insert(position, Chunk::Uniform(Block::Stone));
+                }
+                4..=6 => {
+                    chunks.remove(&position);
+                }
+                _ => {
+                    let axis = rng.next() as i32 % 6 - 3;
+                    chunks.retain(|position, _| position.x != axis);
+                }
+            }
+            assert_adjacency(&chunks);
+        }
+    }
+
+    #[test]
+    fn replacing_keeps_neighbors() {
+        let mut chunks = ChunkMap::default();
+        chunks.insert(IVec3::ZERO, Chunk::Uniform(Block::Stone));
+        chunks.insert(IVec3::X, Chunk::Uniform(Block::Dirt));
+        let previous = chunks.insert(IVec3::X, Chunk::Uniform(Block::Sand));
+
+        assert!(matches!(previous, Some(Chunk::Uniform(Block::Dirt))));
+        let (_, neighbors) = chunks.neighbors(IVec3::ZERO).unwrap();
+        assert!(matches!(neighbors[0], Some(Chunk::Uniform(Block::Sand))));
+        assert_adjacency(&chunks);
+    }
+
+    #[test]
+    fn removed_slots_are_reused() {
+        let mut chunks = ChunkMap::default();
+        chunks.insert(IVec3::ZERO, Chunk::default());
+        chunks.insert(IVec3::Y, Chunk::default());
+        chunks.remove(&IVec3::ZERO);
+        chunks.get_or_insert_default(IVec3::NEG_Y);
+
+        assert_eq!(chunks.entries.len(), 2);
+        assert_eq!(chunks.len(), 2);
+        assert!(!chunks.contains_key(&IVec3::ZERO));
+        assert_adjacency(&chunks);
+    }
+
+    #[test]
+    fn neighbors_stay_consistent_across_threads() {
+        let chunks = Chunks::default();
+        thread::scope(|scope| {
+            for seed in [0x85eb_ca6b, 0xc2b2_ae35] {
+                let chunks = chunks.clone();
+                scope.spawn(move || {
+                    let mut rng = Rng(seed);
+                    for _ in 0..2000 {
+                        let position = rng.position();
+                        let mut chunks = chunks.write();
+                        if rng.next().is_multiple_of(2) {
+                            chunks.insert(position, Chunk::Uniform(Block::Stone));
+                        } else {
+                            chunks.remove(&position);
+                        }
+                    }
+                });
+            }
+            for _ in 0..2 {
+                let chunks = chunks.clone();
+                scope.spawn(move || {
+                    for _ in 0..500 {
+                        assert_adjacency(&chunks.read());
+                    }
+                });
+            }
+        });
+        assert_adjacency(&chunks.read());
+    }
+
+    #[test]
+    fn edits_coalesce_into_dirty_regions() {
+        let chunks = Chunks::default();
+        {
+            let mut chunks = chunks.write();
+            chunks.set_block(IVec3::new(3, 4, 5), Block::Stone);
+            chunks.set_block(IVec3::new(6, 2, 5), Block::Stone);
+            // Same block again, nothing changes
+            chunks.set_block(IVec3::new(6, 2, 5), Block::Stone);
+        }
+        let dirty = chunks.take_dirty();
+        assert_eq!(
+            dirty,
+            HashMap::from([(
+                IVec3::ZERO,
+                DirtyRegion::Blocks {
+                    min: UVec3::new(3, 2, 5),
+                    max: UVec3::new(6, 4, 5),
+                },
+            )])
+        );
+        assert!(chunks.take_dirty().is_empty());
+
+        {
+            let mut chunks = chunks.write();
+            chunks.set_block(IVec3::new(0, 4, 5), Block::Dirt);
+            chunks.set_block(IVec3::new(20, 4, 5), Block::Dirt);
+            chunks.mark_dirty([IVec3::X]);
+        }
+        let dirty = chunks.take_dirty();
+        assert_eq!(dirty[&IVec3::ZERO], DirtyRegion::block(UVec3::new(0, 4, 5)));
+        // The neighbor sharing the face meshes against the block
+        assert_eq!(dirty[&IVec3::NEG_X], DirtyRegion::Whole);
+        assert_eq!(dirty[&IVec3::X], DirtyRegion::Whole);
+        assert_eq!(dirty.len(), 3);
+    }
+
+    #[test]
+    fn corner_edits_remesh_every_touching_chunk() {
+        let chunks = Chunks::default();
+        let corner = IVec3::new(16, -16, 32);
+        chunks.write().set_block(corner, Block::Glowstone);
+
+        let chunk = IVec3::new(1, -1, 2);
+        let dirty = chunks.take_dirty();
+        assert_eq!(dirty.len(), 4);
+        // The edited chunk only patches the block, see `meshes::remesh`
+        assert_eq!(dirty[&chunk], DirtyRegion::block(UVec3::ZERO));
+        for offset in [IVec3::NEG_X, IVec3::NEG_Y, IVec3::NEG_Z] {
+            assert_eq!(dirty[&(chunk + offset)], DirtyRegion::Whole, "{offset}");
+        }
+    }
+
+    #[test]
+    #[ignore = "stress test, takes a few seconds"]
+    fn meshers_never_see_half_written_chunks() {
+        const GENERATIONS: usize = 200;
+        const READERS: usize = 4;
+        const BLOCKS: [Block; 4] = [Block::Stone, Block::Dirt, Block::Sand, Block::Glowstone];
+        // Mesher snapshots each reader has to get in while the writer is busy
+        const MIN_SNAPSHOTS: usize = 20;
+
+        let chunks = Chunks::default();
+        for position in [IVec3::ZERO, IVec3::X] {
+            chunks.write().insert(position, Chunk::Uniform(BLOCKS[0]));
+        }
+        let done = AtomicBool::new(false);
+        let size = RawChunk::SIZE;
+        let center = (1..=size).flat_map(|x| {
+            (1..=size).flat_map(move |y| (1..=size).map(move |z| UVec3::new(x, y, z)))
+        });
+
+        thread::scope(|scope| {
+            let readers = (0..READERS)
+                .map(|_| {
+                    scope.spawn(|| {
+                        let mut snapshots = 0;
+                        while !done.load(Ordering::Acquire) {
+                            let neighborhood = chunks.neighborhood(IVec3::ZERO, None).unwrap();
+                            // Still read outside of the lock while the writer goes on
+                            let block = neighborhood.get(UVec3::ONE);
+                            // The +X neighbor is seen through its face
+                            let border = (1..=size).map(|y| UVec3::new(size + 1, y, 1));
+                            assert!(center
+                                .clone()
+                                .chain(border)
+                                .all(|local| neighborhood.get(local) == block));
+                            snapshots += 1;
+                        }
+                        snapshots
+                    })
+                })
+                .collect::<Vec<_>>();
+
+            let start = Instant::now();
+            for generation in 1..=GENERATIONS {
+                let block = BLOCKS[generation % BLOCKS.len()];
+                // Both chunks change under one lock, so no snapshot may mix generations
+                let mut chunks = chunks.write();
+                for position in [IVec3::ZERO, IVec3::X] {
+                    let min = position * size as i32;
+                    for local in center.clone() {
+                        chunks.set_block(min + local.as_ivec3() - 1, block);
+                    }
+                }
+            }
+            let elapsed = start.elapsed();
+            done.store(true, Ordering::Release);
+
+            let snapshots = readers
+                .into_iter()
+                .map(|reader| reader.join().unwrap())
+                .collect::<Vec<_>>();
+            assert!(
+                snapshots.iter().all(|&count| count >= MIN_SNAPSHOTS),
+                "{snapshots:?} snapshots while {GENERATIONS} generations took {elapsed:?}"
+            );
+        });
     }
 }
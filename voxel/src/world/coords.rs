@@ -0,0 +1,71 @@
+use glam::{IVec3, UVec3, Vec3};
+
+use super::chunk::{ChunkSectionPosition, CHUNK_SIZE};
+
+const SIZE: i32 = CHUNK_SIZE as i32;
+
+/// Block containing the given point in world space
+pub fn position_to_block(position: Vec3) -> IVec3 {
+    position.floor().as_ivec3()
+}
+
+pub fn block_to_chunk(position: IVec3) -> IVec3 {
+    position.div_euclid(IVec3::splat(SIZE))
+}
+
+pub fn block_to_local(position: IVec3) -> UVec3 {
+    position.rem_euclid(IVec3::splat(SIZE)).as_uvec3()
+}
+
+pub fn chunk_to_block_min(position: IVec3) -> IVec3 {
+    position * SIZE
+}
+
+pub fn block_to_section(position: IVec3) -> ChunkSectionPosition {
+    block_to_chunk(position).into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn negative_blocks_round_down() {
+        assert_eq!(block_to_chunk(IVec3::new(-1, 0, 15)), IVec3::new(-1, 0, 0));
+        assert_eq!(
+            block_to_chunk(IVec3::new(-16, -17, 16)),
+            IVec3::new(-1, -2, 1)
+        );
+        assert_eq!(
+            block_to_local(IVec3::new(-1, -16, 17)),
+            UVec3::new(15, 0, 1)
+        );
+        assert_eq!(
+            position_to_block(Vec3::new(-0.5, 0.5, -1.0)),
+            IVec3::new(-1, 0, -1)
+        );
+    }
+
+    #[test]
+    fn chunk_and_local_rebuild_the_block() {
+        for x in -40..40 {
+            for y in [-33, -16, -1, 0, 15, 16, 100] {
+                let block = IVec3::new(x, y, -x * 3);
+                let local = block_to_local(block);
+                assert!(local.max_element() < CHUNK_SIZE as u32);
+                assert_eq!(
+                    chunk_to_block_min(block_to_chunk(block)) + local.as_ivec3(),
+                    block
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn sections_drop_the_height() {
+        assert_eq!(
+            block_to_section(IVec3::new(-1, 200, 32)),
+            ChunkSectionPosition::new(-1, 2)
+        );
+    }
+}
@@ -0,0 +1,222 @@
+use std::ops::RangeInclusive;
+
+use glam::{IVec3, Vec3};
+
+use super::{
+    chunk::{ChunkSection, ChunkSectionPosition, RawChunk, Volume, SECTION_SIZE},
+    coords, Block,
+};
+
+/// Blobs of a block scattered through the stone
+struct Deposit {
+    block: Block,
+    // Every cell of this size holds at most one blob
+    cell_size: i32,
+    chance: f32,
+    radius: RangeInclusive<f32>,
+    heights: RangeInclusive<i32>,
+}
+
+const DEPOSITS: [Deposit; 4] = [
+    Deposit {
+        block: Block::Coal,
+        cell_size: 8,
+        chance: 0.5,
+        radius: 1.0..=2.2,
+        heights: 0..=80,
+    },
+    Deposit {
+        block: Block::Iron,
+        cell_size: 10,
+        chance: 0.35,
+        radius: 0.8..=1.6,
+        heights: 0..=48,
+    },
+    Deposit {
+        block: Block::Gravel,
+        cell_size: 16,
+        chance: 0.3,
+        radius: 2.0..=3.5,
+        heights: 0..=70,
+    },
+    Deposit {
+        block: Block::Dirt,
+        cell_size: 16,
+        chance: 0.3,
+        radius: 2.0..=3.5,
+        heights: 20..=80,
+    },
+];
+
+struct Blob {
+    center: Vec3,
+    radius: f32,
+}
+
+impl Deposit {
+    /// Blobs only depend on their cell, so ones crossing a section border match on both sides
+    fn blob(&self, seed: u32, salt: u32, cell: IVec3) -> Option<Blob> {
+        let mut random = CellRandom::new(seed, salt, cell);
+        if random.next_f32() >= self.chance {
+            return None;
+        }
+
+        let offset = Vec3::new(random.next_f32(), random.next_f32(), random.next_f32());
+        let center = (cell * self.cell_size).as_vec3() + offset * self.cell_size as f32;
+        if !self.heights.contains(&(center.y as i32)) {
+            return None;
+        }
+
+        let radius =
+            self.radius.start() + (self.radius.end() - self.radius.start()) * random.next_f32();
+
+        Some(Blob { center, radius })
+    }
+}
+
+pub fn place_deposits(seed: u32, position: ChunkSectionPosition, section: &mut ChunkSection) {
+    let min = coords::chunk_to_block_min(position.with_y(0));
+    let max =
+        min + IVec3::new(
+            RawChunk::SIZE as i32,
+            (RawChunk::SIZE * SECTION_SIZE as u32) as i32,
+            RawChunk::SIZE as i32,
+        ) - 1;
+
+    for (salt, deposit) in DEPOSITS.iter().enumerate() {
+        let reach = deposit.radius.end().ceil() as i32;
+        let cell_size = IVec3::splat(deposit.cell_size);
+        let min_cell = (min - reach).div_euclid(cell_size);
+        let max_cell = (max + reach).div_euclid(cell_size);
+
+        for x in min_cell.x..=max_cell.x {
+            for y in min_cell.y..=max_cell.y {
+                for z in min_cell.z..=max_cell.z {
+                    let Some(blob) = deposit.blob(seed, salt as u32, IVec3::new(x, y, z)) else {
+                        continue;
+                    };
+
+                    let blob_min = (blob.center - blob.radius).floor().as_ivec3().max(min);
+                    let blob_max = (blob.center + blob.radius).ceil().as_ivec3().min(max);
+
+                    for x in blob_min.x..=blob_max.x {
+                        for y in blob_min.y..=blob_max.y {
+                            for z in blob_min.z..=blob_max.z {
+                                let block = IVec3::new(x, y, z);
+                                if (block.as_vec3() + 0.5).distance_squared(blob.center)
+                                    > blob.radius * blob.radius
+                                {
+                                    continue;
+                                }
+
+                                let local = (block - min).as_uvec3();
+                                if section[local] == Block::Stone {
+                                    section.set(local, deposit.block);
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// SplitMix64 seeded from the world seed and a cell position
+pub(super) struct CellRandom(u64);
+
+impl CellRandom {
+    pub(super) fn new(seed: u32, salt: u32, cell: IVec3) -> Self {
+        let mut random = Self(((seed as u64) << 32) | salt as u64);
+        for axis in cell.to_array() {
+            random.0 = random.next_u64() ^ axis as u32 as u64;
+        }
+
+        random
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E3779B97F4A7C15);
+
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+        z ^ (z >> 31)
+    }
+
+    pub(super) fn next_f32(&mut self) -> f32 {
+        (self.next_u64() >> 40) as f32 / (1u64 << 24) as f32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::PI;
+
+    use glam::{uvec3, UVec3};
+
+    use super::*;
+
+    const SEED: u32 = 7;
+    const SECTIONS: i32 = 8;
+    // Deep enough for every deposit
+    const STONE_HEIGHT: u32 = 96;
+    // Of the expected frequency, big blobs are few even over this many sections
+    const TOLERANCE: f32 = 0.25;
+
+    /// Share of the stone a deposit is expected to take where blobs can be centered
+    fn expected_frequency(deposit: &Deposit) -> f32 {
+        let (min, max) = (*deposit.radius.start(), *deposit.radius.end());
+        // Mean cube of a radius picked uniformly between the two
+        let radius_cubed = (max.powi(4) - min.powi(4)) / (4.0 * (max - min));
+        deposit.chance * 4.0 / 3.0 * PI * radius_cubed / deposit.cell_size.pow(3) as f32
+    }
+
+    #[test]
+    fn deposits_come_up_as_often_as_their_settings_say() {
+        // Deposits found on each layer
+        let mut counts = vec![[0; DEPOSITS.len()]; STONE_HEIGHT as usize];
+        for x in 0..SECTIONS {
+            for z in 0..SECTIONS {
+                let mut section = ChunkSection::default();
+                section.fill_region(UVec3::ZERO, uvec3(15, STONE_HEIGHT - 1, 15), Block::Stone);
+                place_deposits(SEED, ChunkSectionPosition::new(x, z), &mut section);
+
+                for (y, layer) in counts.iter_mut().enumerate() {
+                    for local in (0..16).flat_map(|x| (0..16).map(move |z| uvec3(x, y as u32, z))) {
+                        let block = section[local];
+                        if let Some(index) = DEPOSITS.iter().position(|d| d.block == block) {
+                            layer[index] += 1;
+                        }
+                    }
+                }
+            }
+        }
+
+        let layer_size = (SECTIONS * SECTIONS) as f32 * (RawChunk::SIZE * RawChunk::SIZE) as f32;
+        for (index, deposit) in DEPOSITS.iter().enumerate() {
+            let reach = deposit.radius.end().ceil() as i32;
+            // Layers far enough from the ends of the heights to get blobs from both sides
+            let inner = deposit.heights.start() + reach..=deposit.heights.end() - reach;
+            let outer = deposit.heights.start() - reach..=deposit.heights.end() + reach;
+
+            let found = inner
+                .clone()
+                .map(|y| counts[y as usize][index])
+                .sum::<u32>();
+            let frequency = found as f32 / (inner.count() as f32 * layer_size);
+            let expected = expected_frequency(deposit);
+            assert!(
+                (frequency - expected).abs() <= expected * TOLERANCE,
+                "{:?} takes {frequency} of the stone, expected {expected}",
+                deposit.block
+            );
+
+            let outside = (0..STONE_HEIGHT as i32)
+                .filter(|y| !outer.contains(y))
+                .map(|y| counts[y as usize][index])
+                .sum::<u32>();
+            assert_eq!(outside, 0, "{:?} outside of its heights", deposit.block);
+        }
+    }
+}
@@ -0,0 +1,87 @@
+use std::{
+    fs::File,
+    io::{self, BufReader, BufWriter},
+    path::Path,
+};
+
+use glam::IVec3;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use super::{
+    chunk::{Chunk, RawChunk, OFFSETS},
+    meshes::create_dump_mesh,
+    ChunkMap,
+};
+
+#[derive(Debug, Error)]
+pub enum DumpError {
+    #[error("failed to access dump file: {0}")]
+    Io(#[from] io::Error),
+    #[error("invalid dump: {0}")]
+    Format(#[from] serde_json::Error),
+}
+
+/// A chunk together with its six direct neighbors, enough to reproduce its mesh
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ChunkDump {
+    position: IVec3,
+    center: RawChunk,
+    neighbors: [Option<RawChunk>; 6],
+}
+
+impl ChunkDump {
+    pub fn capture(chunks: &ChunkMap, position: IVec3) -> Option<Self> {
+        let center = chunks.get(&position)?.to_raw();
+        let neighbors = OFFSETS.map(|offset| chunks.get(&(position + offset)).map(Chunk::to_raw));
+
+        Some(Self {
+            position,
+            center,
+            neighbors,
+        })
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, DumpError> {
+        let reader = BufReader::new(File::open(path)?);
+        Ok(serde_json::from_reader(reader)?)
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), DumpError> {
+        let writer = BufWriter::new(File::create(path)?);
+        Ok(serde_json::to_writer(writer, self)?)
+    }
+
+    /// Writes the dump as `{stem}.json` and its mesh as `{stem}.obj`
+    pub fn save_with_mesh(&self, stem: &str) -> Result<(), DumpError> {
+        self.save(format!("{stem}.json"))?;
+
+        let writer = BufWriter::new(File::create(format!("{stem}.obj"))?);
+        create_dump_mesh(self).write_obj(writer)?;
+
+        Ok(())
+    }
+
+    pub fn position(&self) -> IVec3 {
+        self.position
+    }
+
+    pub fn to_chunks(&self) -> ChunkMap {
+        let neighbors = OFFSETS
+            .iter()
+            .zip(&self.neighbors)
+            .filter_map(|(offset, chunk)| Some((self.position + *offset, chunk.clone()?)));
+
+        [(self.position, self.center.clone())]
+            .into_iter()
+            .chain(neighbors)
+            .map(|(position, chunk)| (position, Chunk::from(chunk)))
+            .collect()
+    }
+}
+
+impl RawChunk {
+    pub fn from_dump(path: impl AsRef<Path>) -> Result<Self, DumpError> {
+        Ok(ChunkDump::load(path)?.center)
+    }
+}
@@ -1,10 +1,13 @@
 use glam::{uvec3, IVec3, UVec3};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
 
 use crate::render::Vertex;
 
-use super::block::Block;
+use super::block::{Block, Tint};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum Direction {
     Top,
     Bottom,
@@ -27,12 +30,53 @@ impl Direction {
     }
 }
 
+// Direction of cross faces in the vertices, past the six sides
+const CROSS_DIRECTION: u32 = 6;
+
+// Corners of both diagonal planes in half blocks, every plane once per side
+const CROSS_QUADS: [[UVec3; 4]; 4] = [
+    [
+        uvec3(0, 2, 0),
+        uvec3(2, 2, 2),
+        uvec3(2, 0, 2),
+        uvec3(0, 0, 0),
+    ],
+    [
+        uvec3(2, 2, 2),
+        uvec3(0, 2, 0),
+        uvec3(0, 0, 0),
+        uvec3(2, 0, 2),
+    ],
+    [
+        uvec3(0, 2, 2),
+        uvec3(2, 2, 0),
+        uvec3(2, 0, 0),
+        uvec3(0, 0, 2),
+    ],
+    [
+        uvec3(2, 2, 0),
+        uvec3(0, 2, 2),
+        uvec3(0, 0, 2),
+        uvec3(2, 0, 0),
+    ],
+];
+
+#[derive(Debug, Clone, Copy)]
+enum Shape {
+    Side(Direction),
+    // Index into `CROSS_QUADS`
+    Cross(usize),
+}
+
 #[derive(Debug, Clone, Copy)]
 pub struct Face {
     block: Block,
-    direction: Direction,
+    shape: Shape,
     position: UVec3,
     ao: [u8; 4],
+    size: u32,
+    tint: u32,
+    light: u8,
 }
 
 impl Face {
@@ -41,25 +85,86 @@ impl Face {
             block,
             position,
             ao,
-            direction,
+            shape: Shape::Side(direction),
+            size: 1,
+            tint: 0,
+            light: 0,
         }
     }
 
-    pub fn indices(index: u16) -> [u16; 6] {
+    /// Quads of a cross shaped block, both sides of its two diagonal planes
+    pub fn cross(block: Block, position: UVec3) -> [Self; 4] {
+        [0, 1, 2, 3].map(|quad| Self {
+            shape: Shape::Cross(quad),
+            ..Self::new(block, position, [3; 4], Direction::Top)
+        })
+    }
+
+    /// Stretches the face over `size` blocks, starting at its position
+    pub fn scaled(mut self, size: u32) -> Self {
+        self.size = size;
+        self
+    }
+
+    /// Colors the face by the `temperature` of its column, if its block is tinted
+    pub fn tinted(mut self, temperature: u8) -> Self {
+        // Zero is reserved for untinted faces, the rest of the 6 bits covers the temperature
+        self.tint = match self.block.tint() {
+            Tint::None => 0,
+            Tint::Foliage => 1 + temperature as u32 * 62 / u8::MAX as u32,
+        };
+        self
+    }
+
+    /// Lights the face by the block light in front of it, emissive blocks are at least as
+    /// bright as their own light
+    pub fn lit(mut self, light: u8) -> Self {
+        self.light = light.max(self.block.light());
+        self
+    }
+
+    /// Position of the block the face belongs to
+    pub fn position(&self) -> UVec3 {
+        self.position
+    }
+
+    /// Both triangles wind counter-clockwise seen from outside the block,
+    /// so the cross product of their edges points along the face direction
+    pub fn indices(&self, index: u32) -> [u32; 6] {
         let offset = index * 4;
 
-        [
-            offset,
-            1 + offset,
-            2 + offset,
-            2 + offset,
-            3 + offset,
-            offset,
-        ]
+        // Split along the brighter diagonal, otherwise a single dark corner
+        // gets interpolated across the whole quad
+        if self.ao[1] + self.ao[3] > self.ao[0] + self.ao[2] {
+            [
+                3 + offset,
+                2 + offset,
+                1 + offset,
+                1 + offset,
+                offset,
+                3 + offset,
+            ]
+        } else {
+            [
+                offset,
+                2 + offset,
+                1 + offset,
+                2 + offset,
+                offset,
+                3 + offset,
+            ]
+        }
     }
 
     pub fn vertices(&self) -> [Vertex; 4] {
-        let vertices = match self.direction {
+        let direction = match self.shape {
+            Shape::Side(direction) => direction,
+            Shape::Cross(quad) => {
+                return self.pack_vertices(CROSS_QUADS[quad], self.position * 2, CROSS_DIRECTION)
+            }
+        };
+
+        let vertices = match direction {
             Direction::Top => [
                 uvec3(0, 1, 0),
                 uvec3(1, 1, 0),
@@ -98,14 +203,22 @@ impl Face {
             ],
         };
 
+        let vertices = vertices.map(|vertex_position| vertex_position * self.size * 2);
+        self.pack_vertices(vertices, self.position * 2, direction as u32)
+    }
+
+    // Corners and origin in half blocks
+    fn pack_vertices(&self, corners: [UVec3; 4], origin: UVec3, direction: u32) -> [Vertex; 4] {
         let mut index = 0;
 
-        vertices.map(|vertex_position| {
+        corners.map(|corner| {
             let vertex = Vertex::new(
-                vertex_position + self.position,
+                origin + corner,
                 self.ao[index],
                 self.block.texture_id(),
-                self.direction as u32,
+                direction,
+                self.tint,
+                self.light,
             );
             index += 1;
 
@@ -113,3 +226,91 @@ impl Face {
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use glam::Vec3;
+
+    use super::*;
+
+    const DIRECTIONS: [Direction; 6] = [
+        Direction::Top,
+        Direction::Bottom,
+        Direction::Left,
+        Direction::Right,
+        Direction::Front,
+        Direction::Back,
+    ];
+
+    // Every combination of the four corner levels
+    fn all_ao() -> impl Iterator<Item = [u8; 4]> {
+        (0..256u32).map(|index| [0, 1, 2, 3].map(|corner| (index >> (corner * 2)) as u8 & 3))
+    }
+
+    #[test]
+    fn quads_split_along_the_brighter_diagonal() {
+        for ao in all_ao() {
+            let face = Face::new(Block::Stone, UVec3::ONE, ao, Direction::Top);
+            let indices = face.indices(2);
+            let (first, second) = indices.split_at(3);
+
+            let mut shared = first
+                .iter()
+                .copied()
+                .filter(|index| second.contains(index))
+                .map(|index| index - 8)
+                .collect::<Vec<_>>();
+            shared.sort();
+            let expected = if ao[1] + ao[3] > ao[0] + ao[2] {
+                [1, 3]
+            } else {
+                [0, 2]
+            };
+            assert_eq!(shared, expected, "{ao:?}");
+
+            let mut covered = indices.to_vec();
+            covered.sort();
+            covered.dedup();
+            assert_eq!(covered, [8, 9, 10, 11]);
+        }
+    }
+
+    // Normals of both triangles, by the cross product of their edges
+    fn normals(face: &Face) -> [Vec3; 2] {
+        let positions = face.vertices().map(|vertex| vertex.unpack().position);
+        let indices = face.indices(0);
+        [0, 3].map(|start| {
+            let [a, b, c] = [0, 1, 2].map(|corner| positions[indices[start + corner] as usize]);
+            (b - a).cross(c - a)
+        })
+    }
+
+    #[test]
+    fn triangles_wind_toward_the_face_direction() {
+        for direction in DIRECTIONS {
+            // Both ways of splitting the quad
+            for ao in [[3, 0, 3, 0], [0, 3, 0, 3]] {
+                let face = Face::new(Block::Stone, UVec3::ONE, ao, direction);
+                for normal in normals(&face) {
+                    let expected = direction.to_vec().as_vec3();
+                    assert_eq!(normal.normalize(), expected, "{direction:?} {ao:?}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn cross_quads_face_both_ways() {
+        let faces = Face::cross(Block::TallGrass, UVec3::ONE);
+        let normals = faces.map(|face| {
+            let [first, second] = normals(&face);
+            assert!(first.normalize().abs_diff_eq(second.normalize(), 1e-6));
+            first.normalize()
+        });
+        // Every plane once per side
+        for pair in normals.chunks_exact(2) {
+            assert!(pair[0].abs_diff_eq(-pair[1], 1e-6));
+            assert_eq!(pair[0].y, 0.0);
+        }
+    }
+}
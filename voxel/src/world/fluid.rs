@@ -0,0 +1,256 @@
+use std::collections::HashMap;
+
+use glam::IVec3;
+
+use super::Block;
+
+/// Level of water that was generated or placed, it never runs out
+pub const SOURCE_LEVEL: u8 = 8;
+// Water falling down lands as the fullest flowing water and spreads from there
+const FALLING_LEVEL: u8 = SOURCE_LEVEL - 1;
+
+const HORIZONTAL: [IVec3; 4] = [IVec3::X, IVec3::NEG_X, IVec3::Z, IVec3::NEG_Z];
+
+/// Flowing water, every step it falls into air below it or spreads sideways one level lower,
+/// and dries up one level at a time once nothing feeds it anymore
+#[derive(Debug, Default)]
+pub struct FluidSimulation {
+    // Only flowing water has a level, any other water is a source
+    levels: HashMap<IVec3, u8>,
+}
+
+impl FluidSimulation {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Forgets the level of a block that was replaced from outside, placed water is a source
+    pub fn remove_level(&mut self, position: IVec3) {
+        self.levels.remove(&position);
+    }
+
+    pub fn clear(&mut self) {
+        self.levels.clear();
+    }
+
+    pub fn level(&self, position: IVec3, block: Block) -> Option<u8> {
+        (block == Block::Water).then(|| self.levels.get(&position).copied().unwrap_or(SOURCE_LEVEL))
+    }
+
+    /// Updates the blocks at `positions`, `can_flow` tells whether water may be placed
+    /// somewhere. Returns the blocks to set, their neighbors have to be scheduled once they are
+    pub fn step(
+        &mut self,
+        positions: impl IntoIterator<Item = IVec3>,
+        block_at: impl Fn(IVec3) -> Block,
+        can_flow: impl Fn(IVec3) -> bool,
+    ) -> Vec<(IVec3, Block)> {
+        // Zero dries the water up
+        let mut changes = HashMap::new();
+
+        for position in positions {
+            let Some(mut level) = self.level(position, block_at(position)) else {
+                continue;
+            };
+            if level != SOURCE_LEVEL {
+                let fed = self.fed_level(position, &block_at);
+                if fed != level {
+                    changes.insert(position, fed);
+                }
+                if fed == 0 {
+                    continue;
+                }
+                level = fed;
+            }
+
+            let below = position - IVec3::Y;
+            match block_at(below) {
+                Block::Air if can_flow(below) => {
+                    changes.insert(below, FALLING_LEVEL);
+                }
+                // Water pouring into other water or still falling doesn't spread
+                Block::Air | Block::Water => {}
+                _ if level > 1 => {
+                    for offset in HORIZONTAL {
+                        let neighbor = position + offset;
+                        if block_at(neighbor) == Block::Air && can_flow(neighbor) {
+                            let neighbor_level = changes.entry(neighbor).or_default();
+                            *neighbor_level = (*neighbor_level).max(level - 1);
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        changes
+            .into_iter()
+            .map(|(position, level)| {
+                if level == 0 {
+                    self.levels.remove(&position);
+                    (position, Block::Air)
+                } else {
+                    self.levels.insert(position, level);
+                    (position, Block::Water)
+                }
+            })
+            .collect()
+    }
+
+    /// Level the flowing water at `position` is kept at by the water around it
+    fn fed_level(&self, position: IVec3, block_at: &impl Fn(IVec3) -> Block) -> u8 {
+        let above = position + IVec3::Y;
+        if block_at(above) == Block::Water {
+            return FALLING_LEVEL;
+        }
+
+        HORIZONTAL
+            .iter()
+            .filter_map(|&offset| {
+                let neighbor = position + offset;
+                self.level(neighbor, block_at(neighbor))
+            })
+            .max()
+            .map_or(0, |level| level - 1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::*;
+
+    // Blocks water may flow into from the origin, along both horizontal axes
+    const REACH: i32 = 12;
+
+    // Stone floor below the first layer, air everywhere else that wasn't set
+    #[derive(Default)]
+    struct Pool {
+        blocks: HashMap<IVec3, Block>,
+        fluids: FluidSimulation,
+    }
+
+    fn block_in(blocks: &HashMap<IVec3, Block>, position: IVec3) -> Block {
+        match blocks.get(&position) {
+            Some(&block) => block,
+            None if position.y <= 0 => Block::Stone,
+            None => Block::Air,
+        }
+    }
+
+    impl Pool {
+        fn set(&mut self, position: IVec3, block: Block) {
+            self.blocks.insert(position, block);
+            self.fluids.remove_level(position);
+        }
+
+        fn step(&mut self, positions: impl IntoIterator<Item = IVec3>) -> Vec<(IVec3, Block)> {
+            let changes = self.fluids.step(
+                positions,
+                |position| block_in(&self.blocks, position),
+                |position| position.x.abs() <= REACH && position.z.abs() <= REACH,
+            );
+            self.blocks.extend(changes.iter().copied());
+            changes
+        }
+
+        // Steps until nothing changes, scheduling the neighbors of every change like the world
+        fn settle(&mut self, position: IVec3) {
+            let mut scheduled = HashSet::from([position]);
+            for _ in 0..100 {
+                if scheduled.is_empty() {
+                    return;
+                }
+                scheduled = self
+                    .step(scheduled)
+                    .into_iter()
+                    .flat_map(|(position, _)| {
+                        [IVec3::ZERO, IVec3::Y, IVec3::NEG_Y]
+                            .into_iter()
+                            .chain(HORIZONTAL)
+                            .map(move |offset| position + offset)
+                    })
+                    .collect();
+            }
+            panic!("the water never settled");
+        }
+
+        fn water(&self) -> Vec<IVec3> {
+            self.blocks
+                .iter()
+                .filter(|(_, &block)| block == Block::Water)
+                .map(|(&position, _)| position)
+                .collect()
+        }
+    }
+
+    #[test]
+    fn water_falls_one_block_per_step() {
+        let mut pool = Pool::default();
+        let source = IVec3::new(0, 4, 0);
+        pool.set(source, Block::Water);
+
+        assert_eq!(pool.step([source]), [(source - IVec3::Y, Block::Water)]);
+        assert_eq!(
+            pool.fluids.level(source - IVec3::Y, Block::Water),
+            Some(FALLING_LEVEL)
+        );
+        // Falling water doesn't spread sideways before it lands
+        assert_eq!(
+            pool.step([source - IVec3::Y]),
+            [(source - IVec3::Y * 2, Block::Water)]
+        );
+        assert_eq!(pool.fluids.level(source, Block::Water), Some(SOURCE_LEVEL));
+    }
+
+    #[test]
+    fn spreading_water_runs_out() {
+        let mut pool = Pool::default();
+        let source = IVec3::Y;
+        pool.set(source, Block::Water);
+        pool.settle(source);
+
+        let water = pool.water();
+        for &position in &water {
+            assert_eq!(position.y, 1);
+            let distance = (position - source).abs();
+            let distance = (distance.x + distance.z) as u8;
+            assert!(distance < SOURCE_LEVEL, "{position}");
+            assert_eq!(
+                pool.fluids.level(position, Block::Water),
+                Some(SOURCE_LEVEL - distance)
+            );
+        }
+        // Every block within reach of the source is covered
+        let reach = SOURCE_LEVEL as i32 - 1;
+        assert_eq!(water.len() as i32, 2 * reach * (reach + 1) + 1);
+    }
+
+    #[test]
+    fn water_stays_where_it_may_flow() {
+        let mut pool = Pool::default();
+        let source = IVec3::new(REACH, 1, 0);
+        pool.set(source, Block::Water);
+        pool.settle(source);
+
+        assert!(pool.water().iter().all(|position| position.x <= REACH));
+        assert_eq!(
+            pool.fluids.level(source - IVec3::X, Block::Water),
+            Some(SOURCE_LEVEL - 1)
+        );
+    }
+
+    #[test]
+    fn water_dries_up_without_its_source() {
+        let mut pool = Pool::default();
+        let source = IVec3::new(0, 3, 0);
+        pool.set(source, Block::Water);
+        pool.settle(source);
+        assert!(pool.water().len() > 1);
+
+        pool.set(source, Block::Air);
+        pool.settle(source - IVec3::Y);
+        assert_eq!(pool.water(), []);
+    }
+}
@@ -1,8 +1,12 @@
 use super::{
     chunk::{ChunkSection, ChunkSectionPosition, RawChunk, Volume},
+    coords,
+    deposit::{self, CellRandom},
     Block,
 };
+use glam::IVec3;
 use noise::{Blend, Exponent, Fbm, MultiFractal, NoiseFn, Perlin};
+use std::ops::Range;
 
 pub const SECTION_SIZE: usize = 16;
 
@@ -10,50 +14,105 @@ pub trait Generate {
     fn generate_section(&self, position: ChunkSectionPosition) -> ChunkSection;
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
-pub enum Biome {
-    Plains,
-    Winter,
-    Desert,
+/// Everything that sets a biome apart, the biome of a column is picked by its temperature
+#[derive(Debug, Clone, PartialEq)]
+pub struct BiomeDefinition {
+    pub name: &'static str,
+    // Between `0.0` and `1.0`, the ranges of all biomes follow each other without gaps
+    pub temperature_range: Range<f64>,
+    pub surface_block: Block,
+    // Below the surface down to the stone, and on top where water covers the terrain
+    pub filler_block: Block,
+    pub underwater_block: Block,
+    pub beach_block: Block,
+    // Blocks the terrain rises above `height_offset` at the highest point of the noise
+    pub height_scale: f64,
+    pub height_offset: f64,
+    // Multiplies the chances of plants growing on the surface
+    pub plant_density: f32,
 }
 
+/// Ordered from the coldest biome to the hottest
+pub const BIOMES: [BiomeDefinition; 3] = [
+    BiomeDefinition {
+        name: "winter",
+        temperature_range: 0.0..0.3,
+        surface_block: Block::Snow,
+        filler_block: Block::Dirt,
+        underwater_block: Block::Ice,
+        beach_block: Block::Gravel,
+        height_scale: 64.0,
+        height_offset: 20.0,
+        plant_density: 0.0,
+    },
+    BiomeDefinition {
+        name: "plains",
+        temperature_range: 0.3..0.6,
+        surface_block: Block::Grass,
+        filler_block: Block::Dirt,
+        underwater_block: Block::Water,
+        beach_block: Block::Sand,
+        height_scale: 48.0,
+        height_offset: 24.0,
+        plant_density: 1.0,
+    },
+    BiomeDefinition {
+        name: "desert",
+        temperature_range: 0.6..1.0,
+        surface_block: Block::Sand,
+        filler_block: Block::Sand,
+        underwater_block: Block::Water,
+        beach_block: Block::Sand,
+        height_scale: 32.0,
+        height_offset: 30.0,
+        plant_density: 0.0,
+    },
+];
+
+// Temperature over which the terrain shape of two neighboring biomes blends into the other
+const BIOME_BLEND_WIDTH: f64 = 0.1;
+
+/// Index into [`BIOMES`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Biome(usize);
+
 impl Biome {
     pub fn from_temperature(temperature: f64) -> Self {
-        match temperature {
-            0.0..=0.3 => Biome::Winter,
-            0.3..=0.6 => Biome::Plains,
-            0.6.. => Biome::Desert,
+        let temperature = temperature.clamp(0.0, 1.0);
+        let index = BIOMES
+            .iter()
+            .position(|biome| temperature < biome.temperature_range.end)
+            .unwrap_or(BIOMES.len() - 1);
 
-            _ => Biome::Plains,
-        }
+        Self(index)
     }
 
-    pub fn terrain_block(&self) -> Block {
-        match self {
-            Biome::Plains => Block::Grass,
-            Biome::Winter => Block::Snow,
-            Biome::Desert => Block::Sand,
-        }
+    pub fn definition(self) -> &'static BiomeDefinition {
+        &BIOMES[self.0]
     }
+}
 
-    pub fn terrain_water(&self) -> Block {
-        match self {
-            Biome::Plains | Biome::Desert => Block::Water,
-            Biome::Winter => Block::Ice,
-        }
-    }
+/// Height scale and offset at the temperature, blended linearly across the border of two
+/// biomes so the terrain doesn't break into cliffs there
+pub fn terrain_shape(temperature: f64) -> (f64, f64) {
+    let shape = |biome: &BiomeDefinition| (biome.height_scale, biome.height_offset);
 
-    pub fn terrain_beach(&self) -> Block {
-        match self {
-            Biome::Plains | Biome::Desert => Block::Sand,
-            Biome::Winter => Block::Gravel,
-        }
+    let mut blended = shape(&BIOMES[0]);
+    for (colder, warmer) in BIOMES.iter().zip(&BIOMES[1..]) {
+        let border = colder.temperature_range.end;
+        let weight = ((temperature - border) / BIOME_BLEND_WIDTH + 0.5).clamp(0.0, 1.0);
+        let (scale, offset) = shape(warmer);
+
+        blended.0 += (scale - blended.0) * weight;
+        blended.1 += (offset - blended.1) * weight;
     }
+    blended
 }
 
 pub struct DefaultGenerator {
-    noise: Box<dyn NoiseFn<f64, 2>>,
-    temperature_noise: Box<dyn NoiseFn<f64, 2>>,
+    seed: u32,
+    noise: Box<dyn NoiseFn<f64, 2> + Send + Sync>,
+    temperature_noise: Box<dyn NoiseFn<f64, 2> + Send + Sync>,
 }
 
 impl DefaultGenerator {
@@ -80,6 +139,7 @@ impl DefaultGenerator {
         let noise = Exponent::new(noise).set_exponent(1.4);
 
         Self {
+            seed,
             noise: Box::new(noise),
             temperature_noise: Box::new(temperature_noise),
         }
@@ -90,8 +150,12 @@ const SCALE: f64 = 64.0;
 const TEMPERATURE_SCALE: f64 = 256.0;
 
 const WATER_HEIGHT: u32 = 40;
-const TERRAIN_SCALE: f64 = 48.0;
-const BASE_TERRAIN_HEIGHT: u32 = 24;
+
+// Keeps the plant rolls apart from the deposit ones
+const PLANT_SALT: u32 = 0x706c_616e;
+// Chances of a column to grow each plant, before the plant density of its biome
+const FLOWER_CHANCE: f32 = 0.01;
+const TALL_GRASS_CHANCE: f32 = 0.08;
 
 impl Generate for DefaultGenerator {
     fn generate_section(&self, position: ChunkSectionPosition) -> ChunkSection {
@@ -99,21 +163,24 @@ impl Generate for DefaultGenerator {
 
         for x in 0..RawChunk::SIZE {
             for z in 0..RawChunk::SIZE {
-                let global_x = (position.x * RawChunk::SIZE as i32) + x as i32;
-                let global_z = (position.z * RawChunk::SIZE as i32) + z as i32;
-
-                let noise_x = global_x as f64 / SCALE;
-                let noise_z = global_z as f64 / SCALE;
+                let global = coords::chunk_to_block_min(position.with_y(0))
+                    + IVec3::new(x as i32, 0, z as i32);
 
-                let temperature_x = global_x as f64 / TEMPERATURE_SCALE;
-                let temperature_z = global_z as f64 / TEMPERATURE_SCALE;
+                let noise_x = global.x as f64 / SCALE;
+                let noise_z = global.z as f64 / SCALE;
 
-                let height = self.noise.get([noise_x, noise_z]) / 2.0 + 0.5;
-                let height = BASE_TERRAIN_HEIGHT + (height * TERRAIN_SCALE) as u32;
+                let temperature_x = global.x as f64 / TEMPERATURE_SCALE;
+                let temperature_z = global.z as f64 / TEMPERATURE_SCALE;
 
                 let temperature =
                     self.temperature_noise.get([temperature_x, temperature_z]) / 2.0 + 0.5;
-                let biome = Biome::from_temperature(temperature);
+                let biome = Biome::from_temperature(temperature).definition();
+
+                let (height_scale, height_offset) = terrain_shape(temperature);
+                let height = self.noise.get([noise_x, noise_z]) / 2.0 + 0.5;
+                let height = (height_offset + height * height_scale) as u32;
+
+                section.set_temperature(x, z, (temperature.clamp(0.0, 1.0) * 255.0) as u8);
 
                 for y in 0..RawChunk::SIZE * SECTION_SIZE as u32 {
                     if height > y {
@@ -121,22 +188,125 @@ impl Generate for DefaultGenerator {
 
                         let block = match y {
                             y if diff == 1 && ((WATER_HEIGHT - 1)..=WATER_HEIGHT).contains(&y) => {
-                                biome.terrain_beach()
+                                biome.beach_block
                             }
                             _ if diff > 3 => Block::Stone,
-                            _ => biome.terrain_block(),
+                            y if diff == 1 && y + 1 >= WATER_HEIGHT => biome.surface_block,
+                            _ => biome.filler_block,
                         };
 
                         section.set((x, y, z).into(), block);
                     } else if y < WATER_HEIGHT {
-                        section.set((x, y, z).into(), biome.terrain_water())
+                        section.set((x, y, z).into(), biome.underwater_block)
                     } else {
                         continue;
                     }
                 }
+
+                // The beaches around the water stay bare
+                if biome.plant_density > 0.0 && height > WATER_HEIGHT + 1 {
+                    let roll = CellRandom::new(self.seed, PLANT_SALT, global).next_f32()
+                        / biome.plant_density;
+                    let plant = match roll {
+                        roll if roll < FLOWER_CHANCE => Some(Block::Flower),
+                        roll if roll < FLOWER_CHANCE + TALL_GRASS_CHANCE => Some(Block::TallGrass),
+                        _ => None,
+                    };
+                    if let Some(plant) = plant {
+                        section.set((x, height, z).into(), plant);
+                    }
+                }
             }
         }
 
+        deposit::place_deposits(self.seed, position, &mut section);
         section
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use glam::{uvec3, UVec3};
+
+    use super::*;
+
+    #[test]
+    fn biomes_cover_every_temperature() {
+        assert_eq!(BIOMES[0].temperature_range.start, 0.0);
+        assert_eq!(BIOMES[BIOMES.len() - 1].temperature_range.end, 1.0);
+        for (colder, warmer) in BIOMES.iter().zip(&BIOMES[1..]) {
+            assert_eq!(colder.temperature_range.end, warmer.temperature_range.start);
+        }
+    }
+
+    #[test]
+    fn temperatures_pick_their_biome() {
+        for (index, biome) in BIOMES.iter().enumerate() {
+            let range = &biome.temperature_range;
+            assert_eq!(Biome::from_temperature(range.start), Biome(index));
+            assert_eq!(Biome::from_temperature(range.end - 1e-9), Biome(index));
+        }
+
+        // The noise overshoots a little, the extremes belong to the outer biomes
+        assert_eq!(Biome::from_temperature(-0.2), Biome(0));
+        assert_eq!(Biome::from_temperature(1.0), Biome(BIOMES.len() - 1));
+        assert_eq!(Biome::from_temperature(1.2), Biome(BIOMES.len() - 1));
+    }
+
+    #[test]
+    fn terrain_shape_blends_across_borders() {
+        // Away from the borders every biome keeps its own shape
+        for biome in &BIOMES {
+            let range = &biome.temperature_range;
+            let middle = (range.start + range.end) / 2.0;
+            assert_eq!(
+                terrain_shape(middle),
+                (biome.height_scale, biome.height_offset)
+            );
+        }
+
+        // Small steps in temperature never move the terrain by much
+        let step = 0.001;
+        let mut previous = terrain_shape(0.0);
+        for index in 1..=1000 {
+            let shape = terrain_shape(index as f64 * step);
+            assert!((shape.0 - previous.0).abs() < 0.5, "{previous:?} {shape:?}");
+            assert!((shape.1 - previous.1).abs() < 0.5, "{previous:?} {shape:?}");
+            previous = shape;
+        }
+    }
+
+    fn blocks(section: &ChunkSection) -> impl Iterator<Item = (UVec3, Block)> + '_ {
+        let height = RawChunk::SIZE * SECTION_SIZE as u32;
+        (0..height).flat_map(move |y| {
+            (0..RawChunk::SIZE).flat_map(move |z| {
+                (0..RawChunk::SIZE).map(move |x| {
+                    let position = uvec3(x, y, z);
+                    (position, section.get(position))
+                })
+            })
+        })
+    }
+
+    #[test]
+    fn sections_only_depend_on_the_seed() {
+        let position = ChunkSectionPosition::new(3, -2);
+        let first = DefaultGenerator::new(7).generate_section(position);
+        let second = DefaultGenerator::new(7).generate_section(position);
+        assert!(blocks(&first).eq(blocks(&second)));
+    }
+
+    #[test]
+    fn nothing_is_left_open_below_the_water() {
+        let generator = DefaultGenerator::new(7);
+        for position in [(0, 0), (-5, 9), (40, -12)] {
+            let section =
+                generator.generate_section(ChunkSectionPosition::new(position.0, position.1));
+            for (position, block) in blocks(&section) {
+                if position.y < WATER_HEIGHT {
+                    assert_ne!(block, Block::Air, "{position}");
+                }
+            }
+        }
+    }
+}
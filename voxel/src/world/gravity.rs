@@ -0,0 +1,104 @@
+use glam::IVec3;
+
+use super::Block;
+
+/// Moves the blocks at `positions` that fall, like sand, down one block if there is air below
+/// them, `can_fall` tells whether a block may land somewhere. Returns the blocks to set, their
+/// surroundings have to be scheduled once they are
+pub fn step(
+    positions: impl IntoIterator<Item = IVec3>,
+    block_at: impl Fn(IVec3) -> Block,
+    can_fall: impl Fn(IVec3) -> bool,
+) -> Vec<(IVec3, Block)> {
+    let mut changes = Vec::new();
+
+    for position in positions {
+        let block = block_at(position);
+        let below = position - IVec3::Y;
+        if block.falls() && block_at(below) == Block::Air && can_fall(below) {
+            changes.push((position, Block::Air));
+            changes.push((below, block));
+        }
+    }
+
+    changes
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    // Stone floor below the first layer, air everywhere else that wasn't set
+    fn block_in(blocks: &HashMap<IVec3, Block>, position: IVec3) -> Block {
+        match blocks.get(&position) {
+            Some(&block) => block,
+            None if position.y <= 0 => Block::Stone,
+            None => Block::Air,
+        }
+    }
+
+    #[test]
+    fn sand_falls_one_block_per_step() {
+        let blocks = HashMap::from([(IVec3::new(2, 5, 2), Block::Sand)]);
+        let changes = step(
+            [IVec3::new(2, 5, 2)],
+            |position| block_in(&blocks, position),
+            |_| true,
+        );
+        assert_eq!(
+            changes,
+            [
+                (IVec3::new(2, 5, 2), Block::Air),
+                (IVec3::new(2, 4, 2), Block::Sand)
+            ]
+        );
+    }
+
+    #[test]
+    fn supported_and_held_blocks_stay() {
+        let blocks = HashMap::from([
+            (IVec3::new(0, 1, 0), Block::Gravel),
+            (IVec3::new(1, 3, 0), Block::Stone),
+            (IVec3::new(2, 3, 0), Block::Sand),
+        ]);
+        let changes = step(
+            [
+                IVec3::new(0, 1, 0),
+                IVec3::new(1, 3, 0),
+                IVec3::new(2, 3, 0),
+            ],
+            |position| block_in(&blocks, position),
+            |position| position.x < 2,
+        );
+        assert_eq!(changes, []);
+    }
+
+    #[test]
+    fn sand_columns_collapse_onto_the_floor() {
+        let mut blocks = (4..10)
+            .map(|y| (IVec3::new(0, y, 0), Block::Sand))
+            .collect::<HashMap<_, _>>();
+        blocks.insert(IVec3::new(0, 7, 0), Block::Gravel);
+
+        // Updates the whole column every step until nothing moves anymore
+        let column = (1..10).map(|y| IVec3::new(0, y, 0));
+        for _ in 0..20 {
+            let changes = step(
+                column.clone(),
+                |position| block_in(&blocks, position),
+                |_| true,
+            );
+            if changes.is_empty() {
+                break;
+            }
+            blocks.extend(changes);
+        }
+
+        // The column keeps its order and rests on the floor
+        use Block::{Air, Gravel, Sand};
+        let landed = column.map(|position| block_in(&blocks, position));
+        assert!(landed.eq([Sand, Sand, Sand, Gravel, Sand, Sand, Air, Air, Air]));
+    }
+}
@@ -0,0 +1,288 @@
+use std::collections::{HashSet, VecDeque};
+
+use glam::IVec3;
+
+use super::{
+    chunk::{Chunk, RawChunk, Volume, OFFSETS},
+    coords, Block, ChunkMap, Visibility,
+};
+
+/// Brightest block light, every block away from the source takes one level off
+pub const MAX_LIGHT: u8 = 15;
+
+/// Spreads block light through the chunks after blocks change, creating the chunks it reaches
+/// when they're missing
+pub struct LightPropagation<'c, F> {
+    chunks: &'c mut ChunkMap,
+    // Whether light may spread into the block, keeps it out of sections that aren't generated
+    is_reachable: F,
+    lit: VecDeque<IVec3>,
+    // Positions that went dark together with the level they had
+    unlit: VecDeque<(IVec3, u8)>,
+    changed_chunks: HashSet<IVec3>,
+}
+
+impl<'c, F: Fn(IVec3) -> bool> LightPropagation<'c, F> {
+    pub fn new(chunks: &'c mut ChunkMap, is_reachable: F) -> Self {
+        Self {
+            chunks,
+            is_reachable,
+            lit: VecDeque::new(),
+            unlit: VecDeque::new(),
+            changed_chunks: HashSet::new(),
+        }
+    }
+
+    fn block(&self, position: IVec3) -> Block {
+        self.chunks
+            .get(&coords::block_to_chunk(position))
+            .map_or(Block::Air, |chunk| chunk[coords::block_to_local(position)])
+    }
+
+    pub fn light(&self, position: IVec3) -> u8 {
+        self.chunks
+            .get(&coords::block_to_chunk(position))
+            .map_or(0, |chunk| chunk.light(coords::block_to_local(position)))
+    }
+
+    fn set_light(&mut self, position: IVec3, level: u8) {
+        let chunk_position = coords::block_to_chunk(position);
+        let local = coords::block_to_local(position);
+        let chunk = match self.chunks.get_mut(&chunk_position) {
+            Some(chunk) => chunk,
+            None if level == 0 => return,
+            None => self.chunks.get_or_insert_default(chunk_position),
+        };
+        chunk.set_light(local, level);
+
+        // Faces of the neighboring chunks look into blocks at the border
+        self.changed_chunks.insert(chunk_position);
+        for offset in OFFSETS {
+            self.changed_chunks
+                .insert(coords::block_to_chunk(position + offset));
+        }
+    }
+
+    /// Queues the light changes caused by replacing the block at the position with `block`,
+    /// the chunks have to hold the new block already
+    pub fn block_changed(&mut self, position: IVec3, block: Block) {
+        let previous = self.light(position);
+        if previous > 0 {
+            self.set_light(position, 0);
+            self.unlit.push_back((position, previous));
+        }
+
+        self.add_source(position, block);
+        // Light around the position may now spread through it
+        for offset in OFFSETS {
+            if self.light(position + offset) > 0 {
+                self.lit.push_back(position + offset);
+            }
+        }
+    }
+
+    fn add_source(&mut self, position: IVec3, block: Block) {
+        if block.light() > 0 {
+            self.set_light(position, block.light());
+            self.lit.push_back(position);
+        }
+    }
+
+    /// Queues every light source of a freshly inserted chunk, along with the light
+    /// of its neighbors that can now spread into it
+    pub fn chunk_inserted(&mut self, chunk_position: IVec3) {
+        let Some(chunk) = self.chunks.get(&chunk_position) else {
+            return;
+        };
+        let min = coords::chunk_to_block_min(chunk_position);
+
+        let sources = match chunk {
+            Chunk::Uniform(block) if block.light() == 0 => Vec::new(),
+            Chunk::Uniform(block) => (0..RawChunk::SIZE.pow(3))
+                .map(|index| (RawChunk::delinearize(index), *block))
+                .collect(),
+            Chunk::Dense(chunk) => chunk
+                .iter_enumerate()
+                .filter(|(_, block)| block.light() > 0)
+                .collect(),
+        };
+        for (local, block) in sources {
+            self.add_source(min + local.as_ivec3(), block);
+        }
+
+        let size = RawChunk::SIZE as i32;
+        for offset in OFFSETS {
+            if !self
+                .chunks
+                .get(&(chunk_position + offset))
+                .is_some_and(Chunk::has_light)
+            {
+                continue;
+            }
+
+            // Layer of the neighbor touching this chunk
+            let layer_min = min + offset.max(IVec3::ZERO) * size + offset.min(IVec3::ZERO);
+            let layer_max = layer_min + (IVec3::ONE - offset.abs()) * (size - 1);
+            for z in layer_min.z..=layer_max.z {
+                for y in layer_min.y..=layer_max.y {
+                    for x in layer_min.x..=layer_max.x {
+                        let position = IVec3::new(x, y, z);
+                        if self.light(position) > 0 {
+                            self.lit.push_back(position);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Clears the light of removed sources first, then fills everything back in
+    /// from the remaining ones
+    pub fn propagate(&mut self) {
+        while let Some((position, level)) = self.unlit.pop_front() {
+            for offset in OFFSETS {
+                let neighbor = position + offset;
+                let neighbor_level = self.light(neighbor);
+
+                if neighbor_level != 0 && neighbor_level < level {
+                    self.set_light(neighbor, 0);
+                    self.unlit.push_back((neighbor, neighbor_level));
+                    // Sources lit by a brighter one keep their own light
+                    self.add_source(neighbor, self.block(neighbor));
+                } else if neighbor_level >= level {
+                    self.lit.push_back(neighbor);
+                }
+            }
+        }
+
+        while let Some(position) = self.lit.pop_front() {
+            let level = self.light(position);
+            if level <= 1 {
+                continue;
+            }
+
+            for offset in OFFSETS {
+                let neighbor = position + offset;
+                if self.light(neighbor) >= level - 1
+                    || self.block(neighbor).visibility() == Visibility::Opaque
+                    || !(self.is_reachable)(neighbor)
+                {
+                    continue;
+                }
+
+                self.set_light(neighbor, level - 1);
+                self.lit.push_back(neighbor);
+            }
+        }
+    }
+
+    /// Chunks whose meshes show light that changed
+    pub fn into_changed_chunks(self) -> HashSet<IVec3> {
+        self.changed_chunks
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::ivec3;
+
+    use super::*;
+
+    fn place(chunks: &mut ChunkMap, position: IVec3, block: Block) {
+        chunks
+            .get_or_insert_default(coords::block_to_chunk(position))
+            .set(coords::block_to_local(position), block);
+    }
+
+    /// Places the block and propagates the change, returns the chunks that changed
+    fn change(chunks: &mut ChunkMap, position: IVec3, block: Block) -> HashSet<IVec3> {
+        place(chunks, position, block);
+        let mut light = LightPropagation::new(chunks, |_| true);
+        light.block_changed(position, block);
+        light.propagate();
+        light.into_changed_chunks()
+    }
+
+    fn light(chunks: &mut ChunkMap, position: IVec3) -> u8 {
+        LightPropagation::new(chunks, |_| true).light(position)
+    }
+
+    #[test]
+    fn falls_off_by_one_per_block() {
+        let mut chunks = ChunkMap::default();
+        let source = ivec3(8, 8, 8);
+        change(&mut chunks, source, Block::Glowstone);
+
+        for distance in 0..MAX_LIGHT as i32 {
+            let expected = MAX_LIGHT - distance as u8;
+            assert_eq!(light(&mut chunks, source + IVec3::X * distance), expected);
+            assert_eq!(light(&mut chunks, source - IVec3::Y * distance), expected);
+        }
+        assert_eq!(light(&mut chunks, source + IVec3::X * 15), 0);
+        // Manhattan distance, not a sphere
+        assert_eq!(light(&mut chunks, source + ivec3(3, 4, 5)), 3);
+    }
+
+    #[test]
+    fn opaque_blocks_stop_light() {
+        let mut chunks = ChunkMap::default();
+        let source = ivec3(8, 8, 8);
+        place(&mut chunks, source + IVec3::X, Block::Stone);
+        change(&mut chunks, source, Block::Glowstone);
+
+        assert_eq!(light(&mut chunks, source + IVec3::X), 0);
+        // Around the stone instead of through it
+        assert_eq!(light(&mut chunks, source + IVec3::X * 2), MAX_LIGHT - 4);
+    }
+
+    #[test]
+    fn removed_source_goes_dark() {
+        let mut chunks = ChunkMap::default();
+        let source = ivec3(8, 8, 8);
+        change(&mut chunks, source, Block::Glowstone);
+        change(&mut chunks, source, Block::Air);
+
+        let reach = MAX_LIGHT as i32;
+        for x in -reach..=reach {
+            for y in -reach..=reach {
+                for z in -reach..=reach {
+                    assert_eq!(light(&mut chunks, source + ivec3(x, y, z)), 0);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn removing_one_source_keeps_the_other() {
+        let mut chunks = ChunkMap::default();
+        let first = ivec3(8, 8, 8);
+        let second = first + IVec3::X * 6;
+        change(&mut chunks, first, Block::Glowstone);
+        change(&mut chunks, second, Block::Glowstone);
+        change(&mut chunks, first, Block::Air);
+
+        for x in -10..=20 {
+            let position = first + IVec3::X * x;
+            let distance = (position - second).abs().element_sum() as u8;
+            assert_eq!(
+                light(&mut chunks, position),
+                MAX_LIGHT.saturating_sub(distance),
+                "light at {position}"
+            );
+        }
+    }
+
+    #[test]
+    fn light_crosses_chunk_borders() {
+        let mut chunks = ChunkMap::default();
+        let size = RawChunk::SIZE as i32;
+        let source = ivec3(size - 1, 8, 8);
+        let changed = change(&mut chunks, source, Block::Glowstone);
+
+        assert_eq!(light(&mut chunks, source + IVec3::X), MAX_LIGHT - 1);
+        assert_eq!(light(&mut chunks, source + IVec3::X * 5), MAX_LIGHT - 5);
+        assert!(chunks[&IVec3::X].has_light());
+        assert!(changed.contains(&IVec3::ZERO));
+        assert!(changed.contains(&IVec3::X));
+    }
+}
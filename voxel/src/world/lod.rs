@@ -0,0 +1,134 @@
+use glam::{uvec3, UVec3};
+
+use super::{
+    chunk::{ChunkNeighborhood, CHUNK_SIZE},
+    Block, RenderShape, Visibility,
+};
+
+const _: () = assert!(
+    CHUNK_SIZE.is_multiple_of(Lod::Quarter.scale() as usize),
+    "chunks have to split evenly into the cells of every level"
+);
+
+/// Resolution a chunk is meshed at, distant chunks merge blocks into bigger cells
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Lod {
+    #[default]
+    Full,
+    Half,
+    Quarter,
+}
+
+impl Lod {
+    /// Side of a cell in blocks
+    pub const fn scale(self) -> u32 {
+        match self {
+            Lod::Full => 1,
+            Lod::Half => 2,
+            Lod::Quarter => 4,
+        }
+    }
+
+    /// Every `lod_distance` chunks away from the camera halve the resolution,
+    /// `None` keeps every chunk at full resolution
+    pub fn from_distance(distance: f32, lod_distance: Option<u32>) -> Self {
+        let Some(lod_distance) = lod_distance else {
+            return Lod::Full;
+        };
+
+        match (distance / lod_distance as f32) as u32 {
+            0 => Lod::Full,
+            1 => Lod::Half,
+            _ => Lod::Quarter,
+        }
+    }
+}
+
+fn rank(block: Block) -> u8 {
+    // Plants are too thin to show up in coarser cells
+    if block.shape() == RenderShape::Cross {
+        return 0;
+    }
+
+    match block.visibility() {
+        Visibility::Empty => 0,
+        Visibility::Transparent => 1,
+        Visibility::Opaque => 2,
+    }
+}
+
+/// A chunk neighborhood downsampled into cells of `lod.scale()` blocks
+///
+/// Uses the same layout as [`ChunkNeighborhood`]: cells `1..=size()` belong to the center chunk
+/// and the outer layer is sampled from the single block layer of the neighbors
+pub struct CellGrid {
+    size: u32,
+    cells: Box<[Block]>,
+}
+
+impl CellGrid {
+    pub fn new(neighborhood: &ChunkNeighborhood, lod: Lod) -> Self {
+        let scale = lod.scale();
+        let size = CHUNK_SIZE as u32 / scale;
+        let side = size + 2;
+
+        let mut cells = vec![Block::Air; (side * side * side) as usize].into_boxed_slice();
+        for z in 0..side {
+            for y in 0..side {
+                for x in 0..side {
+                    cells[(x + y * side + z * side * side) as usize] =
+                        Self::sample(neighborhood, uvec3(x, y, z), size, scale);
+                }
+            }
+        }
+
+        Self { size, cells }
+    }
+
+    /// Blocks of the neighborhood covered by `cell` along one axis
+    fn block_range(cell: u32, size: u32, scale: u32) -> [u32; 2] {
+        match cell {
+            0 => [0, 0],
+            cell if cell > size => [CHUNK_SIZE as u32 + 1; 2],
+            cell => [(cell - 1) * scale + 1, cell * scale],
+        }
+    }
+
+    // The most visible block wins, ties go to the highest one so surfaces keep their top block
+    fn sample(neighborhood: &ChunkNeighborhood, cell: UVec3, size: u32, scale: u32) -> Block {
+        let [min_x, max_x] = Self::block_range(cell.x, size, scale);
+        let [min_y, max_y] = Self::block_range(cell.y, size, scale);
+        let [min_z, max_z] = Self::block_range(cell.z, size, scale);
+
+        let mut sampled = Block::Air;
+        for y in (min_y..=max_y).rev() {
+            for x in min_x..=max_x {
+                for z in min_z..=max_z {
+                    let block = neighborhood.get(uvec3(x, y, z));
+                    if rank(block) > rank(sampled) {
+                        sampled = block;
+                    }
+                    if sampled.visibility() == Visibility::Opaque {
+                        return sampled;
+                    }
+                }
+            }
+        }
+
+        sampled
+    }
+
+    /// Cells along one axis of the center chunk
+    pub fn size(&self) -> u32 {
+        self.size
+    }
+
+    pub fn get(&self, cell: UVec3) -> Block {
+        let side = self.size + 2;
+        if cell.max_element() >= side {
+            return Block::Air;
+        }
+
+        self.cells[(cell.x + cell.y * side + cell.z * side * side) as usize]
+    }
+}
@@ -1,20 +1,90 @@
-use std::{iter, sync::LazyLock};
+use std::{
+    io::{self, Write},
+    iter,
+    sync::LazyLock,
+};
 
 use glam::{uvec3, UVec3};
+use itertools::Either;
 use voxel_util::Context;
 
 use crate::{
     render::{world_pass::ChunkBuffer, Vertex},
-    world::chunk::CHUNK_SIZE,
+    world::chunk::{Chunk, RawChunk, CHUNK_SIZE},
 };
 
-use super::{chunk::ChunkNeighborhood, face::Face, Direction, Visibility};
+#[cfg(feature = "serde")]
+use std::path::Path;
+
+#[cfg(feature = "serde")]
+use super::dump::{ChunkDump, DumpError};
+use super::{
+    chunk::ChunkNeighborhood,
+    face::Face,
+    lod::{CellGrid, Lod},
+    Block, Direction, DirtyRegion, RenderShape, Visibility,
+};
+
+/// Index buffer contents, widened to `u32` once the vertices outgrow `u16`
+#[derive(Debug, Clone)]
+pub enum Indices {
+    U16(Vec<u16>),
+    U32(Vec<u32>),
+}
+
+impl Indices {
+    pub fn len(&self) -> usize {
+        match self {
+            Self::U16(indices) => indices.len(),
+            Self::U32(indices) => indices.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        match self {
+            Self::U16(indices) => bytemuck::cast_slice(indices),
+            Self::U32(indices) => bytemuck::cast_slice(indices),
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = u32> + '_ {
+        match self {
+            Self::U16(indices) => Either::Left(indices.iter().map(|&index| index as u32)),
+            Self::U32(indices) => Either::Right(indices.iter().copied()),
+        }
+    }
+
+    fn extend(&mut self, face_indices: [u32; 6]) {
+        if let Self::U16(indices) = self {
+            if face_indices.iter().any(|&index| index > u16::MAX as u32) {
+                let widened = indices.iter().map(|&index| index as u32).collect();
+                *self = Self::U32(widened);
+            }
+        }
+
+        match self {
+            Self::U16(indices) => indices.extend(face_indices.map(|index| index as u16)),
+            Self::U32(indices) => indices.extend(face_indices),
+        }
+    }
+}
+
+impl Default for Indices {
+    fn default() -> Self {
+        Self::U16(Vec::new())
+    }
+}
 
 #[derive(Debug, Default, Clone)]
 pub struct RawMesh {
     verticies: Vec<Vertex>,
-    indices: Vec<u16>,
-    offset: u16,
+    indices: Indices,
+    // Faces pushed so far, each one adds four vertices
+    offset: u32,
 }
 
 impl RawMesh {
@@ -22,9 +92,19 @@ impl RawMesh {
         Self::default()
     }
 
+    pub fn from_faces(faces: impl IntoIterator<Item = Face>) -> Self {
+        let mut mesh = Self::default();
+        for face in faces {
+            mesh.push_face(face);
+        }
+        mesh
+    }
+
     pub fn push_face(&mut self, block_face: Face) {
+        debug_assert_eq!(self.verticies.len(), self.offset as usize * 4);
+
         self.verticies.extend(block_face.vertices());
-        self.indices.extend(Face::indices(self.offset));
+        self.indices.extend(block_face.indices(self.offset));
         self.offset += 1;
     }
 
@@ -32,19 +112,92 @@ impl RawMesh {
         &self.verticies
     }
 
-    pub fn indices(&self) -> &[u16] {
+    pub fn indices(&self) -> &Indices {
         &self.indices
     }
+
+    pub fn write_obj(&self, mut writer: impl Write) -> io::Result<()> {
+        for vertex in &self.verticies {
+            let position = vertex.unpack().position;
+            writeln!(writer, "v {} {} {}", position.x, position.y, position.z)?;
+        }
+
+        let indices = self.indices.iter().collect::<Vec<_>>();
+        for triangle in indices.chunks_exact(3) {
+            // OBJ indices start at 1
+            let [a, b, c] = [triangle[0], triangle[1], triangle[2]].map(|index| index + 1);
+            writeln!(writer, "f {a} {b} {c}")?;
+        }
+
+        writer.flush()
+    }
+}
+
+pub fn create_mesh(
+    neighborhood: ChunkNeighborhood,
+    lod: Lod,
+    ambient_occlusion: bool,
+    context: &Context,
+) -> ChunkBuffer {
+    let mesh = create_lod_mesh(&neighborhood, lod, ambient_occlusion);
+    ChunkBuffer::from_mesh(&mesh, neighborhood.center(), lod, context)
 }
 
-pub fn create_mesh(neighborhood: ChunkNeighborhood, context: &Context) -> ChunkBuffer {
-    ChunkBuffer::from_mesh(
-        &create_raw_mesh(neighborhood),
-        neighborhood.center(),
-        context,
-    )
+fn create_lod_mesh(neighborhood: &ChunkNeighborhood, lod: Lod, ambient_occlusion: bool) -> RawMesh {
+    match lod {
+        _ if neighborhood.is_buried() => RawMesh::default(),
+        Lod::Full => create_raw_mesh(neighborhood, ambient_occlusion),
+        lod => create_raw_lod_mesh(neighborhood, lod),
+    }
 }
 
+/// Builds the mesh of an edited chunk and keeps its faces in it, so the next edit can patch
+/// them. When `previous` kept its faces, only the ones around the blocks in `region` are built
+/// again
+pub fn remesh(
+    neighborhood: ChunkNeighborhood,
+    lod: Lod,
+    ambient_occlusion: bool,
+    previous: Option<&ChunkBuffer>,
+    region: DirtyRegion,
+    context: &Context,
+) -> ChunkBuffer {
+    // Uniform chunks skip the blocks that can't have faces, so theirs can't be patched block
+    // by block
+    if lod != Lod::Full
+        || neighborhood.is_buried()
+        || neighborhood.center_chunk().is_uniform().is_some()
+    {
+        return create_mesh(neighborhood, lod, ambient_occlusion, context);
+    }
+
+    let faces = match (previous.and_then(ChunkBuffer::faces), patch_range(region)) {
+        (Some(previous), Some(range)) => {
+            patch_faces(previous, &neighborhood, range, ambient_occlusion)
+        }
+        _ => mesh_faces(&neighborhood, ambient_occlusion).collect(),
+    };
+
+    let mesh = RawMesh::from_faces(faces.iter().copied());
+    ChunkBuffer::from_mesh(&mesh, neighborhood.center(), lod, context).with_faces(faces.into())
+}
+
+#[cfg(feature = "serde")]
+pub fn create_dump_mesh(dump: &ChunkDump) -> RawMesh {
+    let chunks = dump.to_chunks();
+    let neighborhood = ChunkNeighborhood::new(&chunks, dump.position(), None)
+        .expect("dump always contains its center chunk");
+    create_raw_mesh(&neighborhood, true)
+}
+
+#[cfg(feature = "serde")]
+pub fn mesh_dump(path: impl AsRef<Path>) -> Result<RawMesh, DumpError> {
+    Ok(create_dump_mesh(&ChunkDump::load(path)?))
+}
+
+// Dirty regions spanning more blocks than this along any axis are meshed from scratch
+const MAX_PATCH_EXTENT: u32 = 4;
+
 // Making this `static` does not give any effect
 const NEIGHBORS: [Direction; 6] = [
     Direction::Bottom,
@@ -63,34 +216,211 @@ static MESHING_RANGE: LazyLock<Box<[UVec3]>> = LazyLock::new(|| {
         .collect()
 });
 
-fn create_raw_mesh(neighborhood: ChunkNeighborhood) -> RawMesh {
-    let visible_blocks = MESHING_RANGE
+static MESHING_SHELL: LazyLock<Box<[UVec3]>> = LazyLock::new(|| {
+    const EDGES: [u32; 2] = [1, CHUNK_SIZE as u32];
+
+    MESHING_RANGE
         .iter()
         .copied()
+        .filter(|position| position.to_array().iter().any(|axis| EDGES.contains(axis)))
+        .collect()
+});
+
+/// Positions of all non-air blocks, shifted into the neighborhood space
+fn occupied_positions(chunk: &RawChunk) -> impl Iterator<Item = UVec3> + '_ {
+    (0..CHUNK_SIZE as u32)
+        .filter(|&y| !chunk.is_slice_empty(y))
+        .flat_map(move |y| {
+            (0..CHUNK_SIZE as u32).flat_map(move |x| {
+                let mut row = chunk.row_occupancy(x, y);
+                iter::from_fn(move || {
+                    if row == 0 {
+                        return None;
+                    }
+
+                    let z = row.trailing_zeros();
+                    row &= row - 1;
+
+                    Some(uvec3(x, y, z) + UVec3::ONE)
+                })
+            })
+        })
+}
+
+fn create_raw_mesh(neighborhood: &ChunkNeighborhood, ambient_occlusion: bool) -> RawMesh {
+    RawMesh::from_faces(mesh_faces(neighborhood, ambient_occlusion))
+}
+
+/// Faces of every visible block of the center chunk
+fn mesh_faces(
+    neighborhood: &ChunkNeighborhood,
+    ambient_occlusion: bool,
+) -> impl Iterator<Item = Face> + '_ {
+    let center = neighborhood.center_chunk();
+    let positions = match (center, center.is_uniform()) {
+        (_, Some(block)) if block.visibility() == Visibility::Empty => {
+            Either::Left([].iter().copied())
+        }
+        // Faces between two identical cubes are never emitted,
+        // so only the outer layer of a uniform chunk can produce any
+        (_, Some(block)) if block.shape() == RenderShape::Cube => {
+            Either::Left(MESHING_SHELL.iter().copied())
+        }
+        // Crossed blocks have quads of their own everywhere
+        (_, Some(..)) => Either::Left(MESHING_RANGE.iter().copied()),
+        (Chunk::Dense(chunk), None) => Either::Right(occupied_positions(chunk)),
+        (Chunk::Uniform(..), None) => unreachable!("uniform chunks always have a block"),
+    };
+
+    positions
         .map(|position| (position, neighborhood.get(position)))
-        .filter(|&(_, current)| current.visibility() != Visibility::Empty);
-
-    let block_faces = visible_blocks.flat_map(|(position, current)| {
-        NEIGHBORS.into_iter().filter_map(move |direction| {
-            let neighbor = position.wrapping_add_signed(direction.to_vec());
-            let neighbor = neighborhood.get(neighbor);
-            if neighbor.visibility() == Visibility::Opaque || neighbor == current {
-                return None;
-            }
+        .filter(|&(_, current)| current.visibility() != Visibility::Empty)
+        .flat_map(move |(position, current)| {
+            block_faces(neighborhood, position, current, ambient_occlusion)
+        })
+}
+
+/// Faces of the visible block `current` at `position` in the neighborhood space
+fn block_faces(
+    neighborhood: &ChunkNeighborhood,
+    position: UVec3,
+    current: Block,
+    ambient_occlusion: bool,
+) -> impl Iterator<Item = Face> + '_ {
+    // Positions are in the neighborhood space, shifted by one from the center chunk
+    let center = neighborhood.center_chunk();
+    let temperature = move || center.temperature(position.x - 1, position.z - 1);
+
+    if current.shape() == RenderShape::Cross {
+        let is_enclosed = NEIGHBORS.into_iter().all(|direction| {
+            let neighbor = neighborhood.get(position.wrapping_add_signed(direction.to_vec()));
+            neighbor.visibility() == Visibility::Opaque
+        });
+        let faces = (!is_enclosed).then(|| {
+            Face::cross(current, position)
+                .map(|face| face.tinted(temperature()).lit(neighborhood.light(position)))
+        });
+        return Either::Left(faces.into_iter().flatten());
+    }
+
+    Either::Right(NEIGHBORS.into_iter().filter_map(move |direction| {
+        let neighbor_position = position.wrapping_add_signed(direction.to_vec());
+        let neighbor = neighborhood.get(neighbor_position);
+        if neighbor.visibility() == Visibility::Opaque || neighbor == current {
+            return None;
+        }
+
+        let ao = if ambient_occlusion {
+            ao_values(neighborhood, position, direction)
+        } else {
+            [3; 4]
+        };
+        let light = neighborhood.light(neighbor_position);
+        Some(
+            Face::new(current, position, ao, direction)
+                .tinted(temperature())
+                .lit(light),
+        )
+    }))
+}
+
+/// Blocks whose faces may change with the ones in `region`, in the neighborhood space, or
+/// `None` when the region is too large to be worth patching
+fn patch_range(region: DirtyRegion) -> Option<(UVec3, UVec3)> {
+    let DirtyRegion::Blocks { min, max } = region else {
+        return None;
+    };
+    if (max - min).max_element() >= MAX_PATCH_EXTENT {
+        return None;
+    }
 
-            let ao = ao_values(neighborhood, position, direction);
-            Some(Face::new(current, position, ao, direction))
+    // Faces are culled and shaded by the blocks around theirs, so the blocks next to the
+    // region change too. The ones past the center chunk have no faces in its mesh
+    Some((
+        min.max(UVec3::ONE),
+        (max + 2).min(UVec3::splat(CHUNK_SIZE as u32)),
+    ))
+}
+
+/// Faces of the center chunk built from `previous`, only the blocks between `min` and `max`
+/// get their faces built again
+fn patch_faces(
+    previous: &[Face],
+    neighborhood: &ChunkNeighborhood,
+    (min, max): (UVec3, UVec3),
+    ambient_occlusion: bool,
+) -> Vec<Face> {
+    let is_patched = |position: UVec3| position.cmpge(min).all() && position.cmple(max).all();
+    let kept = previous
+        .iter()
+        .copied()
+        .filter(|face| !is_patched(face.position()));
+
+    let patched = (min.y..=max.y)
+        .flat_map(|y| {
+            (min.x..=max.x).flat_map(move |x| (min.z..=max.z).map(move |z| uvec3(x, y, z)))
         })
-    });
+        .map(|position| (position, neighborhood.get(position)))
+        .filter(|&(_, current)| current.visibility() != Visibility::Empty)
+        .flat_map(|(position, current)| {
+            block_faces(neighborhood, position, current, ambient_occlusion)
+        });
+
+    kept.chain(patched).collect()
+}
+
+/// Meshes the chunk as cells of `lod.scale()` blocks, without ambient occlusion
+fn create_raw_lod_mesh(neighborhood: &ChunkNeighborhood, lod: Lod) -> RawMesh {
+    let center = neighborhood.center_chunk();
+    let cells = CellGrid::new(neighborhood, lod);
+    let size = cells.size();
+    let is_outside = |cell: UVec3| cell.min_element() == 0 || cell.max_element() > size;
 
     let mut mesh = RawMesh::default();
-    for block_face in block_faces {
-        mesh.push_face(block_face);
+    for z in 1..=size {
+        for y in 1..=size {
+            for x in 1..=size {
+                let cell = uvec3(x, y, z);
+                let current = cells.get(cell);
+                if current.visibility() == Visibility::Empty {
+                    continue;
+                }
+
+                let is_surface = cells.get(cell + UVec3::Y).visibility() != Visibility::Opaque;
+                for direction in NEIGHBORS {
+                    let neighbor_cell = cell.wrapping_add_signed(direction.to_vec());
+                    let neighbor = cells.get(neighbor_cell);
+
+                    // Neighbors at another resolution can end up lower than this chunk's
+                    // surface, the skirt keeps the gap between them from being see-through
+                    let is_skirt = is_surface
+                        && current.visibility() == Visibility::Opaque
+                        && direction.to_vec().y == 0
+                        && is_outside(neighbor_cell);
+                    if !is_skirt
+                        && (neighbor.visibility() == Visibility::Opaque || neighbor == current)
+                    {
+                        continue;
+                    }
+
+                    // Cells take the temperature of their first column
+                    let position = (cell - UVec3::ONE) * lod.scale() + UVec3::ONE;
+                    let temperature = center.temperature(position.x - 1, position.z - 1);
+                    mesh.push_face(
+                        Face::new(current, position, [3; 4], direction)
+                            .scaled(lod.scale())
+                            .tinted(temperature)
+                            // Far enough that only emissive blocks keep their light
+                            .lit(0),
+                    );
+                }
+            }
+        }
     }
     mesh
 }
 
-fn ao_values(neighborhood: ChunkNeighborhood, position: UVec3, direction: Direction) -> [u8; 4] {
+fn ao_values(neighborhood: &ChunkNeighborhood, position: UVec3, direction: Direction) -> [u8; 4] {
     let neighbor_offsets = match direction {
         Direction::Left => [
             (-1, 0, -1),
@@ -174,3 +504,268 @@ fn ao_value(side1: bool, corner: bool, side2: bool) -> u8 {
         _ => 2,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{hint::black_box, sync::Arc, time::Instant};
+
+    use glam::IVec3;
+
+    use super::*;
+    use crate::world::{chunk::OFFSETS, ChunkMap};
+
+    // Small xorshift, the tests have to be reproducible
+    struct Rng(u32);
+
+    impl Rng {
+        fn next(&mut self) -> u32 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 17;
+            self.0 ^= self.0 << 5;
+            self.0
+        }
+
+        fn local(&mut self) -> UVec3 {
+            let size = CHUNK_SIZE as u32;
+            uvec3(self.next() % size, self.next() % size, self.next() % size)
+        }
+
+        // Half of the blocks are air, so there are plenty of faces
+        fn block(&mut self) -> Block {
+            match self.next() % 2 {
+                0 => Block::Air,
+                _ => Block::ALL[self.next() as usize % Block::ALL.len()],
+            }
+        }
+    }
+
+    fn random_chunk(rng: &mut Rng) -> Chunk {
+        let mut chunk = Chunk::default();
+        for _ in 0..2000 {
+            chunk.set(rng.local(), rng.block());
+            chunk.set_light(rng.local(), (rng.next() % 16) as u8);
+        }
+        chunk
+    }
+
+    // Order of the faces doesn't matter, the same quads have to be drawn
+    fn quads(faces: &[Face]) -> Vec<Vec<u8>> {
+        let mut quads = faces
+            .iter()
+            .map(|face| bytemuck::cast_slice(&face.vertices()).to_vec())
+            .collect::<Vec<_>>();
+        quads.sort();
+        quads
+    }
+
+    fn neighborhood(center: Chunk) -> ChunkNeighborhood {
+        let chunks = ChunkMap::from_iter([(IVec3::ZERO, center)]);
+        ChunkNeighborhood::new(&chunks, IVec3::ZERO, None).unwrap()
+    }
+
+    // Faces of every block, without any of the fast paths
+    fn every_face(neighborhood: &ChunkNeighborhood) -> Vec<Face> {
+        MESHING_RANGE
+            .iter()
+            .map(|&position| (position, neighborhood.get(position)))
+            .filter(|&(_, current)| current.visibility() != Visibility::Empty)
+            .flat_map(|(position, current)| block_faces(neighborhood, position, current, true))
+            .collect()
+    }
+
+    #[test]
+    fn air_chunks_have_no_faces() {
+        assert_eq!(mesh_faces(&neighborhood(Chunk::default()), true).count(), 0);
+        let emptied = Chunk::Dense(Arc::new(RawChunk::default()));
+        assert_eq!(mesh_faces(&neighborhood(emptied), true).count(), 0);
+    }
+
+    #[test]
+    fn stone_chunks_only_mesh_their_shell() {
+        for chunk in [
+            Chunk::Uniform(Block::Stone),
+            Chunk::Dense(Arc::new(RawChunk::filled(Block::Stone))),
+        ] {
+            let neighborhood = neighborhood(chunk);
+            let faces = mesh_faces(&neighborhood, true).collect::<Vec<_>>();
+            // Every side of the chunk faces the air around it
+            assert_eq!(faces.len(), 6 * CHUNK_SIZE * CHUNK_SIZE);
+            assert_eq!(quads(&faces), quads(&every_face(&neighborhood)));
+        }
+    }
+
+    #[test]
+    fn uniform_crossed_blocks_mesh_every_block() {
+        let neighborhood = neighborhood(Chunk::Uniform(Block::TallGrass));
+        let faces = mesh_faces(&neighborhood, true).collect::<Vec<_>>();
+        assert_eq!(faces.len(), 4 * CHUNK_SIZE.pow(3));
+        assert_eq!(quads(&faces), quads(&every_face(&neighborhood)));
+    }
+
+    #[test]
+    fn mixed_chunks_mesh_every_visible_block() {
+        let mut rng = Rng(0x1f2e_3d4c);
+        let neighborhood = neighborhood(random_chunk(&mut rng));
+        assert_eq!(neighborhood.center_chunk().is_uniform(), None);
+
+        let faces = mesh_faces(&neighborhood, true).collect::<Vec<_>>();
+        assert!(!faces.is_empty());
+        assert_eq!(quads(&faces), quads(&every_face(&neighborhood)));
+    }
+
+    const LODS: [Lod; 3] = [Lod::Full, Lod::Half, Lod::Quarter];
+
+    #[test]
+    fn uniform_air_chunks_mesh_nothing() {
+        let neighborhood = neighborhood(Chunk::Uniform(Block::Air));
+        for lod in LODS {
+            let mesh = create_lod_mesh(&neighborhood, lod, true);
+            assert!(mesh.verticies().is_empty() && mesh.indices().is_empty());
+        }
+    }
+
+    #[test]
+    fn buried_chunks_mesh_nothing() {
+        let stone = || Chunk::Uniform(Block::Stone);
+        let mut chunks = iter::once(IVec3::ZERO)
+            .chain(OFFSETS)
+            .map(|position| (position, stone()))
+            .collect::<ChunkMap>();
+        let neighborhood = ChunkNeighborhood::new(&chunks, IVec3::ZERO, None).unwrap();
+        assert!(neighborhood.is_buried());
+        for lod in LODS {
+            assert!(create_lod_mesh(&neighborhood, lod, true)
+                .verticies()
+                .is_empty());
+        }
+
+        // A single opening uncovers the side facing it
+        chunks.insert(IVec3::Y, Chunk::Uniform(Block::Water));
+        let neighborhood = ChunkNeighborhood::new(&chunks, IVec3::ZERO, None).unwrap();
+        assert!(!neighborhood.is_buried());
+        let mesh = create_lod_mesh(&neighborhood, Lod::Full, true);
+        assert_eq!(mesh.verticies().len(), 4 * CHUNK_SIZE * CHUNK_SIZE);
+    }
+
+    #[test]
+    fn patched_faces_match_a_full_mesh() {
+        let mut rng = Rng(0x2545_f491);
+        let mut chunks = iter::once(IVec3::ZERO)
+            .chain(OFFSETS)
+            .map(|position| (position, random_chunk(&mut rng)))
+            .collect::<ChunkMap>();
+        let neighborhood = |chunks: &ChunkMap| ChunkNeighborhood::new(chunks, IVec3::ZERO, None);
+        let mut faces = mesh_faces(&neighborhood(&chunks).unwrap(), true).collect::<Vec<_>>();
+
+        let mut patched = 0;
+        for _ in 0..300 {
+            let mut region = None::<DirtyRegion>;
+            for _ in 0..=rng.next() % 3 {
+                // Close together, so most of the edits are small enough to patch
+                let local = (rng.local() / 4 + region.map_or(UVec3::ZERO, |_| rng.local() / 8))
+                    .min(UVec3::splat(CHUNK_SIZE as u32 - 1));
+                chunks
+                    .get_mut(&IVec3::ZERO)
+                    .unwrap()
+                    .set(local, rng.block());
+                let edit = DirtyRegion::block(local);
+                region = Some(region.map_or(edit, |region| region.merge(edit)));
+            }
+
+            let neighborhood = neighborhood(&chunks).unwrap();
+            let expected = mesh_faces(&neighborhood, true).collect::<Vec<_>>();
+            if let Some(range) = patch_range(region.unwrap()) {
+                faces = patch_faces(&faces, &neighborhood, range, true);
+                assert_eq!(quads(&faces), quads(&expected));
+                patched += 1;
+            } else {
+                faces = expected;
+            }
+        }
+        assert!(patched > 200, "only {patched} edits were patched");
+    }
+
+    #[test]
+    fn large_regions_are_meshed_whole() {
+        assert_eq!(patch_range(DirtyRegion::Whole), None);
+        assert_eq!(
+            patch_range(DirtyRegion::Blocks {
+                min: UVec3::ZERO,
+                max: UVec3::new(0, MAX_PATCH_EXTENT, 0),
+            }),
+            None
+        );
+        // Blocks past the border have no faces in this chunk
+        assert_eq!(
+            patch_range(DirtyRegion::block(UVec3::ZERO)),
+            Some((UVec3::ONE, UVec3::splat(2)))
+        );
+        assert_eq!(
+            patch_range(DirtyRegion::block(UVec3::splat(CHUNK_SIZE as u32 - 1))),
+            Some((
+                UVec3::splat(CHUNK_SIZE as u32 - 1),
+                UVec3::splat(CHUNK_SIZE as u32)
+            ))
+        );
+    }
+
+    #[test]
+    fn indices_widen_past_u16() {
+        let face = Face::new(Block::Stone, UVec3::ONE, [3; 4], Direction::Top);
+        // The last face that still fits into `u16` indices
+        let fitting = (u16::MAX as usize + 1) / 4;
+        let mut mesh = RawMesh::from_faces(iter::repeat_n(face, fitting));
+        assert!(matches!(mesh.indices(), Indices::U16(..)));
+
+        mesh.push_face(face);
+        let Indices::U32(indices) = mesh.indices() else {
+            panic!("indices weren't widened");
+        };
+        assert_eq!(indices.len(), (fitting + 1) * 6);
+        assert_eq!(mesh.indices().as_bytes().len(), indices.len() * 4);
+        // Nothing lost on the way
+        let expected = (0..=fitting as u32).flat_map(|index| face.indices(index));
+        assert!(mesh.indices().iter().eq(expected));
+        assert_eq!(mesh.verticies().len(), (fitting + 1) * 4);
+    }
+    #[test]
+    #[ignore = "benchmark, run with --release -- --ignored --nocapture"]
+    fn sparse_chunks_skip_their_empty_rows() {
+        const ROUNDS: u32 = 200;
+
+        let mut rng = Rng(0x2545_f491);
+        let mut floor = Chunk::default();
+        for (x, z) in
+            (0..CHUNK_SIZE as u32).flat_map(|x| (0..CHUNK_SIZE as u32).map(move |z| (x, z)))
+        {
+            floor.set(uvec3(x, 0, z), Block::Grass);
+        }
+        // Blocks scattered through the chunk, from a few to a fifth of it
+        let scattered = [8, 80, 800].map(|count| {
+            let mut chunk = Chunk::default();
+            for _ in 0..count {
+                chunk.set(rng.local(), Block::Stone);
+            }
+            (format!("{count} scattered"), chunk)
+        });
+
+        for (name, chunk) in iter::once(("floor".to_owned(), floor)).chain(scattered) {
+            let neighborhood = neighborhood(chunk);
+            let time = |mesh: &dyn Fn() -> Vec<Face>| {
+                let start = Instant::now();
+                for _ in 0..ROUNDS {
+                    black_box(mesh());
+                }
+                start.elapsed() / ROUNDS
+            };
+
+            let skipping = time(&|| mesh_faces(&neighborhood, true).collect());
+            let scanning = time(&|| every_face(&neighborhood));
+            println!("{name}: {skipping:?} skipping empty rows, {scanning:?} scanning every block");
+            assert_eq!(
+                quads(&mesh_faces(&neighborhood, true).collect::<Vec<_>>()),
+                quads(&every_face(&neighborhood))
+            );
+        }
+    }
+}
@@ -1,114 +1,857 @@
 pub mod block;
+pub mod bounds;
 pub mod chunk;
 mod chunks;
+pub mod coords;
+pub mod deposit;
+#[cfg(feature = "serde")]
+pub mod dump;
 pub mod face;
+pub mod fluid;
 pub mod generator;
+pub mod gravity;
+pub mod light;
+pub mod lod;
 pub mod meshes;
+pub mod pending;
+pub mod random_tick;
+pub mod raycast;
+pub mod source;
+pub mod structure;
+pub mod tick;
 
-pub use block::{Block, Visibility};
-use chunk::{ChunkSectionPosition, CHUNK_SIZE};
+pub use block::{Block, RenderShape, Tint, Visibility};
+pub use bounds::WorldBounds;
+use chunk::{Chunk, ChunkSectionPosition, RawChunk, Volume, OFFSETS, SECTION_SIZE};
 pub use chunks::*;
 pub use face::{Direction, Face};
-use generator::{DefaultGenerator, Generate};
-use glam::IVec3;
+use fluid::FluidSimulation;
+use glam::{vec2, IVec3, Vec2, Vec3};
+use light::LightPropagation;
+use lod::Lod;
+use log::warn;
 pub use meshes::RawMesh;
-use std::iter;
+use pending::PendingEdits;
+use random_tick::RandomTicks;
+use raycast::RaycastHit;
+use source::{ChunkSource, LocalSource};
+use std::{
+    iter, mem,
+    sync::{atomic::Ordering, Arc},
+};
+use structure::Structure;
+use tick::TickScheduler;
 
 use std::collections::HashSet;
-use std::sync::LazyLock;
 
 use crate::application::MeshGenerator;
-use crate::camera::Camera;
+use crate::metrics::{Metrics, Progress};
 
-const HORIZONTAL_RENDER_DISTANCE: i32 = 16;
-const VERTICAL_RENDER_DISTANCE: i32 = 10;
-const GENERATION_DISTANCE: i32 = HORIZONTAL_RENDER_DISTANCE + 1;
+pub const DEFAULT_RENDER_DISTANCE: u32 = 16;
+pub const DEFAULT_VERTICAL_RENDER_DISTANCE: u32 = 8;
+// Fixed steps before flowing water next to a change is updated
+const FLUID_TICKS: u64 = 6;
+// Radians off the view direction of the rays the sections ahead are prefetched along
+const PREFETCH_RAYS: [f32; 3] = [-0.3, 0.0, 0.3];
+// How far the rays reach, relative to the generation distance
+const PREFETCH_REACH: f32 = 1.5;
+// Radians the camera has to turn by before the sections ahead are prefetched again
+const PREFETCH_TURN: f32 = 0.2;
 
-static GENERATING_SECTIONS_OFFSETS: LazyLock<Box<[ChunkSectionPosition]>> = LazyLock::new(|| {
-    let mut res = (-GENERATION_DISTANCE..=GENERATION_DISTANCE)
-        .flat_map(|x| iter::repeat(x).zip(-GENERATION_DISTANCE..=GENERATION_DISTANCE))
+/// Sections around the origin section that get generated, nearest first
+fn generating_sections_offsets(distance: i32, spherical: bool) -> Box<[ChunkSectionPosition]> {
+    let mut res = (-distance..=distance)
+        .flat_map(|x| iter::repeat(x).zip(-distance..=distance))
         .map(ChunkSectionPosition::from)
+        .filter(|position| !spherical || position.x.pow(2) + position.z.pow(2) <= distance.pow(2))
         .collect::<Box<_>>();
     res.sort_by_key(|position| position.x.pow(2) + position.z.pow(2));
     res
-});
+}
 
-static VISIBLE_CHUNKS_OFFSETS: LazyLock<Box<[IVec3]>> = LazyLock::new(|| {
-    let mut res = (-HORIZONTAL_RENDER_DISTANCE..=HORIZONTAL_RENDER_DISTANCE)
-        .flat_map(|x| iter::repeat(x).zip(-HORIZONTAL_RENDER_DISTANCE..=HORIZONTAL_RENDER_DISTANCE))
-        .flat_map(|position| {
-            iter::repeat(position).zip(-VERTICAL_RENDER_DISTANCE..=VERTICAL_RENDER_DISTANCE)
-        })
+/// Chunks around the origin chunk that get meshed, nearest first. The spherical region is
+/// still cut off at the vertical distance
+fn visible_chunks_offsets(horizontal: i32, vertical: i32, spherical: bool) -> Box<[IVec3]> {
+    let mut res = (-horizontal..=horizontal)
+        .flat_map(|x| iter::repeat(x).zip(-horizontal..=horizontal))
+        .flat_map(|position| iter::repeat(position).zip(-vertical..=vertical))
         .map(|((x, z), y)| IVec3::new(x, y, z))
+        .filter(|position| !spherical || position.length_squared() <= horizontal.pow(2))
         .collect::<Box<_>>();
     res.sort_by_key(|position| position.length_squared());
     res
-});
+}
+
+// Air where nothing has been generated
+fn block_in(chunks: &ChunkMap, position: IVec3) -> Block {
+    chunks
+        .get(&coords::block_to_chunk(position))
+        .map_or(Block::Air, |chunk| chunk[coords::block_to_local(position)])
+}
+
+fn is_generated(generated_sections: &HashSet<ChunkSectionPosition>, position: IVec3) -> bool {
+    let height = (RawChunk::SIZE * SECTION_SIZE as u32) as i32;
+    (0..height).contains(&position.y)
+        && generated_sections.contains(&coords::block_to_section(position))
+}
+
+// Ticks before the block reacts to a change next to it, `None` for blocks that don't
+fn tick_delay(block: Block) -> Option<u64> {
+    match block {
+        Block::Water => Some(FLUID_TICKS),
+        _ if block.falls() => Some(1),
+        _ => None,
+    }
+}
+
+/// Change made to a block of the generated world
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlockEvent {
+    /// A visible block got replaced by air, `block` is what was there before
+    Broken { position: IVec3, block: Block },
+    /// Air got replaced by `block`
+    Placed { position: IVec3, block: Block },
+}
 
 pub struct World {
     chunks: Chunks,
     generated_sections: HashSet<ChunkSectionPosition>,
-    generator: DefaultGenerator,
-    previous_origin: IVec3,
+    // Asked from the source but not arrived yet
+    requested_sections: HashSet<ChunkSectionPosition>,
+    pending_edits: PendingEdits,
+    source: Box<dyn ChunkSource>,
+    seed: u32,
+    previous_origin: Option<IVec3>,
+    // Horizontal view direction the sections ahead were last prefetched along
+    prefetch_direction: Option<Vec2>,
+    bounds: Option<WorldBounds>,
+    lod_distance: Option<u32>,
+    // In chunks, around the chunk of the camera
+    render_distance: u32,
+    vertical_render_distance: u32,
+    // Drops the corners of the box around the camera that lie past the render distance
+    spherical_render_distance: bool,
+    // In sections, `None` stays a chunk past the render distance
+    generation_distance: Option<u32>,
+    generating_sections_offsets: Box<[ChunkSectionPosition]>,
+    visible_chunks_offsets: Box<[IVec3]>,
+    // Collected until the next `take_block_events`
+    block_events: Vec<BlockEvent>,
+    fluids: FluidSimulation,
+    // Updates of water and falling blocks next to changes
+    scheduled_ticks: TickScheduler,
+    random_ticks: RandomTicks,
+    metrics: Arc<Metrics>,
 }
 
 impl World {
-    pub fn new(chunks: Chunks) -> Self {
+    pub fn new(chunks: Chunks, bounds: Option<WorldBounds>) -> Self {
         Self {
             chunks,
             generated_sections: Default::default(),
-            generator: DefaultGenerator::new(0),
-            previous_origin: Default::default(),
+            requested_sections: Default::default(),
+            pending_edits: Default::default(),
+            source: Box::new(LocalSource::new(0)),
+            seed: 0,
+            previous_origin: None,
+            prefetch_direction: None,
+            bounds,
+            lod_distance: None,
+            render_distance: DEFAULT_RENDER_DISTANCE,
+            vertical_render_distance: DEFAULT_VERTICAL_RENDER_DISTANCE,
+            spherical_render_distance: false,
+            generation_distance: None,
+            generating_sections_offsets: generating_sections_offsets(
+                DEFAULT_RENDER_DISTANCE as i32 + 1,
+                false,
+            ),
+            visible_chunks_offsets: visible_chunks_offsets(
+                DEFAULT_RENDER_DISTANCE as i32,
+                DEFAULT_VERTICAL_RENDER_DISTANCE as i32,
+                false,
+            ),
+            block_events: Vec::new(),
+            fluids: FluidSimulation::new(),
+            scheduled_ticks: TickScheduler::new(),
+            random_ticks: RandomTicks::new(0),
+            metrics: Default::default(),
         }
     }
 
-    pub fn update(&mut self, camera: &Camera, mesh_generator: &MeshGenerator) {
-        let origin = camera.transformation().position().as_ivec3() / CHUNK_SIZE as i32;
-        if origin == self.previous_origin {
-            return;
+    pub fn with_seed(mut self, seed: u32) -> Self {
+        self.source.reset(seed);
+        self.random_ticks.reseed(seed);
+        self.seed = seed;
+        self
+    }
+
+    /// Fetches sections from `source` instead of generating them locally
+    pub fn with_source(mut self, source: impl ChunkSource + 'static) -> Self {
+        self.source = Box::new(source);
+        self.source.reset(self.seed);
+        self
+    }
+
+    /// Reports the loaded and visible chunks into `metrics`
+    pub fn with_metrics(mut self, metrics: Arc<Metrics>) -> Self {
+        self.metrics = metrics;
+        self
+    }
+
+    /// Distance in chunks after which chunks are meshed at a lower resolution
+    pub fn with_lod_distance(mut self, lod_distance: Option<u32>) -> Self {
+        self.lod_distance = lod_distance;
+        self
+    }
+
+    /// Chunks shown around the camera, horizontally and above and below it
+    pub fn with_render_distance(mut self, horizontal: u32, vertical: u32) -> Self {
+        self.render_distance = horizontal;
+        self.vertical_render_distance = vertical;
+        self.update_offsets();
+        self
+    }
+
+    /// Shows chunks within a sphere around the camera instead of a box
+    pub fn with_spherical_render_distance(mut self, spherical: bool) -> Self {
+        self.set_spherical_render_distance(spherical);
+        self
+    }
+
+    /// Generates sections further out than the render distance needs, for example to have
+    /// them ready before the camera gets there
+    pub fn with_generation_distance(mut self, generation_distance: Option<u32>) -> Self {
+        self.set_generation_distance(generation_distance);
+        self
+    }
+
+    /// Blocks picked at random in every chunk each step, see [`RandomTicks`]
+    pub fn with_random_tick_speed(mut self, speed: u32) -> Self {
+        self.random_ticks.set_speed(speed);
+        self
+    }
+
+    pub fn random_tick_speed(&self) -> u32 {
+        self.random_ticks.speed()
+    }
+
+    pub fn set_random_tick_speed(&mut self, speed: u32) {
+        self.random_ticks.set_speed(speed);
+    }
+
+    pub fn render_distance(&self) -> u32 {
+        self.render_distance
+    }
+
+    pub fn vertical_render_distance(&self) -> u32 {
+        self.vertical_render_distance
+    }
+
+    /// Generation follows along, always a chunk further so meshes have their neighbors
+    pub fn set_render_distance(&mut self, render_distance: u32) {
+        self.render_distance = render_distance;
+        self.update_offsets();
+    }
+
+    pub fn set_vertical_render_distance(&mut self, vertical_render_distance: u32) {
+        self.vertical_render_distance = vertical_render_distance;
+        self.update_offsets();
+    }
+
+    pub fn spherical_render_distance(&self) -> bool {
+        self.spherical_render_distance
+    }
+
+    pub fn set_spherical_render_distance(&mut self, spherical: bool) {
+        self.spherical_render_distance = spherical;
+        self.update_offsets();
+    }
+
+    /// Raised to a chunk past the render distance when it's less, meshes need their neighbors
+    pub fn set_generation_distance(&mut self, generation_distance: Option<u32>) {
+        self.generation_distance = generation_distance;
+        self.update_offsets();
+    }
+
+    pub fn generation_distance(&self) -> u32 {
+        self.generation_distance
+            .unwrap_or(self.min_generation_distance())
+    }
+
+    fn min_generation_distance(&self) -> u32 {
+        self.render_distance + 1
+    }
+
+    fn update_offsets(&mut self) {
+        let min = self.min_generation_distance();
+        if let Some(distance) = self.generation_distance.filter(|&distance| distance < min) {
+            warn!(
+                "generation distance {distance} doesn't cover render distance {}, using {min}",
+                self.render_distance
+            );
+            self.generation_distance = Some(min);
+        }
+
+        // The neighbors of a chunk within the sphere are all within one chunk more of it,
+        // so the rounded generation region still covers every meshed chunk and its neighbors
+        self.generating_sections_offsets = generating_sections_offsets(
+            self.generation_distance() as i32,
+            self.spherical_render_distance,
+        );
+        self.visible_chunks_offsets = visible_chunks_offsets(
+            self.render_distance as i32,
+            self.vertical_render_distance as i32,
+            self.spherical_render_distance,
+        );
+        // Picks the new offsets up on the next update
+        self.previous_origin = None;
+    }
+
+    pub fn chunks(&self) -> &Chunks {
+        &self.chunks
+    }
+
+    pub fn seed(&self) -> u32 {
+        self.seed
+    }
+
+    /// Sections requested from the source that haven't arrived yet
+    pub fn pending_sections(&self) -> usize {
+        self.requested_sections.len()
+    }
+
+    pub fn reset(&mut self, seed: u32, mesh_generator: &MeshGenerator) {
+        mesh_generator.clear();
+
+        self.chunks.write().clear();
+        self.generated_sections.clear();
+        self.requested_sections.clear();
+        self.pending_edits.clear();
+        self.block_events.clear();
+        self.fluids.clear();
+        self.scheduled_ticks.clear();
+        self.chunks.take_dirty();
+        self.source.reset(seed);
+        self.random_ticks.reseed(seed);
+        self.seed = seed;
+        self.previous_origin = None;
+        self.prefetch_direction = None;
+    }
+
+    /// Block at the given world position, air where nothing has been generated
+    pub fn block(&self, position: IVec3) -> Block {
+        block_in(&self.chunks.read(), position)
+    }
+
+    /// Throws away every mesh and queues the visible chunks again
+    pub fn remesh(&mut self, mesh_generator: &MeshGenerator) {
+        mesh_generator.clear();
+        self.previous_origin = None;
+    }
+
+    /// Places blocks in the world, queueing the ones that land in sections that haven't been
+    /// generated yet so they're written over the terrain once it is
+    pub fn set_blocks(&mut self, blocks: impl IntoIterator<Item = (IVec3, Block)>) {
+        let blocks = blocks.into_iter().collect::<Vec<_>>();
+        for &(position, _) in &blocks {
+            self.fluids.remove_level(position);
+        }
+        self.place_blocks(blocks, true);
+    }
+
+    /// Places `structure` with its anchor at `origin`, see [`World::set_blocks`]
+    pub fn place_structure(&mut self, origin: IVec3, structure: &Structure) {
+        self.set_blocks(structure.blocks(origin));
+    }
+
+    /// Same as [`World::set_blocks`], `events` tells whether the changes show up in
+    /// [`World::take_block_events`]
+    fn place_blocks(&mut self, blocks: impl IntoIterator<Item = (IVec3, Block)>, events: bool) {
+        let height = (RawChunk::SIZE * SECTION_SIZE as u32) as i32;
+        let mut chunks = self.chunks.write();
+        let mut changed = Vec::new();
+
+        for (position, block) in blocks {
+            if !(0..height).contains(&position.y) {
+                continue;
+            }
+
+            let section = coords::block_to_section(position);
+            if self
+                .bounds
+                .is_some_and(|bounds| !bounds.contains_section(section))
+            {
+                continue;
+            }
+
+            if self.generated_sections.contains(&section) {
+                let previous = chunks.set_block(position, block);
+                for neighbor in iter::once(position).chain(OFFSETS.map(|offset| position + offset))
+                {
+                    if let Some(delay) = tick_delay(block_in(&chunks, neighbor)) {
+                        self.scheduled_ticks.schedule(neighbor, delay);
+                    }
+                }
+                if events {
+                    if block == Block::Air && previous.visibility() != Visibility::Empty {
+                        self.block_events.push(BlockEvent::Broken {
+                            position,
+                            block: previous,
+                        });
+                    } else if block != Block::Air && previous == Block::Air {
+                        self.block_events
+                            .push(BlockEvent::Placed { position, block });
+                    }
+                }
+                changed.push((position, block));
+            } else {
+                self.pending_edits.push(position, block);
+            }
+        }
+
+        let mut light = LightPropagation::new(&mut chunks, |position| {
+            is_generated(&self.generated_sections, position)
+        });
+        for (position, block) in changed {
+            light.block_changed(position, block);
+        }
+        light.propagate();
+        let changed_light = light.into_changed_chunks();
+        chunks.mark_dirty(changed_light);
+    }
+
+    /// Advances the world by one fixed step, firing the updates scheduled for it and the
+    /// random ticks
+    pub fn tick(&mut self) {
+        // Updates in sections that are no longer loaded are dropped
+        let (falling, flowing): (Vec<_>, Vec<_>) = {
+            let chunks = self.chunks.read();
+            self.scheduled_ticks
+                .poll()
+                .into_iter()
+                .filter(|&position| is_generated(&self.generated_sections, position))
+                .partition(|&position| block_in(&chunks, position).falls())
+        };
+
+        // Blocks fall first so water doesn't flow into where they land
+        let changes = {
+            let chunks = self.chunks.read();
+            gravity::step(
+                falling,
+                |position| block_in(&chunks, position),
+                |position| is_generated(&self.generated_sections, position),
+            )
+        };
+        if !changes.is_empty() {
+            self.place_blocks(changes, false);
         }
-        self.previous_origin = origin;
 
-        self.update_chunks(origin);
-        self.update_visible_chunks(origin, mesh_generator);
+        let changes = {
+            let chunks = self.chunks.read();
+            self.fluids.step(
+                flowing,
+                |position| block_in(&chunks, position),
+                |position| is_generated(&self.generated_sections, position),
+            )
+        };
+        if !changes.is_empty() {
+            self.place_blocks(changes, false);
+        }
+
+        let changes = self.random_ticks.step(&self.chunks.read());
+        if !changes.is_empty() {
+            self.place_blocks(changes, false);
+        }
+    }
+
+    /// Blocks broken or placed by [`World::set_blocks`] since the last call
+    pub fn take_block_events(&mut self) -> Vec<BlockEvent> {
+        mem::take(&mut self.block_events)
+    }
+
+    /// First breakable block along the ray, see [`raycast::raycast`]
+    pub fn raycast(&self, origin: Vec3, direction: Vec3, max_distance: f32) -> Option<RaycastHit> {
+        let chunks = self.chunks.read();
+        raycast::raycast(origin, direction, max_distance, |position| {
+            block_in(&chunks, position)
+        })
+    }
+
+    /// Streams sections in around the chunk at `origin`, new ones are only requested once the
+    /// origin moves into another chunk. The ones ahead along `direction` are requested first
+    pub fn update_streaming(
+        &mut self,
+        origin: IVec3,
+        direction: Vec3,
+        mesh_generator: &MeshGenerator,
+    ) {
+        let moved = self.previous_origin != Some(origin);
+        if moved {
+            self.previous_origin = Some(origin);
+            self.update_chunks(origin);
+        }
+        self.prefetch(origin, direction, moved);
+
+        // Sections from a remote source can arrive long after the camera stopped
+        if self.receive_sections() || moved {
+            self.update_visible_chunks(origin, mesh_generator);
+        }
     }
 
+    /// Runs every frame wherever the camera is, the chunks marked dirty since the last call
+    /// are remeshed with a single request
+    pub fn update_dynamic(&mut self, mesh_generator: &MeshGenerator) {
+        let dirty = self.chunks.take_dirty();
+        if !dirty.is_empty() {
+            mesh_generator.remesh(dirty.into_iter().collect());
+        }
+    }
+
+    /// Requests the sections around the origin that weren't asked for yet
     fn update_chunks(&mut self, origin: IVec3) {
         let origin = origin.into();
-        let new_sections_positions = {
-            GENERATING_SECTIONS_OFFSETS
-                .iter()
-                .copied()
-                .map(|position| position + origin)
-                .filter(|&position| self.generated_sections.insert(position))
+        let bounds = self.bounds;
+        self.pending_edits
+            .retain_near(origin, self.generation_distance() as i32 * 2);
+
+        for &offset in &self.generating_sections_offsets {
+            let position = offset + origin;
+            if bounds.is_some_and(|bounds| !bounds.contains_section(position))
+                || self.generated_sections.contains(&position)
+            {
+                continue;
+            }
+            if self.requested_sections.insert(position) {
+                self.source.request(position);
+            }
+        }
+    }
+
+    /// Moves the sections along a few rays ahead of the camera to the front of the requests,
+    /// out past the generation distance, so the terrain in view arrives before the one behind
+    fn prefetch(&mut self, origin: IVec3, direction: Vec3, moved: bool) {
+        // Looking straight up or down
+        let Some(direction) = vec2(direction.x, direction.z).try_normalize() else {
+            return;
         };
+        if !moved
+            && self
+                .prefetch_direction
+                .is_some_and(|previous| previous.angle_to(direction).abs() < PREFETCH_TURN)
+        {
+            return;
+        }
+        self.prefetch_direction = Some(direction);
 
-        let new_chunks = new_sections_positions
-            .flat_map(|position| {
-                let section = self.generator.generate_section(position);
+        let rays = PREFETCH_RAYS.map(|angle| Vec2::from_angle(angle).rotate(direction));
+        let start = vec2(origin.x as f32, origin.z as f32) + 0.5;
+        let reach = self.generation_distance() as f32 * PREFETCH_REACH;
+        let mut prefetched = Vec::new();
+        let mut visited = HashSet::new();
+        // Half a section per step, so a ray doesn't skip the sections it barely crosses. All
+        // rays step together, the nearest sections go first
+        for step in 1..=(reach * 2.0) as u32 {
+            for ray in rays {
+                let point = (start + ray * step as f32 * 0.5).floor();
+                let position = ChunkSectionPosition::from((point.x as i32, point.y as i32));
+                if !visited.insert(position)
+                    || self
+                        .bounds
+                        .is_some_and(|bounds| !bounds.contains_section(position))
+                    || self.generated_sections.contains(&position)
+                {
+                    continue;
+                }
+                if self.requested_sections.insert(position) {
+                    self.source.request(position);
+                }
+                prefetched.push(position);
+            }
+        }
+        self.source.prioritize(&prefetched);
+    }
+
+    /// Inserts the sections the source answered with, returns whether any arrived
+    fn receive_sections(&mut self) -> bool {
+        let mut new_chunks = Vec::new();
+        let mut received = 0;
+        for (position, mut section) in self.source.poll() {
+            // Answers to requests from before a reset
+            if !self.requested_sections.remove(&position) {
+                continue;
+            }
+            self.generated_sections.insert(position);
+            received += 1;
+
+            self.pending_edits.apply(position, &mut section);
+            new_chunks.extend(
                 section
                     .into_chunks()
-                    .map(move |(y, chunk)| (position.with_y(y as i32), chunk))
-            })
-            .collect::<Box<_>>();
+                    .map(|(y, chunk)| (position.with_y(y as i32), chunk)),
+            );
+        }
+        if received > 0 {
+            let generated = self
+                .metrics
+                .sections_generated
+                .fetch_add(received, Ordering::Relaxed)
+                + received;
+            self.metrics.report(Progress::Sections {
+                generated,
+                pending: self.requested_sections.len() as u64,
+            });
+        }
         if new_chunks.is_empty() {
-            return;
+            return false;
+        }
+
+        let mut chunks = self.chunks.write();
+        chunks.extend(new_chunks.iter().cloned());
+
+        let mut light = LightPropagation::new(&mut chunks, |position| {
+            is_generated(&self.generated_sections, position)
+        });
+        for (position, _) in new_chunks.iter() {
+            light.chunk_inserted(*position);
         }
+        light.propagate();
+        let changed_light = light.into_changed_chunks();
+        chunks.mark_dirty(changed_light);
 
-        self.chunks.write().extend(new_chunks.iter().cloned());
+        self.metrics.chunks.set(chunks.len() as u64);
+        self.metrics
+            .chunk_bytes
+            .set(chunks.values().map(Chunk::heap_size).sum::<usize>() as u64);
+        true
     }
 
     fn update_visible_chunks(&self, origin: IVec3, mesh_generator: &MeshGenerator) {
         let visible_chunks = {
             let chunks = self.chunks.read();
-            VISIBLE_CHUNKS_OFFSETS
+            self.visible_chunks_offsets
                 .iter()
                 .copied()
                 .map(|position| position + origin)
+                .filter(|&position| {
+                    self.bounds
+                        .is_none_or(|bounds| bounds.contains_chunk(position))
+                })
                 .filter(|position| chunks.contains_key(position))
+                .map(|position| {
+                    let distance = (position - origin).as_vec3().length();
+                    (position, Lod::from_distance(distance, self.lod_distance))
+                })
                 .collect::<Box<_>>()
         };
 
-        mesh_generator.set_visible(visible_chunks);
+        self.metrics.visible_chunks.set(visible_chunks.len() as u64);
+        mesh_generator.set_visible(origin, visible_chunks);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::UVec3;
+    use parking_lot::Mutex;
+
+    use std::sync::mpsc;
+
+    use super::{chunk::ChunkSection, *};
+    use crate::application::MeshGeneratorMessage;
+
+    #[derive(Default)]
+    struct Log {
+        requested: Vec<ChunkSectionPosition>,
+        prioritized: Vec<ChunkSectionPosition>,
+        // Handed out on the next poll
+        answers: Vec<(ChunkSectionPosition, ChunkSection)>,
+    }
+
+    // Remembers what it was asked and answers with whatever the test put in
+    #[derive(Default, Clone)]
+    struct ScriptedSource(Arc<Mutex<Log>>);
+
+    impl ChunkSource for ScriptedSource {
+        fn request(&mut self, position: ChunkSectionPosition) {
+            self.0.lock().requested.push(position);
+        }
+
+        fn prioritize(&mut self, positions: &[ChunkSectionPosition]) {
+            self.0.lock().prioritized = positions.to_vec();
+        }
+
+        fn poll(&mut self) -> Vec<(ChunkSectionPosition, ChunkSection)> {
+            mem::take(&mut self.0.lock().answers)
+        }
+
+        fn reset(&mut self, _seed: u32) {}
+    }
+
+    fn world(source: &ScriptedSource) -> World {
+        World::new(Chunks::default(), None)
+            .with_render_distance(4, 2)
+            .with_source(source.clone())
+    }
+
+    fn stone_section() -> ChunkSection {
+        let mut section = ChunkSection::default();
+        section.set(UVec3::ZERO, Block::Stone);
+        section
+    }
+
+    #[test]
+    fn prefetch_reaches_ahead_of_the_camera() {
+        let source = ScriptedSource::default();
+        let mut world = world(&source);
+        world.prefetch(IVec3::ZERO, Vec3::X, true);
+
+        let prioritized = mem::take(&mut source.0.lock().prioritized);
+        assert_eq!(prioritized[0], ChunkSectionPosition::new(0, 0));
+        assert!(prioritized.iter().all(|position| position.x >= 0));
+        let reach = world.generation_distance() as i32;
+        assert!(prioritized.iter().any(|position| position.x > reach));
+        // Nearest first, give or take the section a ray steps through
+        let distances = prioritized
+            .iter()
+            .map(|position| vec2(position.x as f32, position.z as f32).length())
+            .collect::<Vec<_>>();
+        assert!(distances.windows(2).all(|pair| pair[0] <= pair[1] + 1.5));
+
+        // A small turn isn't worth prioritizing again
+        world.prefetch(IVec3::ZERO, Vec3::new(1.0, 0.0, 0.1), false);
+        assert!(source.0.lock().prioritized.is_empty());
+
+        world.prefetch(IVec3::ZERO, Vec3::NEG_X, false);
+        let log = source.0.lock();
+        assert!(log.prioritized.iter().all(|position| position.x <= 0));
+        // Requested once, however many times they're prioritized
+        let requested = log.requested.iter().collect::<HashSet<_>>();
+        assert_eq!(requested.len(), log.requested.len());
+    }
+
+    #[test]
+    fn sections_are_received_once() {
+        let source = ScriptedSource::default();
+        let mut world = world(&source);
+        world.prefetch(IVec3::ZERO, Vec3::X, true);
+        let requested = ChunkSectionPosition::new(1, 0);
+        let unrequested = ChunkSectionPosition::new(-5, 0);
+
+        source.0.lock().answers = vec![
+            (requested, stone_section()),
+            (requested, stone_section()),
+            (unrequested, stone_section()),
+        ];
+        assert!(world.receive_sections());
+        assert!(world.generated_sections.contains(&requested));
+        assert!(!world.requested_sections.contains(&requested));
+        assert!(!world.generated_sections.contains(&unrequested));
+        assert_eq!(world.block(IVec3::new(16, 0, 0)), Block::Stone);
+        assert_eq!(world.block(IVec3::new(-80, 0, 0)), Block::Air);
+
+        // Prefetching again doesn't ask for what already arrived
+        world.prefetch(IVec3::ZERO, Vec3::NEG_X, true);
+        world.prefetch(IVec3::ZERO, Vec3::X, true);
+        let log = source.0.lock();
+        assert_eq!(
+            log.requested
+                .iter()
+                .filter(|&&position| position == requested)
+                .count(),
+            1
+        );
+        assert!(!log.prioritized.contains(&requested));
+    }
+
+    #[test]
+    fn edits_wait_for_their_section() {
+        let source = ScriptedSource::default();
+        let mut world = world(&source);
+        world.prefetch(IVec3::ZERO, Vec3::X, true);
+        let position = IVec3::new(20, 5, 3);
+        world.set_blocks([(position, Block::Glowstone)]);
+        assert_eq!(world.block(position), Block::Air);
+
+        source.0.lock().answers = vec![(ChunkSectionPosition::new(1, 0), stone_section())];
+        assert!(world.receive_sections());
+        assert_eq!(world.block(position), Block::Glowstone);
+        assert_eq!(world.block(IVec3::new(16, 0, 0)), Block::Stone);
+    }
+
+    #[test]
+    fn stationary_edits_are_remeshed_once() {
+        let source = ScriptedSource::default();
+        let mut world = world(&source);
+        let (sender, receiver) = mpsc::channel();
+        let mesh_generator = MeshGenerator::new(sender, false);
+        world.update_streaming(IVec3::ZERO, Vec3::X, &mesh_generator);
+        source.0.lock().answers = vec![(ChunkSectionPosition::new(0, 0), stone_section())];
+        world.update_streaming(IVec3::ZERO, Vec3::X, &mesh_generator);
+        world.update_dynamic(&mesh_generator);
+        receiver.try_iter().for_each(drop);
+
+        world.set_blocks([(IVec3::new(4, 5, 6), Block::Glowstone)]);
+        // A few frames with the camera standing still
+        for _ in 0..3 {
+            world.update_streaming(IVec3::ZERO, Vec3::X, &mesh_generator);
+            world.update_dynamic(&mesh_generator);
+        }
+
+        let messages = receiver.try_iter().collect::<Vec<_>>();
+        let [MeshGeneratorMessage::Remesh(chunks)] = messages.as_slice() else {
+            panic!("expected a single remesh");
+        };
+        assert!(chunks.iter().any(|&(position, _)| position == IVec3::ZERO));
+    }
+
+    #[test]
+    fn sand_falls_across_chunk_borders() {
+        let source = ScriptedSource::default();
+        let mut world = world(&source);
+        world.prefetch(IVec3::ZERO, Vec3::X, true);
+        source.0.lock().answers = vec![(ChunkSectionPosition::new(1, 0), stone_section())];
+        assert!(world.receive_sections());
+
+        // Starts two chunks up and lands on the stone in the bottom one
+        let column = [IVec3::new(16, 33, 0), IVec3::new(16, 34, 0)];
+        world.set_blocks(column.map(|position| (position, Block::Sand)));
+        for _ in 0..40 {
+            world.tick();
+        }
+
+        assert_eq!(world.block(IVec3::new(16, 1, 0)), Block::Sand);
+        assert_eq!(world.block(IVec3::new(16, 2, 0)), Block::Sand);
+        assert!((3..40).all(|y| world.block(IVec3::new(16, y, 0)) == Block::Air));
+    }
+
+    #[test]
+    fn structures_are_placed_across_sections() {
+        let source = ScriptedSource::default();
+        let mut world = world(&source);
+        world.prefetch(IVec3::ZERO, Vec3::X, true);
+        source.0.lock().answers = vec![(ChunkSectionPosition::new(1, 0), stone_section())];
+        assert!(world.receive_sections());
+
+        // A glowstone wall from x = 14 to 17, its anchor at the second cell
+        let wall = Structure::new(
+            vec![Block::Glowstone],
+            UVec3::new(4, 1, 1),
+            vec![0; 4],
+            IVec3::X,
+        )
+        .unwrap();
+        world.place_structure(IVec3::new(15, 8, 2), &wall);
+        let placed = |world: &World| {
+            (14..18)
+                .map(|x| world.block(IVec3::new(x, 8, 2)))
+                .collect::<Vec<_>>()
+        };
+        use Block::{Air, Glowstone};
+        assert_eq!(placed(&world), [Air, Air, Glowstone, Glowstone]);
+
+        // The rest waits for its section
+        source.0.lock().answers = vec![(ChunkSectionPosition::new(0, 0), stone_section())];
+        assert!(world.receive_sections());
+        assert_eq!(placed(&world), [Glowstone; 4]);
     }
 }
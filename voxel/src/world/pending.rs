@@ -0,0 +1,133 @@
+use std::collections::HashMap;
+
+use glam::{IVec3, UVec3};
+
+use super::{
+    chunk::{ChunkSection, ChunkSectionPosition},
+    coords, Block,
+};
+
+/// Blocks placed into sections that haven't been generated yet
+#[derive(Default)]
+pub struct PendingEdits {
+    edits: HashMap<ChunkSectionPosition, Vec<(UVec3, Block)>>,
+}
+
+impl PendingEdits {
+    /// Queues the block at the world `position` for the section it lands in
+    pub fn push(&mut self, position: IVec3, block: Block) {
+        let section = coords::block_to_section(position);
+        let min = coords::chunk_to_block_min(section.with_y(0));
+        self.edits
+            .entry(section)
+            .or_default()
+            .push(((position - min).as_uvec3(), block));
+    }
+
+    /// Writes the edits queued for `position` over the freshly generated section
+    pub fn apply(&mut self, position: ChunkSectionPosition, section: &mut ChunkSection) {
+        let Some(edits) = self.edits.remove(&position) else {
+            return;
+        };
+
+        for (position, block) in edits {
+            section.set(position, block);
+        }
+    }
+
+    /// Drops edits for sections further than `distance` sections away from `origin`
+    pub fn retain_near(&mut self, origin: ChunkSectionPosition, distance: i32) {
+        self.edits.retain(|position, _| {
+            (position.x - origin.x).abs() <= distance && (position.z - origin.z).abs() <= distance
+        });
+    }
+
+    pub fn clear(&mut self) {
+        self.edits.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::{ivec3, uvec3};
+
+    use super::*;
+
+    #[test]
+    fn queued_air_carves_generated_blocks() {
+        let position = ChunkSectionPosition::new(0, 0);
+        let mut section = ChunkSection::default();
+        section.fill_region(UVec3::ZERO, uvec3(15, 3, 15), Block::Stone);
+
+        let mut edits = PendingEdits::default();
+        edits.push(ivec3(2, 3, 2), Block::Air);
+        edits.apply(position, &mut section);
+
+        assert_eq!(section.get(uvec3(2, 3, 2)), Block::Air);
+        assert_eq!(section.get(uvec3(2, 2, 2)), Block::Stone);
+    }
+
+    #[test]
+    fn edits_apply_once_in_order() {
+        let position = ChunkSectionPosition::new(3, -2);
+        let mut edits = PendingEdits::default();
+        edits.push(ivec3(49, 40, -31), Block::Dirt);
+        edits.push(ivec3(49, 40, -31), Block::Sand);
+        edits.push(ivec3(1, 40, 1), Block::Snow);
+
+        let mut section = ChunkSection::default();
+        edits.apply(position, &mut section);
+        // The last edit of a block wins, other sections keep theirs
+        assert_eq!(section.get(uvec3(1, 40, 1)), Block::Sand);
+
+        let mut section = ChunkSection::default();
+        edits.apply(position, &mut section);
+        assert_eq!(section.get(uvec3(1, 40, 1)), Block::Air);
+    }
+
+    #[test]
+    fn far_edits_are_dropped() {
+        let near = ChunkSectionPosition::new(2, -2);
+        let far = ChunkSectionPosition::new(3, 0);
+        let mut edits = PendingEdits::default();
+        edits.push(ivec3(32, 0, -32), Block::Stone);
+        edits.push(ivec3(48, 0, 0), Block::Stone);
+        edits.retain_near(ChunkSectionPosition::new(0, 0), 2);
+
+        for (position, expected) in [(near, Block::Stone), (far, Block::Air)] {
+            let mut section = ChunkSection::default();
+            edits.apply(position, &mut section);
+            assert_eq!(section.get(UVec3::ZERO), expected);
+        }
+    }
+
+    #[test]
+    fn rows_across_a_section_border_are_split() {
+        let mut edits = PendingEdits::default();
+        // From x = -3 in the section west of the origin to x = 1 in the one at the origin
+        for x in -3..=1 {
+            edits.push(ivec3(x, 70, -1), Block::Glowstone);
+        }
+
+        let west = ChunkSectionPosition::new(-1, -1);
+        let mut section = ChunkSection::default();
+        edits.apply(west, &mut section);
+        for x in 0..16 {
+            let expected = if x >= 13 {
+                Block::Glowstone
+            } else {
+                Block::Air
+            };
+            assert_eq!(section.get(uvec3(x, 70, 15)), expected, "{x}");
+        }
+
+        let east = ChunkSectionPosition::new(0, -1);
+        let mut section = ChunkSection::default();
+        edits.apply(east, &mut section);
+        for x in 0..16 {
+            let expected = if x <= 1 { Block::Glowstone } else { Block::Air };
+            assert_eq!(section.get(uvec3(x, 70, 15)), expected, "{x}");
+        }
+        assert!(edits.edits.is_empty());
+    }
+}
@@ -0,0 +1,127 @@
+use glam::{uvec3, IVec3, UVec3};
+
+use super::{
+    block_in,
+    chunk::{Chunk, RawChunk, Volume},
+    coords, Block, ChunkMap, Visibility,
+};
+
+// Bits of the random number each axis of a picked block comes from
+const AXIS_BITS: u32 = u32::BITS / 3;
+
+/// Updates a few blocks picked at random in every chunk each step, for changes that happen
+/// slowly over time like grass growing. Steps run at 60 a second, so with a speed of 1 every
+/// block of a 16³ chunk gets picked about once every 68 seconds on average
+#[derive(Debug)]
+pub struct RandomTicks {
+    // State of the xorshift generator picking the blocks
+    random: u32,
+    // Blocks picked in every chunk each step, zero turns random ticks off
+    speed: u32,
+}
+
+impl RandomTicks {
+    /// Picks the same blocks for the same seed, starts out turned off
+    pub fn new(seed: u32) -> Self {
+        Self {
+            // Xorshift never leaves zero
+            random: seed | 1,
+            speed: 0,
+        }
+    }
+
+    /// Starts picking the blocks from the beginning of `seed`, keeping the speed
+    pub fn reseed(&mut self, seed: u32) {
+        self.random = seed | 1;
+    }
+
+    pub fn speed(&self) -> u32 {
+        self.speed
+    }
+
+    pub fn set_speed(&mut self, speed: u32) {
+        self.speed = speed;
+    }
+
+    fn next_random(&mut self) -> u32 {
+        self.random ^= self.random << 13;
+        self.random ^= self.random >> 17;
+        self.random ^= self.random << 5;
+        self.random
+    }
+
+    /// Block of a chunk to update next
+    fn next_local(&mut self) -> UVec3 {
+        let random = self.next_random();
+        uvec3(random, random >> AXIS_BITS, random >> (AXIS_BITS * 2)) % RawChunk::SIZE
+    }
+
+    /// Picks the blocks and returns the ones that change along with what they become
+    pub fn step(&mut self, chunks: &ChunkMap) -> Vec<(IVec3, Block)> {
+        let mut changes = Vec::new();
+        if self.speed == 0 {
+            return changes;
+        }
+
+        for (&position, chunk) in chunks.iter() {
+            // Nothing in a chunk of a single block has anything to react to
+            if let Chunk::Uniform(..) = chunk {
+                continue;
+            }
+
+            let min = coords::chunk_to_block_min(position);
+            for _ in 0..self.speed {
+                let local = self.next_local();
+                let position = min + local.as_ivec3();
+
+                if let Some(block) = random_tick(position, chunk[local], chunks) {
+                    changes.push((position, block));
+                }
+            }
+        }
+
+        changes
+    }
+}
+
+/// What the block turns into when it's picked, `None` if it stays
+fn random_tick(position: IVec3, block: Block, chunks: &ChunkMap) -> Option<Block> {
+    let covered = is_covering(block_in(chunks, position + IVec3::Y));
+
+    match block {
+        Block::Grass if covered => Some(Block::Dirt),
+        Block::Dirt if !covered && is_next_to_grass(position, chunks) => Some(Block::Grass),
+        _ => None,
+    }
+}
+
+// Grass needs the light and air of an uncovered block above it
+fn is_covering(block: Block) -> bool {
+    block.visibility() == Visibility::Opaque || block == Block::Water
+}
+
+/// Whether grass is in the blocks around, one block up or down included
+fn is_next_to_grass(position: IVec3, chunks: &ChunkMap) -> bool {
+    (-1..=1).any(|x| {
+        (-1..=1).any(|y| {
+            (-1..=1).any(|z| block_in(chunks, position + IVec3::new(x, y, z)) == Block::Grass)
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::*;
+
+    #[test]
+    fn every_block_gets_picked() {
+        let mut ticks = RandomTicks::new(7);
+        let blocks = RawChunk::SIZE.pow(3) as usize;
+        let picked = (0..blocks * 20)
+            .map(|_| ticks.next_local())
+            .collect::<HashSet<_>>();
+        assert_eq!(picked.len(), blocks);
+    }
+}
@@ -0,0 +1,63 @@
+use glam::{IVec3, Vec3};
+
+use super::{coords, Block};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RaycastHit {
+    pub position: IVec3,
+    pub block: Block,
+    /// Side of the block the ray entered through, zero when it started inside the block
+    pub normal: IVec3,
+}
+
+/// Walks the blocks along the ray until `block_at` returns one that can be broken
+///
+/// Visits every block the ray passes through, in order, up to `max_distance` away from `origin`
+pub fn raycast(
+    origin: Vec3,
+    direction: Vec3,
+    max_distance: f32,
+    block_at: impl Fn(IVec3) -> Block,
+) -> Option<RaycastHit> {
+    let direction = direction.try_normalize()?;
+    let step = direction.signum().as_ivec3();
+
+    let mut position = coords::position_to_block(origin);
+    // Distance along the ray to cross one block on every axis
+    let delta = direction.recip().abs();
+    // Distance along the ray to the next block boundary on every axis
+    let mut next = Vec3::select(
+        direction.cmpgt(Vec3::ZERO),
+        (position.as_vec3() + 1.0 - origin) * delta,
+        (origin - position.as_vec3()) * delta,
+    );
+    let mut normal = IVec3::ZERO;
+    let mut distance = 0.0;
+
+    while distance <= max_distance {
+        let block = block_at(position);
+        if block.hardness().is_some() {
+            return Some(RaycastHit {
+                position,
+                block,
+                normal,
+            });
+        }
+
+        let axis = if next.x < next.y && next.x < next.z {
+            0
+        } else if next.y < next.z {
+            1
+        } else {
+            2
+        };
+
+        distance = next[axis];
+        next[axis] += delta[axis];
+        position[axis] += step[axis];
+        normal = IVec3::ZERO;
+        normal[axis] = -step[axis];
+    }
+
+    None
+}
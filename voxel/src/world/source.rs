@@ -0,0 +1,282 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{channel, Receiver, Sender},
+        Arc,
+    },
+};
+
+use parking_lot::Mutex;
+use rayon::ThreadPool;
+
+use super::{
+    chunk::{ChunkSection, ChunkSectionPosition},
+    generator::{DefaultGenerator, Generate},
+};
+
+/// Where the world gets its sections from, answers may arrive any number of updates
+/// after the request
+pub trait ChunkSource {
+    fn request(&mut self, position: ChunkSectionPosition);
+
+    /// Moves the outstanding requests among `positions` ahead of the others in that order,
+    /// replacing the ones prioritized before. Sources that can't reorder requests ignore it
+    fn prioritize(&mut self, _positions: &[ChunkSectionPosition]) {}
+
+    /// Sections that arrived since the last call
+    fn poll(&mut self) -> Vec<(ChunkSectionPosition, ChunkSection)>;
+
+    /// Drops the outstanding requests, the sections that follow belong to the new seed
+    fn reset(&mut self, seed: u32);
+}
+
+type Generated = (u32, ChunkSectionPosition, ChunkSection);
+
+/// Requests waiting to be generated, the prioritized ones go first
+struct Requests {
+    prioritized: VecDeque<ChunkSectionPosition>,
+    queued: VecDeque<ChunkSectionPosition>,
+    // Swapped together with the epoch, so a request is never generated with the old seed
+    generator: Arc<DefaultGenerator>,
+    // Bumped on every reset, sections still generating for the old seed are dropped
+    epoch: u32,
+}
+
+impl Requests {
+    fn new(seed: u32) -> Self {
+        Self {
+            prioritized: VecDeque::new(),
+            queued: VecDeque::new(),
+            generator: Arc::new(DefaultGenerator::new(seed)),
+            epoch: 0,
+        }
+    }
+
+    fn prioritize(&mut self, positions: &[ChunkSectionPosition]) {
+        // The previous ones still go before the rest
+        for position in self.prioritized.drain(..).rev() {
+            self.queued.push_front(position);
+        }
+
+        let order = positions
+            .iter()
+            .enumerate()
+            .map(|(index, &position)| (position, index))
+            .collect::<HashMap<_, _>>();
+        // Positions missing from the queue are generating already
+        let (mut prioritized, queued): (VecDeque<_>, _) = self
+            .queued
+            .drain(..)
+            .partition(|position| order.contains_key(position));
+        prioritized
+            .make_contiguous()
+            .sort_by_key(|position| order[position]);
+        self.prioritized = prioritized;
+        self.queued = queued;
+    }
+
+    fn pop(&mut self) -> Option<(u32, Arc<DefaultGenerator>, ChunkSectionPosition)> {
+        let position = self
+            .prioritized
+            .pop_front()
+            .or_else(|| self.queued.pop_front())?;
+        Some((self.epoch, Arc::clone(&self.generator), position))
+    }
+
+    fn reset(&mut self, seed: u32) {
+        self.generator = Arc::new(DefaultGenerator::new(seed));
+        self.prioritized.clear();
+        self.queued.clear();
+        self.epoch = self.epoch.wrapping_add(1);
+    }
+}
+
+/// Generates the requested sections on this machine, on the polling thread unless it's
+/// given a pool to generate on
+pub struct LocalSource {
+    pool: Option<ThreadPool>,
+    // Shared with the pool, every spawned job generates whichever request is first by then
+    requests: Arc<Mutex<Requests>>,
+    sender: Sender<Generated>,
+    receiver: Receiver<Generated>,
+    // Set once the source is dropped, so the pool doesn't generate sections nobody receives
+    cancelled: Arc<AtomicBool>,
+}
+
+impl LocalSource {
+    pub fn new(seed: u32) -> Self {
+        let (sender, receiver) = channel();
+
+        Self {
+            pool: None,
+            requests: Arc::new(Mutex::new(Requests::new(seed))),
+            sender,
+            receiver,
+            cancelled: Default::default(),
+        }
+    }
+
+    /// Generates in the background, the sections arrive as they're done
+    pub fn with_pool(mut self, pool: Option<ThreadPool>) -> Self {
+        self.pool = pool;
+        self
+    }
+}
+
+impl ChunkSource for LocalSource {
+    fn request(&mut self, position: ChunkSectionPosition) {
+        self.requests.lock().queued.push_back(position);
+        let Some(pool) = &self.pool else {
+            return;
+        };
+
+        let requests = Arc::clone(&self.requests);
+        let sender = self.sender.clone();
+        let cancelled = Arc::clone(&self.cancelled);
+        pool.spawn(move || {
+            if cancelled.load(Ordering::Acquire) {
+                return;
+            }
+            // Taken by a reset
+            let Some((epoch, generator, position)) = requests.lock().pop() else {
+                return;
+            };
+            // The source is gone once the receiver is
+            let _ = sender.send((epoch, position, generator.generate_section(position)));
+        });
+    }
+
+    fn prioritize(&mut self, positions: &[ChunkSectionPosition]) {
+        self.requests.lock().prioritize(positions);
+    }
+
+    fn poll(&mut self) -> Vec<(ChunkSectionPosition, ChunkSection)> {
+        let mut requests = self.requests.lock();
+        let epoch = requests.epoch;
+        let mut sections = Vec::new();
+        if self.pool.is_none() {
+            while let Some((_, generator, position)) = requests.pop() {
+                sections.push((position, generator.generate_section(position)));
+            }
+        }
+        drop(requests);
+
+        sections.extend(
+            self.receiver
+                .try_iter()
+                .filter(|(generated, ..)| *generated == epoch)
+                .map(|(_, position, section)| (position, section)),
+        );
+        sections
+    }
+
+    fn reset(&mut self, seed: u32) {
+        self.requests.lock().reset(seed);
+    }
+}
+
+impl Drop for LocalSource {
+    fn drop(&mut self) {
+        self.cancelled.store(true, Ordering::Release);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        iter,
+        ops::Range,
+        thread,
+        time::{Duration, Instant},
+    };
+
+    use rayon::ThreadPoolBuilder;
+
+    use super::*;
+
+    fn positions(range: Range<i32>) -> Vec<ChunkSectionPosition> {
+        range.map(|x| ChunkSectionPosition::new(x, 0)).collect()
+    }
+
+    fn drain(requests: &mut Requests) -> Vec<ChunkSectionPosition> {
+        iter::from_fn(|| requests.pop().map(|(_, _, position)| position)).collect()
+    }
+
+    /// Polls until `count` sections arrived, then a little longer to catch any extra ones
+    fn receive(source: &mut LocalSource, count: usize) -> Vec<ChunkSectionPosition> {
+        let deadline = Instant::now() + Duration::from_secs(60);
+        let mut received = Vec::new();
+        while received.len() < count && Instant::now() < deadline {
+            received.extend(source.poll().into_iter().map(|(position, _)| position));
+            thread::sleep(Duration::from_millis(1));
+        }
+        thread::sleep(Duration::from_millis(50));
+        received.extend(source.poll().into_iter().map(|(position, _)| position));
+        received
+    }
+
+    #[test]
+    fn prioritized_requests_go_first() {
+        let mut requests = Requests::new(0);
+        requests.queued.extend(positions(0..6));
+        requests.prioritize(&[5, 2, 9].map(|x| ChunkSectionPosition::new(x, 0)));
+        let (_, _, first) = requests.pop().unwrap();
+        assert_eq!(first, ChunkSectionPosition::new(5, 0));
+
+        // The ones left from before still go before the rest
+        requests.prioritize(&[ChunkSectionPosition::new(4, 0)]);
+        assert_eq!(
+            drain(&mut requests),
+            [4, 2, 0, 1, 3].map(|x| ChunkSectionPosition::new(x, 0))
+        );
+    }
+
+    #[test]
+    fn reset_drops_requests_and_bumps_the_epoch() {
+        let mut requests = Requests::new(0);
+        requests.queued.extend(positions(0..3));
+        requests.prioritize(&[ChunkSectionPosition::new(1, 0)]);
+        let (epoch, ..) = requests.pop().unwrap();
+
+        requests.reset(1);
+        assert!(requests.pop().is_none());
+        requests.queued.push_back(ChunkSectionPosition::new(7, 0));
+        let (new_epoch, _, position) = requests.pop().unwrap();
+        assert_ne!(new_epoch, epoch);
+        assert_eq!(position, ChunkSectionPosition::new(7, 0));
+    }
+
+    #[test]
+    fn sections_arrive_once_without_a_pool() {
+        let mut source = LocalSource::new(0);
+        for position in positions(0..3) {
+            source.request(position);
+        }
+        let received = source
+            .poll()
+            .into_iter()
+            .map(|(position, _)| position)
+            .collect::<Vec<_>>();
+        assert_eq!(received, positions(0..3));
+        assert!(source.poll().is_empty());
+    }
+
+    #[test]
+    fn sections_from_before_a_reset_are_dropped() {
+        let pool = ThreadPoolBuilder::new().num_threads(2).build().unwrap();
+        let mut source = LocalSource::new(0).with_pool(Some(pool));
+        for position in positions(0..4) {
+            source.request(position);
+        }
+        source.reset(1);
+        for position in positions(10..14) {
+            source.request(position);
+        }
+
+        // Jobs spawned for the dropped requests generate the new ones instead
+        let mut received = receive(&mut source, 4);
+        received.sort();
+        assert_eq!(received, positions(10..14));
+    }
+}
@@ -0,0 +1,282 @@
+#[cfg(feature = "serde")]
+use std::{
+    fs::File,
+    io::{self, BufReader, BufWriter},
+    path::Path,
+};
+
+use glam::{IVec3, UVec3};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+#[cfg(feature = "serde")]
+use crate::asset;
+
+use super::Block;
+
+#[cfg(feature = "serde")]
+#[derive(Debug, Error)]
+pub enum StructureError {
+    #[error("failed to access structure file: {0}")]
+    Io(#[from] io::Error),
+    #[error("invalid structure: {0}")]
+    Format(#[from] serde_json::Error),
+}
+
+#[derive(Debug, Error)]
+pub enum InvalidStructure {
+    #[error("a structure of size {size} has {expected} blocks, got {len}")]
+    Size {
+        size: UVec3,
+        expected: usize,
+        len: usize,
+    },
+    #[error("block {index} refers to palette entry {entry}, the palette has {len}")]
+    Palette { index: usize, entry: u8, len: usize },
+}
+
+#[cfg(feature = "serde")]
+#[derive(Serialize, Deserialize)]
+struct SerializedStructure {
+    palette: Vec<Block>,
+    size: UVec3,
+    anchor: IVec3,
+    #[serde(default)]
+    carve: bool,
+    blocks: Vec<u8>,
+}
+
+/// Blocks placed into the world together, like a building. Every cell is an index into the
+/// palette, layer by layer from the bottom up, each layer row by row along z
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(try_from = "SerializedStructure", into = "SerializedStructure")
+)]
+pub struct Structure {
+    palette: Vec<Block>,
+    size: UVec3,
+    blocks: Vec<u8>,
+    // Cell that lands on the origin the structure is placed at
+    anchor: IVec3,
+    // Air cells replace the terrain instead of leaving it as is
+    carve: bool,
+}
+
+impl Structure {
+    pub fn new(
+        palette: Vec<Block>,
+        size: UVec3,
+        blocks: Vec<u8>,
+        anchor: IVec3,
+    ) -> Result<Self, InvalidStructure> {
+        let expected = size.element_product() as usize;
+        if blocks.len() != expected {
+            return Err(InvalidStructure::Size {
+                size,
+                expected,
+                len: blocks.len(),
+            });
+        }
+        if let Some((index, &entry)) = blocks
+            .iter()
+            .enumerate()
+            .find(|(_, &entry)| entry as usize >= palette.len())
+        {
+            return Err(InvalidStructure::Palette {
+                index,
+                entry,
+                len: palette.len(),
+            });
+        }
+
+        Ok(Self {
+            palette,
+            size,
+            blocks,
+            anchor,
+            carve: false,
+        })
+    }
+
+    /// Small stone platform with a low wall, placed under the camera in new worlds
+    #[cfg(feature = "serde")]
+    pub fn spawn_platform() -> Self {
+        serde_json::from_str(include_str!(asset!("structures/spawn_platform.json")))
+            .expect("invalid spawn platform")
+    }
+
+    pub fn with_carving(mut self, carve: bool) -> Self {
+        self.carve = carve;
+        self
+    }
+
+    #[cfg(feature = "serde")]
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, StructureError> {
+        let reader = BufReader::new(File::open(path)?);
+        Ok(serde_json::from_reader(reader)?)
+    }
+
+    #[cfg(feature = "serde")]
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), StructureError> {
+        let writer = BufWriter::new(File::create(path)?);
+        Ok(serde_json::to_writer(writer, self)?)
+    }
+
+    pub fn size(&self) -> UVec3 {
+        self.size
+    }
+
+    pub fn anchor(&self) -> IVec3 {
+        self.anchor
+    }
+
+    /// Block of the cell at `local`, which has to be inside the structure
+    pub fn block(&self, local: UVec3) -> Block {
+        let index = local.x + local.z * self.size.x + local.y * self.size.x * self.size.z;
+        self.palette[self.blocks[index as usize] as usize]
+    }
+
+    /// World positions and blocks of the structure placed at `origin`, without the air cells
+    /// unless it carves
+    pub fn blocks(&self, origin: IVec3) -> impl Iterator<Item = (IVec3, Block)> + '_ {
+        let size = self.size;
+        (0..size.y)
+            .flat_map(move |y| {
+                (0..size.z).flat_map(move |z| (0..size.x).map(move |x| UVec3::new(x, y, z)))
+            })
+            .map(move |local| (origin + local.as_ivec3() - self.anchor, self.block(local)))
+            .filter(|&(_, block)| self.carve || block != Block::Air)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl TryFrom<SerializedStructure> for Structure {
+    type Error = InvalidStructure;
+
+    fn try_from(structure: SerializedStructure) -> Result<Self, Self::Error> {
+        Ok(Self::new(
+            structure.palette,
+            structure.size,
+            structure.blocks,
+            structure.anchor,
+        )?
+        .with_carving(structure.carve))
+    }
+}
+
+#[cfg(feature = "serde")]
+impl From<Structure> for SerializedStructure {
+    fn from(structure: Structure) -> Self {
+        Self {
+            palette: structure.palette,
+            size: structure.size,
+            anchor: structure.anchor,
+            carve: structure.carve,
+            blocks: structure.blocks,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Stone floor with a glowstone post in one corner, air next to it
+    fn post() -> Structure {
+        Structure::new(
+            vec![Block::Air, Block::Stone, Block::Glowstone],
+            UVec3::new(2, 2, 1),
+            vec![1, 1, 2, 0],
+            IVec3::new(1, 0, 0),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn mismatched_blocks_are_rejected() {
+        let size = UVec3::new(2, 1, 2);
+        let short = Structure::new(vec![Block::Stone], size, vec![0; 3], IVec3::ZERO);
+        assert!(matches!(
+            short,
+            Err(InvalidStructure::Size {
+                expected: 4,
+                len: 3,
+                ..
+            })
+        ));
+
+        let outside = Structure::new(vec![Block::Stone], size, vec![0, 0, 1, 0], IVec3::ZERO);
+        assert!(matches!(
+            outside,
+            Err(InvalidStructure::Palette {
+                index: 2,
+                entry: 1,
+                len: 1
+            })
+        ));
+    }
+
+    #[test]
+    fn blocks_are_placed_around_the_anchor() {
+        let origin = IVec3::new(10, 5, -3);
+        let placed = post().blocks(origin).collect::<Vec<_>>();
+        assert_eq!(
+            placed,
+            [
+                (IVec3::new(9, 5, -3), Block::Stone),
+                (IVec3::new(10, 5, -3), Block::Stone),
+                (IVec3::new(9, 6, -3), Block::Glowstone),
+            ]
+        );
+
+        // Carving structures clear the terrain in their air cells as well
+        let carved = post().with_carving(true).blocks(origin).collect::<Vec<_>>();
+        assert_eq!(carved.len(), 4);
+        assert_eq!(carved[3], (IVec3::new(10, 6, -3), Block::Air));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn structures_round_trip() {
+        let structure = post().with_carving(true);
+        let json = serde_json::to_string(&structure).unwrap();
+        assert_eq!(serde_json::from_str::<Structure>(&json).unwrap(), structure);
+
+        let path = std::env::temp_dir().join(format!("structure-{}.json", std::process::id()));
+        structure.save(&path).unwrap();
+        let loaded = Structure::load(&path);
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(loaded.unwrap(), structure);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn invalid_files_are_reported() {
+        let json = r#"{"palette":["stone"],"size":[1,1,1],"anchor":[0,0,0],"blocks":[3]}"#;
+        assert!(serde_json::from_str::<Structure>(json).is_err());
+
+        let missing = std::env::temp_dir().join("structure-that-does-not-exist.json");
+        assert!(matches!(
+            Structure::load(missing),
+            Err(StructureError::Io(_))
+        ));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn spawn_platform_is_a_floor_under_the_origin() {
+        let blocks = Structure::spawn_platform()
+            .blocks(IVec3::ZERO)
+            .collect::<Vec<_>>();
+        let floor = blocks.iter().filter(|(position, _)| position.y == -1);
+        assert_eq!(floor.count(), 49);
+        assert!(blocks.iter().all(|(position, _)| {
+            (-1..=0).contains(&position.y) && position.x.abs() <= 3 && position.z.abs() <= 3
+        }));
+        // Nothing is placed where the camera stands
+        assert!(blocks.iter().all(|&(position, _)| position != IVec3::ZERO));
+    }
+}
@@ -0,0 +1,129 @@
+use std::collections::{BTreeMap, HashSet};
+
+use glam::IVec3;
+
+/// Delayed block updates, fired in the order they fall due and, within the same tick, in the
+/// order they were scheduled
+#[derive(Debug, Default)]
+pub struct TickScheduler {
+    // Ticks polled so far
+    now: u64,
+    due: BTreeMap<u64, Vec<IVec3>>,
+    // Keeps a block from being updated twice in the same tick
+    scheduled: HashSet<(u64, IVec3)>,
+}
+
+impl TickScheduler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Updates the block `delay_ticks` polls from now, at least on the next one
+    pub fn schedule(&mut self, position: IVec3, delay_ticks: u64) {
+        let tick = self.now + delay_ticks.max(1);
+        if self.scheduled.insert((tick, position)) {
+            self.due.entry(tick).or_default().push(position);
+        }
+    }
+
+    /// Advances by one tick and returns the blocks to update in it
+    pub fn poll(&mut self) -> Vec<IVec3> {
+        self.now += 1;
+
+        let Some(positions) = self.due.remove(&self.now) else {
+            return Vec::new();
+        };
+        for &position in &positions {
+            self.scheduled.remove(&(self.now, position));
+        }
+        positions
+    }
+
+    /// Drops the updates of blocks `keep` returns false for, like the ones in unloaded chunks
+    pub fn retain(&mut self, mut keep: impl FnMut(IVec3) -> bool) {
+        self.due.retain(|_, positions| {
+            positions.retain(|&position| keep(position));
+            !positions.is_empty()
+        });
+        self.scheduled.retain(|&(_, position)| keep(position));
+    }
+
+    pub fn len(&self) -> usize {
+        self.scheduled.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.scheduled.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.due.clear();
+        self.scheduled.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fires_in_order() {
+        let mut ticks = TickScheduler::new();
+        ticks.schedule(IVec3::X, 2);
+        ticks.schedule(IVec3::Y, 1);
+        ticks.schedule(IVec3::Z, 2);
+
+        assert_eq!(ticks.poll(), [IVec3::Y]);
+        assert_eq!(ticks.poll(), [IVec3::X, IVec3::Z]);
+        assert_eq!(ticks.poll(), []);
+        assert!(ticks.is_empty());
+    }
+
+    #[test]
+    fn zero_delay_waits_for_the_next_tick() {
+        let mut ticks = TickScheduler::new();
+        ticks.schedule(IVec3::X, 0);
+        assert_eq!(ticks.poll(), [IVec3::X]);
+    }
+
+    #[test]
+    fn same_tick_is_scheduled_once() {
+        let mut ticks = TickScheduler::new();
+        ticks.schedule(IVec3::X, 3);
+        ticks.schedule(IVec3::X, 3);
+        // Another tick is another update
+        ticks.schedule(IVec3::X, 4);
+        assert_eq!(ticks.len(), 2);
+
+        ticks.poll();
+        ticks.poll();
+        assert_eq!(ticks.poll(), [IVec3::X]);
+        // Already due on the next tick
+        ticks.schedule(IVec3::X, 1);
+        assert_eq!(ticks.len(), 1);
+        assert_eq!(ticks.poll(), [IVec3::X]);
+        assert!(ticks.is_empty());
+    }
+
+    #[test]
+    fn retain_drops_updates() {
+        let mut ticks = TickScheduler::new();
+        ticks.schedule(IVec3::X, 1);
+        ticks.schedule(IVec3::NEG_X, 1);
+        ticks.schedule(IVec3::NEG_X, 2);
+        ticks.retain(|position| position.x > 0);
+
+        assert_eq!(ticks.len(), 1);
+        assert_eq!(ticks.poll(), [IVec3::X]);
+        assert_eq!(ticks.poll(), []);
+    }
+
+    #[test]
+    fn clear_drops_everything() {
+        let mut ticks = TickScheduler::new();
+        ticks.schedule(IVec3::X, 1);
+        ticks.clear();
+        assert!(ticks.is_empty());
+        assert_eq!(ticks.poll(), []);
+    }
+}